@@ -0,0 +1,209 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+//! Compares the memory taken by a keyspace holding values in their compact encodings with the
+//! same keyspace holding them in the plain ones, `raw` strings, hash tables, linked lists and
+//! skip lists. Run with `cargo bench --bench memory`.
+
+use sphagnumdb::core::bytes::Bytes;
+use sphagnumdb::core::data_types::{
+    hash::HashValue, keyspace::Keyspace, list::ListValue, set::SetValue, sorted_set::SortedSet,
+    string::StringValue,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the bytes currently allocated, the benchmark is single-threaded.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const STRING_KEYS: usize = 100_000;
+const COLLECTION_KEYS: usize = 10_000;
+const COLLECTION_LEN: usize = 16;
+
+/// Returns the bytes held by the keyspace `fill` builds.
+fn measure(fill: impl Fn(&mut Keyspace)) -> usize {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let mut keyspace = Keyspace::new();
+    fill(&mut keyspace);
+    keyspace.commit().expect("the memory engine doesn't fail");
+    let used = ALLOCATED.load(Ordering::Relaxed) - before;
+    drop(keyspace);
+    used
+}
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key:{}", i).into_bytes()
+}
+
+fn elements(i: usize) -> impl Iterator<Item = Bytes> {
+    (0..COLLECTION_LEN).map(move |j| Bytes::from(format!("element:{}:{}", i, j)))
+}
+
+fn integers(i: usize) -> impl Iterator<Item = Bytes> {
+    (0..COLLECTION_LEN).map(move |j| Bytes::from((i * COLLECTION_LEN + j).to_string()))
+}
+
+/// A sorted set is converted to a skip list for good once it holds a member too long to pack.
+fn skip_list(members: impl Iterator<Item = Bytes>) -> SortedSet {
+    let mut sorted_set = SortedSet::default();
+    let too_long = Bytes::from(vec![b'-'; 65]);
+    sorted_set.insert(too_long.clone(), 0.0);
+    sorted_set.remove(&too_long);
+    for (score, member) in members.enumerate() {
+        sorted_set.insert(member, score as f64);
+    }
+    sorted_set
+}
+
+fn sorted_set(members: impl Iterator<Item = Bytes>) -> SortedSet {
+    let mut sorted_set = SortedSet::default();
+    for (score, member) in members.enumerate() {
+        sorted_set.insert(member, score as f64);
+    }
+    sorted_set
+}
+
+fn report(workload: &str, plain: usize, compact: usize) {
+    let saved = 100.0 * (plain as f64 - compact as f64) / plain as f64;
+    println!(
+        "{:<20} {:>14} {:>14} {:>8.1}%",
+        workload, plain, compact, saved
+    );
+}
+
+fn main() {
+    println!(
+        "{:<20} {:>14} {:>14} {:>9}",
+        "workload", "plain bytes", "compact bytes", "saved"
+    );
+
+    report(
+        "integer strings",
+        measure(|keyspace| {
+            for i in 0..STRING_KEYS {
+                let value = Bytes::from(i.to_string());
+                keyspace.insert(&key(i), StringValue::Raw(value));
+            }
+        }),
+        measure(|keyspace| {
+            for i in 0..STRING_KEYS {
+                keyspace.insert(&key(i), StringValue::new(i.to_string().as_bytes()));
+            }
+        }),
+    );
+
+    report(
+        "short strings",
+        measure(|keyspace| {
+            for i in 0..STRING_KEYS {
+                let value = Bytes::from(format!("user:{}@example", i));
+                keyspace.insert(&key(i), StringValue::Raw(value));
+            }
+        }),
+        measure(|keyspace| {
+            for i in 0..STRING_KEYS {
+                let value = format!("user:{}@example", i);
+                keyspace.insert(&key(i), StringValue::new(value.as_bytes()));
+            }
+        }),
+    );
+
+    report(
+        "small hashes",
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                let hash: HashMap<Bytes, Bytes> = elements(i).zip(integers(i)).collect();
+                keyspace.insert(&key(i), HashValue::Table(hash));
+            }
+        }),
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                keyspace.insert(&key(i), elements(i).zip(integers(i)).collect::<HashValue>());
+            }
+        }),
+    );
+
+    report(
+        "small lists",
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                let list: VecDeque<Bytes> = elements(i).collect();
+                keyspace.insert(&key(i), ListValue::Linked(list));
+            }
+        }),
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                keyspace.insert(&key(i), elements(i).collect::<ListValue>());
+            }
+        }),
+    );
+
+    report(
+        "integer sets",
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                let set: HashSet<Bytes> = integers(i).collect();
+                keyspace.insert(&key(i), SetValue::Table(set));
+            }
+        }),
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                keyspace.insert(&key(i), integers(i).collect::<SetValue>());
+            }
+        }),
+    );
+
+    report(
+        "small sets",
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                let set: HashSet<Bytes> = elements(i).collect();
+                keyspace.insert(&key(i), SetValue::Table(set));
+            }
+        }),
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                keyspace.insert(&key(i), elements(i).collect::<SetValue>());
+            }
+        }),
+    );
+
+    report(
+        "small sorted sets",
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                keyspace.insert(&key(i), skip_list(elements(i)));
+            }
+        }),
+        measure(|keyspace| {
+            for i in 0..COLLECTION_KEYS {
+                keyspace.insert(&key(i), sorted_set(elements(i)));
+            }
+        }),
+    );
+}
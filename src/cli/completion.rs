@@ -0,0 +1,99 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use rustyline::{
+    completion::Completer, Context, Helper, Highlighter, Hinter, Result as ReadlineResult,
+    Validator,
+};
+
+use super::parser::COMMAND_NAMES;
+
+/// Subcommands completed after the command name.
+const SUBCOMMANDS: &[(&str, &[&str])] = &[
+    ("cluster", &["addreplica", "meet", "peers", "replicas"]),
+    ("object", &["encoding", "idletime"]),
+];
+
+/// Completes command names, and the subcommands of CLUSTER and OBJECT, in the REPL.
+/// Completions keep the case the word was started in, so `HG` completes to `HGET`.
+#[derive(Default, Helper, Highlighter, Hinter, Validator)]
+pub struct CommandCompleter;
+
+impl CommandCompleter {
+    /// Returns where the word at `pos` starts and the ways to complete it.
+    pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |space| space + 1);
+        let word = &line[start..];
+        let previous: Vec<&str> = line[..start].split_whitespace().collect();
+        let names: &[&str] = match previous.as_slice() {
+            [] => COMMAND_NAMES,
+            [command] => SUBCOMMANDS
+                .iter()
+                .find(|(name, _)| command.eq_ignore_ascii_case(name))
+                .map_or(&[], |(_, subcommands)| subcommands),
+            _ => &[],
+        };
+        let lowercase = word.to_lowercase();
+        let uppercase = !word.is_empty() && word == word.to_uppercase();
+        let candidates = names
+            .iter()
+            .filter(|name| name.starts_with(&lowercase))
+            .map(|name| {
+                if uppercase {
+                    name.to_uppercase()
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect();
+        (start, candidates)
+    }
+}
+
+impl Completer for CommandCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> ReadlineResult<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_complete_command_names_in_the_typed_case() {
+        // Arrange
+        let completer = CommandCompleter;
+
+        // Act
+        let lower = completer.candidates("hget", 4);
+        let upper = completer.candidates("ZPOP", 4);
+        let argument = completer.candidates("get ke", 6);
+
+        // Assert
+        assert_eq!(lower, (0, vec!["hget".to_string(), "hgetall".to_string()]));
+        assert_eq!(
+            upper,
+            (0, vec!["ZPOPMAX".to_string(), "ZPOPMIN".to_string()])
+        );
+        assert_eq!(argument, (4, Vec::new()));
+    }
+
+    #[test]
+    fn test_candidates_complete_subcommands() {
+        // Act
+        let candidates = CommandCompleter.candidates("CLUSTER p", 9);
+
+        // Assert
+        assert_eq!(candidates, (8, vec!["peers".to_string()]));
+    }
+}
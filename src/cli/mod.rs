@@ -0,0 +1,10 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+//! Building blocks of the interactive client: turning lines into commands and results into text.
+
+pub mod completion;
+pub mod output;
+pub mod parser;
+pub mod tokenizer;
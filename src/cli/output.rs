@@ -0,0 +1,106 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use crate::core::commands::CommandResult;
+
+/// Formats the result of a command the way redis-cli prints it:
+///
+/// ```text
+/// 1) "text"
+/// 2) (integer) 5
+/// 3) 1) (nil)
+///    2) "nested"
+/// ```
+///
+/// Values are quoted, with quotes, backslashes and bytes that are not printable ASCII escaped.
+pub fn format_result(result: &CommandResult) -> String {
+    format_lines(result).join("\n")
+}
+
+fn format_lines(result: &CommandResult) -> Vec<String> {
+    match result {
+        CommandResult::String(value) => vec![quote(value)],
+        CommandResult::Int(value) => vec![format!("(integer) {}", value)],
+        CommandResult::Bool(value) => vec![format!("(integer) {}", *value as i64)],
+        CommandResult::Nil => vec!["(nil)".to_string()],
+        CommandResult::Error(message) => vec![format!("(error) {}", message)],
+        CommandResult::Array(values) if values.is_empty() => vec!["(empty array)".to_string()],
+        CommandResult::Array(values) => {
+            let width = values.len().to_string().len() + 2;
+            let mut lines = Vec::new();
+            for (i, value) in values.iter().enumerate() {
+                let prefix = format!("{:>width$}", format!("{}) ", i + 1), width = width);
+                for (j, line) in format_lines(value).into_iter().enumerate() {
+                    if j == 0 {
+                        lines.push(format!("{}{}", prefix, line));
+                    } else {
+                        lines.push(format!("{:width$}{}", "", line, width = width));
+                    }
+                }
+            }
+            lines
+        }
+    }
+}
+
+fn quote(value: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &byte in value {
+        match byte {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => quoted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_scalars() {
+        assert_eq!(
+            format_result(&CommandResult::String("say \"hi\"\n".into())),
+            r#""say \"hi\"\n""#
+        );
+        assert_eq!(
+            format_result(&CommandResult::String(b"\x00\xff".into())),
+            r#""\x00\xff""#
+        );
+        assert_eq!(format_result(&CommandResult::Int(-3)), "(integer) -3");
+        assert_eq!(format_result(&CommandResult::Bool(true)), "(integer) 1");
+        assert_eq!(format_result(&CommandResult::Nil), "(nil)");
+        assert_eq!(
+            format_result(&CommandResult::Array(Vec::new())),
+            "(empty array)"
+        );
+    }
+
+    #[test]
+    fn test_format_nested_arrays_aligns_items() {
+        // Arrange
+        let mut values = vec![CommandResult::Array(vec![
+            CommandResult::String("a".into()),
+            CommandResult::Nil,
+        ])];
+        values.extend((0..9).map(CommandResult::Int));
+
+        // Act
+        let formatted = format_result(&CommandResult::Array(values));
+
+        // Assert
+        let lines: Vec<&str> = formatted.lines().collect();
+        assert_eq!(lines[0], " 1) 1) \"a\"");
+        assert_eq!(lines[1], "    2) (nil)");
+        assert_eq!(lines[2], " 2) (integer) 0");
+        assert_eq!(lines[10], "10) (integer) 8");
+    }
+}
@@ -0,0 +1,1111 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::core::{
+    bytes::{parse_number, Bytes},
+    commands::{
+        bitmap::{BitOperation, BitRange, BitUnit, BitmapCommand},
+        generic::GenericCommand,
+        hash::HashCommand,
+        list::{InsertPosition, ListCommand, ListEnd},
+        server::ServerCommand,
+        set::SetCommand,
+        sorted_set::{
+            Aggregate, LexBound, ScoreBound, SortedSetCommand, ZAddComparison, ZAddCondition,
+            ZAddOptions, ZRangeBy, ZRangeLimit,
+        },
+        string::{GetExExpiration, SetExpiration, StringCommand},
+        Command,
+    },
+};
+
+/// Names of all commands `parse_command` understands, in lowercase.
+pub const COMMAND_NAMES: &[&str] = &[
+    // strings
+    "append",
+    "decr",
+    "decrby",
+    "get",
+    "getdel",
+    "getex",
+    "getrange",
+    "getset",
+    "incr",
+    "incrby",
+    "incrbyfloat",
+    "mget",
+    "mset",
+    "msetnx",
+    "set",
+    "setnx",
+    "setrange",
+    "strlen",
+    // keys
+    "copy",
+    "dbsize",
+    "del",
+    "exists",
+    "expire",
+    "expireat",
+    "keys",
+    "object",
+    "persist",
+    "pexpire",
+    "pexpireat",
+    "prefix",
+    "pttl",
+    "randomkey",
+    "range",
+    "rename",
+    "renamenx",
+    "scan",
+    "touch",
+    "ttl",
+    "type",
+    "unlink",
+    // hashes
+    "hdel",
+    "hexists",
+    "hget",
+    "hgetall",
+    "hincrby",
+    "hkeys",
+    "hlen",
+    "hmget",
+    "hset",
+    "hsetnx",
+    "hvals",
+    // lists
+    "lindex",
+    "linsert",
+    "llen",
+    "lmove",
+    "lpop",
+    "lpush",
+    "lrange",
+    "lrem",
+    "lset",
+    "ltrim",
+    "rpop",
+    "rpush",
+    // sets
+    "sadd",
+    "scard",
+    "sdiff",
+    "sdiffstore",
+    "sinter",
+    "sinterstore",
+    "sismember",
+    "smembers",
+    "smismember",
+    "spop",
+    "srandmember",
+    "srem",
+    "sunion",
+    "sunionstore",
+    // sorted sets
+    "zadd",
+    "zcount",
+    "zincrby",
+    "zinterstore",
+    "zpopmax",
+    "zpopmin",
+    "zrange",
+    "zrangebyscore",
+    "zrank",
+    "zrem",
+    "zrevrange",
+    "zrevrank",
+    "zscore",
+    "zunionstore",
+    // bitmaps
+    "bitcount",
+    "bitop",
+    "bitpos",
+    "getbit",
+    "setbit",
+    // server
+    "bgsave",
+    "cluster",
+    "lastsave",
+    "save",
+    "shutdown",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The line contains no command.
+    Empty,
+    UnknownCommand(String),
+    /// The command got too few or too many arguments.
+    WrongNumberOfArguments(String),
+    NotAnInteger,
+    NotAFloat,
+    SyntaxError,
+    /// An argument is malformed, e.g. an invalid score bound or peer id.
+    InvalidArgument(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Empty command"),
+            ParseError::UnknownCommand(name) => write!(f, "Unknown command '{}'", name),
+            ParseError::WrongNumberOfArguments(name) => {
+                write!(f, "Wrong number of arguments for '{}' command", name)
+            }
+            ParseError::NotAnInteger => write!(f, "Value is not an integer or out of range"),
+            ParseError::NotAFloat => write!(f, "Value is not a valid float"),
+            ParseError::SyntaxError => write!(f, "Syntax error"),
+            ParseError::InvalidArgument(reason) => write!(f, "Invalid argument: {}", reason),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Builds a command from the arguments of a line, see `tokenize`.
+/// The command name is case-insensitive, as are the options, e.g. `EX` in SET.
+pub fn parse_command(tokens: &[Bytes]) -> Result<Command, ParseError> {
+    let Some((name, args)) = tokens.split_first() else {
+        return Err(ParseError::Empty);
+    };
+    let name = String::from_utf8_lossy(name).to_lowercase();
+    let mut args = Args {
+        name: &name,
+        tokens: args,
+    };
+    let command = match name.as_str() {
+        "append" | "decr" | "decrby" | "get" | "getdel" | "getex" | "getrange" | "getset"
+        | "incr" | "incrby" | "incrbyfloat" | "mget" | "mset" | "msetnx" | "set" | "setnx"
+        | "setrange" | "strlen" => Command::String(parse_string_command(&mut args)?),
+        "copy" | "dbsize" | "del" | "exists" | "expire" | "expireat" | "keys" | "object"
+        | "persist" | "pexpire" | "pexpireat" | "prefix" | "pttl" | "randomkey" | "range"
+        | "rename" | "renamenx" | "scan" | "touch" | "ttl" | "type" | "unlink" => {
+            Command::Generic(parse_generic_command(&mut args)?)
+        }
+        "hdel" | "hexists" | "hget" | "hgetall" | "hincrby" | "hkeys" | "hlen" | "hmget"
+        | "hset" | "hsetnx" | "hvals" => Command::Hash(parse_hash_command(&mut args)?),
+        "lindex" | "linsert" | "llen" | "lmove" | "lpop" | "lpush" | "lrange" | "lrem" | "lset"
+        | "ltrim" | "rpop" | "rpush" => Command::List(parse_list_command(&mut args)?),
+        "sadd" | "scard" | "sdiff" | "sdiffstore" | "sinter" | "sinterstore" | "sismember"
+        | "smembers" | "smismember" | "spop" | "srandmember" | "srem" | "sunion"
+        | "sunionstore" => Command::Set(parse_set_command(&mut args)?),
+        "zadd" | "zcount" | "zincrby" | "zinterstore" | "zpopmax" | "zpopmin" | "zrange"
+        | "zrangebyscore" | "zrank" | "zrem" | "zrevrange" | "zrevrank" | "zscore"
+        | "zunionstore" => Command::SortedSet(parse_sorted_set_command(&mut args)?),
+        "bitcount" | "bitop" | "bitpos" | "getbit" | "setbit" => {
+            Command::Bitmap(parse_bitmap_command(&mut args)?)
+        }
+        "bgsave" | "cluster" | "lastsave" | "save" | "shutdown" => {
+            Command::Server(parse_server_command(&mut args)?)
+        }
+        _ => return Err(ParseError::UnknownCommand(name)),
+    };
+    args.finish()?;
+    Ok(command)
+}
+
+/// The arguments of a command that have not been consumed yet.
+struct Args<'a> {
+    name: &'a str,
+    tokens: &'a [Bytes],
+}
+
+impl Args<'_> {
+    fn wrong_number(&self) -> ParseError {
+        ParseError::WrongNumberOfArguments(self.name.to_string())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    fn bytes(&mut self) -> Result<Bytes, ParseError> {
+        let (first, rest) = self
+            .tokens
+            .split_first()
+            .ok_or_else(|| self.wrong_number())?;
+        self.tokens = rest;
+        Ok(first.clone())
+    }
+
+    fn text(&mut self) -> Result<String, ParseError> {
+        Ok(String::from_utf8_lossy(&self.bytes()?).into_owned())
+    }
+
+    /// The next argument in lowercase, for options and keywords.
+    fn keyword(&mut self) -> Result<String, ParseError> {
+        Ok(self.text()?.to_lowercase())
+    }
+
+    /// Consumes the next argument if it is the given keyword.
+    fn eat(&mut self, keyword: &str) -> bool {
+        match self.tokens.first() {
+            Some(token) if token.eq_ignore_ascii_case(keyword.as_bytes()) => {
+                self.tokens = &self.tokens[1..];
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn int<T: FromStr>(&mut self) -> Result<T, ParseError> {
+        parse_number(&self.bytes()?).ok_or(ParseError::NotAnInteger)
+    }
+
+    fn float(&mut self) -> Result<f64, ParseError> {
+        parse_number::<f64>(&self.bytes()?)
+            .filter(|value| !value.is_nan())
+            .ok_or(ParseError::NotAFloat)
+    }
+
+    fn parsed<T: FromStr<Err = String>>(&mut self) -> Result<T, ParseError> {
+        self.text()?.parse().map_err(ParseError::InvalidArgument)
+    }
+
+    /// All the remaining arguments, at least one.
+    fn rest(&mut self) -> Result<Vec<Bytes>, ParseError> {
+        if self.tokens.is_empty() {
+            return Err(self.wrong_number());
+        }
+        let rest = self.tokens.to_vec();
+        self.tokens = &[];
+        Ok(rest)
+    }
+
+    /// The remaining arguments as pairs, at least one.
+    fn pairs(&mut self) -> Result<Vec<(Bytes, Bytes)>, ParseError> {
+        if self.tokens.is_empty() || !self.tokens.len().is_multiple_of(2) {
+            return Err(self.wrong_number());
+        }
+        let pairs = self
+            .tokens
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        self.tokens = &[];
+        Ok(pairs)
+    }
+
+    fn optional_int<T: FromStr>(&mut self) -> Result<Option<T>, ParseError> {
+        if self.tokens.is_empty() {
+            Ok(None)
+        } else {
+            self.int().map(Some)
+        }
+    }
+
+    fn finish(&self) -> Result<(), ParseError> {
+        if self.tokens.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseError::SyntaxError)
+        }
+    }
+}
+
+fn parse_string_command(args: &mut Args) -> Result<StringCommand, ParseError> {
+    let command = match args.name {
+        "set" => {
+            let key = args.bytes()?;
+            let value = args.bytes()?;
+            let expiration = match args.tokens.first() {
+                None => None,
+                Some(_) => Some(match args.keyword()?.as_str() {
+                    "ex" => SetExpiration::Ex(args.int()?),
+                    "px" => SetExpiration::Px(args.int()?),
+                    "exat" => SetExpiration::ExAt(args.int()?),
+                    "pxat" => SetExpiration::PxAt(args.int()?),
+                    "keepttl" => SetExpiration::KeepTtl,
+                    _ => return Err(ParseError::SyntaxError),
+                }),
+            };
+            StringCommand::Set {
+                key,
+                value,
+                expiration,
+            }
+        }
+        "get" => StringCommand::Get { key: args.bytes()? },
+        "append" => StringCommand::Append {
+            key: args.bytes()?,
+            value: args.bytes()?,
+        },
+        "incr" => StringCommand::Incr { key: args.bytes()? },
+        "decr" => StringCommand::Decr { key: args.bytes()? },
+        "incrby" => StringCommand::IncrBy {
+            key: args.bytes()?,
+            increment: args.int()?,
+        },
+        "decrby" => StringCommand::DecrBy {
+            key: args.bytes()?,
+            decrement: args.int()?,
+        },
+        "incrbyfloat" => StringCommand::IncrByFloat {
+            key: args.bytes()?,
+            increment: args.float()?,
+        },
+        "mset" => StringCommand::MSet {
+            pairs: args.pairs()?,
+        },
+        "msetnx" => StringCommand::MSetNx {
+            pairs: args.pairs()?,
+        },
+        "mget" => StringCommand::MGet { keys: args.rest()? },
+        "setnx" => StringCommand::SetNx {
+            key: args.bytes()?,
+            value: args.bytes()?,
+        },
+        "getset" => StringCommand::GetSet {
+            key: args.bytes()?,
+            value: args.bytes()?,
+        },
+        "getdel" => StringCommand::GetDel { key: args.bytes()? },
+        "getex" => {
+            let key = args.bytes()?;
+            let expiration = match args.tokens.first() {
+                None => None,
+                Some(_) => Some(match args.keyword()?.as_str() {
+                    "ex" => GetExExpiration::Ex(args.int()?),
+                    "px" => GetExExpiration::Px(args.int()?),
+                    "exat" => GetExExpiration::ExAt(args.int()?),
+                    "pxat" => GetExExpiration::PxAt(args.int()?),
+                    "persist" => GetExExpiration::Persist,
+                    _ => return Err(ParseError::SyntaxError),
+                }),
+            };
+            StringCommand::GetEx { key, expiration }
+        }
+        "strlen" => StringCommand::StrLen { key: args.bytes()? },
+        "getrange" => StringCommand::GetRange {
+            key: args.bytes()?,
+            start: args.int()?,
+            end: args.int()?,
+        },
+        "setrange" => StringCommand::SetRange {
+            key: args.bytes()?,
+            offset: args.int()?,
+            value: args.bytes()?,
+        },
+        _ => unreachable!("not a string command: {}", args.name),
+    };
+    Ok(command)
+}
+
+fn parse_generic_command(args: &mut Args) -> Result<GenericCommand, ParseError> {
+    let command = match args.name {
+        "exists" => GenericCommand::Exists { keys: args.rest()? },
+        "del" => GenericCommand::Delete { keys: args.rest()? },
+        "unlink" => GenericCommand::Unlink { keys: args.rest()? },
+        "touch" => GenericCommand::Touch { keys: args.rest()? },
+        "expire" => GenericCommand::Expire {
+            key: args.bytes()?,
+            seconds: args.int()?,
+        },
+        "pexpire" => GenericCommand::PExpire {
+            key: args.bytes()?,
+            milliseconds: args.int()?,
+        },
+        "expireat" => GenericCommand::ExpireAt {
+            key: args.bytes()?,
+            timestamp: args.int()?,
+        },
+        "pexpireat" => GenericCommand::PExpireAt {
+            key: args.bytes()?,
+            timestamp: args.int()?,
+        },
+        "ttl" => GenericCommand::Ttl { key: args.bytes()? },
+        "pttl" => GenericCommand::PTtl { key: args.bytes()? },
+        "persist" => GenericCommand::Persist { key: args.bytes()? },
+        "scan" => {
+            let cursor = args.text()?;
+            let (mut pattern, mut count, mut value_type) = (None, None, None);
+            while !args.is_empty() {
+                match args.keyword()?.as_str() {
+                    "match" => pattern = Some(args.bytes()?),
+                    "count" => count = Some(args.int()?),
+                    "type" => value_type = Some(args.text()?.to_lowercase()),
+                    _ => return Err(ParseError::SyntaxError),
+                }
+            }
+            GenericCommand::Scan {
+                cursor,
+                pattern,
+                count,
+                value_type,
+            }
+        }
+        "keys" => GenericCommand::Keys {
+            pattern: args.bytes()?,
+        },
+        "range" => parse_range(args)?,
+        "prefix" => {
+            let prefix = args.bytes()?;
+            let (mut limit, mut with_values) = (None, false);
+            while !args.is_empty() {
+                match args.keyword()?.as_str() {
+                    "limit" => limit = Some(args.int()?),
+                    "withvalues" => with_values = true,
+                    _ => return Err(ParseError::SyntaxError),
+                }
+            }
+            GenericCommand::Prefix {
+                prefix,
+                limit,
+                with_values,
+            }
+        }
+        "dbsize" => GenericCommand::DbSize,
+        "randomkey" => GenericCommand::RandomKey,
+        "rename" => GenericCommand::Rename {
+            key: args.bytes()?,
+            new_key: args.bytes()?,
+        },
+        "renamenx" => GenericCommand::RenameNx {
+            key: args.bytes()?,
+            new_key: args.bytes()?,
+        },
+        "copy" => GenericCommand::Copy {
+            source: args.bytes()?,
+            destination: args.bytes()?,
+            replace: args.eat("replace"),
+        },
+        "type" => GenericCommand::Type { key: args.bytes()? },
+        "object" => match args.keyword()?.as_str() {
+            "encoding" => GenericCommand::ObjectEncoding { key: args.bytes()? },
+            "idletime" => GenericCommand::ObjectIdleTime { key: args.bytes()? },
+            _ => return Err(ParseError::SyntaxError),
+        },
+        _ => unreachable!("not a generic command: {}", args.name),
+    };
+    Ok(command)
+}
+
+/// RANGE: `start end [REV] [LIMIT count] [WITHVALUES]`, the bounds are given as in ZRANGE BYLEX.
+/// Unlike ZRANGE, REV only reverses the order, the start is still the lower bound.
+fn parse_range(args: &mut Args) -> Result<GenericCommand, ParseError> {
+    let start = args.parsed::<LexBound>()?;
+    let end = args.parsed::<LexBound>()?;
+    let (mut rev, mut limit, mut with_values) = (false, None, false);
+    while !args.is_empty() {
+        match args.keyword()?.as_str() {
+            "rev" => rev = true,
+            "limit" => limit = Some(args.int()?),
+            "withvalues" => with_values = true,
+            _ => return Err(ParseError::SyntaxError),
+        }
+    }
+    Ok(GenericCommand::Range {
+        start,
+        end,
+        rev,
+        limit,
+        with_values,
+    })
+}
+
+fn parse_hash_command(args: &mut Args) -> Result<HashCommand, ParseError> {
+    let command = match args.name {
+        "hset" => HashCommand::Set {
+            key: args.bytes()?,
+            fields: args.pairs()?,
+        },
+        "hget" => HashCommand::Get {
+            key: args.bytes()?,
+            field: args.bytes()?,
+        },
+        "hmget" => HashCommand::MGet {
+            key: args.bytes()?,
+            fields: args.rest()?,
+        },
+        "hdel" => HashCommand::Del {
+            key: args.bytes()?,
+            fields: args.rest()?,
+        },
+        "hexists" => HashCommand::Exists {
+            key: args.bytes()?,
+            field: args.bytes()?,
+        },
+        "hlen" => HashCommand::Len { key: args.bytes()? },
+        "hkeys" => HashCommand::Keys { key: args.bytes()? },
+        "hvals" => HashCommand::Vals { key: args.bytes()? },
+        "hgetall" => HashCommand::GetAll { key: args.bytes()? },
+        "hincrby" => HashCommand::IncrBy {
+            key: args.bytes()?,
+            field: args.bytes()?,
+            increment: args.int()?,
+        },
+        "hsetnx" => HashCommand::SetNx {
+            key: args.bytes()?,
+            field: args.bytes()?,
+            value: args.bytes()?,
+        },
+        _ => unreachable!("not a hash command: {}", args.name),
+    };
+    Ok(command)
+}
+
+fn parse_list_end(args: &mut Args) -> Result<ListEnd, ParseError> {
+    match args.keyword()?.as_str() {
+        "left" => Ok(ListEnd::Left),
+        "right" => Ok(ListEnd::Right),
+        _ => Err(ParseError::SyntaxError),
+    }
+}
+
+fn parse_list_command(args: &mut Args) -> Result<ListCommand, ParseError> {
+    let command = match args.name {
+        "lpush" => ListCommand::LPush {
+            key: args.bytes()?,
+            values: args.rest()?,
+        },
+        "rpush" => ListCommand::RPush {
+            key: args.bytes()?,
+            values: args.rest()?,
+        },
+        "lpop" => ListCommand::LPop {
+            key: args.bytes()?,
+            count: args.optional_int()?,
+        },
+        "rpop" => ListCommand::RPop {
+            key: args.bytes()?,
+            count: args.optional_int()?,
+        },
+        "lrange" => ListCommand::Range {
+            key: args.bytes()?,
+            start: args.int()?,
+            stop: args.int()?,
+        },
+        "lindex" => ListCommand::Index {
+            key: args.bytes()?,
+            index: args.int()?,
+        },
+        "lset" => ListCommand::Set {
+            key: args.bytes()?,
+            index: args.int()?,
+            value: args.bytes()?,
+        },
+        "llen" => ListCommand::Len { key: args.bytes()? },
+        "linsert" => ListCommand::Insert {
+            key: args.bytes()?,
+            position: match args.keyword()?.as_str() {
+                "before" => InsertPosition::Before,
+                "after" => InsertPosition::After,
+                _ => return Err(ParseError::SyntaxError),
+            },
+            pivot: args.bytes()?,
+            value: args.bytes()?,
+        },
+        "lrem" => ListCommand::Rem {
+            key: args.bytes()?,
+            count: args.int()?,
+            value: args.bytes()?,
+        },
+        "ltrim" => ListCommand::Trim {
+            key: args.bytes()?,
+            start: args.int()?,
+            stop: args.int()?,
+        },
+        "lmove" => ListCommand::Move {
+            source: args.bytes()?,
+            destination: args.bytes()?,
+            from: parse_list_end(args)?,
+            to: parse_list_end(args)?,
+        },
+        _ => unreachable!("not a list command: {}", args.name),
+    };
+    Ok(command)
+}
+
+fn parse_set_command(args: &mut Args) -> Result<SetCommand, ParseError> {
+    let command = match args.name {
+        "sadd" => SetCommand::Add {
+            key: args.bytes()?,
+            members: args.rest()?,
+        },
+        "srem" => SetCommand::Rem {
+            key: args.bytes()?,
+            members: args.rest()?,
+        },
+        "sismember" => SetCommand::IsMember {
+            key: args.bytes()?,
+            member: args.bytes()?,
+        },
+        "smismember" => SetCommand::MIsMember {
+            key: args.bytes()?,
+            members: args.rest()?,
+        },
+        "smembers" => SetCommand::Members { key: args.bytes()? },
+        "scard" => SetCommand::Card { key: args.bytes()? },
+        "spop" => SetCommand::Pop {
+            key: args.bytes()?,
+            count: args.optional_int()?,
+        },
+        "srandmember" => SetCommand::RandMember {
+            key: args.bytes()?,
+            count: args.optional_int()?,
+        },
+        "sinter" => SetCommand::Inter { keys: args.rest()? },
+        "sunion" => SetCommand::Union { keys: args.rest()? },
+        "sdiff" => SetCommand::Diff { keys: args.rest()? },
+        "sinterstore" => SetCommand::InterStore {
+            destination: args.bytes()?,
+            keys: args.rest()?,
+        },
+        "sunionstore" => SetCommand::UnionStore {
+            destination: args.bytes()?,
+            keys: args.rest()?,
+        },
+        "sdiffstore" => SetCommand::DiffStore {
+            destination: args.bytes()?,
+            keys: args.rest()?,
+        },
+        _ => unreachable!("not a set command: {}", args.name),
+    };
+    Ok(command)
+}
+
+fn parse_zadd(args: &mut Args) -> Result<SortedSetCommand, ParseError> {
+    let key = args.bytes()?;
+    let mut options = ZAddOptions::default();
+    loop {
+        if args.eat("nx") {
+            options.condition = Some(ZAddCondition::Nx);
+        } else if args.eat("xx") {
+            options.condition = Some(ZAddCondition::Xx);
+        } else if args.eat("gt") {
+            options.comparison = Some(ZAddComparison::Gt);
+        } else if args.eat("lt") {
+            options.comparison = Some(ZAddComparison::Lt);
+        } else if args.eat("ch") {
+            options.ch = true;
+        } else if args.eat("incr") {
+            options.incr = true;
+        } else {
+            break;
+        }
+    }
+    if args.is_empty() || !args.len().is_multiple_of(2) {
+        return Err(args.wrong_number());
+    }
+    let mut members = Vec::new();
+    while !args.is_empty() {
+        members.push((args.float()?, args.bytes()?));
+    }
+    Ok(SortedSetCommand::Add {
+        key,
+        members,
+        options,
+    })
+}
+
+/// ZRANGE, ZRANGEBYSCORE and ZREVRANGE. Reversed score and lex ranges take the bounds from the
+/// highest, as in Redis.
+fn parse_zrange(args: &mut Args) -> Result<SortedSetCommand, ParseError> {
+    let key = args.bytes()?;
+    let start = args.text()?;
+    let stop = args.text()?;
+    let (mut by_score, mut by_lex) = (args.name == "zrangebyscore", false);
+    let mut rev = args.name == "zrevrange";
+    let (mut limit, mut with_scores) = (None, false);
+    while !args.is_empty() {
+        match args.keyword()?.as_str() {
+            "byscore" if args.name == "zrange" => by_score = true,
+            "bylex" if args.name == "zrange" => by_lex = true,
+            "rev" if args.name == "zrange" => rev = true,
+            "limit" => {
+                limit = Some(ZRangeLimit {
+                    offset: args.int()?,
+                    count: args.int()?,
+                })
+            }
+            "withscores" => with_scores = true,
+            _ => return Err(ParseError::SyntaxError),
+        }
+    }
+    let (min, max) = if rev {
+        (&stop, &start)
+    } else {
+        (&start, &stop)
+    };
+    let by = if by_score {
+        ZRangeBy::Score {
+            min: min
+                .parse::<ScoreBound>()
+                .map_err(ParseError::InvalidArgument)?,
+            max: max
+                .parse::<ScoreBound>()
+                .map_err(ParseError::InvalidArgument)?,
+        }
+    } else if by_lex {
+        ZRangeBy::Lex {
+            min: min
+                .parse::<LexBound>()
+                .map_err(ParseError::InvalidArgument)?,
+            max: max
+                .parse::<LexBound>()
+                .map_err(ParseError::InvalidArgument)?,
+        }
+    } else {
+        ZRangeBy::Index {
+            start: start.parse().map_err(|_| ParseError::NotAnInteger)?,
+            stop: stop.parse().map_err(|_| ParseError::NotAnInteger)?,
+        }
+    };
+    if by_lex && with_scores {
+        return Err(ParseError::SyntaxError);
+    }
+    Ok(SortedSetCommand::Range {
+        key,
+        by,
+        rev,
+        limit,
+        with_scores,
+    })
+}
+
+/// Destination, keys, weights and aggregate of ZUNIONSTORE and ZINTERSTORE.
+type ZStoreArgs = (Bytes, Vec<Bytes>, Option<Vec<f64>>, Aggregate);
+
+/// ZUNIONSTORE and ZINTERSTORE: `destination numkeys key [key ...] [WEIGHTS ...] [AGGREGATE ...]`.
+fn parse_zstore(args: &mut Args) -> Result<ZStoreArgs, ParseError> {
+    let destination = args.bytes()?;
+    let numkeys: usize = args.int()?;
+    if numkeys == 0 || args.len() < numkeys {
+        return Err(args.wrong_number());
+    }
+    let keys = (0..numkeys)
+        .map(|_| args.bytes())
+        .collect::<Result<Vec<_>, _>>()?;
+    let (mut weights, mut aggregate) = (None, Aggregate::default());
+    while !args.is_empty() {
+        match args.keyword()?.as_str() {
+            "weights" => {
+                weights = Some(
+                    (0..numkeys)
+                        .map(|_| args.float())
+                        .collect::<Result<Vec<_>, _>>()?,
+                )
+            }
+            "aggregate" => {
+                aggregate = match args.keyword()?.as_str() {
+                    "sum" => Aggregate::Sum,
+                    "min" => Aggregate::Min,
+                    "max" => Aggregate::Max,
+                    _ => return Err(ParseError::SyntaxError),
+                }
+            }
+            _ => return Err(ParseError::SyntaxError),
+        }
+    }
+    Ok((destination, keys, weights, aggregate))
+}
+
+fn parse_sorted_set_command(args: &mut Args) -> Result<SortedSetCommand, ParseError> {
+    let command = match args.name {
+        "zadd" => parse_zadd(args)?,
+        "zrem" => SortedSetCommand::Rem {
+            key: args.bytes()?,
+            members: args.rest()?,
+        },
+        "zscore" => SortedSetCommand::Score {
+            key: args.bytes()?,
+            member: args.bytes()?,
+        },
+        "zrank" => SortedSetCommand::Rank {
+            key: args.bytes()?,
+            member: args.bytes()?,
+        },
+        "zrevrank" => SortedSetCommand::RevRank {
+            key: args.bytes()?,
+            member: args.bytes()?,
+        },
+        "zrange" | "zrangebyscore" | "zrevrange" => parse_zrange(args)?,
+        "zcount" => SortedSetCommand::Count {
+            key: args.bytes()?,
+            min: args.parsed()?,
+            max: args.parsed()?,
+        },
+        "zincrby" => SortedSetCommand::IncrBy {
+            key: args.bytes()?,
+            increment: args.float()?,
+            member: args.bytes()?,
+        },
+        "zpopmin" => SortedSetCommand::PopMin {
+            key: args.bytes()?,
+            count: args.optional_int()?,
+        },
+        "zpopmax" => SortedSetCommand::PopMax {
+            key: args.bytes()?,
+            count: args.optional_int()?,
+        },
+        "zunionstore" => {
+            let (destination, keys, weights, aggregate) = parse_zstore(args)?;
+            SortedSetCommand::UnionStore {
+                destination,
+                keys,
+                weights,
+                aggregate,
+            }
+        }
+        "zinterstore" => {
+            let (destination, keys, weights, aggregate) = parse_zstore(args)?;
+            SortedSetCommand::InterStore {
+                destination,
+                keys,
+                weights,
+                aggregate,
+            }
+        }
+        _ => unreachable!("not a sorted set command: {}", args.name),
+    };
+    Ok(command)
+}
+
+fn parse_bit(args: &mut Args) -> Result<bool, ParseError> {
+    match args.int::<u8>()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ParseError::InvalidArgument(
+            "bit must be 0 or 1".to_string(),
+        )),
+    }
+}
+
+/// `[start [end [BYTE | BIT]]]`, BITCOUNT requires the end once the start is given.
+fn parse_bit_range(args: &mut Args, end_required: bool) -> Result<Option<BitRange>, ParseError> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    let start = args.int()?;
+    if end_required && args.is_empty() {
+        return Err(ParseError::SyntaxError);
+    }
+    let end = args.optional_int()?;
+    let unit = if args.is_empty() {
+        BitUnit::default()
+    } else {
+        match args.keyword()?.as_str() {
+            "byte" => BitUnit::Byte,
+            "bit" => BitUnit::Bit,
+            _ => return Err(ParseError::SyntaxError),
+        }
+    };
+    Ok(Some(BitRange { start, end, unit }))
+}
+
+fn parse_bitmap_command(args: &mut Args) -> Result<BitmapCommand, ParseError> {
+    let command = match args.name {
+        "setbit" => BitmapCommand::SetBit {
+            key: args.bytes()?,
+            offset: args.int()?,
+            value: parse_bit(args)?,
+        },
+        "getbit" => BitmapCommand::GetBit {
+            key: args.bytes()?,
+            offset: args.int()?,
+        },
+        "bitcount" => BitmapCommand::Count {
+            key: args.bytes()?,
+            range: parse_bit_range(args, true)?,
+        },
+        "bitpos" => BitmapCommand::Pos {
+            key: args.bytes()?,
+            bit: parse_bit(args)?,
+            range: parse_bit_range(args, false)?,
+        },
+        "bitop" => BitmapCommand::Op {
+            operation: match args.keyword()?.as_str() {
+                "and" => BitOperation::And,
+                "or" => BitOperation::Or,
+                "xor" => BitOperation::Xor,
+                "not" => BitOperation::Not,
+                _ => return Err(ParseError::SyntaxError),
+            },
+            destination: args.bytes()?,
+            keys: args.rest()?,
+        },
+        _ => unreachable!("not a bitmap command: {}", args.name),
+    };
+    Ok(command)
+}
+
+fn parse_server_command(args: &mut Args) -> Result<ServerCommand, ParseError> {
+    let command = match args.name {
+        "shutdown" => ServerCommand::Shutdown,
+        "save" => ServerCommand::Save,
+        "bgsave" => ServerCommand::BgSave,
+        "lastsave" => ServerCommand::LastSave,
+        "cluster" => match args.keyword()?.as_str() {
+            "peers" => ServerCommand::ClusterPeers,
+            "replicas" => ServerCommand::ClusterReplicas,
+            "meet" => ServerCommand::ClusterMeet {
+                addr: args
+                    .text()?
+                    .parse()
+                    .map_err(|e| ParseError::InvalidArgument(format!("{}", e)))?,
+            },
+            "addreplica" => ServerCommand::ClusterAddReplica {
+                peer_id: args
+                    .text()?
+                    .parse()
+                    .map_err(|e| ParseError::InvalidArgument(format!("{}", e)))?,
+            },
+            _ => return Err(ParseError::SyntaxError),
+        },
+        _ => unreachable!("not a server command: {}", args.name),
+    };
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::tokenizer::tokenize;
+
+    fn parse(line: &str) -> Result<Command, ParseError> {
+        parse_command(&tokenize(line).unwrap())
+    }
+
+    #[test]
+    fn test_every_command_name_is_parsed() {
+        for name in COMMAND_NAMES {
+            // Arrange
+            let tokens = vec![Bytes::from(*name)];
+
+            // Act
+            let result = parse_command(&tokens);
+
+            // Assert
+            assert!(
+                !matches!(result, Err(ParseError::UnknownCommand(_))),
+                "{} is not parsed",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_set_with_quoted_value_and_options() {
+        // Act
+        let command = parse("SET greeting \"hello world\" px 500").unwrap();
+
+        // Assert
+        match command {
+            Command::String(StringCommand::Set {
+                key,
+                value,
+                expiration,
+            }) => {
+                assert_eq!(key, "greeting");
+                assert_eq!(value, "hello world");
+                assert_eq!(expiration, Some(SetExpiration::Px(500)));
+            }
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_reversed_score_range_takes_bounds_from_the_highest() {
+        // Act
+        let command = parse("zrange board +inf (10 BYSCORE REV LIMIT 0 5 WITHSCORES").unwrap();
+
+        // Assert
+        match command {
+            Command::SortedSet(SortedSetCommand::Range {
+                by,
+                rev,
+                limit,
+                with_scores,
+                ..
+            }) => {
+                assert_eq!(
+                    by,
+                    ZRangeBy::Score {
+                        min: ScoreBound::Exclusive(10.0),
+                        max: ScoreBound::Inclusive(f64::INFINITY),
+                    }
+                );
+                assert!(rev && with_scores);
+                assert_eq!(
+                    limit,
+                    Some(ZRangeLimit {
+                        offset: 0,
+                        count: 5
+                    })
+                );
+            }
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_key_range_keeps_the_lower_bound_first_when_reversed() {
+        // Act
+        let range = parse("RANGE [user:42: (user:43 REV LIMIT 10 WITHVALUES").unwrap();
+        let prefix = parse("prefix user:42: limit 5").unwrap();
+
+        // Assert
+        match range {
+            Command::Generic(GenericCommand::Range {
+                start,
+                end,
+                rev,
+                limit,
+                with_values,
+            }) => {
+                assert_eq!(start, LexBound::Inclusive("user:42:".into()));
+                assert_eq!(end, LexBound::Exclusive("user:43".into()));
+                assert!(rev && with_values);
+                assert_eq!(limit, Some(10));
+            }
+            other => panic!("Unexpected command: {:?}", other),
+        }
+        match prefix {
+            Command::Generic(GenericCommand::Prefix {
+                prefix,
+                limit,
+                with_values,
+            }) => {
+                assert_eq!(prefix, "user:42:");
+                assert_eq!(limit, Some(5));
+                assert!(!with_values);
+            }
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_reports_what_is_wrong() {
+        assert_eq!(parse("").unwrap_err(), ParseError::Empty);
+        assert_eq!(
+            parse("frobnicate key").unwrap_err(),
+            ParseError::UnknownCommand("frobnicate".to_string())
+        );
+        assert_eq!(
+            parse("get").unwrap_err(),
+            ParseError::WrongNumberOfArguments("get".to_string())
+        );
+        assert_eq!(
+            parse("mset a 1 b").unwrap_err(),
+            ParseError::WrongNumberOfArguments("mset".to_string())
+        );
+        assert_eq!(
+            parse("incrby key ten").unwrap_err(),
+            ParseError::NotAnInteger
+        );
+        assert_eq!(parse("get key extra").unwrap_err(), ParseError::SyntaxError);
+        assert_eq!(
+            parse("set key value EX").unwrap_err(),
+            ParseError::WrongNumberOfArguments("set".to_string())
+        );
+    }
+}
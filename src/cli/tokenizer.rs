@@ -0,0 +1,181 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{error::Error, fmt};
+
+use crate::core::bytes::Bytes;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenizeError {
+    /// A quoted argument is not closed by the end of the line.
+    UnterminatedQuote,
+    /// A closing quote is directly followed by another character, e.g. `"a"b`.
+    MissingSpaceAfterQuote,
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizeError::UnterminatedQuote => write!(f, "Unterminated quoted argument"),
+            TokenizeError::MissingSpaceAfterQuote => {
+                write!(f, "Closing quote must be followed by a space")
+            }
+        }
+    }
+}
+
+impl Error for TokenizeError {}
+
+/// Splits a line into arguments, the same way redis-cli does.
+///
+/// Arguments are separated by whitespace. In double quotes, spaces are kept and `\n`, `\r`, `\t`,
+/// `\b`, `\a`, `\\`, `\"` and `\xHH` are unescaped, so any binary value can be written. In single
+/// quotes, everything is literal except `\'`.
+pub fn tokenize(line: &str) -> Result<Vec<Bytes>, TokenizeError> {
+    let input = line.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < input.len() && input[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == input.len() {
+            return Ok(tokens);
+        }
+
+        let mut token = Vec::new();
+        match input[i] {
+            b'"' => i = read_double_quoted(input, i + 1, &mut token)?,
+            b'\'' => i = read_single_quoted(input, i + 1, &mut token)?,
+            _ => {
+                while i < input.len() && !input[i].is_ascii_whitespace() {
+                    token.push(input[i]);
+                    i += 1;
+                }
+            }
+        }
+        tokens.push(token.into());
+    }
+}
+
+/// Reads a double-quoted argument starting after the opening quote, returns where it ends.
+fn read_double_quoted(
+    input: &[u8],
+    mut i: usize,
+    token: &mut Vec<u8>,
+) -> Result<usize, TokenizeError> {
+    loop {
+        match input.get(i) {
+            None => return Err(TokenizeError::UnterminatedQuote),
+            Some(b'"') => return after_closing_quote(input, i + 1),
+            Some(b'\\') => {
+                let Some(&escaped) = input.get(i + 1) else {
+                    return Err(TokenizeError::UnterminatedQuote);
+                };
+                i += 2;
+                match escaped {
+                    b'n' => token.push(b'\n'),
+                    b'r' => token.push(b'\r'),
+                    b't' => token.push(b'\t'),
+                    b'b' => token.push(0x08),
+                    b'a' => token.push(0x07),
+                    b'x' => match input.get(i..i + 2).and_then(parse_hex_byte) {
+                        Some(byte) => {
+                            token.push(byte);
+                            i += 2;
+                        }
+                        None => token.push(b'x'),
+                    },
+                    other => token.push(other),
+                }
+            }
+            Some(&c) => {
+                token.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Reads a single-quoted argument starting after the opening quote, returns where it ends.
+fn read_single_quoted(
+    input: &[u8],
+    mut i: usize,
+    token: &mut Vec<u8>,
+) -> Result<usize, TokenizeError> {
+    loop {
+        match input.get(i) {
+            None => return Err(TokenizeError::UnterminatedQuote),
+            Some(b'\'') => return after_closing_quote(input, i + 1),
+            Some(b'\\') if input.get(i + 1) == Some(&b'\'') => {
+                token.push(b'\'');
+                i += 2;
+            }
+            Some(&c) => {
+                token.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn after_closing_quote(input: &[u8], i: usize) -> Result<usize, TokenizeError> {
+    match input.get(i) {
+        Some(c) if !c.is_ascii_whitespace() => Err(TokenizeError::MissingSpaceAfterQuote),
+        _ => Ok(i),
+    }
+}
+
+fn parse_hex_byte(digits: &[u8]) -> Option<u8> {
+    u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_splits_on_whitespace_and_keeps_quoted_spaces() {
+        // Arrange
+        let line = "  set  'my key' \"hello world\"\t";
+
+        // Act
+        let tokens = tokenize(line).unwrap();
+
+        // Assert
+        assert_eq!(
+            tokens,
+            vec![
+                Bytes::from("set"),
+                Bytes::from("my key"),
+                Bytes::from("hello world"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_unescapes_double_quoted_arguments() {
+        // Arrange
+        let line = r#"set "a\"b\\c\n" "\x00\xff\xzz" 'it\'s \n' """#;
+
+        // Act
+        let tokens = tokenize(line).unwrap();
+
+        // Assert
+        assert_eq!(tokens[1], Bytes::from("a\"b\\c\n"));
+        assert_eq!(tokens[2], Bytes::from(b"\x00\xffxzz"));
+        assert_eq!(tokens[3], Bytes::from("it's \\n"));
+        assert_eq!(tokens[4], Bytes::new());
+    }
+
+    #[test]
+    fn test_tokenize_rejects_unbalanced_quotes() {
+        assert_eq!(tokenize("get \"key"), Err(TokenizeError::UnterminatedQuote));
+        assert_eq!(tokenize("get 'key"), Err(TokenizeError::UnterminatedQuote));
+        assert_eq!(
+            tokenize("get \"key\"suffix"),
+            Err(TokenizeError::MissingSpaceAfterQuote)
+        );
+    }
+}
@@ -0,0 +1,558 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{
+    error::Error,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::commands::Command;
+
+/// Identifies the file format, the last two bytes are its version.
+const MAGIC: &[u8; 8] = b"SPHAOF01";
+
+/// Every record starts with the length and the CRC-32 of its payload, both little-endian.
+const RECORD_HEADER_LEN: u64 = 8;
+
+/// How often the `everysec` policy syncs the log.
+pub const AOF_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When the log is synced to disk, trading durability for write latency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// Before every write is acknowledged, nothing acknowledged is ever lost.
+    Always,
+    /// Once a second, a crash loses at most the last second of writes.
+    #[default]
+    #[value(name = "everysec")]
+    EverySec,
+    /// Whenever the operating system decides, usually within 30 seconds.
+    No,
+}
+
+#[derive(Debug)]
+pub enum AofError {
+    Io(PathBuf, io::Error),
+    /// The file doesn't start with the header of an append-only log.
+    NotAnAof(PathBuf),
+    /// A complete record in the middle of the log is damaged. Unlike a truncated tail, this is
+    /// not the result of a crash, so the node refuses to start rather than lose the rest.
+    Corrupted {
+        path: PathBuf,
+        offset: u64,
+        reason: String,
+    },
+    /// A command of the log failed when it was replayed, e.g. the log was written by another
+    /// version. The node refuses to start rather than run with part of the log applied.
+    Rejected {
+        path: PathBuf,
+        offset: u64,
+        reason: String,
+    },
+}
+
+impl fmt::Display for AofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AofError::Io(path, e) => {
+                write!(f, "Append-only log {} failed: {}", path.display(), e)
+            }
+            AofError::NotAnAof(path) => {
+                write!(f, "{} is not an append-only log", path.display())
+            }
+            AofError::Corrupted {
+                path,
+                offset,
+                reason,
+            } => write!(
+                f,
+                "Append-only log {} is corrupted at byte {}: {}",
+                path.display(),
+                offset,
+                reason
+            ),
+            AofError::Rejected {
+                path,
+                offset,
+                reason,
+            } => write!(
+                f,
+                "Append-only log {} has a command at byte {} that fails: {}",
+                path.display(),
+                offset,
+                reason
+            ),
+        }
+    }
+}
+
+impl Error for AofError {}
+
+/// Why the replay stopped before the end of the file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TruncatedTail {
+    /// Fewer bytes are left than a record header takes.
+    Header { available: u64 },
+    /// The header promises more bytes than the file has.
+    Record { expected: u64, available: u64 },
+    /// The last record is complete, but its checksum doesn't match, e.g. it was torn by a crash.
+    Checksum,
+}
+
+/// What `AppendOnlyLog::open` found in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayReport {
+    /// How many commands were replayed.
+    pub commands: u64,
+    /// Where the last complete record ends, the log continues from here.
+    pub valid_len: u64,
+    /// The length of the file before the truncated tail was cut off.
+    pub file_len: u64,
+    pub truncated: Option<TruncatedTail>,
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Replayed {} commands", self.commands)?;
+        let Some(truncated) = &self.truncated else {
+            return Ok(());
+        };
+        write!(
+            f,
+            ", dropped a truncated tail of {} bytes at byte {} (record {}): ",
+            self.file_len - self.valid_len,
+            self.valid_len,
+            self.commands + 1
+        )?;
+        match truncated {
+            TruncatedTail::Header { available } => {
+                write!(f, "{} of {} header bytes", available, RECORD_HEADER_LEN)
+            }
+            TruncatedTail::Record {
+                expected,
+                available,
+            } => write!(f, "{} of {} payload bytes", available, expected),
+            TruncatedTail::Checksum => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+/// A log of the write commands applied by a node, replayed into `DataStorage` when it starts.
+///
+/// Commands are logged in the form they are replicated in, so replaying them gives the same
+/// result as applying them the first time: relative deadlines are already absolute and random
+/// commands are rewritten into their outcome, see `Command::for_replication`.
+pub struct AppendOnlyLog {
+    path: PathBuf,
+    file: File,
+    fsync: FsyncPolicy,
+    /// Where the next record is written.
+    offset: u64,
+    /// Whether records were written since the last sync.
+    unsynced: bool,
+    last_sync: Instant,
+}
+
+impl AppendOnlyLog {
+    /// Opens the log, creating it if it doesn't exist, and passes every command it holds to
+    /// `apply` in order. A truncated tail, e.g. a record the node was writing when it crashed,
+    /// is reported and cut off, so that new records follow the last complete one.
+    /// Only successful writes are logged, so a command `apply` fails stops the replay with
+    /// `AofError::Rejected`.
+    pub fn open<E: fmt::Display>(
+        path: impl AsRef<Path>,
+        fsync: FsyncPolicy,
+        apply: impl FnMut(Command) -> Result<(), E>,
+    ) -> Result<(Self, ReplayReport), AofError> {
+        Self::open_from(path, fsync, 0, apply)
+    }
+
+    /// Opens the log like `open`, but replays only the commands from byte `start` on, e.g. the
+    /// ones written after a snapshot was taken, see `AppendOnlyLog::offset`.
+    pub fn open_from<E: fmt::Display>(
+        path: impl AsRef<Path>,
+        fsync: FsyncPolicy,
+        start: u64,
+        mut apply: impl FnMut(Command) -> Result<(), E>,
+    ) -> Result<(Self, ReplayReport), AofError> {
+        let path = path.as_ref().to_path_buf();
+        let io_error = |e| AofError::Io(path.clone(), e);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(io_error)?;
+
+        let file_len = file.metadata().map_err(io_error)?.len();
+        let report = if file_len < MAGIC.len() as u64 {
+            // A new log, or one that crashed before its header was written.
+            let mut header = vec![0; file_len as usize];
+            file.read_exact(&mut header).map_err(io_error)?;
+            if !MAGIC.starts_with(&header) {
+                return Err(AofError::NotAnAof(path));
+            }
+            file.set_len(0).map_err(io_error)?;
+            file.write_all(MAGIC).map_err(io_error)?;
+            ReplayReport {
+                commands: 0,
+                valid_len: MAGIC.len() as u64,
+                file_len,
+                truncated: None,
+            }
+        } else {
+            let report = replay(&path, &mut file, file_len, start, &mut apply)?;
+            if report.truncated.is_some() {
+                file.set_len(report.valid_len).map_err(io_error)?;
+            }
+            report
+        };
+        file.seek(SeekFrom::End(0)).map_err(io_error)?;
+        file.sync_all().map_err(io_error)?;
+
+        let log = AppendOnlyLog {
+            path,
+            file,
+            fsync,
+            offset: report.valid_len,
+            unsynced: false,
+            last_sync: Instant::now(),
+        };
+        Ok((log, report))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the next record is written, i.e. the length of the log.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Writes a command to the log. With the `always` policy, it is on disk once this returns.
+    /// If this fails, the log is left as it was.
+    pub fn append(&mut self, command: &Command) -> Result<(), AofError> {
+        let payload = serde_json::to_vec(command)
+            .map_err(|e| AofError::Io(self.path.clone(), io::Error::other(e)))?;
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN as usize + payload.len());
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+        if let Err(e) = self.write_record(&record) {
+            // The command is not applied, so neither a partial record nor one that failed to sync
+            // may stay in front of the next one.
+            let _ = self.file.set_len(self.offset);
+            return Err(e);
+        }
+        self.offset += record.len() as u64;
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> Result<(), AofError> {
+        self.file
+            .write_all(record)
+            .map_err(|e| AofError::Io(self.path.clone(), e))?;
+        self.unsynced = true;
+        match self.fsync {
+            FsyncPolicy::Always => self.sync(),
+            FsyncPolicy::EverySec => self.sync_if_due(),
+            FsyncPolicy::No => Ok(()),
+        }
+    }
+
+    /// Makes every later write fail, as a full or failing disk does.
+    #[cfg(test)]
+    pub(crate) fn fail_writes(&mut self) {
+        self.file = File::open(&self.path).unwrap();
+    }
+
+    /// Syncs the log if the `everysec` policy is due, the node calls this periodically.
+    pub fn sync_if_due(&mut self) -> Result<(), AofError> {
+        if self.fsync == FsyncPolicy::EverySec && self.last_sync.elapsed() >= AOF_SYNC_INTERVAL {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Syncs the log regardless of the policy, e.g. when the node stops.
+    pub fn sync(&mut self) -> Result<(), AofError> {
+        if self.unsynced {
+            self.file
+                .sync_data()
+                .map_err(|e| AofError::Io(self.path.clone(), e))?;
+            self.unsynced = false;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+}
+
+/// Reads the records of a log that has a complete header, starting from byte `start`.
+fn replay<E: fmt::Display>(
+    path: &Path,
+    file: &mut File,
+    file_len: u64,
+    start: u64,
+    apply: &mut impl FnMut(Command) -> Result<(), E>,
+) -> Result<ReplayReport, AofError> {
+    let io_error = |e| AofError::Io(path.to_path_buf(), e);
+    let mut reader = BufReader::new(&mut *file);
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic).map_err(io_error)?;
+    if &magic != MAGIC {
+        return Err(AofError::NotAnAof(path.to_path_buf()));
+    }
+
+    let start = start.max(MAGIC.len() as u64);
+    if start > file_len {
+        return Err(AofError::Corrupted {
+            path: path.to_path_buf(),
+            offset: file_len,
+            reason: format!("the log should continue from byte {}", start),
+        });
+    }
+    reader.seek(SeekFrom::Start(start)).map_err(io_error)?;
+
+    let mut report = ReplayReport {
+        commands: 0,
+        valid_len: start,
+        file_len,
+        truncated: None,
+    };
+    let mut payload = Vec::new();
+    while report.valid_len < file_len {
+        let available = file_len - report.valid_len;
+        if available < RECORD_HEADER_LEN {
+            report.truncated = Some(TruncatedTail::Header { available });
+            break;
+        }
+        let mut header = [0; RECORD_HEADER_LEN as usize];
+        reader.read_exact(&mut header).map_err(io_error)?;
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as u64;
+        let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if available - RECORD_HEADER_LEN < len {
+            report.truncated = Some(TruncatedTail::Record {
+                expected: len,
+                available: available - RECORD_HEADER_LEN,
+            });
+            break;
+        }
+
+        payload.resize(len as usize, 0);
+        reader.read_exact(&mut payload).map_err(io_error)?;
+        let is_last = available == RECORD_HEADER_LEN + len;
+        if crc32fast::hash(&payload) != checksum {
+            if is_last {
+                report.truncated = Some(TruncatedTail::Checksum);
+                break;
+            }
+            return Err(AofError::Corrupted {
+                path: path.to_path_buf(),
+                offset: report.valid_len,
+                reason: "checksum mismatch".to_string(),
+            });
+        }
+        let command = serde_json::from_slice(&payload).map_err(|e| AofError::Corrupted {
+            path: path.to_path_buf(),
+            offset: report.valid_len,
+            reason: e.to_string(),
+        })?;
+        apply(command).map_err(|e| AofError::Rejected {
+            path: path.to_path_buf(),
+            offset: report.valid_len,
+            reason: e.to_string(),
+        })?;
+        report.commands += 1;
+        report.valid_len += RECORD_HEADER_LEN + len;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::commands::string::StringCommand;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "sphagnumdb-aof-{}-{}-{}",
+            std::process::id(),
+            rand::random::<u64>(),
+            name
+        ))
+    }
+
+    fn set(key: &str) -> Command {
+        Command::String(StringCommand::Set {
+            key: key.into(),
+            value: "value".into(),
+            expiration: None,
+        })
+    }
+
+    fn open(path: &Path) -> Result<(AppendOnlyLog, Vec<Command>, ReplayReport), AofError> {
+        let mut commands = Vec::new();
+        let (log, report) = AppendOnlyLog::open(path, FsyncPolicy::Always, |command| {
+            commands.push(command);
+            Ok::<_, AofError>(())
+        })?;
+        Ok((log, commands, report))
+    }
+
+    fn key_of(command: &Command) -> String {
+        match command {
+            Command::String(StringCommand::Set { key, .. }) => key.to_string(),
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reopened_log_replays_commands_in_order() {
+        // Arrange
+        let path = temp_path("replay.aof");
+        let (mut log, _, _) = open(&path).unwrap();
+        for key in ["a", "b", "c"] {
+            log.append(&set(key)).unwrap();
+        }
+        drop(log);
+
+        // Act
+        let (_, commands, report) = open(&path).unwrap();
+
+        // Assert
+        let keys: Vec<String> = commands.iter().map(key_of).collect();
+        assert_eq!(keys, vec!["a", "b", "c"]);
+        assert_eq!(report.commands, 3);
+        assert_eq!(report.valid_len, report.file_len);
+        assert_eq!(report.truncated, None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_log_is_replayed_from_an_offset() {
+        // Arrange
+        let path = temp_path("offset.aof");
+        let (mut log, _, _) = open(&path).unwrap();
+        log.append(&set("a")).unwrap();
+        let offset = log.offset();
+        log.append(&set("b")).unwrap();
+        let end = log.offset();
+        drop(log);
+
+        // Act
+        let mut commands = Vec::new();
+        let (log, report) = AppendOnlyLog::open_from(&path, FsyncPolicy::No, offset, |command| {
+            commands.push(command);
+            Ok::<_, AofError>(())
+        })
+        .unwrap();
+        let beyond =
+            AppendOnlyLog::open_from(&path, FsyncPolicy::No, end + 1, |_| Ok::<_, AofError>(()));
+
+        // Assert
+        let keys: Vec<String> = commands.iter().map(key_of).collect();
+        assert_eq!(keys, vec!["b"]);
+        assert_eq!(report.commands, 1);
+        assert_eq!(end, std::fs::metadata(&path).unwrap().len());
+        assert_eq!(log.offset(), end);
+        assert!(matches!(beyond, Err(AofError::Corrupted { .. })));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_tail_is_reported_and_cut_off() {
+        // Arrange
+        let path = temp_path("truncated.aof");
+        let (mut log, _, _) = open(&path).unwrap();
+        log.append(&set("a")).unwrap();
+        log.append(&set("b")).unwrap();
+        drop(log);
+        let complete_len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(complete_len - 5).unwrap();
+
+        // Act
+        let (mut log, commands, report) = open(&path).unwrap();
+        log.append(&set("c")).unwrap();
+        drop(log);
+        let (_, reopened, reopened_report) = open(&path).unwrap();
+
+        // Assert
+        assert_eq!(commands.len(), 1);
+        let expected = complete_len - report.valid_len - RECORD_HEADER_LEN;
+        assert_eq!(
+            report.truncated,
+            Some(TruncatedTail::Record {
+                expected,
+                available: expected - 5,
+            })
+        );
+        assert!(report.to_string().contains("(record 2)"));
+        let keys: Vec<String> = reopened.iter().map(key_of).collect();
+        assert_eq!(keys, vec!["a", "c"]);
+        assert_eq!(reopened_report.truncated, None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_damaged_record_in_the_middle_is_an_error() {
+        // Arrange
+        let path = temp_path("corrupted.aof");
+        let (mut log, _, _) = open(&path).unwrap();
+        log.append(&set("a")).unwrap();
+        log.append(&set("b")).unwrap();
+        drop(log);
+        let mut content = std::fs::read(&path).unwrap();
+        let damaged = MAGIC.len() + RECORD_HEADER_LEN as usize + 2;
+        content[damaged] ^= 0xff;
+        std::fs::write(&path, content).unwrap();
+
+        // Act
+        let result = open(&path);
+
+        // Assert
+        match result {
+            Err(AofError::Corrupted { offset, .. }) => assert_eq!(offset, MAGIC.len() as u64),
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("The damaged log was accepted"),
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_command_that_fails_on_replay_is_an_error() {
+        // Arrange
+        let path = temp_path("rejected.aof");
+        let (mut log, _, _) = open(&path).unwrap();
+        log.append(&set("a")).unwrap();
+        let offset = log.offset();
+        log.append(&set("b")).unwrap();
+        drop(log);
+
+        // Act
+        let result = AppendOnlyLog::open(&path, FsyncPolicy::No, |command| {
+            match key_of(&command).as_str() {
+                "b" => Err("WRONGTYPE"),
+                _ => Ok(()),
+            }
+        });
+
+        // Assert
+        match result {
+            Err(AofError::Rejected { offset: at, .. }) => assert_eq!(at, offset),
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("The failing command was accepted"),
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{error::Error, fmt, time::Duration};
+
+use libp2p::PeerId;
+use tokio::sync::{mpsc, oneshot};
+
+use super::{
+    commands::{Command, CommandResult},
+    req_resp_codec::ReplyError,
+};
+
+/// How long `SphagnumClient::execute` waits for a reply unless configured otherwise.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientError {
+    /// The command was executed and failed, e.g. it was run against a key of another type.
+    Command(ReplyError),
+    /// No reply arrived in time. The command may still have been executed.
+    Timeout,
+    /// The connection to the target node was closed before it replied.
+    PeerDisconnected,
+    /// The request could not be delivered, e.g. the target node is unreachable.
+    RequestFailed(String),
+    /// The node the client was created from is no longer running.
+    NodeStopped,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Command(e) => write!(f, "{}", e.message),
+            ClientError::Timeout => write!(f, "Request timed out"),
+            ClientError::PeerDisconnected => write!(f, "Peer disconnected before replying"),
+            ClientError::RequestFailed(reason) => write!(f, "Request failed: {}", reason),
+            ClientError::NodeStopped => write!(f, "Node is not running"),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+pub(crate) type ClientReply = Result<CommandResult, ClientError>;
+
+/// A command submitted by a client, the node sends the outcome back through `reply`.
+pub(crate) struct ClientRequest {
+    pub target: PeerId,
+    pub command: Command,
+    pub reply: oneshot::Sender<ClientReply>,
+}
+
+/// Executes commands on a node of the cluster and returns their results to the caller.
+///
+/// Clients are created with `SphagnumNode::client` and talk to that node, which must keep handling
+/// events for requests to make progress. They are cheap to clone and can be moved to other tasks.
+#[derive(Clone)]
+pub struct SphagnumClient {
+    target: PeerId,
+    requests: mpsc::UnboundedSender<ClientRequest>,
+    timeout: Duration,
+}
+
+impl SphagnumClient {
+    pub(crate) fn new(target: PeerId, requests: mpsc::UnboundedSender<ClientRequest>) -> Self {
+        Self {
+            target,
+            requests,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Sets how long each request waits for a reply.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The node commands are executed on.
+    pub fn target(&self) -> PeerId {
+        self.target
+    }
+
+    pub async fn execute(&self, command: Command) -> Result<CommandResult, ClientError> {
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send(ClientRequest {
+                target: self.target,
+                command,
+                reply,
+            })
+            .map_err(|_| ClientError::NodeStopped)?;
+        match tokio::time::timeout(self.timeout, response).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(ClientError::NodeStopped),
+            Err(_) => Err(ClientError::Timeout),
+        }
+    }
+}
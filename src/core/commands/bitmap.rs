@@ -0,0 +1,84 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use crate::core::bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// Whether the bounds of a BITCOUNT/BITPOS range are given in bytes or in bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum BitUnit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+/// An inclusive range, negative values count from the end of the bitmap.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BitRange {
+    pub start: i64,
+    /// None means the end of the bitmap.
+    pub end: Option<i64>,
+    pub unit: BitUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BitmapCommand {
+    SetBit {
+        key: Bytes,
+        offset: u64,
+        value: bool,
+    },
+    GetBit {
+        key: Bytes,
+        offset: u64,
+    },
+    Count {
+        key: Bytes,
+        range: Option<BitRange>,
+    },
+    Pos {
+        key: Bytes,
+        bit: bool,
+        range: Option<BitRange>,
+    },
+    Op {
+        operation: BitOperation,
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+}
+
+impl BitmapCommand {
+    /// Returns true if the command modifies the stored data and therefore has to be replicated.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            BitmapCommand::SetBit { .. } | BitmapCommand::Op { .. }
+        )
+    }
+
+    /// Returns the keys the command works on, the keyspace loads them from the storage engine
+    /// before the command is executed.
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            BitmapCommand::SetBit { key, .. }
+            | BitmapCommand::GetBit { key, .. }
+            | BitmapCommand::Count { key, .. }
+            | BitmapCommand::Pos { key, .. } => vec![key],
+            BitmapCommand::Op {
+                destination, keys, ..
+            } => std::iter::once(destination.as_slice())
+                .chain(keys.iter().map(Bytes::as_slice))
+                .collect(),
+        }
+    }
+}
@@ -2,11 +2,178 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
+use crate::core::bytes::Bytes;
+use crate::core::commands::sorted_set::LexBound;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GenericCommand {
-    Exists { keys: Vec<String> },
-    Delete { keys: Vec<String> },
+    Exists {
+        keys: Vec<Bytes>,
+    },
+    Delete {
+        keys: Vec<Bytes>,
+    },
+    /// A non-positive timeout deletes the key.
+    Expire {
+        key: Bytes,
+        seconds: i64,
+    },
+    PExpire {
+        key: Bytes,
+        milliseconds: i64,
+    },
+    /// A deadline in the past deletes the key.
+    ExpireAt {
+        key: Bytes,
+        timestamp: i64,
+    },
+    PExpireAt {
+        key: Bytes,
+        timestamp: i64,
+    },
+    /// Remaining time to live in seconds: -2 if the key does not exist, -1 if it has no deadline.
+    Ttl {
+        key: Bytes,
+    },
+    PTtl {
+        key: Bytes,
+    },
+    Persist {
+        key: Bytes,
+    },
+    /// Incrementally iterates over the keyspace. A scan starts and ends with the cursor "0",
+    /// every key present for the whole scan is returned exactly once.
+    Scan {
+        cursor: String,
+        /// Glob-style pattern the keys must match.
+        #[serde(default)]
+        pattern: Option<Bytes>,
+        /// How many keys to visit in this call, 10 by default.
+        #[serde(default)]
+        count: Option<u64>,
+        /// Only keys holding this type: "string", "hash", "list", "set", "zset" or "bitmap".
+        #[serde(default)]
+        value_type: Option<String>,
+    },
+    /// All keys matching a glob-style pattern. Walks the whole keyspace at once.
+    Keys {
+        pattern: Bytes,
+    },
+    /// Returns the keys between the bounds in order, or from the highest with `rev`, up to
+    /// `limit` keys. `-` and `+` leave the range open, as in ZRANGE BYLEX.
+    /// With `with_values` every key is followed by its value. Only string values are returned,
+    /// a key holding another type is followed by nil.
+    Range {
+        start: LexBound,
+        end: LexBound,
+        #[serde(default)]
+        rev: bool,
+        #[serde(default)]
+        limit: Option<u64>,
+        #[serde(default)]
+        with_values: bool,
+    },
+    /// Returns the keys starting with the prefix in order, up to `limit` keys, see `Range`.
+    Prefix {
+        prefix: Bytes,
+        #[serde(default)]
+        limit: Option<u64>,
+        #[serde(default)]
+        with_values: bool,
+    },
+    DbSize,
+    RandomKey,
+    /// Fails with "no such key" if the key does not exist. The deadline moves with the value.
+    Rename {
+        key: Bytes,
+        new_key: Bytes,
+    },
+    /// Renames the key only if the new key does not exist.
+    RenameNx {
+        key: Bytes,
+        new_key: Bytes,
+    },
+    /// Copies the value and its deadline. The destination is only overwritten with `replace`.
+    Copy {
+        source: Bytes,
+        destination: Bytes,
+        #[serde(default)]
+        replace: bool,
+    },
+    /// The type of the value: "string", "hash", "list", "set", "zset", "bitmap" or "none".
+    Type {
+        key: Bytes,
+    },
+    /// Updates the access time of the keys and returns how many of them exist.
+    Touch {
+        keys: Vec<Bytes>,
+    },
+    /// Same as DEL.
+    Unlink {
+        keys: Vec<Bytes>,
+    },
+    ObjectEncoding {
+        key: Bytes,
+    },
+    /// Seconds since the key was last read or written.
+    ObjectIdleTime {
+        key: Bytes,
+    },
     // TODO
 }
+
+impl GenericCommand {
+    /// Returns true if the command modifies the stored data and therefore has to be replicated.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            GenericCommand::Delete { .. }
+                | GenericCommand::Expire { .. }
+                | GenericCommand::PExpire { .. }
+                | GenericCommand::ExpireAt { .. }
+                | GenericCommand::PExpireAt { .. }
+                | GenericCommand::Persist { .. }
+                | GenericCommand::Rename { .. }
+                | GenericCommand::RenameNx { .. }
+                | GenericCommand::Copy { .. }
+                | GenericCommand::Unlink { .. }
+        )
+    }
+
+    /// Returns the keys the command works on, the keyspace loads them from the storage engine
+    /// before the command is executed.
+    /// SCAN, KEYS, RANGE, PREFIX and RANDOMKEY walk the keyspace instead.
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            GenericCommand::Exists { keys }
+            | GenericCommand::Delete { keys }
+            | GenericCommand::Touch { keys }
+            | GenericCommand::Unlink { keys } => keys.iter().map(Bytes::as_slice).collect(),
+            GenericCommand::Expire { key, .. }
+            | GenericCommand::PExpire { key, .. }
+            | GenericCommand::ExpireAt { key, .. }
+            | GenericCommand::PExpireAt { key, .. }
+            | GenericCommand::Ttl { key }
+            | GenericCommand::PTtl { key }
+            | GenericCommand::Persist { key }
+            | GenericCommand::Type { key }
+            | GenericCommand::ObjectEncoding { key }
+            | GenericCommand::ObjectIdleTime { key } => vec![key],
+            GenericCommand::Rename { key, new_key } | GenericCommand::RenameNx { key, new_key } => {
+                vec![key, new_key]
+            }
+            GenericCommand::Copy {
+                source,
+                destination,
+                ..
+            } => vec![source, destination],
+            GenericCommand::Scan { .. }
+            | GenericCommand::Keys { .. }
+            | GenericCommand::Range { .. }
+            | GenericCommand::Prefix { .. }
+            | GenericCommand::DbSize
+            | GenericCommand::RandomKey => Vec::new(),
+        }
+    }
+}
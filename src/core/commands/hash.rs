@@ -0,0 +1,83 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use crate::core::bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum HashCommand {
+    Set {
+        key: Bytes,
+        fields: Vec<(Bytes, Bytes)>,
+    },
+    Get {
+        key: Bytes,
+        field: Bytes,
+    },
+    MGet {
+        key: Bytes,
+        fields: Vec<Bytes>,
+    },
+    Del {
+        key: Bytes,
+        fields: Vec<Bytes>,
+    },
+    Exists {
+        key: Bytes,
+        field: Bytes,
+    },
+    Len {
+        key: Bytes,
+    },
+    Keys {
+        key: Bytes,
+    },
+    Vals {
+        key: Bytes,
+    },
+    GetAll {
+        key: Bytes,
+    },
+    IncrBy {
+        key: Bytes,
+        field: Bytes,
+        increment: i64,
+    },
+    SetNx {
+        key: Bytes,
+        field: Bytes,
+        value: Bytes,
+    },
+}
+
+impl HashCommand {
+    /// Returns true if the command modifies the stored data and therefore has to be replicated.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            HashCommand::Set { .. }
+                | HashCommand::Del { .. }
+                | HashCommand::IncrBy { .. }
+                | HashCommand::SetNx { .. }
+        )
+    }
+
+    /// Returns the keys the command works on, the keyspace loads them from the storage engine
+    /// before the command is executed.
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            HashCommand::Set { key, .. }
+            | HashCommand::Get { key, .. }
+            | HashCommand::MGet { key, .. }
+            | HashCommand::Del { key, .. }
+            | HashCommand::Exists { key, .. }
+            | HashCommand::Len { key }
+            | HashCommand::Keys { key }
+            | HashCommand::Vals { key }
+            | HashCommand::GetAll { key }
+            | HashCommand::IncrBy { key, .. }
+            | HashCommand::SetNx { key, .. } => vec![key],
+        }
+    }
+}
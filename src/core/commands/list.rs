@@ -0,0 +1,112 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use crate::core::bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// The end of a list that an element is pushed to or popped from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+/// Where LINSERT puts the new element relative to the pivot.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InsertPosition {
+    Before,
+    After,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ListCommand {
+    LPush {
+        key: Bytes,
+        values: Vec<Bytes>,
+    },
+    RPush {
+        key: Bytes,
+        values: Vec<Bytes>,
+    },
+    LPop {
+        key: Bytes,
+        count: Option<u64>,
+    },
+    RPop {
+        key: Bytes,
+        count: Option<u64>,
+    },
+    Range {
+        key: Bytes,
+        start: i64,
+        stop: i64,
+    },
+    Index {
+        key: Bytes,
+        index: i64,
+    },
+    Set {
+        key: Bytes,
+        index: i64,
+        value: Bytes,
+    },
+    Len {
+        key: Bytes,
+    },
+    Insert {
+        key: Bytes,
+        position: InsertPosition,
+        pivot: Bytes,
+        value: Bytes,
+    },
+    Rem {
+        key: Bytes,
+        count: i64,
+        value: Bytes,
+    },
+    Trim {
+        key: Bytes,
+        start: i64,
+        stop: i64,
+    },
+    Move {
+        source: Bytes,
+        destination: Bytes,
+        from: ListEnd,
+        to: ListEnd,
+    },
+}
+
+impl ListCommand {
+    /// Returns true if the command modifies the stored data and therefore has to be replicated.
+    pub fn is_write(&self) -> bool {
+        !matches!(
+            self,
+            ListCommand::Range { .. } | ListCommand::Index { .. } | ListCommand::Len { .. }
+        )
+    }
+
+    /// Returns the keys the command works on, the keyspace loads them from the storage engine
+    /// before the command is executed.
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            ListCommand::LPush { key, .. }
+            | ListCommand::RPush { key, .. }
+            | ListCommand::LPop { key, .. }
+            | ListCommand::RPop { key, .. }
+            | ListCommand::Range { key, .. }
+            | ListCommand::Index { key, .. }
+            | ListCommand::Set { key, .. }
+            | ListCommand::Len { key }
+            | ListCommand::Insert { key, .. }
+            | ListCommand::Rem { key, .. }
+            | ListCommand::Trim { key, .. } => vec![key],
+            ListCommand::Move {
+                source,
+                destination,
+                ..
+            } => vec![source, destination],
+        }
+    }
+}
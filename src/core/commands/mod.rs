@@ -2,25 +2,322 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-use crate::core::commands::{generic::GenericCommand, string::StringCommand};
+use crate::core::bytes::Bytes;
+use crate::core::commands::{
+    bitmap::BitmapCommand,
+    generic::GenericCommand,
+    hash::HashCommand,
+    list::ListCommand,
+    server::ServerCommand,
+    set::SetCommand,
+    sorted_set::SortedSetCommand,
+    string::{GetExExpiration, SetExpiration, StringCommand},
+};
 use serde::{Deserialize, Serialize};
 
+pub mod bitmap;
 pub mod generic;
+pub mod hash;
+pub mod list;
+pub mod server;
+pub mod set;
+pub mod sorted_set;
 pub mod string;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Command {
     String(StringCommand),
+    Hash(HashCommand),
+    List(ListCommand),
+    Set(SetCommand),
+    SortedSet(SortedSetCommand),
+    Bitmap(BitmapCommand),
     Generic(GenericCommand),
+    Server(ServerCommand),
     // TODO
 }
 
+impl Command {
+    /// Returns true if the command modifies the stored data.
+    /// Only such commands are sent to the replica set.
+    pub fn is_write(&self) -> bool {
+        match self {
+            Command::String(cmd) => cmd.is_write(),
+            Command::Hash(cmd) => cmd.is_write(),
+            Command::List(cmd) => cmd.is_write(),
+            Command::Set(cmd) => cmd.is_write(),
+            Command::SortedSet(cmd) => cmd.is_write(),
+            Command::Bitmap(cmd) => cmd.is_write(),
+            Command::Generic(cmd) => cmd.is_write(),
+            Command::Server(_) => false,
+        }
+    }
+
+    /// Returns the keys the command works on, see `StringCommand::keys`.
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            Command::String(cmd) => cmd.keys(),
+            Command::Hash(cmd) => cmd.keys(),
+            Command::List(cmd) => cmd.keys(),
+            Command::Set(cmd) => cmd.keys(),
+            Command::SortedSet(cmd) => cmd.keys(),
+            Command::Bitmap(cmd) => cmd.keys(),
+            Command::Generic(cmd) => cmd.keys(),
+            Command::Server(_) => Vec::new(),
+        }
+    }
+
+    /// Returns the command that reproduces the effect of this one on a replica.
+    /// Most commands are deterministic and are replicated as is, while random ones are rewritten
+    /// into their observed outcome (e.g. SPOP becomes SREM of the popped members).
+    /// INCRBYFLOAT is replicated as SET of the result, so that floating point rounding cannot
+    /// make the replicas diverge.
+    pub fn for_replication(self, result: &CommandResult) -> Command {
+        match (self, result) {
+            (Command::Set(SetCommand::Pop { key, .. }), result) => {
+                let members = match result {
+                    CommandResult::String(member) => vec![member.clone()],
+                    CommandResult::Array(members) => members
+                        .iter()
+                        .filter_map(|member| match member {
+                            CommandResult::String(member) => Some(member.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                Command::Set(SetCommand::Rem { key, members })
+            }
+            (
+                Command::String(StringCommand::IncrByFloat { key, .. }),
+                CommandResult::String(value),
+            ) => Command::String(StringCommand::Set {
+                key,
+                value: value.clone(),
+                expiration: Some(SetExpiration::KeepTtl),
+            }),
+            (command, _) => command,
+        }
+    }
+
+    /// Replaces relative expiration times with absolute deadlines computed from `now`
+    /// (milliseconds since the Unix epoch). The node does this before executing a command, so
+    /// that the primary and its replicas expire the key at the same moment.
+    /// Zero timeouts are left as they are, so that they are still rejected as invalid.
+    pub fn with_absolute_expiry(self, now: u64) -> Command {
+        let now = now as i64;
+        match self {
+            Command::Generic(GenericCommand::Expire { key, seconds }) => {
+                Command::Generic(GenericCommand::PExpireAt {
+                    key,
+                    timestamp: now.saturating_add(seconds.saturating_mul(1000)),
+                })
+            }
+            Command::Generic(GenericCommand::PExpire { key, milliseconds }) => {
+                Command::Generic(GenericCommand::PExpireAt {
+                    key,
+                    timestamp: now.saturating_add(milliseconds),
+                })
+            }
+            Command::Generic(GenericCommand::ExpireAt { key, timestamp }) => {
+                Command::Generic(GenericCommand::PExpireAt {
+                    key,
+                    timestamp: timestamp.saturating_mul(1000),
+                })
+            }
+            Command::String(StringCommand::Set {
+                key,
+                value,
+                expiration,
+            }) => {
+                let expiration = expiration.map(|expiration| match expiration {
+                    SetExpiration::Ex(seconds) if seconds > 0 => SetExpiration::PxAt(
+                        (now as u64).saturating_add(seconds.saturating_mul(1000)),
+                    ),
+                    SetExpiration::Px(milliseconds) if milliseconds > 0 => {
+                        SetExpiration::PxAt((now as u64).saturating_add(milliseconds))
+                    }
+                    SetExpiration::ExAt(timestamp) if timestamp > 0 => {
+                        SetExpiration::PxAt(timestamp.saturating_mul(1000))
+                    }
+                    other => other,
+                });
+                Command::String(StringCommand::Set {
+                    key,
+                    value,
+                    expiration,
+                })
+            }
+            Command::String(StringCommand::GetEx { key, expiration }) => {
+                let expiration = expiration.map(|expiration| match expiration {
+                    GetExExpiration::Ex(seconds) if seconds > 0 => GetExExpiration::PxAt(
+                        (now as u64).saturating_add(seconds.saturating_mul(1000)),
+                    ),
+                    GetExExpiration::Px(milliseconds) if milliseconds > 0 => {
+                        GetExExpiration::PxAt((now as u64).saturating_add(milliseconds))
+                    }
+                    GetExExpiration::ExAt(timestamp) if timestamp > 0 => {
+                        GetExExpiration::PxAt(timestamp.saturating_mul(1000))
+                    }
+                    other => other,
+                });
+                Command::String(StringCommand::GetEx { key, expiration })
+            }
+            command => command,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum CommandResult {
     // todo: check other docs
-    String(String),
-    Int(u64),
+    String(Bytes),
+    Int(i64),
     Bool(bool),
+    Array(Vec<CommandResult>),
     Nil,
     Error(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_replication_rewrites_spop_into_srem_of_popped_members() {
+        // Arrange
+        let command = Command::Set(SetCommand::Pop {
+            key: "key".into(),
+            count: Some(2),
+        });
+        let result = CommandResult::Array(vec![
+            CommandResult::String("a".into()),
+            CommandResult::String("b".into()),
+        ]);
+
+        // Act
+        let replicated = command.for_replication(&result);
+
+        // Assert
+        match replicated {
+            Command::Set(SetCommand::Rem { key, members }) => {
+                assert_eq!(key, "key");
+                assert_eq!(members, vec![Bytes::from("a"), Bytes::from("b")]);
+            }
+            other => panic!("Unexpected replicated command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_for_replication_keeps_deterministic_commands() {
+        // Arrange
+        let command = Command::String(StringCommand::Append {
+            key: "key".into(),
+            value: "value".into(),
+        });
+
+        // Act
+        let replicated = command.for_replication(&CommandResult::Int(5));
+
+        // Assert
+        assert!(matches!(
+            replicated,
+            Command::String(StringCommand::Append { .. })
+        ));
+    }
+
+    #[test]
+    fn test_for_replication_rewrites_incrbyfloat_into_set_of_the_result() {
+        // Arrange
+        let command = Command::String(StringCommand::IncrByFloat {
+            key: "key".into(),
+            increment: 0.1,
+        });
+
+        // Act
+        let replicated = command.for_replication(&CommandResult::String("10.6".into()));
+
+        // Assert
+        match replicated {
+            Command::String(StringCommand::Set {
+                key,
+                value,
+                expiration,
+            }) => {
+                assert_eq!(key, "key");
+                assert_eq!(value, "10.6");
+                assert_eq!(expiration, Some(SetExpiration::KeepTtl));
+            }
+            other => panic!("Unexpected replicated command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_with_absolute_expiry_converts_relative_timeouts_into_deadlines() {
+        // Arrange
+        let expire = Command::Generic(GenericCommand::Expire {
+            key: "key".into(),
+            seconds: 10,
+        });
+        let set = Command::String(StringCommand::Set {
+            key: "key".into(),
+            value: "value".into(),
+            expiration: Some(SetExpiration::Px(500)),
+        });
+
+        // Act
+        let expire = expire.with_absolute_expiry(1_000);
+        let set = set.with_absolute_expiry(1_000);
+
+        // Assert
+        assert!(matches!(
+            expire,
+            Command::Generic(GenericCommand::PExpireAt {
+                timestamp: 11_000,
+                ..
+            })
+        ));
+        assert!(matches!(
+            set,
+            Command::String(StringCommand::Set {
+                expiration: Some(SetExpiration::PxAt(1_500)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_with_absolute_expiry_keeps_invalid_and_keepttl_options() {
+        // Arrange
+        let zero = Command::String(StringCommand::Set {
+            key: "key".into(),
+            value: "value".into(),
+            expiration: Some(SetExpiration::Ex(0)),
+        });
+        let keep_ttl = Command::String(StringCommand::Set {
+            key: "key".into(),
+            value: "value".into(),
+            expiration: Some(SetExpiration::KeepTtl),
+        });
+
+        // Act
+        let zero = zero.with_absolute_expiry(1_000);
+        let keep_ttl = keep_ttl.with_absolute_expiry(1_000);
+
+        // Assert
+        assert!(matches!(
+            zero,
+            Command::String(StringCommand::Set {
+                expiration: Some(SetExpiration::Ex(0)),
+                ..
+            })
+        ));
+        assert!(matches!(
+            keep_ttl,
+            Command::String(StringCommand::Set {
+                expiration: Some(SetExpiration::KeepTtl),
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,29 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+/// Commands that control the node itself rather than the stored data.
+/// They are executed by the node, never by `DataStorage`, and are not replicated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ServerCommand {
+    /// Stops accepting requests, finishes the ones in flight and stops the node.
+    Shutdown,
+    /// Returns the peers the node is connected to.
+    ClusterPeers,
+    /// Returns the peers the node replicates its writes to.
+    ClusterReplicas,
+    /// Makes the node dial another node. The reply only means that the dial has started,
+    /// an address the node can't dial at all is an invalid argument.
+    ClusterMeet { addr: Multiaddr },
+    /// Adds a peer to the replica set of the node, which can't be the node itself.
+    ClusterAddReplica { peer_id: PeerId },
+    /// Saves a snapshot of the keyspace before replying, the node handles nothing else meanwhile.
+    Save,
+    /// Starts saving a snapshot of the keyspace, the node keeps handling requests meanwhile.
+    BgSave,
+    /// Returns when the last snapshot was saved, in seconds since the Unix epoch.
+    LastSave,
+}
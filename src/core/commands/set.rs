@@ -0,0 +1,101 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use crate::core::bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SetCommand {
+    Add {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    Rem {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    IsMember {
+        key: Bytes,
+        member: Bytes,
+    },
+    MIsMember {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    Members {
+        key: Bytes,
+    },
+    Card {
+        key: Bytes,
+    },
+    Pop {
+        key: Bytes,
+        count: Option<u64>,
+    },
+    RandMember {
+        key: Bytes,
+        count: Option<i64>,
+    },
+    Inter {
+        keys: Vec<Bytes>,
+    },
+    Union {
+        keys: Vec<Bytes>,
+    },
+    Diff {
+        keys: Vec<Bytes>,
+    },
+    InterStore {
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+    UnionStore {
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+    DiffStore {
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+}
+
+impl SetCommand {
+    /// Returns true if the command modifies the stored data and therefore has to be replicated.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            SetCommand::Add { .. }
+                | SetCommand::Rem { .. }
+                | SetCommand::Pop { .. }
+                | SetCommand::InterStore { .. }
+                | SetCommand::UnionStore { .. }
+                | SetCommand::DiffStore { .. }
+        )
+    }
+
+    /// Returns the keys the command works on, the keyspace loads them from the storage engine
+    /// before the command is executed.
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            SetCommand::Add { key, .. }
+            | SetCommand::Rem { key, .. }
+            | SetCommand::IsMember { key, .. }
+            | SetCommand::MIsMember { key, .. }
+            | SetCommand::Members { key }
+            | SetCommand::Card { key }
+            | SetCommand::Pop { key, .. }
+            | SetCommand::RandMember { key, .. } => vec![key],
+            SetCommand::Inter { keys } | SetCommand::Union { keys } | SetCommand::Diff { keys } => {
+                keys.iter().map(Bytes::as_slice).collect()
+            }
+            SetCommand::InterStore { destination, keys }
+            | SetCommand::UnionStore { destination, keys }
+            | SetCommand::DiffStore { destination, keys } => {
+                std::iter::once(destination.as_slice())
+                    .chain(keys.iter().map(Bytes::as_slice))
+                    .collect()
+            }
+        }
+    }
+}
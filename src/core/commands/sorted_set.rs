@@ -0,0 +1,251 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use crate::core::bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// NX/XX condition of ZADD: only add new members or only update existing ones.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ZAddCondition {
+    Nx,
+    Xx,
+}
+
+/// GT/LT comparison of ZADD: only update a score if the new one is greater/less.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ZAddComparison {
+    Gt,
+    Lt,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ZAddOptions {
+    pub condition: Option<ZAddCondition>,
+    pub comparison: Option<ZAddComparison>,
+    /// Count changed members in the result, not only added ones.
+    pub ch: bool,
+    /// Increment the score like ZINCRBY, only a single member is allowed.
+    pub incr: bool,
+}
+
+/// A bound of a score interval, `-inf`/`+inf` are inclusive infinite bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+impl FromStr for ScoreBound {
+    type Err = String;
+
+    /// Parses `1.5`, `(1.5`, `-inf` and `+inf`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (exclusive, value) = match s.strip_prefix('(') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let value = match value {
+            "-inf" => f64::NEG_INFINITY,
+            "+inf" | "inf" => f64::INFINITY,
+            value => value
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid score bound: {}", s))?,
+        };
+        Ok(if exclusive {
+            ScoreBound::Exclusive(value)
+        } else {
+            ScoreBound::Inclusive(value)
+        })
+    }
+}
+
+/// A bound of a lexicographical interval, `-`/`+` are the minimal and maximal strings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+}
+
+impl FromStr for LexBound {
+    type Err = String;
+
+    /// Parses `-`, `+`, `[member` and `(member`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "-" => Ok(LexBound::Min),
+            "+" => Ok(LexBound::Max),
+            _ => {
+                if let Some(rest) = s.strip_prefix('[') {
+                    Ok(LexBound::Inclusive(rest.into()))
+                } else if let Some(rest) = s.strip_prefix('(') {
+                    Ok(LexBound::Exclusive(rest.into()))
+                } else {
+                    Err(format!("Invalid lex bound: {}", s))
+                }
+            }
+        }
+    }
+}
+
+/// What the interval of ZRANGE refers to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ZRangeBy {
+    /// Ranks, negative values count from the end.
+    Index {
+        start: i64,
+        stop: i64,
+    },
+    Score {
+        min: ScoreBound,
+        max: ScoreBound,
+    },
+    /// Only meaningful when all members have the same score.
+    Lex {
+        min: LexBound,
+        max: LexBound,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ZRangeLimit {
+    pub offset: u64,
+    pub count: u64,
+}
+
+/// How scores of the same member are combined by ZUNIONSTORE/ZINTERSTORE.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SortedSetCommand {
+    Add {
+        key: Bytes,
+        members: Vec<(f64, Bytes)>,
+        options: ZAddOptions,
+    },
+    Rem {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    Score {
+        key: Bytes,
+        member: Bytes,
+    },
+    Rank {
+        key: Bytes,
+        member: Bytes,
+    },
+    RevRank {
+        key: Bytes,
+        member: Bytes,
+    },
+    /// With `rev` the result is ordered from the highest score, the bounds keep their meaning.
+    Range {
+        key: Bytes,
+        by: ZRangeBy,
+        rev: bool,
+        limit: Option<ZRangeLimit>,
+        with_scores: bool,
+    },
+    Count {
+        key: Bytes,
+        min: ScoreBound,
+        max: ScoreBound,
+    },
+    IncrBy {
+        key: Bytes,
+        increment: f64,
+        member: Bytes,
+    },
+    PopMin {
+        key: Bytes,
+        count: Option<u64>,
+    },
+    PopMax {
+        key: Bytes,
+        count: Option<u64>,
+    },
+    UnionStore {
+        destination: Bytes,
+        keys: Vec<Bytes>,
+        weights: Option<Vec<f64>>,
+        aggregate: Aggregate,
+    },
+    InterStore {
+        destination: Bytes,
+        keys: Vec<Bytes>,
+        weights: Option<Vec<f64>>,
+        aggregate: Aggregate,
+    },
+}
+
+impl SortedSetCommand {
+    /// Returns true if the command modifies the stored data and therefore has to be replicated.
+    pub fn is_write(&self) -> bool {
+        !matches!(
+            self,
+            SortedSetCommand::Score { .. }
+                | SortedSetCommand::Rank { .. }
+                | SortedSetCommand::RevRank { .. }
+                | SortedSetCommand::Range { .. }
+                | SortedSetCommand::Count { .. }
+        )
+    }
+
+    /// Returns the keys the command works on, the keyspace loads them from the storage engine
+    /// before the command is executed.
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            SortedSetCommand::Add { key, .. }
+            | SortedSetCommand::Rem { key, .. }
+            | SortedSetCommand::Score { key, .. }
+            | SortedSetCommand::Rank { key, .. }
+            | SortedSetCommand::RevRank { key, .. }
+            | SortedSetCommand::Range { key, .. }
+            | SortedSetCommand::Count { key, .. }
+            | SortedSetCommand::IncrBy { key, .. }
+            | SortedSetCommand::PopMin { key, .. }
+            | SortedSetCommand::PopMax { key, .. } => vec![key],
+            SortedSetCommand::UnionStore {
+                destination, keys, ..
+            }
+            | SortedSetCommand::InterStore {
+                destination, keys, ..
+            } => std::iter::once(destination.as_slice())
+                .chain(keys.iter().map(Bytes::as_slice))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_bound_from_str() {
+        assert_eq!("1.5".parse(), Ok(ScoreBound::Inclusive(1.5)));
+        assert_eq!("(2".parse(), Ok(ScoreBound::Exclusive(2.0)));
+        assert_eq!("-inf".parse(), Ok(ScoreBound::Inclusive(f64::NEG_INFINITY)));
+        assert_eq!("(+inf".parse(), Ok(ScoreBound::Exclusive(f64::INFINITY)));
+        assert!("abc".parse::<ScoreBound>().is_err());
+    }
+
+    #[test]
+    fn test_lex_bound_from_str() {
+        assert_eq!("-".parse(), Ok(LexBound::Min));
+        assert_eq!("+".parse(), Ok(LexBound::Max));
+        assert_eq!("[a".parse(), Ok(LexBound::Inclusive("a".into())));
+        assert_eq!("(a".parse(), Ok(LexBound::Exclusive("a".into())));
+        assert!("a".parse::<LexBound>().is_err());
+    }
+}
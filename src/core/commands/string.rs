@@ -2,12 +2,150 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
+use crate::core::bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
+/// Expiration options of the SET command.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SetExpiration {
+    /// Expire after the given number of seconds.
+    Ex(u64),
+    /// Expire after the given number of milliseconds.
+    Px(u64),
+    /// Expire at the given Unix time in seconds.
+    ExAt(u64),
+    /// Expire at the given Unix time in milliseconds.
+    PxAt(u64),
+    /// Retain the deadline the key already has.
+    KeepTtl,
+}
+
+/// Expiration options of the GETEX command.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GetExExpiration {
+    Ex(u64),
+    Px(u64),
+    ExAt(u64),
+    PxAt(u64),
+    /// Remove the deadline of the key.
+    Persist,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum StringCommand {
-    Set { key: String, value: String },
-    Get { key: String },
-    Append { key: String, value: String },
+    Set {
+        key: Bytes,
+        value: Bytes,
+        /// Without an expiration option, SET discards the deadline the key had.
+        #[serde(default)]
+        expiration: Option<SetExpiration>,
+    },
+    Get {
+        key: Bytes,
+    },
+    Append {
+        key: Bytes,
+        value: Bytes,
+    },
+    Incr {
+        key: Bytes,
+    },
+    Decr {
+        key: Bytes,
+    },
+    IncrBy {
+        key: Bytes,
+        increment: i64,
+    },
+    DecrBy {
+        key: Bytes,
+        decrement: i64,
+    },
+    IncrByFloat {
+        key: Bytes,
+        increment: f64,
+    },
+    /// Sets all the pairs at once.
+    MSet {
+        pairs: Vec<(Bytes, Bytes)>,
+    },
+    MGet {
+        keys: Vec<Bytes>,
+    },
+    /// Sets all the pairs only if none of the keys exists.
+    MSetNx {
+        pairs: Vec<(Bytes, Bytes)>,
+    },
+    SetNx {
+        key: Bytes,
+        value: Bytes,
+    },
+    GetSet {
+        key: Bytes,
+        value: Bytes,
+    },
+    GetDel {
+        key: Bytes,
+    },
+    /// GET that optionally changes the deadline of the key.
+    GetEx {
+        key: Bytes,
+        expiration: Option<GetExExpiration>,
+    },
+    StrLen {
+        key: Bytes,
+    },
+    /// Returns the substring between the inclusive byte offsets, negative offsets count from the end.
+    GetRange {
+        key: Bytes,
+        start: i64,
+        end: i64,
+    },
+    /// Overwrites the string starting at the byte offset, padding it with zero bytes if needed.
+    SetRange {
+        key: Bytes,
+        offset: u64,
+        value: Bytes,
+    },
     // TODO
 }
+
+impl StringCommand {
+    /// Returns true if the command modifies the stored data and therefore has to be replicated.
+    pub fn is_write(&self) -> bool {
+        match self {
+            StringCommand::Get { .. }
+            | StringCommand::MGet { .. }
+            | StringCommand::StrLen { .. }
+            | StringCommand::GetRange { .. } => false,
+            StringCommand::GetEx { expiration, .. } => expiration.is_some(),
+            _ => true,
+        }
+    }
+
+    /// Returns the keys the command works on, the keyspace loads them from the storage engine
+    /// before the command is executed.
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            StringCommand::Set { key, .. }
+            | StringCommand::Get { key }
+            | StringCommand::Append { key, .. }
+            | StringCommand::Incr { key }
+            | StringCommand::Decr { key }
+            | StringCommand::IncrBy { key, .. }
+            | StringCommand::DecrBy { key, .. }
+            | StringCommand::IncrByFloat { key, .. }
+            | StringCommand::SetNx { key, .. }
+            | StringCommand::GetSet { key, .. }
+            | StringCommand::GetDel { key }
+            | StringCommand::GetEx { key, .. }
+            | StringCommand::StrLen { key }
+            | StringCommand::GetRange { key, .. }
+            | StringCommand::SetRange { key, .. } => vec![key],
+            StringCommand::MSet { pairs } | StringCommand::MSetNx { pairs } => {
+                pairs.iter().map(|(key, _)| key.as_slice()).collect()
+            }
+            StringCommand::MGet { keys } => keys.iter().map(Bytes::as_slice).collect(),
+        }
+    }
+}
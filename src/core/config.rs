@@ -0,0 +1,713 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Args;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    aof::FsyncPolicy,
+    client::DEFAULT_REQUEST_TIMEOUT,
+    data_storage::DEFAULT_EXPIRATION_SWEEP_LIMIT,
+    sphagnum::DEFAULT_EXPIRATION_SWEEP_INTERVAL,
+    storage::{lsm::DEFAULT_MEMTABLE_SIZE, EngineKind},
+};
+
+/// How long a shutting down node waits for requests in flight and for its connections to close.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a node waits for the response of another node before the request fails.
+pub const DEFAULT_PEER_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The passport field of a node that doesn't configure one.
+pub const DEFAULT_PASSPORT_FIELD: &str = "lawn";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file can't be read.
+    Read(PathBuf, std::io::Error),
+    /// The configuration file is neither TOML nor JSON, judging by its extension.
+    UnsupportedFormat(PathBuf),
+    /// The configuration is not valid TOML or JSON, or contains unknown settings.
+    Parse(String),
+    /// A setting has a value the node can't work with.
+    InvalidValue {
+        setting: &'static str,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "Failed to read {}: {}", path.display(), e),
+            ConfigError::UnsupportedFormat(path) => write!(
+                f,
+                "Unsupported configuration format of {}, expected .toml or .json",
+                path.display()
+            ),
+            ConfigError::Parse(e) => write!(f, "Failed to parse configuration: {}", e),
+            ConfigError::InvalidValue { setting, reason } => {
+                write!(f, "Invalid value of {}: {}", setting, reason)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Everything a node needs to know to start, see `SphagnumNode::spawn`.
+///
+/// A configuration is usually loaded from a TOML or JSON file, where durations are written as
+/// e.g. "10s" or "500ms" and every setting is optional:
+///
+/// ```toml
+/// identity_file = "/var/lib/sphagnumdb/node.key"
+/// listen_addrs = ["/ip4/0.0.0.0/tcp/3301"]
+/// bootstrap_peers = ["/ip4/10.0.0.2/tcp/3301"]
+/// request_timeout = "5s"
+///
+/// [passport]
+/// field = "lawn"
+///
+/// [storage]
+/// expiration_sweep_interval = "100ms"
+/// # "lsm" keeps the keyspace on disk in data_dir, instead of the persistence below.
+/// engine = "memory"
+///
+/// [persistence]
+/// aof_file = "/var/lib/sphagnumdb/appendonly.aof"
+/// aof_fsync = "everysec"
+/// snapshot_file = "/var/lib/sphagnumdb/dump.snapshot"
+/// snapshot_interval = "5m"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    /// Ed25519 keypair of the node, generated there on first start, see `identity`.
+    /// Without it, the node gets a new `PeerId` on every start.
+    pub identity_file: Option<PathBuf>,
+    /// Addresses the node accepts connections on.
+    pub listen_addrs: Vec<Multiaddr>,
+    /// Nodes dialed on start.
+    pub bootstrap_peers: Vec<Multiaddr>,
+    /// Nodes every write is replicated to.
+    pub replica_set: Vec<PeerId>,
+    pub passport: PassportConfig,
+    /// How long a connection without traffic is kept open, forever if not set.
+    #[serde(with = "humantime_serde")]
+    pub idle_timeout: Option<Duration>,
+    /// How long the node waits for the response of another node.
+    #[serde(with = "humantime_serde")]
+    pub request_timeout: Duration,
+    /// How long the clients created by the node wait for a reply, see `SphagnumClient`.
+    #[serde(with = "humantime_serde")]
+    pub client_timeout: Duration,
+    /// How long shutdown waits for requests in flight before it closes the connections anyway.
+    #[serde(with = "humantime_serde")]
+    pub drain_timeout: Duration,
+    /// Whether the node prints what it is doing, e.g. the connections and requests it handles.
+    pub event_output: bool,
+    pub storage: StorageConfig,
+    pub persistence: PersistenceConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PassportConfig {
+    pub field: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// How often expired keys that nobody accesses are evicted.
+    #[serde(with = "humantime_serde")]
+    pub expiration_sweep_interval: Duration,
+    /// The largest number of expired keys removed by a single sweep.
+    pub expiration_sweep_limit: usize,
+    /// Where the keyspace is kept, in memory or on disk, see `storage`.
+    pub engine: EngineKind,
+    /// Directory of the `lsm` engine, created if it doesn't exist.
+    pub data_dir: Option<PathBuf>,
+    /// How many bytes of writes the `lsm` engine keeps in memory before it writes them to a table.
+    pub memtable_size: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PersistenceConfig {
+    /// Log of the writes, replayed when the node starts, see `AppendOnlyLog`.
+    /// Without it, the data is lost when the node stops.
+    pub aof_file: Option<PathBuf>,
+    pub aof_fsync: FsyncPolicy,
+    /// Snapshot of the keyspace, loaded when the node starts and saved by SAVE and BGSAVE, on
+    /// schedule and when the node stops, see `snapshot`.
+    pub snapshot_file: Option<PathBuf>,
+    /// How often a snapshot is saved in the background if the keyspace changed, never if not set.
+    #[serde(with = "humantime_serde")]
+    pub snapshot_interval: Option<Duration>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            identity_file: None,
+            listen_addrs: Vec::new(),
+            bootstrap_peers: Vec::new(),
+            replica_set: Vec::new(),
+            passport: PassportConfig::default(),
+            idle_timeout: None,
+            request_timeout: DEFAULT_PEER_REQUEST_TIMEOUT,
+            client_timeout: DEFAULT_REQUEST_TIMEOUT,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            event_output: true,
+            storage: StorageConfig::default(),
+            persistence: PersistenceConfig::default(),
+        }
+    }
+}
+
+impl Default for PassportConfig {
+    fn default() -> Self {
+        Self {
+            field: DEFAULT_PASSPORT_FIELD.to_string(),
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            expiration_sweep_interval: DEFAULT_EXPIRATION_SWEEP_INTERVAL,
+            expiration_sweep_limit: DEFAULT_EXPIRATION_SWEEP_LIMIT,
+            engine: EngineKind::default(),
+            data_dir: None,
+            memtable_size: DEFAULT_MEMTABLE_SIZE,
+        }
+    }
+}
+
+impl NodeConfig {
+    pub fn builder() -> NodeConfigBuilder {
+        NodeConfigBuilder::default()
+    }
+
+    /// Loads a configuration from a `.toml` or `.json` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let content =
+            fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            toml::from_str(content).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_json(content: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            serde_json::from_str(content).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |setting, reason| Err(ConfigError::InvalidValue { setting, reason });
+        if self.passport.field.is_empty() {
+            return invalid("passport.field", "must not be empty");
+        }
+        if self.idle_timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return invalid("idle_timeout", "must be positive");
+        }
+        if self.request_timeout.is_zero() {
+            return invalid("request_timeout", "must be positive");
+        }
+        if self.client_timeout.is_zero() {
+            return invalid("client_timeout", "must be positive");
+        }
+        if self.storage.expiration_sweep_interval.is_zero() {
+            return invalid("storage.expiration_sweep_interval", "must be positive");
+        }
+        if self.storage.expiration_sweep_limit == 0 {
+            return invalid("storage.expiration_sweep_limit", "must be positive");
+        }
+        if self.storage.memtable_size == 0 {
+            return invalid("storage.memtable_size", "must be positive");
+        }
+        if self.storage.engine == EngineKind::Lsm {
+            // The engine persists every write itself, replaying a log or loading a snapshot on
+            // top of it would apply the writes twice.
+            if self.storage.data_dir.is_none() {
+                return invalid("storage.data_dir", "is required by the lsm engine");
+            }
+            if self.persistence.aof_file.is_some() {
+                return invalid("persistence.aof_file", "can't be used with the lsm engine");
+            }
+            if self.persistence.snapshot_file.is_some() {
+                return invalid(
+                    "persistence.snapshot_file",
+                    "can't be used with the lsm engine",
+                );
+            }
+        }
+        if let Some(interval) = self.persistence.snapshot_interval {
+            if interval.is_zero() {
+                return invalid("persistence.snapshot_interval", "must be positive");
+            }
+            if self.persistence.snapshot_file.is_none() {
+                return invalid("persistence.snapshot_interval", "requires a snapshot_file");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds a `NodeConfig` in code, settings that are not set keep their defaults.
+#[derive(Debug, Clone, Default)]
+pub struct NodeConfigBuilder {
+    config: NodeConfig,
+}
+
+impl NodeConfigBuilder {
+    pub fn identity_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.identity_file = Some(path.into());
+        self
+    }
+
+    pub fn listen_addr(mut self, addr: Multiaddr) -> Self {
+        self.config.listen_addrs.push(addr);
+        self
+    }
+
+    pub fn bootstrap_peer(mut self, addr: Multiaddr) -> Self {
+        self.config.bootstrap_peers.push(addr);
+        self
+    }
+
+    pub fn replica(mut self, peer_id: PeerId) -> Self {
+        self.config.replica_set.push(peer_id);
+        self
+    }
+
+    pub fn passport_field(mut self, field: impl Into<String>) -> Self {
+        self.config.passport.field = field.into();
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = timeout;
+        self
+    }
+
+    pub fn client_timeout(mut self, timeout: Duration) -> Self {
+        self.config.client_timeout = timeout;
+        self
+    }
+
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.config.drain_timeout = timeout;
+        self
+    }
+
+    pub fn event_output(mut self, enabled: bool) -> Self {
+        self.config.event_output = enabled;
+        self
+    }
+
+    pub fn expiration_sweep_interval(mut self, interval: Duration) -> Self {
+        self.config.storage.expiration_sweep_interval = interval;
+        self
+    }
+
+    pub fn expiration_sweep_limit(mut self, limit: usize) -> Self {
+        self.config.storage.expiration_sweep_limit = limit;
+        self
+    }
+
+    pub fn storage_engine(mut self, engine: EngineKind) -> Self {
+        self.config.storage.engine = engine;
+        self
+    }
+
+    pub fn data_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.storage.data_dir = Some(path.into());
+        self
+    }
+
+    pub fn memtable_size(mut self, size: usize) -> Self {
+        self.config.storage.memtable_size = size;
+        self
+    }
+
+    pub fn aof_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.persistence.aof_file = Some(path.into());
+        self
+    }
+
+    pub fn aof_fsync(mut self, fsync: FsyncPolicy) -> Self {
+        self.config.persistence.aof_fsync = fsync;
+        self
+    }
+
+    pub fn snapshot_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.persistence.snapshot_file = Some(path.into());
+        self
+    }
+
+    pub fn snapshot_interval(mut self, interval: Duration) -> Self {
+        self.config.persistence.snapshot_interval = Some(interval);
+        self
+    }
+
+    pub fn build(self) -> Result<NodeConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Command line flags that override the configuration file.
+/// Each flag can also be set with an environment variable, a flag wins over its variable, and
+/// both win over the file. Lists given this way replace the lists of the file.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConfigOverrides {
+    /// Configuration file, .toml or .json
+    #[arg(long, env = "SPHAGNUM_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Identity key file, generated if it doesn't exist
+    #[arg(long, env = "SPHAGNUM_IDENTITY_FILE")]
+    pub identity_file: Option<PathBuf>,
+    /// Address to listen on, may be repeated
+    #[arg(long = "listen", env = "SPHAGNUM_LISTEN", value_delimiter = ',')]
+    pub listen_addrs: Vec<Multiaddr>,
+    /// Address of a node to dial on start, may be repeated
+    #[arg(long = "bootstrap", env = "SPHAGNUM_BOOTSTRAP", value_delimiter = ',')]
+    pub bootstrap_peers: Vec<Multiaddr>,
+    /// Peer id of a node to replicate writes to, may be repeated
+    #[arg(long = "replica", env = "SPHAGNUM_REPLICA", value_delimiter = ',')]
+    pub replica_set: Vec<PeerId>,
+    #[arg(long, env = "SPHAGNUM_PASSPORT_FIELD")]
+    pub passport_field: Option<String>,
+    #[arg(long, env = "SPHAGNUM_IDLE_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub idle_timeout: Option<Duration>,
+    #[arg(long, env = "SPHAGNUM_REQUEST_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub request_timeout: Option<Duration>,
+    #[arg(long, env = "SPHAGNUM_CLIENT_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub client_timeout: Option<Duration>,
+    #[arg(long, env = "SPHAGNUM_DRAIN_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub drain_timeout: Option<Duration>,
+    #[arg(
+        long,
+        env = "SPHAGNUM_EXPIRATION_SWEEP_INTERVAL",
+        value_parser = humantime::parse_duration
+    )]
+    pub expiration_sweep_interval: Option<Duration>,
+    #[arg(long, env = "SPHAGNUM_EXPIRATION_SWEEP_LIMIT")]
+    pub expiration_sweep_limit: Option<usize>,
+    /// Where the keyspace is kept
+    #[arg(long, env = "SPHAGNUM_STORAGE_ENGINE")]
+    pub storage_engine: Option<EngineKind>,
+    /// Directory of the lsm storage engine
+    #[arg(long, env = "SPHAGNUM_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+    /// Bytes of writes the lsm storage engine keeps in memory
+    #[arg(long, env = "SPHAGNUM_MEMTABLE_SIZE")]
+    pub memtable_size: Option<usize>,
+    /// Append-only log of the writes, replayed on start
+    #[arg(long, env = "SPHAGNUM_AOF_FILE")]
+    pub aof_file: Option<PathBuf>,
+    #[arg(long, env = "SPHAGNUM_AOF_FSYNC")]
+    pub aof_fsync: Option<FsyncPolicy>,
+    /// Snapshot of the keyspace, loaded on start and saved on stop
+    #[arg(long, env = "SPHAGNUM_SNAPSHOT_FILE")]
+    pub snapshot_file: Option<PathBuf>,
+    #[arg(long, env = "SPHAGNUM_SNAPSHOT_INTERVAL", value_parser = humantime::parse_duration)]
+    pub snapshot_interval: Option<Duration>,
+}
+
+impl ConfigOverrides {
+    /// Loads the configuration file, if any, and applies the overrides to it.
+    pub fn load(&self) -> Result<NodeConfig, ConfigError> {
+        let config = match &self.config {
+            Some(path) => NodeConfig::from_file(path)?,
+            None => NodeConfig::default(),
+        };
+        self.apply(config)
+    }
+
+    pub fn apply(&self, mut config: NodeConfig) -> Result<NodeConfig, ConfigError> {
+        if let Some(path) = &self.identity_file {
+            config.identity_file = Some(path.clone());
+        }
+        if !self.listen_addrs.is_empty() {
+            config.listen_addrs = self.listen_addrs.clone();
+        }
+        if !self.bootstrap_peers.is_empty() {
+            config.bootstrap_peers = self.bootstrap_peers.clone();
+        }
+        if !self.replica_set.is_empty() {
+            config.replica_set = self.replica_set.clone();
+        }
+        if let Some(field) = &self.passport_field {
+            config.passport.field = field.clone();
+        }
+        if let Some(timeout) = self.idle_timeout {
+            config.idle_timeout = Some(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            config.request_timeout = timeout;
+        }
+        if let Some(timeout) = self.client_timeout {
+            config.client_timeout = timeout;
+        }
+        if let Some(timeout) = self.drain_timeout {
+            config.drain_timeout = timeout;
+        }
+        if let Some(interval) = self.expiration_sweep_interval {
+            config.storage.expiration_sweep_interval = interval;
+        }
+        if let Some(limit) = self.expiration_sweep_limit {
+            config.storage.expiration_sweep_limit = limit;
+        }
+        if let Some(engine) = self.storage_engine {
+            config.storage.engine = engine;
+        }
+        if let Some(path) = &self.data_dir {
+            config.storage.data_dir = Some(path.clone());
+        }
+        if let Some(size) = self.memtable_size {
+            config.storage.memtable_size = size;
+        }
+        if let Some(path) = &self.aof_file {
+            config.persistence.aof_file = Some(path.clone());
+        }
+        if let Some(fsync) = self.aof_fsync {
+            config.persistence.aof_fsync = fsync;
+        }
+        if let Some(path) = &self.snapshot_file {
+            config.persistence.snapshot_file = Some(path.clone());
+        }
+        if let Some(interval) = self.snapshot_interval {
+            config.persistence.snapshot_interval = Some(interval);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        overrides: ConfigOverrides,
+    }
+
+    #[test]
+    fn test_toml_and_json_files_describe_the_same_config() {
+        // Arrange
+        let peer_id = PeerId::random();
+        let toml = format!(
+            r#"
+            listen_addrs = ["/ip4/0.0.0.0/tcp/3301"]
+            replica_set = ["{peer_id}"]
+            request_timeout = "5s"
+
+            [storage]
+            expiration_sweep_interval = "250ms"
+
+            [persistence]
+            aof_file = "appendonly.aof"
+            aof_fsync = "always"
+            snapshot_file = "dump.snapshot"
+            snapshot_interval = "5m"
+            "#
+        );
+        let json = format!(
+            r#"{{
+                "listen_addrs": ["/ip4/0.0.0.0/tcp/3301"],
+                "replica_set": ["{peer_id}"],
+                "request_timeout": "5s",
+                "storage": {{ "expiration_sweep_interval": "250ms" }},
+                "persistence": {{
+                    "aof_file": "appendonly.aof",
+                    "aof_fsync": "always",
+                    "snapshot_file": "dump.snapshot",
+                    "snapshot_interval": "5m"
+                }}
+            }}"#
+        );
+
+        // Act
+        let from_toml = NodeConfig::from_toml(&toml).unwrap();
+        let from_json = NodeConfig::from_json(&json).unwrap();
+
+        // Assert
+        assert_eq!(from_toml, from_json);
+        assert_eq!(from_toml.replica_set, vec![peer_id]);
+        assert_eq!(from_toml.request_timeout, Duration::from_secs(5));
+        assert_eq!(
+            from_toml.storage.expiration_sweep_interval,
+            Duration::from_millis(250)
+        );
+        assert_eq!(from_toml.passport.field, DEFAULT_PASSPORT_FIELD);
+        assert_eq!(from_toml.drain_timeout, DEFAULT_DRAIN_TIMEOUT);
+        assert_eq!(from_toml.persistence.aof_fsync, FsyncPolicy::Always);
+        assert_eq!(
+            from_toml.persistence.snapshot_interval,
+            Some(Duration::from_secs(300))
+        );
+    }
+
+    #[test]
+    fn test_unknown_and_invalid_settings_are_rejected() {
+        // Arrange
+        let unknown = "listen_adrs = []";
+        let invalid = "request_timeout = \"0s\"";
+
+        // Act
+        let unknown = NodeConfig::from_toml(unknown);
+        let invalid = NodeConfig::from_toml(invalid);
+
+        // Assert
+        assert!(matches!(unknown, Err(ConfigError::Parse(_))));
+        assert!(matches!(
+            invalid,
+            Err(ConfigError::InvalidValue {
+                setting: "request_timeout",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_lsm_engine_needs_a_directory_and_no_other_persistence() {
+        // Arrange
+        let lsm = "[storage]\nengine = \"lsm\"\ndata_dir = \"data\"\nmemtable_size = 1024";
+
+        // Act
+        let config = NodeConfig::from_toml(lsm).unwrap();
+        let without_dir = NodeConfig::builder()
+            .storage_engine(EngineKind::Lsm)
+            .build();
+        let with_aof = NodeConfig::builder()
+            .storage_engine(EngineKind::Lsm)
+            .data_dir("data")
+            .aof_file("appendonly.aof")
+            .build();
+
+        // Assert
+        assert_eq!(config.storage.engine, EngineKind::Lsm);
+        assert_eq!(config.storage.data_dir, Some(PathBuf::from("data")));
+        assert_eq!(config.storage.memtable_size, 1024);
+        assert!(matches!(
+            without_dir,
+            Err(ConfigError::InvalidValue {
+                setting: "storage.data_dir",
+                ..
+            })
+        ));
+        assert!(matches!(
+            with_aof,
+            Err(ConfigError::InvalidValue {
+                setting: "persistence.aof_file",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_builder_sets_only_given_settings() {
+        // Arrange
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/3301".parse().unwrap();
+
+        // Act
+        let config = NodeConfig::builder()
+            .listen_addr(addr.clone())
+            .passport_field("forest")
+            .idle_timeout(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let empty_field = NodeConfig::builder().passport_field("").build();
+
+        // Assert
+        assert_eq!(config.listen_addrs, vec![addr]);
+        assert_eq!(config.passport.field, "forest");
+        assert_eq!(config.idle_timeout, Some(Duration::from_secs(60)));
+        assert_eq!(config.client_timeout, DEFAULT_REQUEST_TIMEOUT);
+        assert!(empty_field.is_err());
+    }
+
+    #[test]
+    fn test_command_line_overrides_the_file() {
+        // Arrange
+        let file = NodeConfig::from_toml(
+            r#"
+            listen_addrs = ["/ip4/0.0.0.0/tcp/3301"]
+            bootstrap_peers = ["/ip4/10.0.0.2/tcp/3301"]
+            drain_timeout = "30s"
+            "#,
+        )
+        .unwrap();
+        let cli = Cli::try_parse_from([
+            "sphagnumdb",
+            "--listen",
+            "/ip4/0.0.0.0/tcp/4401,/ip4/0.0.0.0/tcp/4402",
+            "--drain-timeout",
+            "1m",
+            "--expiration-sweep-limit",
+            "10",
+            "--storage-engine",
+            "lsm",
+            "--data-dir",
+            "/var/lib/sphagnumdb",
+        ])
+        .unwrap();
+
+        // Act
+        let config = cli.overrides.apply(file).unwrap();
+
+        // Assert
+        assert_eq!(
+            config.listen_addrs,
+            vec![
+                "/ip4/0.0.0.0/tcp/4401".parse::<Multiaddr>().unwrap(),
+                "/ip4/0.0.0.0/tcp/4402".parse().unwrap()
+            ]
+        );
+        assert_eq!(
+            config.bootstrap_peers,
+            vec!["/ip4/10.0.0.2/tcp/3301".parse::<Multiaddr>().unwrap()]
+        );
+        assert_eq!(config.drain_timeout, Duration::from_secs(60));
+        assert_eq!(config.storage.expiration_sweep_limit, 10);
+        assert_eq!(config.storage.engine, EngineKind::Lsm);
+        assert_eq!(
+            config.storage.data_dir,
+            Some(PathBuf::from("/var/lib/sphagnumdb"))
+        );
+    }
+}
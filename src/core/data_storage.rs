@@ -4,14 +4,37 @@
 
 use std::{error::Error, fmt};
 
-use super::data_types::{data_type::DataType, string::StringStore};
+use super::data_types::{
+    bitmap::BitmapOperations,
+    data_type::{DataTypeError, GenericOperations, KeyspaceAccess},
+    hash::HashOperations,
+    keyspace::{unix_time_millis, Keyspace, KeyspaceSnapshot, Value},
+    list::ListOperations,
+    set::SetOperations,
+    sorted_set::SortedSetOperations,
+    string::StringOperations,
+};
 use crate::core::commands::{Command, CommandResult};
+use crate::core::config::StorageConfig;
+use crate::core::storage::{self, EngineError};
 
 #[derive(Debug)]
 pub enum DataStorageError {
     InitializationError,
     DataRetrievalError,
     DataModificationError,
+    WrongType,
+    /// The command was rejected by the data type, e.g. the value is not an integer.
+    CommandError(DataTypeError),
+    /// The command is not about the stored data, e.g. it is a `ServerCommand`.
+    UnsupportedCommand,
+    /// An argument of the command is well-formed but can't be used, e.g. an address the node
+    /// can't dial.
+    InvalidArgument(String),
+    /// The command couldn't be persisted, e.g. to the append-only log, so it was not applied.
+    PersistenceError(String),
+    /// The storage engine failed to read or write the keys, the command was not applied.
+    StorageEngineError(EngineError),
 }
 
 impl fmt::Display for DataStorageError {
@@ -20,30 +43,940 @@ impl fmt::Display for DataStorageError {
             DataStorageError::InitializationError => write!(f, "Failed to initialize DataStorage"),
             DataStorageError::DataRetrievalError => write!(f, "Failed to retrieve data"),
             DataStorageError::DataModificationError => write!(f, "Failed to modify data"),
+            DataStorageError::WrongType => write!(
+                f,
+                "WRONGTYPE Operation against a key holding the wrong kind of value"
+            ),
+            DataStorageError::CommandError(e) => write!(f, "{}", e),
+            DataStorageError::UnsupportedCommand => {
+                write!(f, "Command is not supported by the data storage")
+            }
+            DataStorageError::InvalidArgument(e) => write!(f, "Invalid argument: {}", e),
+            DataStorageError::PersistenceError(e) => {
+                write!(f, "Failed to persist command, it was not applied: {}", e)
+            }
+            DataStorageError::StorageEngineError(e) => write!(f, "{}", e),
         }
     }
 }
 
 impl Error for DataStorageError {}
 
+/// The largest number of expired keys removed by a single active expiration sweep.
+pub const DEFAULT_EXPIRATION_SWEEP_LIMIT: usize = 1000;
+
 /// To work with the data that will be stored on the node.
-/// At this stage, it's a simple mock, which is still far from a hashmap, but it's enough for the
-/// initial stage.
+/// All data types share a single keyspace, so a key holds exactly one value of one type.
+/// A command against a key of another type fails with `DataStorageError::WrongType`.
+///
+/// The keys are kept by the storage engine the config selects. Every command loads its keys
+/// from the engine first and writes its changes back once it completes.
 pub struct DataStorage {
-    storage: Box<dyn DataType>,
+    keyspace: Keyspace,
+    expiration_sweep_limit: usize,
 }
 
 impl DataStorage {
     pub fn new() -> Result<Self, DataStorageError> {
-        let store = StringStore::new().map_err(|_| DataStorageError::InitializationError)?;
+        Self::with_config(&StorageConfig::default())
+    }
+
+    pub fn with_config(config: &StorageConfig) -> Result<Self, DataStorageError> {
+        let engine = storage::open(config).map_err(DataStorageError::StorageEngineError)?;
+        let keyspace =
+            Keyspace::with_engine(engine).map_err(DataStorageError::StorageEngineError)?;
         Ok(Self {
-            storage: Box::new(store),
+            keyspace,
+            expiration_sweep_limit: config.expiration_sweep_limit,
         })
     }
 
     pub fn handle_command(&mut self, command: Command) -> Result<CommandResult, DataStorageError> {
-        self.storage
-            .handle_command(command)
-            .map_err(|_| DataStorageError::DataModificationError)
+        self.execute(command, |_| Ok(()))
+    }
+
+    /// Executes the command like `handle_command`, but keeps its changes only if `confirm`
+    /// accepts its result, e.g. once the write has been logged. Otherwise the changes are
+    /// discarded, including the ones made in place, and the error of `confirm` is returned.
+    pub fn handle_command_confirmed(
+        &mut self,
+        command: Command,
+        confirm: impl FnOnce(&CommandResult) -> Result<(), DataStorageError>,
+    ) -> Result<CommandResult, DataStorageError> {
+        self.keyspace.set_rollback(true);
+        let result = self.execute(command, confirm);
+        self.keyspace.set_rollback(false);
+        result
+    }
+
+    fn execute(
+        &mut self,
+        command: Command,
+        confirm: impl FnOnce(&CommandResult) -> Result<(), DataStorageError>,
+    ) -> Result<CommandResult, DataStorageError> {
+        if let Command::Server(_) = command {
+            return Err(DataStorageError::UnsupportedCommand);
+        }
+        if let Err(e) = self.keyspace.load(command.keys()) {
+            self.keyspace.discard();
+            return Err(DataStorageError::StorageEngineError(e));
+        }
+        let result = match command {
+            Command::String(cmd) => self.handle_string_command(cmd),
+            Command::Hash(cmd) => self.handle_hash_command(cmd),
+            Command::List(cmd) => self.handle_list_command(cmd),
+            Command::Set(cmd) => self.handle_set_command(cmd),
+            Command::SortedSet(cmd) => self.handle_sorted_set_command(cmd),
+            Command::Bitmap(cmd) => self.handle_bitmap_command(cmd),
+            Command::Generic(cmd) => self.handle_generic_command(cmd),
+            Command::Server(_) => unreachable!("server commands are rejected above"),
+        }
+        .map_err(|e| match e.downcast_ref::<DataTypeError>() {
+            Some(DataTypeError::WrongType) => DataStorageError::WrongType,
+            Some(e) => DataStorageError::CommandError(*e),
+            None => DataStorageError::DataModificationError,
+        })
+        .and_then(|result| confirm(&result).map(|()| result));
+        // A failed command changes nothing: commands check their arguments before they modify a
+        // value in place, and whatever else they changed is discarded.
+        match result {
+            Ok(result) => {
+                self.commit()?;
+                Ok(result)
+            }
+            Err(e) => {
+                self.keyspace.discard();
+                Err(e)
+            }
+        }
+    }
+
+    /// Removes keys whose deadline has passed and returns their number.
+    /// Expired keys are also evicted lazily on access, this sweep reclaims the ones nobody touches.
+    pub fn evict_expired(&mut self) -> Result<usize, DataStorageError> {
+        let evicted = self
+            .keyspace
+            .evict_expired(unix_time_millis(), self.expiration_sweep_limit);
+        self.commit()?;
+        Ok(evicted)
+    }
+
+    /// Returns the whole keyspace as it is now, see `Keyspace::snapshot`.
+    pub fn snapshot(&self) -> Result<KeyspaceSnapshot, DataStorageError> {
+        self.keyspace
+            .snapshot()
+            .map_err(DataStorageError::StorageEngineError)
+    }
+
+    /// Stores a value loaded from a snapshot, replacing whatever the key held.
+    pub fn restore(
+        &mut self,
+        key: &[u8],
+        value: Value,
+        deadline: Option<u64>,
+    ) -> Result<(), DataStorageError> {
+        self.keyspace.insert_value(key, value);
+        if let Some(deadline) = deadline {
+            self.keyspace.set_expiry(key, deadline);
+        }
+        self.commit()
+    }
+
+    /// Makes the changes written so far survive a crash of the machine, see `StorageEngine::sync`.
+    pub fn sync(&mut self) -> Result<(), DataStorageError> {
+        self.keyspace
+            .sync()
+            .map_err(DataStorageError::StorageEngineError)
+    }
+
+    fn commit(&mut self) -> Result<(), DataStorageError> {
+        self.keyspace
+            .commit()
+            .map_err(DataStorageError::StorageEngineError)
+    }
+}
+
+impl KeyspaceAccess for DataStorage {
+    fn keyspace(&self) -> &Keyspace {
+        &self.keyspace
+    }
+
+    fn keyspace_mut(&mut self) -> &mut Keyspace {
+        &mut self.keyspace
+    }
+}
+
+impl StringOperations for DataStorage {}
+impl HashOperations for DataStorage {}
+impl ListOperations for DataStorage {}
+impl SetOperations for DataStorage {}
+impl SortedSetOperations for DataStorage {}
+impl BitmapOperations for DataStorage {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::bytes::Bytes;
+    use crate::core::commands::{
+        generic::GenericCommand,
+        hash::HashCommand,
+        list::ListCommand,
+        set::SetCommand,
+        sorted_set::{SortedSetCommand, ZAddOptions},
+        string::{SetExpiration, StringCommand},
+    };
+    use crate::core::data_types::list::ListValue;
+    use crate::core::storage::EngineKind;
+
+    fn set(key: &str, value: &str) -> Command {
+        Command::String(StringCommand::Set {
+            key: key.into(),
+            value: value.into(),
+            expiration: None,
+        })
+    }
+
+    fn set_with(key: &str, value: &str, expiration: SetExpiration) -> Command {
+        Command::String(StringCommand::Set {
+            key: key.into(),
+            value: value.into(),
+            expiration: Some(expiration),
+        })
+    }
+
+    fn get(key: &str) -> Command {
+        Command::String(StringCommand::Get { key: key.into() })
+    }
+
+    fn generic(command: GenericCommand) -> Command {
+        Command::Generic(command)
+    }
+
+    fn rpush(key: &str, value: &str) -> Command {
+        Command::List(ListCommand::RPush {
+            key: key.into(),
+            values: vec![value.into()],
+        })
+    }
+
+    #[test]
+    fn test_command_against_key_of_another_type_fails_with_wrong_type() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(set("key", "value")).unwrap();
+
+        // Act
+        let push_result = storage.handle_command(rpush("key", "a"));
+        let hget_result = storage.handle_command(Command::Hash(HashCommand::Get {
+            key: "key".into(),
+            field: "field".into(),
+        }));
+
+        // Assert
+        assert!(matches!(push_result, Err(DataStorageError::WrongType)));
+        assert!(matches!(hget_result, Err(DataStorageError::WrongType)));
+    }
+
+    #[test]
+    fn test_string_get_against_list_fails_and_keeps_the_list() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(rpush("key", "a")).unwrap();
+
+        // Act
+        let result =
+            storage.handle_command(Command::String(StringCommand::Get { key: "key".into() }));
+        let len = storage
+            .handle_command(Command::List(ListCommand::Len { key: "key".into() }))
+            .unwrap();
+
+        // Assert
+        assert!(matches!(result, Err(DataStorageError::WrongType)));
+        assert_eq!(len, CommandResult::Int(1));
+    }
+
+    #[test]
+    fn test_set_overwrites_key_of_another_type() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(rpush("key", "a")).unwrap();
+
+        // Act
+        storage.handle_command(set("key", "value")).unwrap();
+        let result = storage
+            .handle_command(Command::String(StringCommand::Get { key: "key".into() }))
+            .unwrap();
+
+        // Assert
+        assert_eq!(result, CommandResult::String("value".into()));
+    }
+
+    #[test]
+    fn test_exists_and_delete_work_across_all_types() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(set("string", "value")).unwrap();
+        storage.handle_command(rpush("list", "a")).unwrap();
+        storage
+            .handle_command(Command::Set(SetCommand::Add {
+                key: "set".into(),
+                members: vec!["a".into()],
+            }))
+            .unwrap();
+        let keys: Vec<Bytes> = ["string", "list", "set", "missing"]
+            .iter()
+            .map(|key| Bytes::from(*key))
+            .collect();
+
+        // Act
+        let exists = storage
+            .handle_command(Command::Generic(GenericCommand::Exists {
+                keys: keys.clone(),
+            }))
+            .unwrap();
+        let deleted = storage
+            .handle_command(Command::Generic(GenericCommand::Delete {
+                keys: keys.clone(),
+            }))
+            .unwrap();
+        let exists_after = storage
+            .handle_command(Command::Generic(GenericCommand::Exists { keys }))
+            .unwrap();
+
+        // Assert
+        assert_eq!(exists, CommandResult::Int(3));
+        assert_eq!(deleted, CommandResult::Int(3));
+        assert_eq!(exists_after, CommandResult::Int(0));
+    }
+
+    #[test]
+    fn test_ttl_of_missing_persistent_and_expiring_keys() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(set("persistent", "value")).unwrap();
+        storage
+            .handle_command(set_with("expiring", "value", SetExpiration::Ex(100)))
+            .unwrap();
+        let ttl = |key: &str| generic(GenericCommand::Ttl { key: key.into() });
+
+        // Act
+        let missing = storage.handle_command(ttl("missing")).unwrap();
+        let persistent = storage.handle_command(ttl("persistent")).unwrap();
+        let expiring = storage.handle_command(ttl("expiring")).unwrap();
+
+        // Assert
+        assert_eq!(missing, CommandResult::Int(-2));
+        assert_eq!(persistent, CommandResult::Int(-1));
+        assert_eq!(expiring, CommandResult::Int(100));
+    }
+
+    #[test]
+    fn test_expire_in_the_past_deletes_the_key() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(rpush("key", "a")).unwrap();
+
+        // Act
+        let result = storage
+            .handle_command(generic(GenericCommand::ExpireAt {
+                key: "key".into(),
+                timestamp: 1,
+            }))
+            .unwrap();
+        let exists = storage
+            .handle_command(generic(GenericCommand::Exists {
+                keys: vec!["key".into()],
+            }))
+            .unwrap();
+
+        // Assert
+        assert_eq!(result, CommandResult::Bool(true));
+        assert_eq!(exists, CommandResult::Int(0));
+    }
+
+    #[test]
+    fn test_expired_key_is_evicted_lazily_and_by_the_sweep() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage
+            .handle_command(set_with("lazy", "value", SetExpiration::PxAt(1)))
+            .unwrap();
+        storage
+            .handle_command(set_with("swept", "value", SetExpiration::PxAt(1)))
+            .unwrap();
+
+        // Act
+        let lazy = storage.handle_command(get("lazy")).unwrap();
+        let evicted = storage.evict_expired().unwrap();
+
+        // Assert
+        assert_eq!(lazy, CommandResult::Nil);
+        assert_eq!(evicted, 2);
+        assert!(storage.keyspace.is_empty());
+    }
+
+    #[test]
+    fn test_persist_and_set_discard_the_deadline_but_keepttl_retains_it() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage
+            .handle_command(set_with("persisted", "value", SetExpiration::Ex(100)))
+            .unwrap();
+        storage
+            .handle_command(set_with("overwritten", "value", SetExpiration::Ex(100)))
+            .unwrap();
+        storage
+            .handle_command(set_with("kept", "value", SetExpiration::Ex(100)))
+            .unwrap();
+
+        // Act
+        let persisted = storage
+            .handle_command(generic(GenericCommand::Persist {
+                key: "persisted".into(),
+            }))
+            .unwrap();
+        storage.handle_command(set("overwritten", "other")).unwrap();
+        storage
+            .handle_command(set_with("kept", "other", SetExpiration::KeepTtl))
+            .unwrap();
+
+        // Assert
+        assert_eq!(persisted, CommandResult::Bool(true));
+        assert_eq!(storage.keyspace.expiry(b"persisted"), None);
+        assert_eq!(storage.keyspace.expiry(b"overwritten"), None);
+        assert!(storage.keyspace.expiry(b"kept").is_some());
+        assert_eq!(
+            storage.handle_command(get("kept")).unwrap(),
+            CommandResult::String("other".into())
+        );
+    }
+
+    #[test]
+    fn test_set_with_zero_timeout_is_rejected() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+
+        // Act
+        let result = storage.handle_command(set_with("key", "value", SetExpiration::Ex(0)));
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(
+            storage.handle_command(get("key")).unwrap(),
+            CommandResult::Nil
+        );
+    }
+
+    fn scan(cursor: &str, pattern: Option<&str>, value_type: Option<&str>) -> Command {
+        generic(GenericCommand::Scan {
+            cursor: cursor.to_string(),
+            pattern: pattern.map(Bytes::from),
+            count: Some(2),
+            value_type: value_type.map(str::to_string),
+        })
+    }
+
+    /// Runs a full scan and returns all keys it reported.
+    fn scan_all(
+        storage: &mut DataStorage,
+        pattern: Option<&str>,
+        value_type: Option<&str>,
+    ) -> Vec<Bytes> {
+        let mut cursor = String::from("0");
+        let mut keys = Vec::new();
+        loop {
+            let result = storage
+                .handle_command(scan(&cursor, pattern, value_type))
+                .unwrap();
+            let CommandResult::Array(mut reply) = result else {
+                panic!("unexpected scan reply: {:?}", result);
+            };
+            let Some(CommandResult::Array(batch)) = reply.pop() else {
+                panic!("scan reply without keys");
+            };
+            let Some(CommandResult::String(next)) = reply.pop() else {
+                panic!("scan reply without cursor");
+            };
+            keys.extend(batch.into_iter().map(|key| match key {
+                CommandResult::String(key) => key,
+                other => panic!("unexpected key: {:?}", other),
+            }));
+            if next == "0" {
+                return keys;
+            }
+            cursor = next.to_string();
+        }
+    }
+
+    #[test]
+    fn test_scan_filters_by_pattern_and_type_across_all_types() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(set("user:1", "alice")).unwrap();
+        storage.handle_command(set("user:2", "bob")).unwrap();
+        storage.handle_command(rpush("user:queue", "a")).unwrap();
+        storage.handle_command(rpush("jobs", "a")).unwrap();
+        storage.handle_command(set("expired", "value")).unwrap();
+        storage
+            .handle_command(generic(GenericCommand::PExpireAt {
+                key: "expired".into(),
+                timestamp: 1,
+            }))
+            .unwrap();
+        storage.handle_command(set("expiring", "value")).unwrap();
+        storage.keyspace.set_expiry(b"expiring", 1);
+
+        // Act
+        let all = scan_all(&mut storage, None, None);
+        let users = scan_all(&mut storage, Some("user:*"), None);
+        let lists = scan_all(&mut storage, None, Some("list"));
+
+        // Assert
+        assert_eq!(all, vec!["jobs", "user:1", "user:2", "user:queue"]);
+        assert_eq!(users, vec!["user:1", "user:2", "user:queue"]);
+        assert_eq!(lists, vec!["jobs", "user:queue"]);
+    }
+
+    #[test]
+    fn test_scan_rejects_invalid_cursor_and_zero_count() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+
+        // Act
+        let invalid_cursor = storage.handle_command(scan("xyz", None, None));
+        let zero_count = storage.handle_command(generic(GenericCommand::Scan {
+            cursor: "0".into(),
+            pattern: None,
+            count: Some(0),
+            value_type: None,
+        }));
+
+        // Assert
+        assert!(matches!(
+            invalid_cursor,
+            Err(DataStorageError::CommandError(DataTypeError::InvalidCursor))
+        ));
+        assert!(matches!(
+            zero_count,
+            Err(DataStorageError::CommandError(DataTypeError::SyntaxError))
+        ));
+    }
+
+    #[test]
+    fn test_keys_dbsize_and_randomkey() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        let empty = storage
+            .handle_command(generic(GenericCommand::RandomKey))
+            .unwrap();
+        storage.handle_command(set("a1", "value")).unwrap();
+        storage.handle_command(rpush("a2", "value")).unwrap();
+        storage.handle_command(set("b1", "value")).unwrap();
+
+        // Act
+        let keys = storage
+            .handle_command(generic(GenericCommand::Keys {
+                pattern: "a?".into(),
+            }))
+            .unwrap();
+        let size = storage
+            .handle_command(generic(GenericCommand::DbSize))
+            .unwrap();
+        let random = storage
+            .handle_command(generic(GenericCommand::RandomKey))
+            .unwrap();
+
+        // Assert
+        assert_eq!(empty, CommandResult::Nil);
+        assert_eq!(
+            keys,
+            CommandResult::Array(vec![
+                CommandResult::String("a1".into()),
+                CommandResult::String("a2".into()),
+            ])
+        );
+        assert_eq!(size, CommandResult::Int(3));
+        assert!(
+            matches!(random, CommandResult::String(key) if ["a1", "a2", "b1"].contains(&key.as_str().unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_rename_works_across_types_and_fails_for_missing_key() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(rpush("list", "a")).unwrap();
+        storage.handle_command(set("string", "value")).unwrap();
+
+        // Act
+        let renamed_nx = storage
+            .handle_command(generic(GenericCommand::RenameNx {
+                key: "list".into(),
+                new_key: "string".into(),
+            }))
+            .unwrap();
+        let renamed = storage
+            .handle_command(generic(GenericCommand::Rename {
+                key: "list".into(),
+                new_key: "string".into(),
+            }))
+            .unwrap();
+        let missing = storage.handle_command(generic(GenericCommand::Rename {
+            key: "list".into(),
+            new_key: "other".into(),
+        }));
+        let value_type = storage
+            .handle_command(generic(GenericCommand::Type {
+                key: "string".into(),
+            }))
+            .unwrap();
+
+        // Assert
+        assert_eq!(renamed_nx, CommandResult::Bool(false));
+        assert_eq!(renamed, CommandResult::String("OK".into()));
+        assert!(matches!(
+            missing,
+            Err(DataStorageError::CommandError(DataTypeError::NoSuchKey))
+        ));
+        assert_eq!(value_type, CommandResult::String("list".into()));
+    }
+
+    #[test]
+    fn test_copy_replaces_destination_only_when_asked() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(rpush("source", "a")).unwrap();
+        storage.handle_command(set("destination", "value")).unwrap();
+        let copy = |replace| {
+            generic(GenericCommand::Copy {
+                source: "source".into(),
+                destination: "destination".into(),
+                replace,
+            })
+        };
+
+        // Act
+        let without_replace = storage.handle_command(copy(false)).unwrap();
+        let with_replace = storage.handle_command(copy(true)).unwrap();
+        let same_key = storage.handle_command(generic(GenericCommand::Copy {
+            source: "source".into(),
+            destination: "source".into(),
+            replace: true,
+        }));
+        storage.handle_command(rpush("source", "b")).unwrap();
+        storage.keyspace.load([&b"destination"[..]]).unwrap();
+
+        // Assert
+        assert_eq!(without_replace, CommandResult::Bool(false));
+        assert_eq!(with_replace, CommandResult::Bool(true));
+        assert!(matches!(
+            same_key,
+            Err(DataStorageError::CommandError(DataTypeError::SameObject))
+        ));
+        assert_eq!(
+            storage
+                .keyspace
+                .get::<ListValue>(b"destination")
+                .unwrap()
+                .map(|list| list.iter().collect::<Vec<_>>()),
+            Some(vec![&b"a"[..]])
+        );
+    }
+
+    #[test]
+    fn test_type_touch_unlink_and_object() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        storage.handle_command(set("string", "value")).unwrap();
+        storage.handle_command(rpush("list", "a")).unwrap();
+        let keys = vec!["string".into(), "missing".into()];
+
+        // Act
+        let missing_type = storage
+            .handle_command(generic(GenericCommand::Type {
+                key: "missing".into(),
+            }))
+            .unwrap();
+        let touched = storage
+            .handle_command(generic(GenericCommand::Touch { keys: keys.clone() }))
+            .unwrap();
+        let encoding = storage
+            .handle_command(generic(GenericCommand::ObjectEncoding {
+                key: "list".into(),
+            }))
+            .unwrap();
+        let idle_time = storage
+            .handle_command(generic(GenericCommand::ObjectIdleTime {
+                key: "list".into(),
+            }))
+            .unwrap();
+        let unlinked = storage
+            .handle_command(generic(GenericCommand::Unlink { keys }))
+            .unwrap();
+        let missing_encoding = storage
+            .handle_command(generic(GenericCommand::ObjectEncoding {
+                key: "string".into(),
+            }))
+            .unwrap();
+
+        // Assert
+        assert_eq!(missing_type, CommandResult::String("none".into()));
+        assert_eq!(touched, CommandResult::Int(1));
+        assert_eq!(encoding, CommandResult::String("listpack".into()));
+        assert_eq!(idle_time, CommandResult::Int(0));
+        assert_eq!(unlinked, CommandResult::Int(1));
+        assert_eq!(missing_encoding, CommandResult::Nil);
+    }
+
+    fn encoding(storage: &mut DataStorage, key: &str) -> CommandResult {
+        storage
+            .handle_command(generic(GenericCommand::ObjectEncoding { key: key.into() }))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_object_encoding_of_strings_is_picked_again_on_every_write() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        let long = "x".repeat(23);
+        storage.handle_command(set("int", "-42")).unwrap();
+        storage.handle_command(set("padded", "042")).unwrap();
+        storage.handle_command(set("long", &long)).unwrap();
+
+        // Act
+        let before = [
+            encoding(&mut storage, "int"),
+            encoding(&mut storage, "padded"),
+            encoding(&mut storage, "long"),
+        ];
+        storage
+            .handle_command(Command::String(StringCommand::Append {
+                key: "int".into(),
+                value: "x".into(),
+            }))
+            .unwrap();
+        storage.handle_command(set("long", "1")).unwrap();
+        storage
+            .handle_command(Command::String(StringCommand::Incr { key: "long".into() }))
+            .unwrap();
+        let value = storage.handle_command(get("long")).unwrap();
+
+        // Assert
+        assert_eq!(
+            before,
+            ["int", "embstr", "raw"].map(|name| CommandResult::String(name.into()))
+        );
+        assert_eq!(
+            encoding(&mut storage, "int"),
+            CommandResult::String("embstr".into())
+        );
+        assert_eq!(
+            encoding(&mut storage, "long"),
+            CommandResult::String("int".into())
+        );
+        assert_eq!(value, CommandResult::String("2".into()));
+    }
+
+    #[test]
+    fn test_packed_collections_convert_once_they_grow() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        let names: Vec<Bytes> = (0..128).map(|i| format!("m{}", i).into()).collect();
+        storage
+            .handle_command(Command::Hash(HashCommand::Set {
+                key: "hash".into(),
+                fields: names
+                    .iter()
+                    .map(|name| (name.clone(), "v".into()))
+                    .collect(),
+            }))
+            .unwrap();
+        storage.handle_command(rpush("list", "a")).unwrap();
+        storage
+            .handle_command(Command::Set(SetCommand::Add {
+                key: "set".into(),
+                members: vec!["1".into(), "-2".into()],
+            }))
+            .unwrap();
+        storage
+            .handle_command(Command::SortedSet(SortedSetCommand::Add {
+                key: "zset".into(),
+                members: names.iter().map(|name| (1.0, name.clone())).collect(),
+                options: ZAddOptions::default(),
+            }))
+            .unwrap();
+        let packed = ["hash", "list", "set", "zset"].map(|key| encoding(&mut storage, key));
+
+        // Act
+        storage
+            .handle_command(Command::Hash(HashCommand::Set {
+                key: "hash".into(),
+                fields: vec![("one more".into(), "v".into())],
+            }))
+            .unwrap();
+        storage
+            .handle_command(rpush("list", &"x".repeat(65)))
+            .unwrap();
+        storage
+            .handle_command(Command::Set(SetCommand::Add {
+                key: "set".into(),
+                members: vec!["a".into()],
+            }))
+            .unwrap();
+        let set_with_string = encoding(&mut storage, "set");
+        storage
+            .handle_command(Command::SortedSet(SortedSetCommand::Add {
+                key: "zset".into(),
+                members: vec![(0.5, "first".into())],
+                options: ZAddOptions::default(),
+            }))
+            .unwrap();
+        let converted = ["hash", "list", "set", "zset"].map(|key| encoding(&mut storage, key));
+        let first = storage
+            .handle_command(Command::SortedSet(SortedSetCommand::Rank {
+                key: "zset".into(),
+                member: "first".into(),
+            }))
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            packed,
+            ["listpack", "listpack", "intset", "listpack"]
+                .map(|name| CommandResult::String(name.into()))
+        );
+        assert_eq!(set_with_string, CommandResult::String("listpack".into()));
+        assert_eq!(
+            converted,
+            ["hashtable", "linkedlist", "listpack", "skiplist"]
+                .map(|name| CommandResult::String(name.into()))
+        );
+        assert_eq!(first, CommandResult::Int(0));
+    }
+
+    #[test]
+    fn test_binary_key_and_value_survive_the_json_wire_format() {
+        // Arrange
+        let mut storage = DataStorage::new().unwrap();
+        let key = Bytes::from(vec![0xff, 0x00, b'k']);
+        let value = Bytes::from(vec![0x89, b'P', b'N', b'G', 0x00, 0xfe]);
+        let command = Command::String(StringCommand::Set {
+            key: key.clone(),
+            value: value.clone(),
+            expiration: None,
+        });
+        let wire = serde_json::to_string(&command).unwrap();
+
+        // Act
+        storage
+            .handle_command(serde_json::from_str(&wire).unwrap())
+            .unwrap();
+        let result = storage
+            .handle_command(Command::String(StringCommand::Get { key }))
+            .unwrap();
+
+        // Assert
+        assert_eq!(result, CommandResult::String(value));
+    }
+
+    #[test]
+    fn test_lsm_engine_keeps_the_keyspace_across_restarts() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!(
+            "sphagnumdb-storage-{}-{}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let config = StorageConfig {
+            engine: EngineKind::Lsm,
+            data_dir: Some(dir.clone()),
+            memtable_size: 256,
+            ..StorageConfig::default()
+        };
+        let mut storage = DataStorage::with_config(&config).unwrap();
+        for i in 0..50 {
+            storage
+                .handle_command(set(&format!("key:{}", i), &i.to_string()))
+                .unwrap();
+        }
+        storage.handle_command(rpush("list", "a")).unwrap();
+        storage.handle_command(rpush("list", "b")).unwrap();
+        storage
+            .handle_command(generic(GenericCommand::Delete {
+                keys: vec!["key:7".into()],
+            }))
+            .unwrap();
+        storage.sync().unwrap();
+        drop(storage);
+
+        // Act
+        let mut restarted = DataStorage::with_config(&config).unwrap();
+        let value = restarted.handle_command(get("key:42")).unwrap();
+        let deleted = restarted.handle_command(get("key:7")).unwrap();
+        let pushed = restarted.handle_command(rpush("list", "c")).unwrap();
+        let size = restarted
+            .handle_command(generic(GenericCommand::DbSize))
+            .unwrap();
+        let prefixed = restarted
+            .handle_command(Command::Generic(GenericCommand::Prefix {
+                prefix: "key:1".into(),
+                limit: Some(3),
+                with_values: false,
+            }))
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Assert
+        assert_eq!(value, CommandResult::String("42".into()));
+        assert_eq!(deleted, CommandResult::Nil);
+        assert_eq!(pushed, CommandResult::Int(3));
+        assert_eq!(size, CommandResult::Int(50));
+        assert_eq!(
+            prefixed,
+            CommandResult::Array(
+                ["key:1", "key:10", "key:11"]
+                    .map(|key| CommandResult::String(key.into()))
+                    .to_vec()
+            )
+        );
+    }
+
+    #[test]
+    fn test_failed_command_leaves_nothing_in_the_lsm_engine() {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!(
+            "sphagnumdb-storage-{}-{}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let config = StorageConfig {
+            engine: EngineKind::Lsm,
+            data_dir: Some(dir.clone()),
+            ..StorageConfig::default()
+        };
+        let mut storage = DataStorage::with_config(&config).unwrap();
+        storage.handle_command(set("key", "value")).unwrap();
+        storage.handle_command(rpush("list", "a")).unwrap();
+        let wal_len = || std::fs::metadata(dir.join("wal.log")).unwrap().len();
+        let before = wal_len();
+
+        // Act
+        let incr =
+            storage.handle_command(Command::String(StringCommand::Incr { key: "key".into() }));
+        let rename = storage.handle_command(generic(GenericCommand::Rename {
+            key: "missing".into(),
+            new_key: "list".into(),
+        }));
+        let after = wal_len();
+        drop(storage);
+        let mut restarted = DataStorage::with_config(&config).unwrap();
+        let value = restarted.handle_command(get("key")).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Assert
+        assert!(incr.is_err());
+        assert!(rename.is_err());
+        assert_eq!(after, before);
+        assert_eq!(value, CommandResult::String("value".into()));
     }
 }
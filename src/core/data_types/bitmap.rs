@@ -2,4 +2,557 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-// TODO
+use crate::core::commands::{
+    bitmap::{BitOperation, BitRange, BitUnit, BitmapCommand},
+    Command, CommandResult,
+};
+use crate::core::data_types::{
+    data_type::{DataType, DataTypeError, GenericOperations, KeyspaceAccess},
+    keyspace::Keyspace,
+};
+use std::error::Error;
+
+/// The largest bit offset that can be set, which limits a bitmap to 512 MB.
+const MAX_BIT_OFFSET: u64 = (1 << 32) - 1;
+
+/// Stores bitmaps under keys.
+/// Bit 0 is the most significant bit of the first byte, a bitmap grows with zero bytes when a
+/// bit past its end is set.
+#[derive(Debug)]
+pub struct BitmapStore {
+    data: Keyspace,
+}
+
+impl DataType for BitmapStore {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(BitmapStore {
+            data: Keyspace::new(),
+        })
+    }
+
+    fn handle_command(&mut self, command: Command) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            Command::Bitmap(cmd) => self.handle_bitmap_command(cmd),
+            Command::Generic(cmd) => self.handle_generic_command(cmd),
+            #[allow(unreachable_patterns)]
+            _ => Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Command not supported by BitmapStore",
+            ))),
+        }
+    }
+}
+
+impl KeyspaceAccess for BitmapStore {
+    fn keyspace(&self) -> &Keyspace {
+        &self.data
+    }
+
+    fn keyspace_mut(&mut self) -> &mut Keyspace {
+        &mut self.data
+    }
+}
+
+impl BitmapOperations for BitmapStore {}
+
+fn bit_at(bytes: &[u8], offset: u64) -> bool {
+    bytes
+        .get((offset / 8) as usize)
+        .is_some_and(|byte| byte & (0x80 >> (offset % 8)) != 0)
+}
+
+/// Converts a range into inclusive bit positions inside a bitmap of `len` bytes.
+/// Returns None if the range is empty.
+fn bit_bounds(len: usize, range: Option<BitRange>) -> Option<(u64, u64)> {
+    let range = range.unwrap_or(BitRange {
+        start: 0,
+        end: None,
+        unit: BitUnit::Byte,
+    });
+    let units = match range.unit {
+        BitUnit::Byte => len as i64,
+        BitUnit::Bit => len as i64 * 8,
+    };
+    let normalize = |index: i64| if index < 0 { units + index } else { index };
+    let start = normalize(range.start).max(0);
+    let end = normalize(range.end.unwrap_or(-1)).min(units - 1);
+    if start > end {
+        return None;
+    }
+    Some(match range.unit {
+        BitUnit::Byte => (start as u64 * 8, end as u64 * 8 + 7),
+        BitUnit::Bit => (start as u64, end as u64),
+    })
+}
+
+/// Operations of the Bitmap data type.
+pub trait BitmapOperations: KeyspaceAccess {
+    /// Handles a bitmap command and returns the result.
+    fn handle_bitmap_command(
+        &mut self,
+        command: BitmapCommand,
+    ) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            BitmapCommand::SetBit { key, offset, value } => {
+                let previous = self.setbit(&key, offset, value)?;
+                Ok(CommandResult::Int(previous as i64))
+            }
+            BitmapCommand::GetBit { key, offset } => {
+                let bit = self.getbit(&key, offset)?;
+                Ok(CommandResult::Int(bit as i64))
+            }
+            BitmapCommand::Count { key, range } => {
+                let count = self.bitcount(&key, range)?;
+                Ok(CommandResult::Int(count as i64))
+            }
+            BitmapCommand::Pos { key, bit, range } => {
+                let position = self.bitpos(&key, bit, range)?;
+                Ok(CommandResult::Int(position))
+            }
+            BitmapCommand::Op {
+                operation,
+                destination,
+                keys,
+            } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                let len = self.bitop(operation, &destination, keys_ref)?;
+                Ok(CommandResult::Int(len as i64))
+            }
+        }
+    }
+
+    /// Sets the bit and returns its previous value.
+    fn setbit(&mut self, key: &[u8], offset: u64, value: bool) -> Result<bool, Box<dyn Error>> {
+        if offset > MAX_BIT_OFFSET {
+            return Err(Box::new(DataTypeError::IndexOutOfRange));
+        }
+        let bytes = self.keyspace_mut().get_or_insert_default::<Vec<u8>>(key)?;
+        let index = (offset / 8) as usize;
+        if index >= bytes.len() {
+            bytes.resize(index + 1, 0);
+        }
+        let mask = 0x80 >> (offset % 8);
+        let previous = bytes[index] & mask != 0;
+        if value {
+            bytes[index] |= mask;
+        } else {
+            bytes[index] &= !mask;
+        }
+        Ok(previous)
+    }
+
+    fn getbit(&self, key: &[u8], offset: u64) -> Result<bool, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<Vec<u8>>(key)?
+            .is_some_and(|bytes| bit_at(bytes, offset)))
+    }
+
+    fn bitcount(&self, key: &[u8], range: Option<BitRange>) -> Result<u64, Box<dyn Error>> {
+        let Some(bytes) = self.keyspace().get::<Vec<u8>>(key)? else {
+            return Ok(0);
+        };
+        let Some((first, last)) = bit_bounds(bytes.len(), range) else {
+            return Ok(0);
+        };
+        let mut count = 0;
+        let mut offset = first;
+        while offset <= last {
+            if offset % 8 == 0 && offset + 7 <= last {
+                count += bytes[(offset / 8) as usize].count_ones() as u64;
+                offset += 8;
+            } else {
+                count += bit_at(bytes, offset) as u64;
+                offset += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Returns the position of the first bit with the given value, or -1 if there is none.
+    /// Without an explicit end, a missing clear bit is reported right after the end of the
+    /// bitmap, since the bitmap is padded with zeros on the right.
+    fn bitpos(
+        &self,
+        key: &[u8],
+        bit: bool,
+        range: Option<BitRange>,
+    ) -> Result<i64, Box<dyn Error>> {
+        let Some(bytes) = self.keyspace().get::<Vec<u8>>(key)? else {
+            return Ok(if bit { -1 } else { 0 });
+        };
+        let has_end = range.is_some_and(|range| range.end.is_some());
+        let Some((first, last)) = bit_bounds(bytes.len(), range) else {
+            return Ok(-1);
+        };
+        // Whole bytes without the bit are skipped, like in BITCOUNT.
+        let skipped = if bit { 0x00 } else { 0xff };
+        let mut offset = first;
+        while offset <= last {
+            if offset % 8 == 0 && offset + 7 <= last {
+                let byte = bytes[(offset / 8) as usize];
+                if byte != skipped {
+                    let index = if bit {
+                        byte.leading_zeros()
+                    } else {
+                        byte.leading_ones()
+                    };
+                    return Ok((offset + index as u64) as i64);
+                }
+                offset += 8;
+            } else {
+                if bit_at(bytes, offset) == bit {
+                    return Ok(offset as i64);
+                }
+                offset += 1;
+            }
+        }
+        if !bit && !has_end {
+            return Ok(bytes.len() as i64 * 8);
+        }
+        Ok(-1)
+    }
+
+    /// Stores the result of the operation in the destination and returns its length in bytes.
+    /// Missing keys and shorter bitmaps are treated as zero-padded.
+    fn bitop(
+        &mut self,
+        operation: BitOperation,
+        destination: &[u8],
+        keys: Vec<&[u8]>,
+    ) -> Result<u64, Box<dyn Error>> {
+        if keys.is_empty() || (operation == BitOperation::Not && keys.len() != 1) {
+            return Err(Box::new(DataTypeError::SyntaxError));
+        }
+        let empty = Vec::new();
+        let mut sources: Vec<&Vec<u8>> = Vec::with_capacity(keys.len());
+        for key in keys {
+            sources.push(self.keyspace().get::<Vec<u8>>(key)?.unwrap_or(&empty));
+        }
+        let len = sources.iter().map(|bytes| bytes.len()).max().unwrap_or(0);
+        let byte_at = |bytes: &Vec<u8>, i: usize| bytes.get(i).copied().unwrap_or(0);
+        let result: Vec<u8> = (0..len)
+            .map(|i| {
+                let mut values = sources.iter().map(|bytes| byte_at(bytes, i));
+                let first = values.next().unwrap_or(0);
+                match operation {
+                    BitOperation::And => values.fold(first, |acc, byte| acc & byte),
+                    BitOperation::Or => values.fold(first, |acc, byte| acc | byte),
+                    BitOperation::Xor => values.fold(first, |acc, byte| acc ^ byte),
+                    BitOperation::Not => !first,
+                }
+            })
+            .collect();
+        if result.is_empty() {
+            self.keyspace_mut().remove(destination);
+        } else {
+            self.keyspace_mut().insert(destination, result);
+        }
+        Ok(len as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::commands::generic::GenericCommand;
+
+    fn store_with(key: &str, bytes: &[u8]) -> BitmapStore {
+        let mut store = BitmapStore::new().unwrap();
+        store.data.insert(key.as_bytes(), bytes.to_vec());
+        store
+    }
+
+    fn range(start: i64, end: Option<i64>, unit: BitUnit) -> Option<BitRange> {
+        Some(BitRange { start, end, unit })
+    }
+
+    #[test]
+    fn test_new() {
+        // Arrange
+        let store = BitmapStore::new();
+        // Act
+        // Assert
+        assert!(store.is_ok());
+    }
+
+    #[test]
+    fn test_setbit_grows_bitmap_and_returns_previous_bit() {
+        // Arrange
+        let mut store = BitmapStore::new().unwrap();
+
+        // Act
+        let previous_1 = store.setbit(b"key", 10, true).unwrap();
+        let previous_2 = store.setbit(b"key", 10, false).unwrap();
+        let previous_3 = store.setbit(b"key", 0, true).unwrap();
+
+        // Assert
+        assert!(!previous_1);
+        assert!(previous_2);
+        assert!(!previous_3);
+        assert_eq!(
+            store.data.get::<Vec<u8>>(b"key").unwrap().unwrap(),
+            &vec![0b1000_0000, 0]
+        );
+    }
+
+    #[test]
+    fn test_setbit_with_too_large_offset_fails() {
+        // Arrange
+        let mut store = BitmapStore::new().unwrap();
+
+        // Act
+        let result = store.setbit(b"key", MAX_BIT_OFFSET + 1, true);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::IndexOutOfRange)
+        );
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_getbit_past_the_end_and_on_missing_key() {
+        // Arrange
+        let store = store_with("key", &[0b0100_0000]);
+
+        // Act & Assert
+        assert!(store.getbit(b"key", 1).unwrap());
+        assert!(!store.getbit(b"key", 0).unwrap());
+        assert!(!store.getbit(b"key", 100).unwrap());
+        assert!(!store.getbit(b"missing", 1).unwrap());
+    }
+
+    #[test]
+    fn test_bitcount_over_byte_ranges() {
+        // Arrange
+        let store = store_with("key", &[0xff, 0x0f, 0x01]);
+
+        // Act & Assert
+        assert_eq!(store.bitcount(b"key", None).unwrap(), 13);
+        assert_eq!(
+            store
+                .bitcount(b"key", range(1, Some(1), BitUnit::Byte))
+                .unwrap(),
+            4
+        );
+        assert_eq!(
+            store
+                .bitcount(b"key", range(-2, None, BitUnit::Byte))
+                .unwrap(),
+            5
+        );
+        assert_eq!(
+            store
+                .bitcount(b"key", range(2, Some(1), BitUnit::Byte))
+                .unwrap(),
+            0
+        );
+        assert_eq!(store.bitcount(b"missing", None).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_bitcount_over_bit_ranges() {
+        // Arrange
+        let store = store_with("key", &[0xff, 0x0f]);
+
+        // Act & Assert
+        assert_eq!(
+            store
+                .bitcount(b"key", range(4, Some(11), BitUnit::Bit))
+                .unwrap(),
+            4
+        );
+        assert_eq!(
+            store
+                .bitcount(b"key", range(-4, Some(-1), BitUnit::Bit))
+                .unwrap(),
+            4
+        );
+        assert_eq!(
+            store
+                .bitcount(b"key", range(8, Some(11), BitUnit::Bit))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_bitpos() {
+        // Arrange
+        let store = store_with("key", &[0x00, 0x1f, 0xff]);
+
+        // Act & Assert
+        assert_eq!(store.bitpos(b"key", true, None).unwrap(), 11);
+        assert_eq!(store.bitpos(b"key", false, None).unwrap(), 0);
+        assert_eq!(
+            store
+                .bitpos(b"key", true, range(2, None, BitUnit::Byte))
+                .unwrap(),
+            16
+        );
+        assert_eq!(
+            store
+                .bitpos(b"key", true, range(0, Some(10), BitUnit::Bit))
+                .unwrap(),
+            -1
+        );
+        assert_eq!(store.bitpos(b"missing", true, None).unwrap(), -1);
+        assert_eq!(store.bitpos(b"missing", false, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_bitpos_for_clear_bit_in_full_bitmap() {
+        // Arrange
+        let store = store_with("key", &[0xff, 0xff]);
+
+        // Act & Assert
+        assert_eq!(store.bitpos(b"key", false, None).unwrap(), 16);
+        assert_eq!(
+            store
+                .bitpos(b"key", false, range(0, Some(-1), BitUnit::Byte))
+                .unwrap(),
+            -1
+        );
+    }
+
+    #[test]
+    fn test_bitpos_in_large_bitmap_with_bit_near_the_end() {
+        // Arrange
+        let len = 16 * 1024 * 1024;
+        let mut zeros = vec![0x00; len];
+        zeros[len - 2] = 0x04;
+        let mut ones = vec![0xff; len];
+        ones[len - 1] = 0xfe;
+        let mut store = store_with("zeros", &zeros);
+        store.data.insert(b"ones", ones);
+        let last_bit = len as i64 * 8 - 1;
+
+        // Act & Assert
+        assert_eq!(store.bitpos(b"zeros", true, None).unwrap(), last_bit - 10);
+        assert_eq!(store.bitpos(b"ones", false, None).unwrap(), last_bit);
+        assert_eq!(
+            store
+                .bitpos(b"zeros", true, range(3, Some(last_bit - 11), BitUnit::Bit))
+                .unwrap(),
+            -1
+        );
+        assert_eq!(
+            store
+                .bitpos(b"zeros", true, range(3, Some(last_bit - 10), BitUnit::Bit))
+                .unwrap(),
+            last_bit - 10
+        );
+    }
+
+    #[test]
+    fn test_bitop_and_or_xor_pad_shorter_bitmaps() {
+        // Arrange
+        let mut store = store_with("key1", &[0b1100_1100, 0xff]);
+        store.data.insert(b"key2", vec![0b1010_1010]);
+
+        // Act
+        let len = store
+            .bitop(BitOperation::And, b"and", vec![b"key1", b"key2"])
+            .unwrap();
+        store
+            .bitop(BitOperation::Or, b"or", vec![b"key1", b"key2", b"missing"])
+            .unwrap();
+        store
+            .bitop(BitOperation::Xor, b"xor", vec![b"key1", b"key2"])
+            .unwrap();
+
+        // Assert
+        assert_eq!(len, 2);
+        assert_eq!(
+            store.data.get::<Vec<u8>>(b"and").unwrap().unwrap(),
+            &vec![0b1000_1000, 0x00]
+        );
+        assert_eq!(
+            store.data.get::<Vec<u8>>(b"or").unwrap().unwrap(),
+            &vec![0b1110_1110, 0xff]
+        );
+        assert_eq!(
+            store.data.get::<Vec<u8>>(b"xor").unwrap().unwrap(),
+            &vec![0b0110_0110, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_bitop_not() {
+        // Arrange
+        let mut store = store_with("key", &[0b1111_0000]);
+
+        // Act
+        let result = store.bitop(BitOperation::Not, b"dest", vec![b"key"]);
+        let invalid = store.bitop(BitOperation::Not, b"dest", vec![b"key", b"key"]);
+
+        // Assert
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(
+            store.data.get::<Vec<u8>>(b"dest").unwrap().unwrap(),
+            &vec![0b0000_1111]
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_bitop_on_missing_keys_removes_destination() {
+        // Arrange
+        let mut store = store_with("dest", &[0xff]);
+
+        // Act
+        let len = store
+            .bitop(BitOperation::Or, b"dest", vec![b"missing"])
+            .unwrap();
+
+        // Assert
+        assert_eq!(len, 0);
+        assert_eq!(store.exists(vec![b"dest"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_handle_command_setbit_and_bitcount() {
+        // Arrange
+        let mut store = BitmapStore::new().unwrap();
+        let setbit = Command::Bitmap(BitmapCommand::SetBit {
+            key: "key".into(),
+            offset: 7,
+            value: true,
+        });
+        let bitcount = Command::Bitmap(BitmapCommand::Count {
+            key: "key".into(),
+            range: None,
+        });
+
+        // Act
+        let setbit_result = store.handle_command(setbit).unwrap();
+        let bitcount_result = store.handle_command(bitcount).unwrap();
+
+        // Assert
+        assert_eq!(setbit_result, CommandResult::Int(0));
+        assert_eq!(bitcount_result, CommandResult::Int(1));
+    }
+
+    #[test]
+    fn test_handle_command_exists_and_delete() {
+        // Arrange
+        let mut store = store_with("key1", &[0x01]);
+        let exists_command = Command::Generic(GenericCommand::Exists {
+            keys: vec!["key1".into(), "key2".into()],
+        });
+        let delete_command = Command::Generic(GenericCommand::Delete {
+            keys: vec!["key1".into(), "key2".into()],
+        });
+
+        // Act
+        let exists_result = store.handle_command(exists_command).unwrap();
+        let delete_result = store.handle_command(delete_command).unwrap();
+
+        // Assert
+        assert_eq!(exists_result, CommandResult::Int(1));
+        assert_eq!(delete_result, CommandResult::Int(1));
+        assert!(!store.getbit(b"key1", 7).unwrap());
+    }
+}
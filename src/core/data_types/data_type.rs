@@ -2,18 +2,556 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-use crate::core::commands::{Command, CommandResult};
-use std::error::Error;
+use crate::core::bytes::Bytes;
+use crate::core::commands::{
+    generic::GenericCommand, sorted_set::LexBound, Command, CommandResult,
+};
+use crate::core::data_types::keyspace::{unix_time_millis, Keyspace, Value};
+use crate::core::glob::glob_match;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataTypeError {
+    NotAnInteger,
+    Overflow,
+    IndexOutOfRange,
+    NoSuchKey,
+    NotAFloat,
+    SyntaxError,
+    WrongType,
+    InvalidExpireTime,
+    InvalidCursor,
+    SameObject,
+}
+
+impl fmt::Display for DataTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataTypeError::NotAnInteger => write!(f, "Value is not an integer or out of range"),
+            DataTypeError::Overflow => write!(f, "Increment or decrement would overflow"),
+            DataTypeError::IndexOutOfRange => write!(f, "Index out of range"),
+            DataTypeError::NoSuchKey => write!(f, "No such key"),
+            DataTypeError::NotAFloat => write!(f, "Value is not a valid float"),
+            DataTypeError::SyntaxError => write!(f, "Syntax error"),
+            DataTypeError::WrongType => write!(
+                f,
+                "WRONGTYPE Operation against a key holding the wrong kind of value"
+            ),
+            DataTypeError::InvalidExpireTime => write!(f, "Invalid expire time"),
+            DataTypeError::InvalidCursor => write!(f, "Invalid cursor"),
+            DataTypeError::SameObject => {
+                write!(f, "Source and destination objects are the same")
+            }
+        }
+    }
+}
+
+impl Error for DataTypeError {}
+
+/// The number of keys a SCAN visits when no COUNT is given.
+const DEFAULT_SCAN_COUNT: u64 = 10;
+
+/// The cursor returned when a scan has visited the whole keyspace, also used to start one.
+const SCAN_CURSOR_START: &str = "0";
+
+/// A scan cursor is the hex-encoded key to resume from, so it stays valid however the keyspace
+/// changes between calls.
+fn encode_cursor(key: Option<&[u8]>) -> String {
+    match key {
+        Some(key) => key.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        None => SCAN_CURSOR_START.to_string(),
+    }
+}
+
+fn decode_cursor(cursor: &str) -> Result<Option<Bytes>, DataTypeError> {
+    if cursor == SCAN_CURSOR_START {
+        return Ok(None);
+    }
+    if cursor.is_empty() || !cursor.len().is_multiple_of(2) {
+        return Err(DataTypeError::InvalidCursor);
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| {
+            cursor
+                .get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or(DataTypeError::InvalidCursor)
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    Ok(Some(Bytes::from(bytes)))
+}
+
+/// Keys with their values, None if the values were not asked for or the key holds another type.
+pub type KeyValues = Vec<(Bytes, Option<Bytes>)>;
+
+fn to_key_values(keys: KeyValues, with_values: bool) -> CommandResult {
+    let mut result = Vec::new();
+    for (key, value) in keys {
+        result.push(CommandResult::String(key));
+        if with_values {
+            result.push(value.map_or(CommandResult::Nil, CommandResult::String));
+        }
+    }
+    CommandResult::Array(result)
+}
+
+/// Converts the start of a lex interval into a bound of the keyspace, None if no key is after it.
+fn start_bound(bound: &LexBound) -> Option<Bound<&[u8]>> {
+    match bound {
+        LexBound::Min => Some(Bound::Unbounded),
+        LexBound::Max => None,
+        LexBound::Inclusive(key) => Some(Bound::Included(key)),
+        LexBound::Exclusive(key) => Some(Bound::Excluded(key)),
+    }
+}
+
+/// Converts the end of a lex interval into a bound of the keyspace, None if no key is before it.
+fn end_bound(bound: &LexBound) -> Option<Bound<&[u8]>> {
+    match bound {
+        LexBound::Min => None,
+        LexBound::Max => Some(Bound::Unbounded),
+        LexBound::Inclusive(key) => Some(Bound::Included(key)),
+        LexBound::Exclusive(key) => Some(Bound::Excluded(key)),
+    }
+}
+
+/// Returns the first key after all the keys starting with the prefix,
+/// None if there is no such key, e.g. for an empty prefix.
+fn prefix_end(prefix: &[u8]) -> Option<Bytes> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(Bytes::from(end));
+        }
+    }
+    None
+}
+
+/// Returns the keys between the bounds, see `GenericOperations::range`.
+fn key_values(
+    keyspace: &Keyspace,
+    (start, end): (Bound<&[u8]>, Bound<&[u8]>),
+    rev: bool,
+    limit: Option<u64>,
+    with_values: bool,
+) -> KeyValues {
+    let limit = limit.map_or(usize::MAX, |limit| {
+        usize::try_from(limit).unwrap_or(usize::MAX)
+    });
+    if !with_values {
+        return keyspace
+            .key_range(start, end, rev)
+            .take(limit)
+            .map(|key| (key, None))
+            .collect();
+    }
+    keyspace
+        .range(start, end, rev)
+        .take(limit)
+        .map(|(key, value)| {
+            let value = match Arc::as_ref(&value) {
+                Value::String(value) => Some(Bytes::from(value.as_bytes().as_ref())),
+                _ => None,
+            };
+            (key, value)
+        })
+        .collect()
+}
 
 /// Generic methods for all Data Types.
 pub trait GenericOperations {
     /// Returns the number of keys that exist from those specified.
     /// If the same key is provided multiple times, it is counted multiple times.
-    fn exists(&self, keys: Vec<&str>) -> Result<u64, Box<dyn Error>>;
+    fn exists(&self, keys: Vec<&[u8]>) -> Result<u64, Box<dyn Error>>;
 
     /// Deletes the specified keys and returns the number of keys that were removed.
     /// A key is ignored if it does not exist.
-    fn delete(&mut self, keys: Vec<&str>) -> Result<u64, Box<dyn Error>>;
+    fn delete(&mut self, keys: Vec<&[u8]>) -> Result<u64, Box<dyn Error>>;
+
+    /// Sets the deadline of the key in milliseconds since the Unix epoch.
+    /// A deadline in the past deletes the key. Returns false if the key does not exist.
+    fn expire_at(&mut self, key: &[u8], deadline: i64) -> Result<bool, Box<dyn Error>>;
+
+    /// Returns the remaining time to live in milliseconds,
+    /// -2 if the key does not exist and -1 if it has no deadline.
+    fn pttl(&self, key: &[u8]) -> Result<i64, Box<dyn Error>>;
+
+    /// Removes the deadline of the key, returns false if the key does not exist or has none.
+    fn persist(&mut self, key: &[u8]) -> Result<bool, Box<dyn Error>>;
+
+    /// Visits up to `count` keys starting from the cursor and returns the next cursor
+    /// with the visited keys that match the pattern and hold the given type.
+    fn scan(
+        &self,
+        cursor: &str,
+        pattern: Option<&[u8]>,
+        count: u64,
+        value_type: Option<&str>,
+    ) -> Result<(String, Vec<Bytes>), Box<dyn Error>>;
+
+    /// Returns all keys matching the pattern.
+    fn keys(&self, pattern: &[u8]) -> Result<Vec<Bytes>, Box<dyn Error>>;
+
+    /// Returns the keys between the bounds in order, or from the highest with `rev`, up to
+    /// `limit` keys. The keys are kept in order, so only the keys that are returned are read.
+    /// With `with_values`, the keys holding a string come with their value and other keys with
+    /// None, as only string values are returned.
+    fn range(
+        &self,
+        start: &LexBound,
+        end: &LexBound,
+        rev: bool,
+        limit: Option<u64>,
+        with_values: bool,
+    ) -> Result<KeyValues, Box<dyn Error>>;
+
+    /// Returns the keys starting with the prefix in order, up to `limit` keys, see `range`.
+    /// Keys sharing a prefix are next to each other, e.g. all keys under `user:42:`.
+    fn prefix(
+        &self,
+        prefix: &[u8],
+        limit: Option<u64>,
+        with_values: bool,
+    ) -> Result<KeyValues, Box<dyn Error>>;
+
+    /// Returns the number of keys, including expired keys that have not been evicted yet.
+    fn dbsize(&self) -> Result<u64, Box<dyn Error>>;
+
+    /// Returns a random key, or None if the keyspace is empty.
+    fn random_key(&self) -> Result<Option<Bytes>, Box<dyn Error>>;
+
+    /// Moves the value of the key to the new key. Fails with `NoSuchKey` if the key does not exist.
+    /// Without `replace` nothing happens if the new key exists, and false is returned.
+    fn rename(&mut self, key: &[u8], new_key: &[u8], replace: bool)
+        -> Result<bool, Box<dyn Error>>;
+
+    /// Copies the value to the destination and returns true.
+    /// Returns false if the source does not exist, or the destination does and `replace` is false.
+    fn copy(
+        &mut self,
+        source: &[u8],
+        destination: &[u8],
+        replace: bool,
+    ) -> Result<bool, Box<dyn Error>>;
+
+    /// Returns the type of the value, or None if the key does not exist.
+    fn key_type(&self, key: &[u8]) -> Result<Option<&'static str>, Box<dyn Error>>;
+
+    /// Updates the access time of the keys and returns the number of keys that exist.
+    fn touch(&self, keys: Vec<&[u8]>) -> Result<u64, Box<dyn Error>>;
+
+    /// Returns the internal representation of the value, or None if the key does not exist.
+    fn object_encoding(&self, key: &[u8]) -> Result<Option<&'static str>, Box<dyn Error>>;
+
+    /// Returns the number of seconds since the key was last accessed.
+    fn object_idle_time(&self, key: &[u8]) -> Result<Option<u64>, Box<dyn Error>>;
+
+    /// Handles a generic command and returns the result.
+    fn handle_generic_command(
+        &mut self,
+        command: GenericCommand,
+    ) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            GenericCommand::Exists { keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                let result = self.exists(keys_ref)?;
+                Ok(CommandResult::Int(result as i64))
+            }
+            GenericCommand::Delete { keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                let result = self.delete(keys_ref)?;
+                Ok(CommandResult::Int(result as i64))
+            }
+            GenericCommand::Expire { key, seconds } => {
+                let deadline =
+                    (unix_time_millis() as i64).saturating_add(seconds.saturating_mul(1000));
+                Ok(CommandResult::Bool(self.expire_at(&key, deadline)?))
+            }
+            GenericCommand::PExpire { key, milliseconds } => {
+                let deadline = (unix_time_millis() as i64).saturating_add(milliseconds);
+                Ok(CommandResult::Bool(self.expire_at(&key, deadline)?))
+            }
+            GenericCommand::ExpireAt { key, timestamp } => {
+                let deadline = timestamp.saturating_mul(1000);
+                Ok(CommandResult::Bool(self.expire_at(&key, deadline)?))
+            }
+            GenericCommand::PExpireAt { key, timestamp } => {
+                Ok(CommandResult::Bool(self.expire_at(&key, timestamp)?))
+            }
+            GenericCommand::Ttl { key } => {
+                let ttl = self.pttl(&key)?;
+                // Rounded to the nearest second, the same way Redis does it.
+                Ok(CommandResult::Int(if ttl < 0 {
+                    ttl
+                } else {
+                    (ttl + 500) / 1000
+                }))
+            }
+            GenericCommand::PTtl { key } => Ok(CommandResult::Int(self.pttl(&key)?)),
+            GenericCommand::Persist { key } => Ok(CommandResult::Bool(self.persist(&key)?)),
+            GenericCommand::Scan {
+                cursor,
+                pattern,
+                count,
+                value_type,
+            } => {
+                let count = count.unwrap_or(DEFAULT_SCAN_COUNT);
+                let (cursor, keys) =
+                    self.scan(&cursor, pattern.as_deref(), count, value_type.as_deref())?;
+                Ok(CommandResult::Array(vec![
+                    CommandResult::String(cursor.into()),
+                    CommandResult::Array(keys.into_iter().map(CommandResult::String).collect()),
+                ]))
+            }
+            GenericCommand::Keys { pattern } => Ok(CommandResult::Array(
+                self.keys(&pattern)?
+                    .into_iter()
+                    .map(CommandResult::String)
+                    .collect(),
+            )),
+            GenericCommand::Range {
+                start,
+                end,
+                rev,
+                limit,
+                with_values,
+            } => {
+                let keys = self.range(&start, &end, rev, limit, with_values)?;
+                Ok(to_key_values(keys, with_values))
+            }
+            GenericCommand::Prefix {
+                prefix,
+                limit,
+                with_values,
+            } => {
+                let keys = self.prefix(&prefix, limit, with_values)?;
+                Ok(to_key_values(keys, with_values))
+            }
+            GenericCommand::DbSize => Ok(CommandResult::Int(self.dbsize()? as i64)),
+            GenericCommand::RandomKey => Ok(match self.random_key()? {
+                Some(key) => CommandResult::String(key),
+                None => CommandResult::Nil,
+            }),
+            GenericCommand::Rename { key, new_key } => {
+                self.rename(&key, &new_key, true)?;
+                Ok(CommandResult::String("OK".into()))
+            }
+            GenericCommand::RenameNx { key, new_key } => {
+                Ok(CommandResult::Bool(self.rename(&key, &new_key, false)?))
+            }
+            GenericCommand::Copy {
+                source,
+                destination,
+                replace,
+            } => Ok(CommandResult::Bool(self.copy(
+                &source,
+                &destination,
+                replace,
+            )?)),
+            GenericCommand::Type { key } => Ok(CommandResult::String(
+                self.key_type(&key)?.unwrap_or("none").into(),
+            )),
+            GenericCommand::Touch { keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                Ok(CommandResult::Int(self.touch(keys_ref)? as i64))
+            }
+            GenericCommand::Unlink { keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                Ok(CommandResult::Int(self.delete(keys_ref)? as i64))
+            }
+            GenericCommand::ObjectEncoding { key } => Ok(match self.object_encoding(&key)? {
+                Some(encoding) => CommandResult::String(encoding.into()),
+                None => CommandResult::Nil,
+            }),
+            GenericCommand::ObjectIdleTime { key } => Ok(match self.object_idle_time(&key)? {
+                Some(seconds) => CommandResult::Int(seconds as i64),
+                None => CommandResult::Nil,
+            }),
+        }
+    }
+}
+
+/// Access to the keyspace that the operations of a data type work on.
+/// Standalone stores own a keyspace with a single data type in it,
+/// while `DataStorage` shares one keyspace between all data types.
+pub trait KeyspaceAccess {
+    fn keyspace(&self) -> &Keyspace;
+    fn keyspace_mut(&mut self) -> &mut Keyspace;
+}
+
+impl<T: KeyspaceAccess> GenericOperations for T {
+    fn exists(&self, keys: Vec<&[u8]>) -> Result<u64, Box<dyn Error>> {
+        let count = keys
+            .iter()
+            .filter(|&&key| self.keyspace().contains_key(key))
+            .count() as u64;
+        Ok(count)
+    }
+
+    fn delete(&mut self, keys: Vec<&[u8]>) -> Result<u64, Box<dyn Error>> {
+        let mut count = 0;
+        for key in keys {
+            if self.keyspace_mut().remove(key).is_some() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn expire_at(&mut self, key: &[u8], deadline: i64) -> Result<bool, Box<dyn Error>> {
+        if !self.keyspace().contains_key(key) {
+            return Ok(false);
+        }
+        if deadline <= unix_time_millis() as i64 {
+            self.keyspace_mut().remove(key);
+        } else {
+            self.keyspace_mut().set_expiry(key, deadline as u64);
+        }
+        Ok(true)
+    }
+
+    fn pttl(&self, key: &[u8]) -> Result<i64, Box<dyn Error>> {
+        if !self.keyspace().contains_key(key) {
+            return Ok(-2);
+        }
+        Ok(match self.keyspace().expiry(key) {
+            Some(deadline) => deadline.saturating_sub(unix_time_millis()) as i64,
+            None => -1,
+        })
+    }
+
+    fn persist(&mut self, key: &[u8]) -> Result<bool, Box<dyn Error>> {
+        if !self.keyspace().contains_key(key) {
+            return Ok(false);
+        }
+        Ok(self.keyspace_mut().clear_expiry(key))
+    }
+
+    fn scan(
+        &self,
+        cursor: &str,
+        pattern: Option<&[u8]>,
+        count: u64,
+        value_type: Option<&str>,
+    ) -> Result<(String, Vec<Bytes>), Box<dyn Error>> {
+        if count == 0 {
+            return Err(Box::new(DataTypeError::SyntaxError));
+        }
+        let start = decode_cursor(cursor)?;
+        let count = usize::try_from(count).unwrap_or(usize::MAX);
+        let (keys, next) = self.keyspace().scan(start.as_deref(), count, |key, value| {
+            pattern.is_none_or(|pattern| glob_match(pattern, key))
+                && value_type.is_none_or(|value_type| value.type_name() == value_type)
+        });
+        Ok((encode_cursor(next.as_deref()), keys))
+    }
+
+    fn keys(&self, pattern: &[u8]) -> Result<Vec<Bytes>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .keys()
+            .filter(|key| glob_match(pattern, key))
+            .collect())
+    }
+
+    fn range(
+        &self,
+        start: &LexBound,
+        end: &LexBound,
+        rev: bool,
+        limit: Option<u64>,
+        with_values: bool,
+    ) -> Result<KeyValues, Box<dyn Error>> {
+        let (Some(start), Some(end)) = (start_bound(start), end_bound(end)) else {
+            return Ok(Vec::new());
+        };
+        Ok(key_values(
+            self.keyspace(),
+            (start, end),
+            rev,
+            limit,
+            with_values,
+        ))
+    }
+
+    fn prefix(
+        &self,
+        prefix: &[u8],
+        limit: Option<u64>,
+        with_values: bool,
+    ) -> Result<KeyValues, Box<dyn Error>> {
+        let end = prefix_end(prefix);
+        let end = end.as_deref().map_or(Bound::Unbounded, Bound::Excluded);
+        Ok(key_values(
+            self.keyspace(),
+            (Bound::Included(prefix), end),
+            false,
+            limit,
+            with_values,
+        ))
+    }
+
+    fn dbsize(&self) -> Result<u64, Box<dyn Error>> {
+        Ok(self.keyspace().len() as u64)
+    }
+
+    fn random_key(&self) -> Result<Option<Bytes>, Box<dyn Error>> {
+        Ok(self.keyspace().random_key())
+    }
+
+    fn rename(
+        &mut self,
+        key: &[u8],
+        new_key: &[u8],
+        replace: bool,
+    ) -> Result<bool, Box<dyn Error>> {
+        if !self.keyspace().contains_key(key) {
+            return Err(Box::new(DataTypeError::NoSuchKey));
+        }
+        Ok(self.keyspace_mut().rename(key, new_key, replace))
+    }
+
+    fn copy(
+        &mut self,
+        source: &[u8],
+        destination: &[u8],
+        replace: bool,
+    ) -> Result<bool, Box<dyn Error>> {
+        if source == destination {
+            return Err(Box::new(DataTypeError::SameObject));
+        }
+        Ok(self.keyspace_mut().copy(source, destination, replace))
+    }
+
+    fn key_type(&self, key: &[u8]) -> Result<Option<&'static str>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .peek_value(key)
+            .map(|value| value.type_name()))
+    }
+
+    fn touch(&self, keys: Vec<&[u8]>) -> Result<u64, Box<dyn Error>> {
+        let count = keys
+            .iter()
+            .filter(|&&key| self.keyspace().touch(key))
+            .count() as u64;
+        Ok(count)
+    }
+
+    fn object_encoding(&self, key: &[u8]) -> Result<Option<&'static str>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .peek_value(key)
+            .map(|value| value.encoding()))
+    }
+
+    fn object_idle_time(&self, key: &[u8]) -> Result<Option<u64>, Box<dyn Error>> {
+        Ok(self.keyspace().idle_time(key).map(|millis| millis / 1000))
+    }
 }
 
 /// Base trait for all Data Types.
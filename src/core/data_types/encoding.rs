@@ -0,0 +1,235 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use crate::core::bytes::Bytes;
+
+/// Collections with more elements than this are converted from a packed encoding to a hash table,
+/// a linked list or a skip list. Lookups in a packed collection are linear.
+pub const MAX_PACKED_ENTRIES: usize = 128;
+
+/// Collections with a longer element than this are converted from a packed encoding.
+pub const MAX_PACKED_VALUE_LEN: usize = 64;
+
+/// Returns the integer if the bytes are its canonical decimal form, the one `i64::to_string`
+/// produces. Only such values are stored as integers, so that they read back byte for byte:
+/// "12" is an integer, while "012", "+12" and "-0" are not.
+pub fn canonical_int(bytes: &[u8]) -> Option<i64> {
+    // i64::MIN is the longest one, 20 bytes.
+    if bytes.is_empty() || bytes.len() > 20 {
+        return None;
+    }
+    let (negative, digits) = match bytes {
+        [b'-', digits @ ..] => (true, digits),
+        digits => (false, digits),
+    };
+    let leading_zero = digits.len() > 1 && digits[0] == b'0';
+    if digits.is_empty()
+        || !digits.iter().all(u8::is_ascii_digit)
+        || leading_zero
+        || (negative && digits == b"0")
+    {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+/// Byte strings packed one after another into a single buffer, each prefixed with its length
+/// as a LEB128 varint. A small collection costs one allocation this way, instead of one per
+/// element plus the buckets of a hash table. Access by index walks the buffer, so packed
+/// collections are converted once they grow, see `MAX_PACKED_ENTRIES`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListPack {
+    buf: Vec<u8>,
+    len: usize,
+}
+
+impl ListPack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> ListPackIter<'_> {
+        ListPackIter {
+            buf: &self.buf,
+            remaining: self.len,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        self.iter().nth(index)
+    }
+
+    pub fn position(&self, element: &[u8]) -> Option<usize> {
+        self.iter().position(|candidate| candidate == element)
+    }
+
+    pub fn push_back(&mut self, element: &[u8]) {
+        write_varint(&mut self.buf, element.len());
+        self.buf.extend_from_slice(element);
+        self.len += 1;
+    }
+
+    /// Inserts the element before the one at `index`, or at the end if `index` is the length.
+    pub fn insert(&mut self, index: usize, element: &[u8]) {
+        assert!(index <= self.len, "index {} out of {}", index, self.len);
+        let offset = self.offset(index);
+        let mut encoded = Vec::with_capacity(element.len() + 2);
+        write_varint(&mut encoded, element.len());
+        encoded.extend_from_slice(element);
+        self.buf.splice(offset..offset, encoded);
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, index: usize) -> Bytes {
+        assert!(index < self.len, "index {} out of {}", index, self.len);
+        let offset = self.offset(index);
+        let (len, start) = read_varint(&self.buf, offset);
+        let element = Bytes::from(&self.buf[start..start + len]);
+        self.buf.drain(offset..start + len);
+        self.len -= 1;
+        element
+    }
+
+    pub fn replace(&mut self, index: usize, element: &[u8]) {
+        self.remove(index);
+        self.insert(index, element);
+    }
+
+    /// Keeps the first `len` elements.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            let offset = self.offset(len);
+            self.buf.truncate(offset);
+            self.len = len;
+        }
+    }
+
+    /// Removes the first `count` elements.
+    pub fn remove_front(&mut self, count: usize) {
+        let count = count.min(self.len);
+        let offset = self.offset(count);
+        self.buf.drain(..offset);
+        self.len -= count;
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.clear();
+        self.len = 0;
+    }
+
+    /// Returns where the element at `index` starts, or the end of the buffer.
+    fn offset(&self, index: usize) -> usize {
+        let mut offset = 0;
+        for _ in 0..index {
+            let (len, start) = read_varint(&self.buf, offset);
+            offset = start + len;
+        }
+        offset
+    }
+}
+
+pub struct ListPackIter<'a> {
+    buf: &'a [u8],
+    remaining: usize,
+}
+
+impl<'a> Iterator for ListPackIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (len, start) = read_varint(self.buf, 0);
+        let (element, rest) = self.buf[start..].split_at(len);
+        self.buf = rest;
+        self.remaining -= 1;
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for ListPackIter<'_> {}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Returns the value and where the bytes after it start.
+fn read_varint(buf: &[u8], mut offset: usize) -> (usize, usize) {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = buf[offset];
+        offset += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte < 0x80 {
+            return (value, offset);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elements(pack: &ListPack) -> Vec<&[u8]> {
+        pack.iter().collect()
+    }
+
+    #[test]
+    fn test_canonical_int_accepts_only_what_to_string_produces() {
+        assert_eq!(canonical_int(b"0"), Some(0));
+        assert_eq!(canonical_int(b"-42"), Some(-42));
+        assert_eq!(canonical_int(b"-9223372036854775808"), Some(i64::MIN));
+        assert_eq!(canonical_int(b"9223372036854775808"), None);
+        for value in ["", "-", "-0", "007", "+1", " 1", "1.0", "1e3"] {
+            assert_eq!(canonical_int(value.as_bytes()), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_list_pack_edits_keep_the_other_elements() {
+        // Arrange
+        let mut pack = ListPack::new();
+        let long = vec![b'x'; 300];
+        for element in [&b"a"[..], b"", &long, b"d"] {
+            pack.push_back(element);
+        }
+
+        // Act
+        pack.insert(0, b"first");
+        let removed = pack.remove(3);
+        pack.replace(1, b"A");
+        pack.insert(pack.len(), b"last");
+
+        // Assert
+        assert_eq!(removed, Bytes::from(long));
+        assert_eq!(
+            elements(&pack),
+            vec![&b"first"[..], b"A", b"", b"d", b"last"]
+        );
+        assert_eq!(pack.get(3), Some(&b"d"[..]));
+        assert_eq!(pack.position(b"last"), Some(4));
+        pack.remove_front(2);
+        pack.truncate(2);
+        assert_eq!(elements(&pack), vec![&b""[..], b"d"]);
+    }
+}
@@ -2,4 +2,670 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-// TODO
+use crate::core::bytes::{parse_number, Bytes};
+use crate::core::commands::{hash::HashCommand, Command, CommandResult};
+use crate::core::data_types::{
+    data_type::{DataType, DataTypeError, GenericOperations, KeyspaceAccess},
+    encoding::{ListPack, MAX_PACKED_ENTRIES, MAX_PACKED_VALUE_LEN},
+    keyspace::Keyspace,
+};
+use std::collections::HashMap;
+use std::error::Error;
+
+type FieldValuePairs<'a> = Vec<(&'a [u8], &'a [u8])>;
+
+/// A hash in the most compact of its encodings, as reported by OBJECT ENCODING: `listpack`
+/// while it is small, `hashtable` once it has more than `MAX_PACKED_ENTRIES` fields or a field
+/// or value longer than `MAX_PACKED_VALUE_LEN`. A hash table is never converted back.
+#[derive(Debug, Clone)]
+pub enum HashValue {
+    /// Fields and their values alternate.
+    Packed(ListPack),
+    Table(HashMap<Bytes, Bytes>),
+}
+
+impl HashValue {
+    pub fn len(&self) -> usize {
+        match self {
+            HashValue::Packed(pack) => pack.len() / 2,
+            HashValue::Table(table) => table.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&[u8]> {
+        match self {
+            HashValue::Packed(_) => self
+                .iter()
+                .find(|(candidate, _)| *candidate == field)
+                .map(|(_, value)| value),
+            HashValue::Table(table) => table.get(field).map(|value| value.as_slice()),
+        }
+    }
+
+    pub fn contains_key(&self, field: &[u8]) -> bool {
+        self.get(field).is_some()
+    }
+
+    /// Sets the value of the field, returns true if the field is new.
+    pub fn insert(&mut self, field: Bytes, value: Bytes) -> bool {
+        if let HashValue::Packed(pack) = self {
+            if let Some(index) = field_index(pack, &field) {
+                if value.len() <= MAX_PACKED_VALUE_LEN {
+                    pack.replace(index + 1, &value);
+                    return false;
+                }
+            } else if pack.len() / 2 < MAX_PACKED_ENTRIES
+                && field.len() <= MAX_PACKED_VALUE_LEN
+                && value.len() <= MAX_PACKED_VALUE_LEN
+            {
+                pack.push_back(&field);
+                pack.push_back(&value);
+                return true;
+            }
+            self.convert_to_table();
+        }
+        match self {
+            HashValue::Table(table) => table.insert(field, value).is_none(),
+            HashValue::Packed(_) => unreachable!("converted to a table"),
+        }
+    }
+
+    /// Removes the field, returns true if it existed.
+    pub fn remove(&mut self, field: &[u8]) -> bool {
+        match self {
+            HashValue::Packed(pack) => match field_index(pack, field) {
+                Some(index) => {
+                    pack.remove(index);
+                    pack.remove(index);
+                    true
+                }
+                None => false,
+            },
+            HashValue::Table(table) => table.remove(field).is_some(),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&[u8], &[u8])> + '_> {
+        match self {
+            HashValue::Packed(pack) => {
+                let mut elements = pack.iter();
+                Box::new(std::iter::from_fn(move || {
+                    Some((elements.next()?, elements.next()?))
+                }))
+            }
+            HashValue::Table(table) => Box::new(
+                table
+                    .iter()
+                    .map(|(field, value)| (field.as_slice(), value.as_slice())),
+            ),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.iter().map(|(field, _)| field)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &[u8]> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            HashValue::Packed(_) => "listpack",
+            HashValue::Table(_) => "hashtable",
+        }
+    }
+
+    fn convert_to_table(&mut self) {
+        let table = self
+            .iter()
+            .map(|(field, value)| (Bytes::from(field), Bytes::from(value)))
+            .collect();
+        *self = HashValue::Table(table);
+    }
+}
+
+impl Default for HashValue {
+    fn default() -> Self {
+        HashValue::Packed(ListPack::new())
+    }
+}
+
+impl FromIterator<(Bytes, Bytes)> for HashValue {
+    fn from_iter<I: IntoIterator<Item = (Bytes, Bytes)>>(pairs: I) -> Self {
+        let mut hash = HashValue::default();
+        for (field, value) in pairs {
+            hash.insert(field, value);
+        }
+        hash
+    }
+}
+
+/// Returns the index of the element holding the field in a packed hash.
+fn field_index(pack: &ListPack, field: &[u8]) -> Option<usize> {
+    pack.iter()
+        .step_by(2)
+        .position(|candidate| candidate == field)
+        .map(|pair| pair * 2)
+}
+
+/// Stores field-value maps under keys.
+/// A key is removed as soon as its last field is deleted.
+#[derive(Debug)]
+pub struct HashStore {
+    data: Keyspace,
+}
+
+impl DataType for HashStore {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(HashStore {
+            data: Keyspace::new(),
+        })
+    }
+
+    fn handle_command(&mut self, command: Command) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            Command::Hash(cmd) => self.handle_hash_command(cmd),
+            Command::Generic(cmd) => self.handle_generic_command(cmd),
+            #[allow(unreachable_patterns)]
+            _ => Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Command not supported by HashStore",
+            ))),
+        }
+    }
+}
+
+impl KeyspaceAccess for HashStore {
+    fn keyspace(&self) -> &Keyspace {
+        &self.data
+    }
+
+    fn keyspace_mut(&mut self) -> &mut Keyspace {
+        &mut self.data
+    }
+}
+
+impl HashOperations for HashStore {}
+
+fn to_array(values: Vec<&[u8]>) -> CommandResult {
+    CommandResult::Array(
+        values
+            .into_iter()
+            .map(|s| CommandResult::String(s.into()))
+            .collect(),
+    )
+}
+
+/// Operations of the Hash data type.
+pub trait HashOperations: KeyspaceAccess {
+    /// Handles a hash command and returns the result.
+    fn handle_hash_command(
+        &mut self,
+        command: HashCommand,
+    ) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            HashCommand::Set { key, fields } => {
+                let added = self.hset(&key, fields)?;
+                Ok(CommandResult::Int(added as i64))
+            }
+            HashCommand::Get { key, field } => {
+                let result = self.hget(&key, &field)?;
+                Ok(result.map_or(CommandResult::Nil, |s| CommandResult::String(s.into())))
+            }
+            HashCommand::MGet { key, fields } => {
+                let fields_ref: Vec<&[u8]> = fields.iter().map(|s| s.as_slice()).collect();
+                let result = self.hmget(&key, fields_ref)?;
+                Ok(CommandResult::Array(
+                    result
+                        .into_iter()
+                        .map(|v| v.map_or(CommandResult::Nil, |s| CommandResult::String(s.into())))
+                        .collect(),
+                ))
+            }
+            HashCommand::Del { key, fields } => {
+                let fields_ref: Vec<&[u8]> = fields.iter().map(|s| s.as_slice()).collect();
+                let removed = self.hdel(&key, fields_ref)?;
+                Ok(CommandResult::Int(removed as i64))
+            }
+            HashCommand::Exists { key, field } => {
+                let result = self.hexists(&key, &field)?;
+                Ok(CommandResult::Bool(result))
+            }
+            HashCommand::Len { key } => {
+                let len = self.hlen(&key)?;
+                Ok(CommandResult::Int(len as i64))
+            }
+            HashCommand::Keys { key } => {
+                let fields = self.hkeys(&key)?;
+                Ok(to_array(fields))
+            }
+            HashCommand::Vals { key } => {
+                let values = self.hvals(&key)?;
+                Ok(to_array(values))
+            }
+            HashCommand::GetAll { key } => {
+                let pairs = self.hgetall(&key)?;
+                Ok(to_array(
+                    pairs.into_iter().flat_map(|(f, v)| [f, v]).collect(),
+                ))
+            }
+            HashCommand::IncrBy {
+                key,
+                field,
+                increment,
+            } => {
+                let value = self.hincrby(&key, &field, increment)?;
+                Ok(CommandResult::Int(value))
+            }
+            HashCommand::SetNx { key, field, value } => {
+                let result = self.hsetnx(&key, &field, &value)?;
+                Ok(CommandResult::Bool(result))
+            }
+        }
+    }
+
+    /// Sets the fields and returns the number of fields that were newly added.
+    fn hset(&mut self, key: &[u8], fields: Vec<(Bytes, Bytes)>) -> Result<u64, Box<dyn Error>> {
+        let hash = self
+            .keyspace_mut()
+            .get_or_insert_default::<HashValue>(key)?;
+        let mut added = 0;
+        for (field, value) in fields {
+            if hash.insert(field, value) {
+                added += 1;
+            }
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(added)
+    }
+
+    fn hget(&self, key: &[u8], field: &[u8]) -> Result<Option<&[u8]>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<HashValue>(key)?
+            .and_then(|hash| hash.get(field)))
+    }
+
+    fn hmget(&self, key: &[u8], fields: Vec<&[u8]>) -> Result<Vec<Option<&[u8]>>, Box<dyn Error>> {
+        let hash = self.keyspace().get::<HashValue>(key)?;
+        Ok(fields
+            .into_iter()
+            .map(|field| hash.and_then(|h| h.get(field)))
+            .collect())
+    }
+
+    fn hdel(&mut self, key: &[u8], fields: Vec<&[u8]>) -> Result<u64, Box<dyn Error>> {
+        let Some(hash) = self.keyspace_mut().get_mut::<HashValue>(key)? else {
+            return Ok(0);
+        };
+        let mut removed = 0;
+        for field in fields {
+            if hash.remove(field) {
+                removed += 1;
+            }
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(removed)
+    }
+
+    fn hexists(&self, key: &[u8], field: &[u8]) -> Result<bool, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<HashValue>(key)?
+            .is_some_and(|hash| hash.contains_key(field)))
+    }
+
+    fn hlen(&self, key: &[u8]) -> Result<u64, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<HashValue>(key)?
+            .map_or(0, |hash| hash.len() as u64))
+    }
+
+    fn hkeys(&self, key: &[u8]) -> Result<Vec<&[u8]>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<HashValue>(key)?
+            .map(|hash| hash.keys().collect())
+            .unwrap_or_default())
+    }
+
+    fn hvals(&self, key: &[u8]) -> Result<Vec<&[u8]>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<HashValue>(key)?
+            .map(|hash| hash.values().collect())
+            .unwrap_or_default())
+    }
+
+    fn hgetall(&self, key: &[u8]) -> Result<FieldValuePairs<'_>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<HashValue>(key)?
+            .map(|hash| hash.iter().collect())
+            .unwrap_or_default())
+    }
+
+    /// Increments the integer stored at the field, a missing field is treated as 0.
+    fn hincrby(&mut self, key: &[u8], field: &[u8], increment: i64) -> Result<i64, Box<dyn Error>> {
+        let current = match self.hget(key, field)? {
+            Some(value) => parse_number::<i64>(value).ok_or(DataTypeError::NotAnInteger)?,
+            None => 0,
+        };
+        let new_value = current
+            .checked_add(increment)
+            .ok_or(DataTypeError::Overflow)?;
+        self.keyspace_mut()
+            .get_or_insert_default::<HashValue>(key)?
+            .insert(Bytes::from(field), new_value.to_string().into());
+        Ok(new_value)
+    }
+
+    fn hsetnx(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> Result<bool, Box<dyn Error>> {
+        let hash = self
+            .keyspace_mut()
+            .get_or_insert_default::<HashValue>(key)?;
+        if hash.contains_key(field) {
+            return Ok(false);
+        }
+        hash.insert(Bytes::from(field), Bytes::from(value));
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::bytes::Bytes;
+    use crate::core::commands::generic::GenericCommand;
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<(Bytes, Bytes)> {
+        pairs
+            .iter()
+            .map(|(f, v)| (Bytes::from(*f), Bytes::from(*v)))
+            .collect()
+    }
+
+    #[test]
+    fn test_new() {
+        // Arrange
+        let store = HashStore::new();
+        // Act
+        // Assert
+        assert!(store.is_ok());
+    }
+
+    #[test]
+    fn test_hset_counts_only_new_fields() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+
+        // Act
+        let result_1 = store
+            .hset(b"key", fields(&[("f1", "v1"), ("f2", "v2")]))
+            .unwrap();
+        let result_2 = store
+            .hset(b"key", fields(&[("f2", "v2_new"), ("f3", "v3")]))
+            .unwrap();
+
+        // Assert
+        assert_eq!(result_1, 2);
+        assert_eq!(result_2, 1);
+        assert_eq!(store.hget(b"key", b"f2").unwrap(), Some(&b"v2_new"[..]));
+        assert_eq!(store.hlen(b"key").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_hget_with_non_existent_key_and_field() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store.hset(b"key", fields(&[("f1", "v1")])).unwrap();
+
+        // Act
+        let result_1 = store.hget(b"other", b"f1").unwrap();
+        let result_2 = store.hget(b"key", b"f2").unwrap();
+
+        // Assert
+        assert_eq!(result_1, None);
+        assert_eq!(result_2, None);
+    }
+
+    #[test]
+    fn test_hmget_keeps_order_of_requested_fields() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store
+            .hset(b"key", fields(&[("f1", "v1"), ("f2", "v2")]))
+            .unwrap();
+
+        // Act
+        let result = store.hmget(b"key", vec![b"f2", b"missing", b"f1"]).unwrap();
+
+        // Assert
+        assert_eq!(result, vec![Some(&b"v2"[..]), None, Some(&b"v1"[..])]);
+    }
+
+    #[test]
+    fn test_hdel_removes_key_when_last_field_is_deleted() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store
+            .hset(b"key", fields(&[("f1", "v1"), ("f2", "v2")]))
+            .unwrap();
+
+        // Act
+        let result_1 = store.hdel(b"key", vec![b"f1", b"missing"]).unwrap();
+        let result_2 = store.hdel(b"key", vec![b"f2"]).unwrap();
+
+        // Assert
+        assert_eq!(result_1, 1);
+        assert_eq!(result_2, 1);
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_hexists() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store.hset(b"key", fields(&[("f1", "v1")])).unwrap();
+
+        // Act & Assert
+        assert!(store.hexists(b"key", b"f1").unwrap());
+        assert!(!store.hexists(b"key", b"f2").unwrap());
+        assert!(!store.hexists(b"other", b"f1").unwrap());
+    }
+
+    #[test]
+    fn test_hkeys_hvals_and_hgetall() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store
+            .hset(b"key", fields(&[("f1", "v1"), ("f2", "v2")]))
+            .unwrap();
+
+        // Act
+        let mut keys = store.hkeys(b"key").unwrap();
+        let mut vals = store.hvals(b"key").unwrap();
+        let mut all = store.hgetall(b"key").unwrap();
+        keys.sort();
+        vals.sort();
+        all.sort();
+
+        // Assert
+        assert_eq!(keys, vec![&b"f1"[..], b"f2"]);
+        assert_eq!(vals, vec![&b"v1"[..], b"v2"]);
+        assert_eq!(
+            all,
+            vec![(&b"f1"[..], &b"v1"[..]), (&b"f2"[..], &b"v2"[..])]
+        );
+        assert!(store.hgetall(b"missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_hincrby_creates_and_increments_field() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+
+        // Act
+        let result_1 = store.hincrby(b"key", b"counter", 5).unwrap();
+        let result_2 = store.hincrby(b"key", b"counter", -8).unwrap();
+
+        // Assert
+        assert_eq!(result_1, 5);
+        assert_eq!(result_2, -3);
+        assert_eq!(store.hget(b"key", b"counter").unwrap(), Some(&b"-3"[..]));
+    }
+
+    #[test]
+    fn test_hincrby_with_non_integer_value_fails() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store.hset(b"key", fields(&[("f1", "abc")])).unwrap();
+
+        // Act
+        let result = store.hincrby(b"key", b"f1", 1);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::NotAnInteger)
+        );
+    }
+
+    #[test]
+    fn test_hincrby_detects_overflow() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store
+            .hset(b"key", fields(&[("f1", i64::MAX.to_string().as_str())]))
+            .unwrap();
+
+        // Act
+        let result = store.hincrby(b"key", b"f1", 1);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::Overflow)
+        );
+        assert_eq!(
+            store.hget(b"key", b"f1").unwrap(),
+            Some(i64::MAX.to_string().as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_hsetnx_does_not_overwrite_existing_field() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+
+        // Act
+        let result_1 = store.hsetnx(b"key", b"f1", b"v1").unwrap();
+        let result_2 = store.hsetnx(b"key", b"f1", b"v2").unwrap();
+
+        // Assert
+        assert!(result_1);
+        assert!(!result_2);
+        assert_eq!(store.hget(b"key", b"f1").unwrap(), Some(&b"v1"[..]));
+    }
+
+    #[test]
+    fn test_exists_and_delete_for_ranges_of_keys() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store.hset(b"key1", fields(&[("f", "v")])).unwrap();
+        store.hset(b"key2", fields(&[("f", "v")])).unwrap();
+
+        // Act
+        let exists = store.exists(vec![b"key1", b"key2", b"key3"]).unwrap();
+        let deleted = store.delete(vec![b"key1", b"key3"]).unwrap();
+
+        // Assert
+        assert_eq!(exists, 2);
+        assert_eq!(deleted, 1);
+        assert_eq!(store.exists(vec![b"key1", b"key2"]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_handle_command_set_and_get() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        let set_command = Command::Hash(HashCommand::Set {
+            key: "key".into(),
+            fields: fields(&[("f1", "v1")]),
+        });
+        let get_command = Command::Hash(HashCommand::Get {
+            key: "key".into(),
+            field: "f1".into(),
+        });
+
+        // Act
+        let set_result = store.handle_command(set_command).unwrap();
+        let get_result = store.handle_command(get_command).unwrap();
+
+        // Assert
+        assert_eq!(set_result, CommandResult::Int(1));
+        assert_eq!(get_result, CommandResult::String("v1".into()));
+    }
+
+    #[test]
+    fn test_handle_command_mget_returns_nil_for_missing_fields() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store.hset(b"key", fields(&[("f1", "v1")])).unwrap();
+        let command = Command::Hash(HashCommand::MGet {
+            key: "key".into(),
+            fields: vec!["f1".into(), "f2".into()],
+        });
+
+        // Act
+        let result = store.handle_command(command).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            CommandResult::Array(vec![CommandResult::String("v1".into()), CommandResult::Nil])
+        );
+    }
+
+    #[test]
+    fn test_handle_command_incrby() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        let command = Command::Hash(HashCommand::IncrBy {
+            key: "key".into(),
+            field: "counter".into(),
+            increment: -2,
+        });
+
+        // Act
+        let result = store.handle_command(command).unwrap();
+
+        // Assert
+        assert_eq!(result, CommandResult::Int(-2));
+    }
+
+    #[test]
+    fn test_handle_command_delete_with_existent_and_non_existent_keys() {
+        // Arrange
+        let mut store = HashStore::new().unwrap();
+        store.hset(b"key1", fields(&[("f", "v")])).unwrap();
+        let command = Command::Generic(GenericCommand::Delete {
+            keys: vec!["key1".into(), "key2".into()],
+        });
+
+        // Act
+        let result = store.handle_command(command).unwrap();
+
+        // Assert
+        assert_eq!(result, CommandResult::Int(1));
+        assert_eq!(store.hget(b"key1", b"f").unwrap(), None);
+    }
+}
@@ -0,0 +1,1177 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use crate::core::bytes::Bytes;
+use crate::core::data_types::{
+    data_type::DataTypeError, hash::HashValue, list::ListValue, set::SetValue,
+    sorted_set::SortedSet, string::StringValue,
+};
+use crate::core::storage::{
+    is_empty_range, memory::MemoryEngine, EngineError, Record, StorageEngine,
+};
+use rand::seq::IteratorRandom;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::iter;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the current time in milliseconds since the Unix epoch.
+/// Expiration deadlines are absolute, so that every node evicts a key at the same moment.
+pub fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// A value stored under a key, every key holds exactly one data type.
+#[derive(Debug, Clone)]
+pub enum Value {
+    String(StringValue),
+    Hash(HashValue),
+    List(ListValue),
+    Set(SetValue),
+    SortedSet(SortedSet),
+    Bitmap(Vec<u8>),
+}
+
+impl Value {
+    /// Returns the name of the data type, as reported by the TYPE command.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Hash(_) => "hash",
+            Value::List(_) => "list",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Bitmap(_) => "bitmap",
+        }
+    }
+
+    /// Returns the name of the internal representation, as reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Value::String(string) => string.encoding(),
+            Value::Hash(hash) => hash.encoding(),
+            Value::List(list) => list.encoding(),
+            Value::Set(set) => set.encoding(),
+            Value::SortedSet(sorted_set) => sorted_set.encoding(),
+            Value::Bitmap(_) => "raw",
+        }
+    }
+
+    /// Collections are removed from the keyspace as soon as they become empty.
+    /// Strings and bitmaps are never considered empty, an empty string is a valid value.
+    fn is_empty_collection(&self) -> bool {
+        match self {
+            Value::String(_) | Value::Bitmap(_) => false,
+            Value::Hash(hash) => hash.is_empty(),
+            Value::List(list) => list.is_empty(),
+            Value::Set(set) => set.is_empty(),
+            Value::SortedSet(sorted_set) => sorted_set.is_empty(),
+        }
+    }
+}
+
+/// A concrete type that can be stored in a `Value`.
+pub trait TypedValue: Default {
+    fn from_value(value: &Value) -> Option<&Self>;
+    fn from_value_mut(value: &mut Value) -> Option<&mut Self>;
+    fn into_value(self) -> Value;
+}
+
+macro_rules! impl_typed_value {
+    ($type:ty, $variant:ident) => {
+        impl TypedValue for $type {
+            fn from_value(value: &Value) -> Option<&Self> {
+                match value {
+                    Value::$variant(inner) => Some(inner),
+                    _ => None,
+                }
+            }
+
+            fn from_value_mut(value: &mut Value) -> Option<&mut Self> {
+                match value {
+                    Value::$variant(inner) => Some(inner),
+                    _ => None,
+                }
+            }
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+        }
+    };
+}
+
+impl_typed_value!(StringValue, String);
+impl_typed_value!(HashValue, Hash);
+impl_typed_value!(ListValue, List);
+impl_typed_value!(SetValue, Set);
+impl_typed_value!(SortedSet, SortedSet);
+impl_typed_value!(Vec<u8>, Bitmap);
+
+/// The keys with their values and deadlines as they were when `Keyspace::snapshot` was called.
+pub type KeyspaceSnapshot = Vec<(Bytes, Arc<Value>, Option<u64>)>;
+
+/// A value together with the time it was last accessed.
+/// The access time is updated by reads as well, hence the `Cell`.
+///
+/// The value is shared with the snapshots taken while it was stored. A write copies it first if
+/// a snapshot still holds it, so a snapshot never changes and taking one copies no values.
+#[derive(Debug, Clone)]
+struct Entry {
+    value: Arc<Value>,
+    /// Milliseconds since the Unix epoch.
+    accessed: Cell<u64>,
+}
+
+impl Entry {
+    fn new(value: Value) -> Self {
+        Entry {
+            value: Arc::new(value),
+            accessed: Cell::new(unix_time_millis()),
+        }
+    }
+
+    fn touch(&self) {
+        self.accessed.set(unix_time_millis());
+    }
+}
+
+/// A key loaded from the storage engine, together with what the engine holds for it.
+#[derive(Debug)]
+struct Slot {
+    /// None if the key doesn't exist or has been removed.
+    entry: Option<Entry>,
+    /// Whether the engine holds the key.
+    stored: bool,
+    /// The deadline the engine holds, restored if the changes are discarded.
+    stored_deadline: Option<u64>,
+    stored_accessed: u64,
+    /// Whether the value or the deadline changed, so that the key is written back.
+    dirty: bool,
+    /// Whether the engine was told the value is modified in place, see `StorageEngine::release`.
+    released: bool,
+}
+
+/// A single keyspace shared by all data types.
+/// Typed accessors fail with `DataTypeError::WrongType` if the key holds another data type.
+///
+/// The keys are kept by a storage engine, in memory or on disk. The keyspace works on the keys
+/// loaded from it: mutable accessors load the key themselves, while the others only see the
+/// keys loaded by `load`, so the keys of a command are loaded before it is executed, see
+/// `Command::keys`. `commit` writes the changes back to the engine once the command completes.
+/// The expiration deadlines are kept in memory as well, so that expired keys are found without
+/// reading the engine.
+///
+/// Keys are kept in order, so that a scan can resume from any key however the keyspace changes
+/// in the meantime.
+///
+/// A key may have an expiration deadline. Expired keys are invisible to all accessors, mutable
+/// accessors evict them on access and `evict_expired` removes them in the background.
+#[derive(Debug)]
+pub struct Keyspace {
+    engine: Box<dyn StorageEngine>,
+    /// The keys loaded from the engine since the last commit.
+    slots: BTreeMap<Bytes, Slot>,
+    /// The number of keys the engine holds.
+    stored_len: usize,
+    /// Deadlines in milliseconds since the Unix epoch.
+    expires: HashMap<Bytes, u64>,
+    /// The same deadlines ordered by time, so that the expired keys can be found without a scan.
+    deadlines: BTreeSet<(u64, Bytes)>,
+    /// Whether the values the engine holds are copied rather than modified in place, see
+    /// `set_rollback`.
+    rollback: bool,
+    /// The first failure of the engine since the last commit, which discards the changes.
+    /// Reads that fail see the key as missing until then.
+    error: RefCell<Option<EngineError>>,
+}
+
+impl Default for Keyspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keyspace {
+    /// Creates an empty keyspace kept in memory.
+    pub fn new() -> Self {
+        Self::with_engine(Box::new(MemoryEngine::new())).expect("an empty engine can't fail")
+    }
+
+    /// Creates a keyspace over the keys the engine holds. This reads every key to count them
+    /// and to find their deadlines, but not the values.
+    pub fn with_engine(engine: Box<dyn StorageEngine>) -> Result<Self, EngineError> {
+        let mut stored_len = 0;
+        let mut expires = HashMap::new();
+        let mut deadlines = BTreeSet::new();
+        for key in engine.keys(Bound::Unbounded, Bound::Unbounded, false) {
+            let (key, deadline) = key?;
+            stored_len += 1;
+            if let Some(deadline) = deadline {
+                expires.insert(key.clone(), deadline);
+                deadlines.insert((deadline, key));
+            }
+        }
+        Ok(Self {
+            engine,
+            slots: BTreeMap::new(),
+            stored_len,
+            expires,
+            deadlines,
+            rollback: false,
+            error: RefCell::new(None),
+        })
+    }
+
+    /// Loads the keys from the engine, so that all accessors see them.
+    pub fn load<'a>(
+        &mut self,
+        keys: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Result<(), EngineError> {
+        for key in keys {
+            if !self.slots.contains_key(key) {
+                let record = self.engine.get(key)?;
+                self.insert_slot(key, record);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the changed keys back to the engine and forgets the loaded ones.
+    /// If the engine failed since the last commit, the changes are discarded and the failure is
+    /// returned. If a write fails, the keys not written yet are kept and written by the next
+    /// commit.
+    pub fn commit(&mut self) -> Result<(), EngineError> {
+        if let Some(e) = self.error.get_mut().take() {
+            self.discard();
+            return Err(e);
+        }
+        while let Some((key, slot)) = self.slots.pop_first() {
+            if let Err(e) = self.write_back(&key, &slot) {
+                self.slots.insert(key, slot);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the changes made since the last commit, e.g. those of a command that failed, and
+    /// the loaded keys. A value the engine released to have it modified in place is put back as
+    /// it is now, see `StorageEngine::release`.
+    pub fn discard(&mut self) {
+        self.error.get_mut().take();
+        for (key, slot) in std::mem::take(&mut self.slots) {
+            if slot.dirty {
+                self.index_deadline(&key, slot.stored_deadline);
+            }
+            if !slot.released {
+                continue;
+            }
+            match slot.entry {
+                Some(entry) => {
+                    let record = Record {
+                        value: entry.value,
+                        deadline: slot.stored_deadline,
+                        accessed: entry.accessed.get(),
+                    };
+                    if let Err(e) = self.engine.put(&key, record) {
+                        self.fail(e);
+                    }
+                }
+                // The engine doesn't hold the key anymore.
+                None => {
+                    self.index_deadline(&key, None);
+                    self.stored_len -= 1;
+                }
+            }
+        }
+    }
+
+    /// Makes `discard` restore the values modified in place as well. The engine keeps holding the
+    /// values then, so every value is copied before it is modified, which costs as much as the
+    /// value is large. Without rollback, only the engines that share their values with the
+    /// keyspace copy them, see `StorageEngine::release`.
+    pub fn set_rollback(&mut self, rollback: bool) {
+        self.rollback = rollback;
+    }
+
+    /// Makes the committed changes survive a crash of the machine, see `StorageEngine::sync`.
+    pub fn sync(&mut self) -> Result<(), EngineError> {
+        self.engine.sync()
+    }
+
+    pub fn get<T: TypedValue>(&self, key: &[u8]) -> Result<Option<&T>, DataTypeError> {
+        match self.value(key) {
+            Some(value) => T::from_value(value)
+                .map(Some)
+                .ok_or(DataTypeError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_mut<T: TypedValue>(&mut self, key: &[u8]) -> Result<Option<&mut T>, DataTypeError> {
+        self.evict_if_expired(key);
+        let Some(entry) = &self.load_slot(key).entry else {
+            return Ok(None);
+        };
+        entry.touch();
+        if T::from_value(&entry.value).is_none() {
+            return Err(DataTypeError::WrongType);
+        }
+        let entry = self.entry_mut(key).expect("the key exists");
+        T::from_value_mut(Arc::make_mut(&mut entry.value))
+            .map(Some)
+            .ok_or(DataTypeError::WrongType)
+    }
+
+    /// Returns the value, creating an empty one if the key does not exist.
+    /// Callers that may leave the value empty must call `remove_if_empty` afterwards.
+    pub fn get_or_insert_default<T: TypedValue>(
+        &mut self,
+        key: &[u8],
+    ) -> Result<&mut T, DataTypeError> {
+        self.evict_if_expired(key);
+        let slot = self.load_slot(key);
+        let entry = slot
+            .entry
+            .get_or_insert_with(|| Entry::new(T::default().into_value()));
+        entry.touch();
+        if T::from_value(&entry.value).is_none() {
+            return Err(DataTypeError::WrongType);
+        }
+        let entry = self.entry_mut(key).expect("the key exists");
+        T::from_value_mut(Arc::make_mut(&mut entry.value)).ok_or(DataTypeError::WrongType)
+    }
+
+    /// Stores the value, replacing whatever the key held before, regardless of its type.
+    /// The expiration deadline of the previous value is discarded.
+    pub fn insert<T: TypedValue>(&mut self, key: &[u8], value: T) {
+        self.insert_value(key, value.into_value());
+    }
+
+    /// Stores a value of any type, like `insert`.
+    pub fn insert_value(&mut self, key: &[u8], value: Value) {
+        self.clear_expiry(key);
+        self.put_entry(key, Entry::new(value));
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Value> {
+        self.evict_if_expired(key);
+        self.clear_expiry(key);
+        self.take_entry(key)
+            .map(|entry| Arc::unwrap_or_clone(entry.value))
+    }
+
+    /// Moves the value and its expiration deadline to another key, replacing what it held
+    /// unless `replace` is false. Returns false if the source does not exist
+    /// or the destination does and may not be replaced.
+    pub fn rename(&mut self, key: &[u8], new_key: &[u8], replace: bool) -> bool {
+        if !self.contains_key(key) || (!replace && self.contains_key(new_key)) {
+            return false;
+        }
+        if key == new_key {
+            return true;
+        }
+        let deadline = self.expiry(key);
+        self.clear_expiry(key);
+        let entry = self.take_entry(key).expect("the key exists");
+        self.remove(new_key);
+        self.put_entry(new_key, entry);
+        if let Some(deadline) = deadline {
+            self.set_expiry(new_key, deadline);
+        }
+        true
+    }
+
+    /// Copies the value and its expiration deadline to another key, replacing what it held
+    /// unless `replace` is false. Returns false if the source does not exist
+    /// or the destination does and may not be replaced.
+    pub fn copy(&mut self, source: &[u8], destination: &[u8], replace: bool) -> bool {
+        if !replace && self.contains_key(destination) {
+            return false;
+        }
+        let Some(entry) = self.peek(source) else {
+            return false;
+        };
+        entry.touch();
+        // The copy shares the value until either of them is modified.
+        let value = Arc::clone(&entry.value);
+        let deadline = self.expiry(source);
+        self.remove(destination);
+        self.put_entry(
+            destination,
+            Entry {
+                value,
+                accessed: Cell::new(unix_time_millis()),
+            },
+        );
+        if let Some(deadline) = deadline {
+            self.set_expiry(destination, deadline);
+        }
+        true
+    }
+
+    /// Updates the access time of the key, returns false if the key does not exist.
+    pub fn touch(&self, key: &[u8]) -> bool {
+        self.value(key).is_some()
+    }
+
+    /// Returns how long the key has not been accessed, in milliseconds.
+    /// Unlike the other accessors this does not count as an access.
+    pub fn idle_time(&self, key: &[u8]) -> Option<u64> {
+        self.peek(key)
+            .map(|entry| unix_time_millis().saturating_sub(entry.accessed.get()))
+    }
+
+    /// Returns the value regardless of its type, without updating the access time.
+    pub fn peek_value(&self, key: &[u8]) -> Option<&Value> {
+        self.peek(key).map(|entry| entry.value.as_ref())
+    }
+
+    /// Removes the key if it holds an empty collection.
+    pub fn remove_if_empty(&mut self, key: &[u8]) {
+        if self
+            .slots
+            .get(key)
+            .and_then(|slot| slot.entry.as_ref())
+            .is_some_and(|entry| entry.value.is_empty_collection())
+        {
+            self.remove(key);
+        }
+    }
+
+    /// Checking whether a key exists does not count as an access.
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.peek(key).is_some()
+    }
+
+    /// Returns the value regardless of its type.
+    pub fn value(&self, key: &[u8]) -> Option<&Value> {
+        let entry = self.peek(key)?;
+        entry.touch();
+        Some(&entry.value)
+    }
+
+    /// Returns the number of keys, including expired keys that have not been evicted yet.
+    pub fn len(&self) -> usize {
+        let added = self
+            .slots
+            .values()
+            .filter(|slot| !slot.stored && slot.entry.is_some())
+            .count();
+        let removed = self
+            .slots
+            .values()
+            .filter(|slot| slot.stored && slot.entry.is_none())
+            .count();
+        self.stored_len + added - removed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the expiration deadline of the key in milliseconds since the Unix epoch.
+    pub fn expiry(&self, key: &[u8]) -> Option<u64> {
+        if self.is_expired(key) {
+            return None;
+        }
+        self.expires.get(key).copied()
+    }
+
+    /// Sets the expiration deadline of an existing key, returns false if the key does not exist.
+    pub fn set_expiry(&mut self, key: &[u8], deadline: u64) -> bool {
+        self.evict_if_expired(key);
+        if self.load_slot(key).entry.is_none() {
+            return false;
+        }
+        self.replace_deadline(key, Some(deadline));
+        true
+    }
+
+    /// Removes the expiration deadline, returns false if the key had none.
+    pub fn clear_expiry(&mut self, key: &[u8]) -> bool {
+        if !self.expires.contains_key(key) {
+            return false;
+        }
+        self.replace_deadline(key, None);
+        true
+    }
+
+    /// Removes up to `limit` keys whose deadline is not later than `now` and returns their number.
+    /// The limit keeps a single sweep short when many keys expire at once.
+    pub fn evict_expired(&mut self, now: u64, limit: usize) -> usize {
+        let expired: Vec<Bytes> = self
+            .deadlines
+            .iter()
+            .take_while(|(deadline, _)| *deadline <= now)
+            .take(limit)
+            .map(|(_, key)| key.clone())
+            .collect();
+        for key in &expired {
+            self.evict(key);
+        }
+        expired.len()
+    }
+
+    /// Iterates over the keys that have not expired, in order.
+    pub fn keys(&self) -> impl Iterator<Item = Bytes> + '_ {
+        self.key_range(Bound::Unbounded, Bound::Unbounded, false)
+    }
+
+    /// Iterates over the keys between the bounds that have not expired, in order, or from the
+    /// highest key with `rev`. Only the keys that are iterated over are read, so listing the
+    /// first keys under a prefix doesn't walk the keyspace.
+    pub fn key_range(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        rev: bool,
+    ) -> impl Iterator<Item = Bytes> + '_ {
+        let stored = self
+            .engine
+            .keys(start, end, rev)
+            .map(|key| key.map(|(key, _)| (key, ())));
+        self.merged(start, end, rev, stored, |_| ())
+            .map(|(key, _)| key)
+            .filter(|key| !self.is_expired(key))
+    }
+
+    /// Iterates over the keys between the bounds that have not expired with their values, in
+    /// order, see `key_range`. Values read this way are not accessed.
+    pub fn range(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        rev: bool,
+    ) -> impl Iterator<Item = (Bytes, Arc<Value>)> + '_ {
+        self.records(start, end, rev)
+            .filter(|(key, _)| !self.is_expired(key))
+    }
+
+    /// Returns the keys that have not expired with their values and deadlines.
+    /// This copies the keys but not the values, which stay shared until the keyspace modifies
+    /// them, so the snapshot can be written out while the keyspace keeps changing.
+    pub fn snapshot(&self) -> Result<KeyspaceSnapshot, EngineError> {
+        let now = unix_time_millis();
+        let snapshot = self
+            .records(Bound::Unbounded, Bound::Unbounded, false)
+            .filter_map(|(key, value)| {
+                let deadline = self.expires.get(&key).copied();
+                if deadline.is_some_and(|deadline| deadline <= now) {
+                    return None;
+                }
+                Some((key, value, deadline))
+            })
+            .collect();
+        match self.error.borrow_mut().take() {
+            Some(e) => Err(e),
+            None => Ok(snapshot),
+        }
+    }
+
+    /// Returns a random key that has not expired. This walks the keyspace, so it costs O(n).
+    pub fn random_key(&self) -> Option<Bytes> {
+        self.keys().choose(&mut rand::thread_rng())
+    }
+
+    /// Visits up to `count` keys in order, starting from `start` (inclusive) or from the first key.
+    /// Returns the visited keys that have not expired and are accepted by the filter, together
+    /// with the key to resume from, which is None once the whole keyspace has been visited.
+    pub fn scan<F>(
+        &self,
+        start: Option<&[u8]>,
+        count: usize,
+        filter: F,
+    ) -> (Vec<Bytes>, Option<Bytes>)
+    where
+        F: Fn(&[u8], &Value) -> bool,
+    {
+        let lower = start.map_or(Bound::Unbounded, Bound::Included);
+        let mut records = self.records(lower, Bound::Unbounded, false);
+        let keys = records
+            .by_ref()
+            .take(count)
+            .filter(|(key, value)| !self.is_expired(key) && filter(key, value))
+            .map(|(key, _)| key)
+            .collect();
+        let next = records.next().map(|(key, _)| key);
+        (keys, next)
+    }
+
+    /// Iterates over the keys between the bounds with their values, including expired keys.
+    fn records(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        rev: bool,
+    ) -> impl Iterator<Item = (Bytes, Arc<Value>)> + '_ {
+        let stored = self
+            .engine
+            .range(start, end, rev)
+            .map(|record| record.map(|(key, record)| (key, record.value)));
+        self.merged(start, end, rev, stored, |entry| Arc::clone(&entry.value))
+    }
+
+    /// Merges the keys between the bounds that the engine holds with the loaded ones, which take
+    /// precedence. Both go from the highest key with `rev`.
+    /// A failure of the engine ends the iteration and is kept for `commit`.
+    fn merged<'a, T: 'a>(
+        &'a self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        rev: bool,
+        stored: impl Iterator<Item = Result<(Bytes, T), EngineError>> + 'a,
+        from_entry: impl Fn(&Entry) -> T + 'a,
+    ) -> impl Iterator<Item = (Bytes, T)> + 'a {
+        let mut stored = stored
+            .map_while(|record| record.map_err(|e| self.fail(e)).ok())
+            .peekable();
+        let loaded = (!is_empty_range(start, end))
+            .then(|| self.slots.range::<[u8], _>((start, end)))
+            .into_iter()
+            .flatten();
+        let loaded: Box<dyn Iterator<Item = (&Bytes, &Slot)> + 'a> = if rev {
+            Box::new(loaded.rev())
+        } else {
+            Box::new(loaded)
+        };
+        let mut loaded = loaded.peekable();
+        iter::from_fn(move || loop {
+            let order = match (stored.peek(), loaded.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((stored_key, _)), Some((loaded_key, _))) if rev => {
+                    stored_key.cmp(loaded_key).reverse()
+                }
+                (Some((stored_key, _)), Some((loaded_key, _))) => stored_key.cmp(loaded_key),
+            };
+            if order == Ordering::Less {
+                return stored.next();
+            }
+            if order == Ordering::Equal {
+                stored.next();
+            }
+            let (key, slot) = loaded.next().expect("a loaded key is next");
+            if let Some(entry) = &slot.entry {
+                return Some((key.clone(), from_entry(entry)));
+            }
+        })
+    }
+
+    fn fail(&self, e: EngineError) {
+        self.error.borrow_mut().get_or_insert(e);
+    }
+
+    fn insert_slot(&mut self, key: &[u8], record: Option<Record>) -> &mut Slot {
+        let slot = Slot {
+            stored: record.is_some(),
+            stored_deadline: self.expires.get(key).copied(),
+            stored_accessed: record.as_ref().map_or(0, |record| record.accessed),
+            entry: record.map(|record| Entry {
+                value: record.value,
+                accessed: Cell::new(record.accessed),
+            }),
+            dirty: false,
+            released: false,
+        };
+        self.slots.entry(Bytes::from(key)).or_insert(slot)
+    }
+
+    /// Returns the loaded key, loading it if it isn't yet.
+    fn load_slot(&mut self, key: &[u8]) -> &mut Slot {
+        if !self.slots.contains_key(key) {
+            let record = self.engine.get(key).unwrap_or_else(|e| {
+                self.fail(e);
+                None
+            });
+            return self.insert_slot(key, record);
+        }
+        self.slots.get_mut(key).expect("the key is loaded")
+    }
+
+    /// Marks the loaded key as changed, so that it is written back by `commit`.
+    fn modify(&mut self, key: &[u8]) -> &mut Slot {
+        let slot = self.slots.get_mut(key).expect("the key is loaded");
+        slot.dirty = true;
+        slot
+    }
+
+    /// Returns the loaded entry to be modified in place.
+    fn entry_mut(&mut self, key: &[u8]) -> Option<&mut Entry> {
+        let slot = self.slots.get_mut(key)?;
+        slot.dirty = true;
+        if slot.stored && !slot.released && !self.rollback {
+            self.engine.release(key);
+            slot.released = true;
+        }
+        slot.entry.as_mut()
+    }
+
+    fn take_entry(&mut self, key: &[u8]) -> Option<Entry> {
+        self.load_slot(key).entry.as_ref()?;
+        self.modify(key).entry.take()
+    }
+
+    fn put_entry(&mut self, key: &[u8], entry: Entry) {
+        self.load_slot(key);
+        self.modify(key).entry = Some(entry);
+    }
+
+    /// Changes the deadline of a loaded key, so that it is written back with the key.
+    fn replace_deadline(&mut self, key: &[u8], deadline: Option<u64>) {
+        self.load_slot(key);
+        self.modify(key);
+        self.index_deadline(key, deadline);
+    }
+
+    fn index_deadline(&mut self, key: &[u8], deadline: Option<u64>) {
+        let previous = match deadline {
+            Some(deadline) => self.expires.insert(Bytes::from(key), deadline),
+            None => self.expires.remove(key),
+        };
+        if let Some(previous) = previous {
+            self.deadlines.remove(&(previous, Bytes::from(key)));
+        }
+        if let Some(deadline) = deadline {
+            self.deadlines.insert((deadline, Bytes::from(key)));
+        }
+    }
+
+    /// Writes a key back to the engine, or only its access time if it didn't change otherwise.
+    fn write_back(&mut self, key: &[u8], slot: &Slot) -> Result<(), EngineError> {
+        match &slot.entry {
+            Some(entry) if !slot.dirty => {
+                let accessed = entry.accessed.get();
+                if accessed != slot.stored_accessed {
+                    self.engine.touch(key, accessed)?;
+                }
+            }
+            Some(entry) => {
+                let record = Record {
+                    value: Arc::clone(&entry.value),
+                    deadline: self.expires.get(key).copied(),
+                    accessed: entry.accessed.get(),
+                };
+                self.engine.put(key, record)?;
+                if !slot.stored {
+                    self.stored_len += 1;
+                }
+            }
+            None if slot.dirty && slot.stored => {
+                self.engine.delete(key)?;
+                self.stored_len -= 1;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn peek(&self, key: &[u8]) -> Option<&Entry> {
+        if self.is_expired(key) {
+            return None;
+        }
+        self.slots.get(key)?.entry.as_ref()
+    }
+
+    fn is_expired(&self, key: &[u8]) -> bool {
+        self.expires
+            .get(key)
+            .is_some_and(|deadline| *deadline <= unix_time_millis())
+    }
+
+    fn evict_if_expired(&mut self, key: &[u8]) {
+        if self.is_expired(key) {
+            self.evict(key);
+        }
+    }
+
+    /// Removes an expired key without reading its value.
+    fn evict(&mut self, key: &[u8]) {
+        if !self.slots.contains_key(key) {
+            // Only the keys the engine holds have a deadline without being loaded.
+            let slot = Slot {
+                entry: None,
+                stored: true,
+                stored_deadline: self.expires.get(key).copied(),
+                stored_accessed: 0,
+                dirty: true,
+                released: false,
+            };
+            self.slots.insert(Bytes::from(key), slot);
+        }
+        self.take_entry(key);
+        self.replace_deadline(key, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_with_wrong_type_fails() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"key", StringValue::from("value"));
+
+        // Act
+        let as_string = keyspace.get::<StringValue>(b"key");
+        let as_list = keyspace.get::<ListValue>(b"key");
+
+        // Assert
+        assert_eq!(as_string.unwrap(), Some(&"value".into()));
+        assert_eq!(as_list.unwrap_err(), DataTypeError::WrongType);
+    }
+
+    #[test]
+    fn test_get_or_insert_default_does_not_replace_other_type() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"key", StringValue::from("value"));
+
+        // Act
+        let result = keyspace.get_or_insert_default::<SetValue>(b"key");
+
+        // Assert
+        assert_eq!(result.unwrap_err(), DataTypeError::WrongType);
+        assert_eq!(keyspace.value(b"key").unwrap().type_name(), "string");
+    }
+
+    #[test]
+    fn test_remove_if_empty_keeps_empty_strings() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"string", StringValue::default());
+        keyspace.insert(b"list", ListValue::default());
+
+        // Act
+        keyspace.remove_if_empty(b"string");
+        keyspace.remove_if_empty(b"list");
+
+        // Assert
+        assert!(keyspace.contains_key(b"string"));
+        assert!(!keyspace.contains_key(b"list"));
+    }
+
+    #[test]
+    fn test_insert_replaces_value_of_another_type() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"key", vec![0xffu8]);
+
+        // Act
+        keyspace.insert(b"key", StringValue::from("value"));
+
+        // Assert
+        assert_eq!(keyspace.len(), 1);
+        assert_eq!(keyspace.value(b"key").unwrap().type_name(), "string");
+    }
+
+    #[test]
+    fn test_expired_key_is_invisible_and_evicted_on_write_access() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"key", StringValue::from("value"));
+        keyspace.set_expiry(b"key", 1);
+
+        // Act
+        let read = keyspace.get::<StringValue>(b"key").unwrap().cloned();
+        let len_before_write = keyspace.len();
+        let write = keyspace.get_mut::<StringValue>(b"key").unwrap().is_some();
+
+        // Assert
+        assert_eq!(read, None);
+        assert_eq!(len_before_write, 1);
+        assert!(!write);
+        assert_eq!(keyspace.len(), 0);
+    }
+
+    #[test]
+    fn test_insert_discards_expiry() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"key", StringValue::from("value"));
+        keyspace.set_expiry(b"key", u64::MAX);
+
+        // Act
+        keyspace.insert(b"key", StringValue::from("other"));
+
+        // Assert
+        assert_eq!(keyspace.expiry(b"key"), None);
+    }
+
+    #[test]
+    fn test_set_expiry_on_missing_key_fails() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+
+        // Act
+        let result = keyspace.set_expiry(b"key", u64::MAX);
+
+        // Assert
+        assert!(!result);
+        assert_eq!(keyspace.expiry(b"key"), None);
+    }
+
+    #[test]
+    fn test_evict_expired_removes_only_due_keys_up_to_limit() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        for (key, deadline) in [("a", 10), ("b", 20), ("c", 30), ("d", 1000)] {
+            keyspace.insert(key.as_bytes(), StringValue::from(key));
+            keyspace.set_expiry(key.as_bytes(), deadline);
+        }
+        keyspace.insert(b"persistent", StringValue::from("value"));
+
+        // Act
+        let first_sweep = keyspace.evict_expired(100, 2);
+        let second_sweep = keyspace.evict_expired(100, 2);
+
+        // Assert
+        assert_eq!(first_sweep, 2);
+        assert_eq!(second_sweep, 1);
+        assert_eq!(keyspace.len(), 2);
+        assert!(keyspace.slots[&b"d"[..]].entry.is_some());
+        assert!(keyspace.slots[&b"persistent"[..]].entry.is_some());
+    }
+
+    #[test]
+    fn test_scan_resumes_from_the_returned_key() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        for key in ["a", "b", "c", "d", "e"] {
+            keyspace.insert(key.as_bytes(), StringValue::from(key));
+        }
+
+        // Act
+        let (first, next) = keyspace.scan(None, 2, |_, _| true);
+        keyspace.remove(b"c");
+        keyspace.insert(b"bb", StringValue::from("bb"));
+        keyspace.insert(b"cc", StringValue::from("cc"));
+        let (second, next) = keyspace.scan(next.as_deref(), 2, |_, _| true);
+        let (third, last) = keyspace.scan(next.as_deref(), 2, |_, _| true);
+
+        // Assert
+        assert_eq!(first, vec![Bytes::from("a"), Bytes::from("b")]);
+        assert_eq!(second, vec![Bytes::from("cc"), Bytes::from("d")]);
+        assert_eq!(third, vec![Bytes::from("e")]);
+        assert_eq!(last, None);
+    }
+
+    #[test]
+    fn test_scan_skips_expired_and_filtered_keys_but_counts_them_as_visited() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"expired", StringValue::from("value"));
+        keyspace.set_expiry(b"expired", 1);
+        keyspace.insert(b"list", ListValue::from_iter([Bytes::from("a")]));
+        keyspace.insert(b"string", StringValue::from("value"));
+
+        // Act
+        let (keys, next) = keyspace.scan(None, 2, |_, value| value.type_name() == "string");
+
+        // Assert
+        assert!(keys.is_empty());
+        assert_eq!(next, Some(Bytes::from("string")));
+    }
+
+    #[test]
+    fn test_random_key_skips_expired_keys() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"expired", StringValue::from("value"));
+        keyspace.set_expiry(b"expired", 1);
+
+        // Act
+        let empty = keyspace.random_key().is_none();
+        keyspace.insert(b"live", StringValue::from("value"));
+        let live = keyspace.random_key();
+
+        // Assert
+        assert!(empty);
+        assert_eq!(live, Some(Bytes::from("live")));
+    }
+
+    #[test]
+    fn test_reads_update_access_time_but_existence_checks_do_not() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        keyspace.insert(b"read", StringValue::from("value"));
+        keyspace.insert(b"checked", StringValue::from("value"));
+        for slot in keyspace.slots.values() {
+            slot.entry.as_ref().unwrap().accessed.set(0);
+        }
+
+        // Act
+        keyspace.get::<StringValue>(b"read").unwrap();
+        keyspace.contains_key(b"checked");
+
+        // Assert
+        assert!(keyspace.idle_time(b"read").unwrap() < 1000);
+        assert!(keyspace.idle_time(b"checked").unwrap() > 1000);
+        assert_eq!(keyspace.idle_time(b"missing"), None);
+    }
+
+    #[test]
+    fn test_rename_and_copy_carry_the_deadline() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        let deadline = unix_time_millis() + 60_000;
+        keyspace.insert(b"key", StringValue::from("value"));
+        keyspace.set_expiry(b"key", deadline);
+        keyspace.insert(b"taken", StringValue::from("other"));
+
+        // Act
+        let not_replaced = keyspace.rename(b"key", b"taken", false);
+        let copied = keyspace.copy(b"key", b"copy", false);
+        let renamed = keyspace.rename(b"key", b"taken", true);
+
+        // Assert
+        assert!(!not_replaced);
+        assert!(copied);
+        assert!(renamed);
+        assert!(!keyspace.contains_key(b"key"));
+        assert_eq!(keyspace.expiry(b"taken"), Some(deadline));
+        assert_eq!(keyspace.expiry(b"copy"), Some(deadline));
+        assert_eq!(keyspace.deadlines.len(), 2);
+        assert_eq!(
+            keyspace.get::<StringValue>(b"taken").unwrap(),
+            Some(&"value".into())
+        );
+    }
+
+    #[test]
+    fn test_snapshot_is_not_changed_by_later_writes() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        let deadline = unix_time_millis() + 60_000;
+        keyspace.insert(b"list", ListValue::from_iter([Bytes::from("a")]));
+        keyspace.insert(b"string", StringValue::from("value"));
+        keyspace.set_expiry(b"string", deadline);
+        keyspace.insert(b"expired", StringValue::from("value"));
+        keyspace.set_expiry(b"expired", 1);
+
+        // Act
+        let snapshot = keyspace.snapshot().unwrap();
+        keyspace
+            .get_mut::<ListValue>(b"list")
+            .unwrap()
+            .unwrap()
+            .push_back("b".into());
+        keyspace.remove(b"string");
+
+        // Assert
+        let keys: Vec<&[u8]> = snapshot.iter().map(|(key, _, _)| key.as_slice()).collect();
+        assert_eq!(keys, vec![b"list".as_slice(), b"string"]);
+        assert!(matches!(snapshot[0].1.as_ref(), Value::List(list) if list.len() == 1));
+        assert_eq!(snapshot[1].2, Some(deadline));
+        assert_eq!(
+            keyspace.get::<ListValue>(b"list").unwrap().unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_commit_writes_the_loaded_keys_back_to_the_engine() {
+        // Arrange
+        let mut engine = MemoryEngine::new();
+        for key in ["kept", "removed", "changed"] {
+            let record = Record {
+                value: Arc::new(StringValue::from(key).into_value()),
+                deadline: None,
+                accessed: 0,
+            };
+            engine.put(key.as_bytes(), record).unwrap();
+        }
+        let mut keyspace = Keyspace::with_engine(Box::new(engine)).unwrap();
+
+        // Act
+        let unloaded = keyspace.contains_key(b"kept");
+        keyspace.load([&b"kept"[..]]).unwrap();
+        let loaded = keyspace.touch(b"kept");
+        keyspace.remove(b"removed");
+        keyspace.insert(b"changed", StringValue::from("new"));
+        keyspace.set_expiry(b"changed", unix_time_millis() + 60_000);
+        keyspace.insert(b"added", StringValue::from("added"));
+        keyspace.commit().unwrap();
+
+        // Assert
+        assert!(!unloaded);
+        assert!(loaded);
+        assert!(keyspace.slots.is_empty());
+        assert_eq!(keyspace.len(), 3);
+        let keys: Vec<Bytes> = keyspace.keys().collect();
+        assert_eq!(
+            keys,
+            vec![Bytes::from("added"), "changed".into(), "kept".into()]
+        );
+        let changed = keyspace.engine.get(b"changed").unwrap().unwrap();
+        assert_eq!(changed.deadline, keyspace.expiry(b"changed"));
+        assert!(keyspace.engine.get(b"kept").unwrap().unwrap().accessed > 0);
+    }
+
+    #[test]
+    fn test_discard_forgets_the_changes_since_the_last_commit() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        let deadline = unix_time_millis() + 60_000;
+        keyspace.insert(b"kept", StringValue::from("value"));
+        keyspace.set_expiry(b"kept", deadline);
+        keyspace.insert(b"list", ListValue::from_iter([Bytes::from("a")]));
+        keyspace.commit().unwrap();
+
+        // Act
+        keyspace.remove(b"kept");
+        keyspace.insert(b"added", StringValue::from("value"));
+        keyspace.set_expiry(b"added", deadline);
+        keyspace.get_mut::<ListValue>(b"list").unwrap();
+        keyspace.discard();
+        keyspace.load([&b"kept"[..], b"list"]).unwrap();
+
+        // Assert
+        assert_eq!(keyspace.len(), 2);
+        assert!(keyspace.contains_key(b"kept"));
+        assert_eq!(keyspace.expiry(b"kept"), Some(deadline));
+        assert_eq!(keyspace.expiry(b"added"), None);
+        assert_eq!(keyspace.deadlines.len(), 1);
+        assert_eq!(
+            keyspace.get::<ListValue>(b"list").unwrap().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_key_range_merges_loaded_keys_in_reverse_order() {
+        // Arrange
+        let mut keyspace = Keyspace::new();
+        for key in ["a", "c", "e", "g"] {
+            keyspace.insert(key.as_bytes(), StringValue::from(key));
+        }
+        keyspace.commit().unwrap();
+        keyspace.insert(b"d", StringValue::from("d"));
+        keyspace.remove(b"e");
+        keyspace.insert(b"g", StringValue::from("new"));
+
+        // Act
+        let reversed: Vec<Bytes> = keyspace
+            .key_range(Bound::Excluded(b"a"), Bound::Unbounded, true)
+            .collect();
+        let last: Vec<Bytes> = keyspace
+            .range(Bound::Unbounded, Bound::Unbounded, true)
+            .take(1)
+            .map(|(key, _)| key)
+            .collect();
+
+        // Assert
+        assert_eq!(reversed, ["g", "d", "c"].map(Bytes::from));
+        assert_eq!(last, [Bytes::from("g")]);
+    }
+}
@@ -2,4 +2,779 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-// TODO
+use crate::core::bytes::Bytes;
+use crate::core::commands::{
+    list::{InsertPosition, ListCommand, ListEnd},
+    Command, CommandResult,
+};
+use crate::core::data_types::{
+    data_type::{DataType, DataTypeError, GenericOperations, KeyspaceAccess},
+    encoding::{ListPack, MAX_PACKED_ENTRIES, MAX_PACKED_VALUE_LEN},
+    keyspace::Keyspace,
+};
+use std::collections::VecDeque;
+use std::error::Error;
+
+/// A list in the most compact of its encodings, as reported by OBJECT ENCODING: `listpack`
+/// while it is small, `linkedlist` once it has more than `MAX_PACKED_ENTRIES` elements or an
+/// element longer than `MAX_PACKED_VALUE_LEN`. A linked list is never converted back.
+#[derive(Debug, Clone)]
+pub enum ListValue {
+    Packed(ListPack),
+    Linked(VecDeque<Bytes>),
+}
+
+impl ListValue {
+    pub fn len(&self) -> usize {
+        match self {
+            ListValue::Packed(pack) => pack.len(),
+            ListValue::Linked(list) => list.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        match self {
+            ListValue::Packed(pack) => pack.get(index),
+            ListValue::Linked(list) => list.get(index).map(|element| element.as_slice()),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &[u8]> + '_> {
+        match self {
+            ListValue::Packed(pack) => Box::new(pack.iter()),
+            ListValue::Linked(list) => Box::new(list.iter().map(|element| element.as_slice())),
+        }
+    }
+
+    pub fn push_front(&mut self, element: Bytes) {
+        self.insert(0, element);
+    }
+
+    pub fn push_back(&mut self, element: Bytes) {
+        self.insert(self.len(), element);
+    }
+
+    pub fn pop_front(&mut self) -> Option<Bytes> {
+        match self {
+            ListValue::Packed(pack) => (!pack.is_empty()).then(|| pack.remove(0)),
+            ListValue::Linked(list) => list.pop_front(),
+        }
+    }
+
+    pub fn pop_back(&mut self) -> Option<Bytes> {
+        match self {
+            ListValue::Packed(pack) => (!pack.is_empty()).then(|| pack.remove(pack.len() - 1)),
+            ListValue::Linked(list) => list.pop_back(),
+        }
+    }
+
+    /// Inserts the element before the one at `index`, or at the end if `index` is the length.
+    pub fn insert(&mut self, index: usize, element: Bytes) {
+        self.make_room(&element, 1);
+        match self {
+            ListValue::Packed(pack) => pack.insert(index, &element),
+            ListValue::Linked(list) => list.insert(index, element),
+        }
+    }
+
+    /// Replaces the element at `index`, which must exist.
+    pub fn set(&mut self, index: usize, element: Bytes) {
+        self.make_room(&element, 0);
+        match self {
+            ListValue::Packed(pack) => pack.replace(index, &element),
+            ListValue::Linked(list) => list[index] = element,
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Bytes {
+        match self {
+            ListValue::Packed(pack) => pack.remove(index),
+            ListValue::Linked(list) => list.remove(index).expect("index out of range"),
+        }
+    }
+
+    /// Keeps the first `len` elements.
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            ListValue::Packed(pack) => pack.truncate(len),
+            ListValue::Linked(list) => list.truncate(len),
+        }
+    }
+
+    /// Removes the first `count` elements.
+    pub fn remove_front(&mut self, count: usize) {
+        match self {
+            ListValue::Packed(pack) => pack.remove_front(count),
+            ListValue::Linked(list) => {
+                list.drain(..count.min(list.len()));
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        match self {
+            ListValue::Packed(pack) => pack.clear(),
+            ListValue::Linked(list) => list.clear(),
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            ListValue::Packed(_) => "listpack",
+            ListValue::Linked(_) => "linkedlist",
+        }
+    }
+
+    /// Converts a packed list that can't take the element, growing by `added` elements.
+    fn make_room(&mut self, element: &[u8], added: usize) {
+        if let ListValue::Packed(pack) = self {
+            if pack.len() + added > MAX_PACKED_ENTRIES || element.len() > MAX_PACKED_VALUE_LEN {
+                *self = ListValue::Linked(pack.iter().map(Bytes::from).collect());
+            }
+        }
+    }
+}
+
+impl Default for ListValue {
+    fn default() -> Self {
+        ListValue::Packed(ListPack::new())
+    }
+}
+
+impl FromIterator<Bytes> for ListValue {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(elements: I) -> Self {
+        let mut list = ListValue::default();
+        for element in elements {
+            list.push_back(element);
+        }
+        list
+    }
+}
+
+/// Stores lists of strings under keys.
+/// A key is removed as soon as its list becomes empty.
+#[derive(Debug)]
+pub struct ListStore {
+    data: Keyspace,
+}
+
+impl DataType for ListStore {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(ListStore {
+            data: Keyspace::new(),
+        })
+    }
+
+    fn handle_command(&mut self, command: Command) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            Command::List(cmd) => self.handle_list_command(cmd),
+            Command::Generic(cmd) => self.handle_generic_command(cmd),
+            #[allow(unreachable_patterns)]
+            _ => Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Command not supported by ListStore",
+            ))),
+        }
+    }
+}
+
+impl KeyspaceAccess for ListStore {
+    fn keyspace(&self) -> &Keyspace {
+        &self.data
+    }
+
+    fn keyspace_mut(&mut self) -> &mut Keyspace {
+        &mut self.data
+    }
+}
+
+impl ListOperations for ListStore {}
+
+/// Without a count a single element (or nil) is returned, with a count - an array.
+fn popped_to_result(popped: Option<Vec<Bytes>>, with_count: bool) -> CommandResult {
+    match popped {
+        None => CommandResult::Nil,
+        Some(values) if with_count => {
+            CommandResult::Array(values.into_iter().map(CommandResult::String).collect())
+        }
+        Some(mut values) => values
+            .pop()
+            .map_or(CommandResult::Nil, CommandResult::String),
+    }
+}
+
+/// Converts a possibly negative index into a position in a list of the given length.
+fn normalize_index(len: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    if index < 0 || index >= len as i64 {
+        None
+    } else {
+        Some(index as usize)
+    }
+}
+
+/// Converts possibly negative inclusive bounds into a range of positions.
+/// Returns None if the range is empty.
+fn normalize_range(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        None
+    } else {
+        Some((start as usize, stop as usize))
+    }
+}
+
+/// Operations of the List data type.
+pub trait ListOperations: KeyspaceAccess {
+    /// Handles a list command and returns the result.
+    fn handle_list_command(
+        &mut self,
+        command: ListCommand,
+    ) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            ListCommand::LPush { key, values } => {
+                let len = self.push(&key, values, ListEnd::Left)?;
+                Ok(CommandResult::Int(len as i64))
+            }
+            ListCommand::RPush { key, values } => {
+                let len = self.push(&key, values, ListEnd::Right)?;
+                Ok(CommandResult::Int(len as i64))
+            }
+            ListCommand::LPop { key, count } => {
+                let popped = self.pop(&key, count.unwrap_or(1), ListEnd::Left)?;
+                Ok(popped_to_result(popped, count.is_some()))
+            }
+            ListCommand::RPop { key, count } => {
+                let popped = self.pop(&key, count.unwrap_or(1), ListEnd::Right)?;
+                Ok(popped_to_result(popped, count.is_some()))
+            }
+            ListCommand::Range { key, start, stop } => {
+                let values = self.lrange(&key, start, stop)?;
+                Ok(CommandResult::Array(
+                    values
+                        .into_iter()
+                        .map(|s| CommandResult::String(s.into()))
+                        .collect(),
+                ))
+            }
+            ListCommand::Index { key, index } => {
+                let result = self.lindex(&key, index)?;
+                Ok(result.map_or(CommandResult::Nil, |s| CommandResult::String(s.into())))
+            }
+            ListCommand::Set { key, index, value } => {
+                self.lset(&key, index, value)?;
+                Ok(CommandResult::String("OK".into()))
+            }
+            ListCommand::Len { key } => {
+                let len = self.llen(&key)?;
+                Ok(CommandResult::Int(len as i64))
+            }
+            ListCommand::Insert {
+                key,
+                position,
+                pivot,
+                value,
+            } => {
+                let result = self.linsert(&key, position, &pivot, value)?;
+                Ok(CommandResult::Int(result))
+            }
+            ListCommand::Rem { key, count, value } => {
+                let removed = self.lrem(&key, count, &value)?;
+                Ok(CommandResult::Int(removed as i64))
+            }
+            ListCommand::Trim { key, start, stop } => {
+                self.ltrim(&key, start, stop)?;
+                Ok(CommandResult::String("OK".into()))
+            }
+            ListCommand::Move {
+                source,
+                destination,
+                from,
+                to,
+            } => {
+                let result = self.lmove(&source, &destination, from, to)?;
+                Ok(result.map_or(CommandResult::Nil, CommandResult::String))
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        key: &[u8],
+        values: Vec<Bytes>,
+        end: ListEnd,
+    ) -> Result<u64, Box<dyn Error>> {
+        let list = self
+            .keyspace_mut()
+            .get_or_insert_default::<ListValue>(key)?;
+        for value in values {
+            match end {
+                ListEnd::Left => list.push_front(value),
+                ListEnd::Right => list.push_back(value),
+            }
+        }
+        let len = list.len() as u64;
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(len)
+    }
+
+    fn pop(
+        &mut self,
+        key: &[u8],
+        count: u64,
+        end: ListEnd,
+    ) -> Result<Option<Vec<Bytes>>, Box<dyn Error>> {
+        let Some(list) = self.keyspace_mut().get_mut::<ListValue>(key)? else {
+            return Ok(None);
+        };
+        let mut popped = Vec::new();
+        for _ in 0..count {
+            let value = match end {
+                ListEnd::Left => list.pop_front(),
+                ListEnd::Right => list.pop_back(),
+            };
+            match value {
+                Some(value) => popped.push(value),
+                None => break,
+            }
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(Some(popped))
+    }
+
+    fn lrange(&self, key: &[u8], start: i64, stop: i64) -> Result<Vec<&[u8]>, Box<dyn Error>> {
+        let Some(list) = self.keyspace().get::<ListValue>(key)? else {
+            return Ok(Vec::new());
+        };
+        Ok(match normalize_range(list.len(), start, stop) {
+            Some((start, stop)) => list.iter().skip(start).take(stop - start + 1).collect(),
+            None => Vec::new(),
+        })
+    }
+
+    fn lindex(&self, key: &[u8], index: i64) -> Result<Option<&[u8]>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<ListValue>(key)?
+            .and_then(|list| normalize_index(list.len(), index).and_then(|i| list.get(i))))
+    }
+
+    fn lset(&mut self, key: &[u8], index: i64, value: Bytes) -> Result<(), Box<dyn Error>> {
+        let list = self
+            .keyspace_mut()
+            .get_mut::<ListValue>(key)?
+            .ok_or(DataTypeError::NoSuchKey)?;
+        let index = normalize_index(list.len(), index).ok_or(DataTypeError::IndexOutOfRange)?;
+        list.set(index, value);
+        Ok(())
+    }
+
+    fn llen(&self, key: &[u8]) -> Result<u64, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<ListValue>(key)?
+            .map_or(0, |list| list.len() as u64))
+    }
+
+    /// Returns the new length, -1 if the pivot was not found and 0 if the key does not exist.
+    fn linsert(
+        &mut self,
+        key: &[u8],
+        position: InsertPosition,
+        pivot: &[u8],
+        value: Bytes,
+    ) -> Result<i64, Box<dyn Error>> {
+        let Some(list) = self.keyspace_mut().get_mut::<ListValue>(key)? else {
+            return Ok(0);
+        };
+        let Some(pivot_index) = list.iter().position(|s| s == pivot) else {
+            return Ok(-1);
+        };
+        let index = match position {
+            InsertPosition::Before => pivot_index,
+            InsertPosition::After => pivot_index + 1,
+        };
+        list.insert(index, value);
+        Ok(list.len() as i64)
+    }
+
+    /// Removes occurrences of the value: the first `count` from the head if count is positive,
+    /// the last `|count|` from the tail if negative, all of them if zero.
+    fn lrem(&mut self, key: &[u8], count: i64, value: &[u8]) -> Result<u64, Box<dyn Error>> {
+        let Some(list) = self.keyspace_mut().get_mut::<ListValue>(key)? else {
+            return Ok(0);
+        };
+        let limit = if count == 0 {
+            usize::MAX
+        } else {
+            count.unsigned_abs() as usize
+        };
+        let mut removed = 0;
+        if count >= 0 {
+            let mut i = 0;
+            while i < list.len() && removed < limit {
+                if list.get(i) == Some(value) {
+                    list.remove(i);
+                    removed += 1;
+                } else {
+                    i += 1;
+                }
+            }
+        } else {
+            let mut i = list.len();
+            while i > 0 && removed < limit {
+                i -= 1;
+                if list.get(i) == Some(value) {
+                    list.remove(i);
+                    removed += 1;
+                }
+            }
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(removed as u64)
+    }
+
+    fn ltrim(&mut self, key: &[u8], start: i64, stop: i64) -> Result<(), Box<dyn Error>> {
+        let Some(list) = self.keyspace_mut().get_mut::<ListValue>(key)? else {
+            return Ok(());
+        };
+        match normalize_range(list.len(), start, stop) {
+            Some((start, stop)) => {
+                list.truncate(stop + 1);
+                list.remove_front(start);
+            }
+            None => list.clear(),
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(())
+    }
+
+    fn lmove(
+        &mut self,
+        source: &[u8],
+        destination: &[u8],
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<Bytes>, Box<dyn Error>> {
+        // The destination is checked first, so a wrong type does not lose the popped element.
+        self.keyspace().get::<ListValue>(destination)?;
+        let Some(mut popped) = self.pop(source, 1, from)? else {
+            return Ok(None);
+        };
+        let Some(value) = popped.pop() else {
+            return Ok(None);
+        };
+        self.push(destination, vec![value.clone()], to)?;
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::commands::generic::GenericCommand;
+
+    fn values(values: &[&str]) -> Vec<Bytes> {
+        values.iter().map(|s| Bytes::from(*s)).collect()
+    }
+
+    fn store_with(key: &str, elements: &[&str]) -> ListStore {
+        let mut store = ListStore::new().unwrap();
+        store
+            .push(key.as_bytes(), values(elements), ListEnd::Right)
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn test_new() {
+        // Arrange
+        let store = ListStore::new();
+        // Act
+        // Assert
+        assert!(store.is_ok());
+    }
+
+    #[test]
+    fn test_lpush_and_rpush_keep_element_order() {
+        // Arrange
+        let mut store = ListStore::new().unwrap();
+
+        // Act
+        let len_1 = store
+            .push(b"key", values(&["a", "b"]), ListEnd::Left)
+            .unwrap();
+        let len_2 = store
+            .push(b"key", values(&["c", "d"]), ListEnd::Right)
+            .unwrap();
+
+        // Assert
+        assert_eq!(len_1, 2);
+        assert_eq!(len_2, 4);
+        assert_eq!(
+            store.lrange(b"key", 0, -1).unwrap(),
+            vec![&b"b"[..], b"a", b"c", b"d"]
+        );
+    }
+
+    #[test]
+    fn test_pop_with_count_removes_key_when_list_becomes_empty() {
+        // Arrange
+        let mut store = store_with("key", &["a", "b", "c"]);
+
+        // Act
+        let left = store.pop(b"key", 2, ListEnd::Left).unwrap();
+        let right = store.pop(b"key", 5, ListEnd::Right).unwrap();
+        let missing = store.pop(b"key", 1, ListEnd::Right).unwrap();
+
+        // Assert
+        assert_eq!(left, Some(values(&["a", "b"])));
+        assert_eq!(right, Some(values(&["c"])));
+        assert_eq!(missing, None);
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_range_with_negative_and_out_of_bounds_indexes() {
+        // Arrange
+        let store = store_with("key", &["a", "b", "c", "d"]);
+
+        // Act & Assert
+        assert_eq!(store.lrange(b"key", 1, 2).unwrap(), vec![&b"b"[..], b"c"]);
+        assert_eq!(store.lrange(b"key", -2, -1).unwrap(), vec![&b"c"[..], b"d"]);
+        assert_eq!(
+            store.lrange(b"key", -100, 100).unwrap(),
+            vec![&b"a"[..], b"b", b"c", b"d"]
+        );
+        assert!(store.lrange(b"key", 3, 1).unwrap().is_empty());
+        assert!(store.lrange(b"key", 10, 20).unwrap().is_empty());
+        assert!(store.lrange(b"missing", 0, -1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index() {
+        // Arrange
+        let store = store_with("key", &["a", "b", "c"]);
+
+        // Act & Assert
+        assert_eq!(store.lindex(b"key", 0).unwrap(), Some(&b"a"[..]));
+        assert_eq!(store.lindex(b"key", -1).unwrap(), Some(&b"c"[..]));
+        assert_eq!(store.lindex(b"key", 3).unwrap(), None);
+        assert_eq!(store.lindex(b"missing", 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_with_out_of_range_index_and_missing_key() {
+        // Arrange
+        let mut store = store_with("key", &["a", "b"]);
+
+        // Act
+        let ok = store.lset(b"key", -1, "z".into());
+        let out_of_range = store.lset(b"key", 2, "z".into());
+        let missing = store.lset(b"missing", 0, "z".into());
+
+        // Assert
+        assert!(ok.is_ok());
+        assert_eq!(store.lrange(b"key", 0, -1).unwrap(), vec![&b"a"[..], b"z"]);
+        assert_eq!(
+            out_of_range.unwrap_err().downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::IndexOutOfRange)
+        );
+        assert_eq!(
+            missing.unwrap_err().downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::NoSuchKey)
+        );
+    }
+
+    #[test]
+    fn test_insert_before_and_after_pivot() {
+        // Arrange
+        let mut store = store_with("key", &["a", "c"]);
+
+        // Act
+        let result_1 = store
+            .linsert(b"key", InsertPosition::Before, b"c", "b".into())
+            .unwrap();
+        let result_2 = store
+            .linsert(b"key", InsertPosition::After, b"c", "d".into())
+            .unwrap();
+        let result_3 = store
+            .linsert(b"key", InsertPosition::After, b"x", "y".into())
+            .unwrap();
+        let result_4 = store
+            .linsert(b"missing", InsertPosition::After, b"a", "b".into())
+            .unwrap();
+
+        // Assert
+        assert_eq!(result_1, 3);
+        assert_eq!(result_2, 4);
+        assert_eq!(result_3, -1);
+        assert_eq!(result_4, 0);
+        assert_eq!(
+            store.lrange(b"key", 0, -1).unwrap(),
+            vec![&b"a"[..], b"b", b"c", b"d"]
+        );
+    }
+
+    #[test]
+    fn test_rem_from_head_tail_and_all() {
+        // Arrange
+        let mut store = store_with("key", &["x", "a", "x", "b", "x"]);
+
+        // Act & Assert
+        assert_eq!(store.lrem(b"key", 1, b"x").unwrap(), 1);
+        assert_eq!(
+            store.lrange(b"key", 0, -1).unwrap(),
+            vec![&b"a"[..], b"x", b"b", b"x"]
+        );
+        assert_eq!(store.lrem(b"key", -1, b"x").unwrap(), 1);
+        assert_eq!(
+            store.lrange(b"key", 0, -1).unwrap(),
+            vec![&b"a"[..], b"x", b"b"]
+        );
+        assert_eq!(store.lrem(b"key", 0, b"x").unwrap(), 1);
+        assert_eq!(store.lrange(b"key", 0, -1).unwrap(), vec![&b"a"[..], b"b"]);
+    }
+
+    #[test]
+    fn test_trim_keeps_only_range() {
+        // Arrange
+        let mut store = store_with("key", &["a", "b", "c", "d"]);
+
+        // Act
+        store.ltrim(b"key", 1, -2).unwrap();
+
+        // Assert
+        assert_eq!(store.lrange(b"key", 0, -1).unwrap(), vec![&b"b"[..], b"c"]);
+    }
+
+    #[test]
+    fn test_trim_with_empty_range_removes_key() {
+        // Arrange
+        let mut store = store_with("key", &["a", "b"]);
+
+        // Act
+        store.ltrim(b"key", 5, 10).unwrap();
+
+        // Assert
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_lmove_between_lists_and_rotation() {
+        // Arrange
+        let mut store = store_with("src", &["a", "b", "c"]);
+
+        // Act
+        let moved = store
+            .lmove(b"src", b"dst", ListEnd::Right, ListEnd::Left)
+            .unwrap();
+        let rotated = store
+            .lmove(b"src", b"src", ListEnd::Left, ListEnd::Right)
+            .unwrap();
+        let missing = store
+            .lmove(b"missing", b"dst", ListEnd::Left, ListEnd::Left)
+            .unwrap();
+
+        // Assert
+        assert_eq!(moved, Some("c".into()));
+        assert_eq!(rotated, Some("a".into()));
+        assert_eq!(missing, None);
+        assert_eq!(store.lrange(b"src", 0, -1).unwrap(), vec![&b"b"[..], b"a"]);
+        assert_eq!(store.lrange(b"dst", 0, -1).unwrap(), vec![&b"c"[..]]);
+    }
+
+    #[test]
+    fn test_handle_command_lpop_with_and_without_count() {
+        // Arrange
+        let mut store = store_with("key", &["a", "b", "c"]);
+        let without_count = Command::List(ListCommand::LPop {
+            key: "key".into(),
+            count: None,
+        });
+        let with_count = Command::List(ListCommand::LPop {
+            key: "key".into(),
+            count: Some(5),
+        });
+
+        // Act
+        let result_1 = store.handle_command(without_count.clone()).unwrap();
+        let result_2 = store.handle_command(with_count).unwrap();
+        let result_3 = store.handle_command(without_count).unwrap();
+
+        // Assert
+        assert_eq!(result_1, CommandResult::String("a".into()));
+        assert_eq!(
+            result_2,
+            CommandResult::Array(vec![
+                CommandResult::String("b".into()),
+                CommandResult::String("c".into())
+            ])
+        );
+        assert_eq!(result_3, CommandResult::Nil);
+    }
+
+    #[test]
+    fn test_handle_command_rpush_and_range() {
+        // Arrange
+        let mut store = ListStore::new().unwrap();
+        let push_command = Command::List(ListCommand::RPush {
+            key: "key".into(),
+            values: values(&["a", "b"]),
+        });
+        let range_command = Command::List(ListCommand::Range {
+            key: "key".into(),
+            start: 0,
+            stop: -1,
+        });
+
+        // Act
+        let push_result = store.handle_command(push_command).unwrap();
+        let range_result = store.handle_command(range_command).unwrap();
+
+        // Assert
+        assert_eq!(push_result, CommandResult::Int(2));
+        assert_eq!(
+            range_result,
+            CommandResult::Array(vec![
+                CommandResult::String("a".into()),
+                CommandResult::String("b".into())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_handle_command_exists_and_delete() {
+        // Arrange
+        let mut store = store_with("key1", &["a"]);
+        let exists_command = Command::Generic(GenericCommand::Exists {
+            keys: vec!["key1".into(), "key2".into()],
+        });
+        let delete_command = Command::Generic(GenericCommand::Delete {
+            keys: vec!["key1".into(), "key2".into()],
+        });
+
+        // Act
+        let exists_result = store.handle_command(exists_command).unwrap();
+        let delete_result = store.handle_command(delete_command).unwrap();
+
+        // Assert
+        assert_eq!(exists_result, CommandResult::Int(1));
+        assert_eq!(delete_result, CommandResult::Int(1));
+        assert_eq!(store.llen(b"key1").unwrap(), 0);
+    }
+}
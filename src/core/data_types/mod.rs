@@ -4,7 +4,9 @@
 
 pub mod bitmap;
 pub mod data_type;
+pub mod encoding;
 pub mod hash;
+pub mod keyspace;
 pub mod list;
 pub mod set;
 pub mod sorted_set;
@@ -2,4 +2,673 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-// TODO
+use crate::core::bytes::Bytes;
+use crate::core::commands::{set::SetCommand, Command, CommandResult};
+use crate::core::data_types::{
+    data_type::{DataType, GenericOperations, KeyspaceAccess},
+    encoding::{canonical_int, ListPack, MAX_PACKED_ENTRIES, MAX_PACKED_VALUE_LEN},
+    keyspace::Keyspace,
+};
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// Sets of integers only are kept sorted in an array of up to this many members.
+pub const MAX_INTSET_ENTRIES: usize = 512;
+
+/// A set in the most compact of its encodings, as reported by OBJECT ENCODING: `intset` while
+/// all its members are integers, `listpack` while it is small, `hashtable` once it has grown.
+/// A set is never converted back to a more compact encoding.
+#[derive(Debug, Clone)]
+pub enum SetValue {
+    Ints(Vec<i64>),
+    Packed(ListPack),
+    Table(HashSet<Bytes>),
+}
+
+impl SetValue {
+    pub fn len(&self) -> usize {
+        match self {
+            SetValue::Ints(ints) => ints.len(),
+            SetValue::Packed(pack) => pack.len(),
+            SetValue::Table(set) => set.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match self {
+            SetValue::Ints(ints) => {
+                canonical_int(member).is_some_and(|n| ints.binary_search(&n).is_ok())
+            }
+            SetValue::Packed(pack) => pack.position(member).is_some(),
+            SetValue::Table(set) => set.contains(member),
+        }
+    }
+
+    /// Returns the members, integer ones are formatted on the way.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Bytes> + '_> {
+        match self {
+            SetValue::Ints(ints) => Box::new(ints.iter().map(|n| Bytes::from(n.to_string()))),
+            SetValue::Packed(pack) => Box::new(pack.iter().map(Bytes::from)),
+            SetValue::Table(set) => Box::new(set.iter().cloned()),
+        }
+    }
+
+    /// Adds the member and returns true if it was not in the set.
+    pub fn insert(&mut self, member: Bytes) -> bool {
+        if let SetValue::Ints(ints) = self {
+            if let Some(n) = canonical_int(&member) {
+                let Err(index) = ints.binary_search(&n) else {
+                    return false;
+                };
+                if ints.len() < MAX_INTSET_ENTRIES {
+                    ints.insert(index, n);
+                    return true;
+                }
+            }
+            *self = if ints.len() < MAX_PACKED_ENTRIES && member.len() <= MAX_PACKED_VALUE_LEN {
+                let mut pack = ListPack::new();
+                for n in ints.iter() {
+                    pack.push_back(n.to_string().as_bytes());
+                }
+                SetValue::Packed(pack)
+            } else {
+                SetValue::Table(self.iter().collect())
+            };
+        }
+        if let SetValue::Packed(pack) = self {
+            if pack.position(&member).is_some() {
+                return false;
+            }
+            if pack.len() < MAX_PACKED_ENTRIES && member.len() <= MAX_PACKED_VALUE_LEN {
+                pack.push_back(&member);
+                return true;
+            }
+            *self = SetValue::Table(self.iter().collect());
+        }
+        match self {
+            SetValue::Table(set) => set.insert(member),
+            _ => unreachable!("converted to a table above"),
+        }
+    }
+
+    /// Removes the member and returns true if it was in the set.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self {
+            SetValue::Ints(ints) => {
+                let Some(Ok(index)) = canonical_int(member).map(|n| ints.binary_search(&n)) else {
+                    return false;
+                };
+                ints.remove(index);
+                true
+            }
+            SetValue::Packed(pack) => pack.position(member).map(|i| pack.remove(i)).is_some(),
+            SetValue::Table(set) => set.remove(member),
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            SetValue::Ints(_) => "intset",
+            SetValue::Packed(_) => "listpack",
+            SetValue::Table(_) => "hashtable",
+        }
+    }
+}
+
+impl Default for SetValue {
+    fn default() -> Self {
+        SetValue::Ints(Vec::new())
+    }
+}
+
+impl FromIterator<Bytes> for SetValue {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(members: I) -> Self {
+        let mut set = SetValue::default();
+        for member in members {
+            set.insert(member);
+        }
+        set
+    }
+}
+
+/// Stores unordered sets of unique strings under keys.
+/// A key is removed as soon as its set becomes empty.
+#[derive(Debug)]
+pub struct SetStore {
+    data: Keyspace,
+}
+
+impl DataType for SetStore {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(SetStore {
+            data: Keyspace::new(),
+        })
+    }
+
+    fn handle_command(&mut self, command: Command) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            Command::Set(cmd) => self.handle_set_command(cmd),
+            Command::Generic(cmd) => self.handle_generic_command(cmd),
+            #[allow(unreachable_patterns)]
+            _ => Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Command not supported by SetStore",
+            ))),
+        }
+    }
+}
+
+impl KeyspaceAccess for SetStore {
+    fn keyspace(&self) -> &Keyspace {
+        &self.data
+    }
+
+    fn keyspace_mut(&mut self) -> &mut Keyspace {
+        &mut self.data
+    }
+}
+
+impl SetOperations for SetStore {}
+
+fn to_array(members: Vec<Bytes>) -> CommandResult {
+    CommandResult::Array(members.into_iter().map(CommandResult::String).collect())
+}
+
+/// Operations of the Set data type.
+pub trait SetOperations: KeyspaceAccess {
+    /// Handles a set command and returns the result.
+    fn handle_set_command(&mut self, command: SetCommand) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            SetCommand::Add { key, members } => {
+                let added = self.sadd(&key, members)?;
+                Ok(CommandResult::Int(added as i64))
+            }
+            SetCommand::Rem { key, members } => {
+                let members_ref: Vec<&[u8]> = members.iter().map(|s| s.as_slice()).collect();
+                let removed = self.srem(&key, members_ref)?;
+                Ok(CommandResult::Int(removed as i64))
+            }
+            SetCommand::IsMember { key, member } => {
+                let result = self.sismember(&key, &member)?;
+                Ok(CommandResult::Bool(result))
+            }
+            SetCommand::MIsMember { key, members } => {
+                let members_ref: Vec<&[u8]> = members.iter().map(|s| s.as_slice()).collect();
+                let result = self.smismember(&key, members_ref)?;
+                Ok(CommandResult::Array(
+                    result.into_iter().map(CommandResult::Bool).collect(),
+                ))
+            }
+            SetCommand::Members { key } => {
+                let members = self.smembers(&key)?;
+                Ok(to_array(members))
+            }
+            SetCommand::Card { key } => {
+                let card = self.scard(&key)?;
+                Ok(CommandResult::Int(card as i64))
+            }
+            SetCommand::Pop { key, count } => {
+                let popped = self.spop(&key, count.unwrap_or(1))?;
+                match count {
+                    Some(_) => Ok(to_array(popped)),
+                    None => Ok(popped
+                        .into_iter()
+                        .next()
+                        .map_or(CommandResult::Nil, CommandResult::String)),
+                }
+            }
+            SetCommand::RandMember { key, count } => match count {
+                Some(count) => {
+                    let members = self.srandmember(&key, count)?;
+                    Ok(to_array(members))
+                }
+                None => {
+                    let members = self.srandmember(&key, 1)?;
+                    Ok(members
+                        .into_iter()
+                        .next()
+                        .map_or(CommandResult::Nil, CommandResult::String))
+                }
+            },
+            SetCommand::Inter { keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                Ok(to_array(self.sinter(keys_ref)?.into_iter().collect()))
+            }
+            SetCommand::Union { keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                Ok(to_array(self.sunion(keys_ref)?.into_iter().collect()))
+            }
+            SetCommand::Diff { keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                Ok(to_array(self.sdiff(keys_ref)?.into_iter().collect()))
+            }
+            SetCommand::InterStore { destination, keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                let result = self.sinter(keys_ref)?;
+                let card = self.store_members(&destination, result)?;
+                Ok(CommandResult::Int(card as i64))
+            }
+            SetCommand::UnionStore { destination, keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                let result = self.sunion(keys_ref)?;
+                let card = self.store_members(&destination, result)?;
+                Ok(CommandResult::Int(card as i64))
+            }
+            SetCommand::DiffStore { destination, keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                let result = self.sdiff(keys_ref)?;
+                let card = self.store_members(&destination, result)?;
+                Ok(CommandResult::Int(card as i64))
+            }
+        }
+    }
+
+    fn sadd(&mut self, key: &[u8], members: Vec<Bytes>) -> Result<u64, Box<dyn Error>> {
+        let set = self.keyspace_mut().get_or_insert_default::<SetValue>(key)?;
+        let mut added = 0;
+        for member in members {
+            if set.insert(member) {
+                added += 1;
+            }
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(added)
+    }
+
+    fn srem(&mut self, key: &[u8], members: Vec<&[u8]>) -> Result<u64, Box<dyn Error>> {
+        let Some(set) = self.keyspace_mut().get_mut::<SetValue>(key)? else {
+            return Ok(0);
+        };
+        let mut removed = 0;
+        for member in members {
+            if set.remove(member) {
+                removed += 1;
+            }
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(removed)
+    }
+
+    fn sismember(&self, key: &[u8], member: &[u8]) -> Result<bool, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<SetValue>(key)?
+            .is_some_and(|set| set.contains(member)))
+    }
+
+    fn smismember(&self, key: &[u8], members: Vec<&[u8]>) -> Result<Vec<bool>, Box<dyn Error>> {
+        let set = self.keyspace().get::<SetValue>(key)?;
+        Ok(members
+            .into_iter()
+            .map(|member| set.is_some_and(|set| set.contains(member)))
+            .collect())
+    }
+
+    fn smembers(&self, key: &[u8]) -> Result<Vec<Bytes>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<SetValue>(key)?
+            .map(|set| set.iter().collect())
+            .unwrap_or_default())
+    }
+
+    fn scard(&self, key: &[u8]) -> Result<u64, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<SetValue>(key)?
+            .map_or(0, |set| set.len() as u64))
+    }
+
+    /// Removes and returns up to `count` random members.
+    fn spop(&mut self, key: &[u8], count: u64) -> Result<Vec<Bytes>, Box<dyn Error>> {
+        let Some(set) = self.keyspace_mut().get_mut::<SetValue>(key)? else {
+            return Ok(Vec::new());
+        };
+        let popped: Vec<Bytes> = set
+            .iter()
+            .choose_multiple(&mut rand::thread_rng(), count as usize);
+        for member in &popped {
+            set.remove(member);
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(popped)
+    }
+
+    /// Returns random members without removing them.
+    /// A positive count returns distinct members, a negative one may return the same member
+    /// several times and always returns exactly `|count|` members.
+    fn srandmember(&self, key: &[u8], count: i64) -> Result<Vec<Bytes>, Box<dyn Error>> {
+        let Some(set) = self.keyspace().get::<SetValue>(key)? else {
+            return Ok(Vec::new());
+        };
+        let mut rng = rand::thread_rng();
+        if count >= 0 {
+            return Ok(set.iter().choose_multiple(&mut rng, count as usize));
+        }
+        let members: Vec<Bytes> = set.iter().collect();
+        Ok((0..count.unsigned_abs())
+            .map(|_| members[rng.gen_range(0..members.len())].clone())
+            .collect())
+    }
+
+    fn sinter(&self, keys: Vec<&[u8]>) -> Result<HashSet<Bytes>, Box<dyn Error>> {
+        let mut sets = Vec::with_capacity(keys.len());
+        for key in keys {
+            match self.keyspace().get::<SetValue>(key)? {
+                Some(set) => sets.push(set),
+                None => return Ok(HashSet::new()),
+            }
+        }
+        sets.sort_by_key(|set| set.len());
+        let Some((smallest, rest)) = sets.split_first() else {
+            return Ok(HashSet::new());
+        };
+        Ok(smallest
+            .iter()
+            .filter(|member| rest.iter().all(|set| set.contains(member)))
+            .collect())
+    }
+
+    fn sunion(&self, keys: Vec<&[u8]>) -> Result<HashSet<Bytes>, Box<dyn Error>> {
+        let mut union = HashSet::new();
+        for key in keys {
+            if let Some(set) = self.keyspace().get::<SetValue>(key)? {
+                union.extend(set.iter());
+            }
+        }
+        Ok(union)
+    }
+
+    /// Returns the members of the first set that are not in any of the following sets.
+    fn sdiff(&self, keys: Vec<&[u8]>) -> Result<HashSet<Bytes>, Box<dyn Error>> {
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(HashSet::new());
+        };
+        let Some(first) = self.keyspace().get::<SetValue>(first)? else {
+            return Ok(HashSet::new());
+        };
+        let mut others = Vec::with_capacity(rest.len());
+        for key in rest {
+            if let Some(set) = self.keyspace().get::<SetValue>(key)? {
+                others.push(set);
+            }
+        }
+        Ok(first
+            .iter()
+            .filter(|member| !others.iter().any(|set| set.contains(member)))
+            .collect())
+    }
+
+    /// Overwrites the destination with the given members and returns its cardinality.
+    /// An empty result removes the destination.
+    fn store_members(
+        &mut self,
+        destination: &[u8],
+        members: HashSet<Bytes>,
+    ) -> Result<u64, Box<dyn Error>> {
+        let card = members.len() as u64;
+        if members.is_empty() {
+            self.keyspace_mut().remove(destination);
+        } else {
+            self.keyspace_mut()
+                .insert(destination, members.into_iter().collect::<SetValue>());
+        }
+        Ok(card)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::commands::generic::GenericCommand;
+
+    fn members(members: &[&str]) -> Vec<Bytes> {
+        members.iter().map(|s| Bytes::from(*s)).collect()
+    }
+
+    fn sorted<I: IntoIterator<Item = Bytes>>(members: I) -> Vec<Bytes> {
+        let mut members: Vec<Bytes> = members.into_iter().collect();
+        members.sort();
+        members
+    }
+
+    fn sorted_array(result: CommandResult) -> Vec<Bytes> {
+        match result {
+            CommandResult::Array(values) => sorted(values.into_iter().map(|v| match v {
+                CommandResult::String(s) => s,
+                other => panic!("Unexpected array element: {:?}", other),
+            })),
+            other => panic!("Expected array, got {:?}", other),
+        }
+    }
+
+    fn store_with(sets: &[(&str, &[&str])]) -> SetStore {
+        let mut store = SetStore::new().unwrap();
+        for (key, elements) in sets {
+            store.sadd(key.as_bytes(), members(elements)).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn test_new() {
+        // Arrange
+        let store = SetStore::new();
+        // Act
+        // Assert
+        assert!(store.is_ok());
+    }
+
+    #[test]
+    fn test_sadd_ignores_duplicates() {
+        // Arrange
+        let mut store = SetStore::new().unwrap();
+
+        // Act
+        let result_1 = store.sadd(b"key", members(&["a", "b", "a"])).unwrap();
+        let result_2 = store.sadd(b"key", members(&["b", "c"])).unwrap();
+
+        // Assert
+        assert_eq!(result_1, 2);
+        assert_eq!(result_2, 1);
+        assert_eq!(store.scard(b"key").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_srem_removes_key_when_set_becomes_empty() {
+        // Arrange
+        let mut store = store_with(&[("key", &["a", "b"])]);
+
+        // Act
+        let result_1 = store.srem(b"key", vec![b"a", b"missing"]).unwrap();
+        let result_2 = store.srem(b"key", vec![b"b"]).unwrap();
+
+        // Assert
+        assert_eq!(result_1, 1);
+        assert_eq!(result_2, 1);
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sismember_and_smismember() {
+        // Arrange
+        let store = store_with(&[("key", &["a", "b"])]);
+
+        // Act & Assert
+        assert!(store.sismember(b"key", b"a").unwrap());
+        assert!(!store.sismember(b"key", b"c").unwrap());
+        assert!(!store.sismember(b"missing", b"a").unwrap());
+        assert_eq!(
+            store.smismember(b"key", vec![b"a", b"c", b"b"]).unwrap(),
+            vec![true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_smembers() {
+        // Arrange
+        let store = store_with(&[("key", &["b", "a"])]);
+
+        // Act
+        let result = store.smembers(b"key").unwrap();
+
+        // Assert
+        assert_eq!(sorted(result), members(&["a", "b"]));
+        assert!(store.smembers(b"missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_spop_removes_returned_members() {
+        // Arrange
+        let mut store = store_with(&[("key", &["a", "b", "c"])]);
+
+        // Act
+        let popped = store.spop(b"key", 2).unwrap();
+        let rest = store.smembers(b"key").unwrap();
+        let popped_all = store.spop(b"key", 10).unwrap();
+
+        // Assert
+        assert_eq!(popped.len(), 2);
+        assert_eq!(rest.len(), 1);
+        assert!(!popped.contains(&rest[0]));
+        assert_eq!(popped_all, rest);
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_srandmember_with_positive_and_negative_count() {
+        // Arrange
+        let store = store_with(&[("key", &["a", "b", "c"])]);
+
+        // Act
+        let distinct = store.srandmember(b"key", 5).unwrap();
+        let repeated = store.srandmember(b"key", -7).unwrap();
+
+        // Assert
+        assert_eq!(sorted(distinct), members(&["a", "b", "c"]));
+        assert_eq!(repeated.len(), 7);
+        assert_eq!(store.scard(b"key").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_sinter_sunion_and_sdiff() {
+        // Arrange
+        let store = store_with(&[
+            ("key1", &["a", "b", "c", "d"]),
+            ("key2", &["c"]),
+            ("key3", &["a", "c", "e"]),
+        ]);
+
+        // Act
+        let inter = store.sinter(vec![b"key1", b"key2", b"key3"]).unwrap();
+        let inter_missing = store.sinter(vec![b"key1", b"missing"]).unwrap();
+        let union = store.sunion(vec![b"key1", b"key3", b"missing"]).unwrap();
+        let diff = store.sdiff(vec![b"key1", b"key2", b"key3"]).unwrap();
+
+        // Assert
+        assert_eq!(sorted(inter), members(&["c"]));
+        assert!(inter_missing.is_empty());
+        assert_eq!(sorted(union), members(&["a", "b", "c", "d", "e"]));
+        assert_eq!(sorted(diff), members(&["b", "d"]));
+    }
+
+    #[test]
+    fn test_handle_command_store_variants_overwrite_destination() {
+        // Arrange
+        let mut store = store_with(&[
+            ("key1", &["a", "b"]),
+            ("key2", &["b", "c"]),
+            ("dest", &["z"]),
+        ]);
+        let inter_store = Command::Set(SetCommand::InterStore {
+            destination: "dest".into(),
+            keys: members(&["key1", "key2"]),
+        });
+        let union_store = Command::Set(SetCommand::UnionStore {
+            destination: "union".into(),
+            keys: members(&["key1", "key2"]),
+        });
+        let diff_store = Command::Set(SetCommand::DiffStore {
+            destination: "dest".into(),
+            keys: members(&["key1", "key1"]),
+        });
+
+        // Act & Assert
+        assert_eq!(
+            store.handle_command(inter_store).unwrap(),
+            CommandResult::Int(1)
+        );
+        assert_eq!(sorted(store.smembers(b"dest").unwrap()), members(&["b"]));
+        assert_eq!(
+            store.handle_command(union_store).unwrap(),
+            CommandResult::Int(3)
+        );
+        assert_eq!(
+            sorted(store.smembers(b"union").unwrap()),
+            members(&["a", "b", "c"])
+        );
+        assert_eq!(
+            store.handle_command(diff_store).unwrap(),
+            CommandResult::Int(0)
+        );
+        assert_eq!(store.exists(vec![b"dest"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_handle_command_inter() {
+        // Arrange
+        let mut store = store_with(&[("key1", &["a", "b"]), ("key2", &["b", "a", "c"])]);
+        let command = Command::Set(SetCommand::Inter {
+            keys: members(&["key1", "key2"]),
+        });
+
+        // Act
+        let result = store.handle_command(command).unwrap();
+
+        // Assert
+        assert_eq!(sorted_array(result), members(&["a", "b"]));
+    }
+
+    #[test]
+    fn test_handle_command_pop_without_count_on_missing_key() {
+        // Arrange
+        let mut store = SetStore::new().unwrap();
+        let command = Command::Set(SetCommand::Pop {
+            key: "key".into(),
+            count: None,
+        });
+
+        // Act
+        let result = store.handle_command(command).unwrap();
+
+        // Assert
+        assert_eq!(result, CommandResult::Nil);
+    }
+
+    #[test]
+    fn test_handle_command_exists_and_delete() {
+        // Arrange
+        let mut store = store_with(&[("key1", &["a"])]);
+        let exists_command = Command::Generic(GenericCommand::Exists {
+            keys: members(&["key1", "key2"]),
+        });
+        let delete_command = Command::Generic(GenericCommand::Delete {
+            keys: members(&["key1", "key2"]),
+        });
+
+        // Act
+        let exists_result = store.handle_command(exists_command).unwrap();
+        let delete_result = store.handle_command(delete_command).unwrap();
+
+        // Assert
+        assert_eq!(exists_result, CommandResult::Int(1));
+        assert_eq!(delete_result, CommandResult::Int(1));
+        assert_eq!(store.scard(b"key1").unwrap(), 0);
+    }
+}
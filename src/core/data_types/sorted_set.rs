@@ -2,4 +2,1203 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-// TODO
+use crate::core::bytes::Bytes;
+use crate::core::commands::{
+    sorted_set::{
+        Aggregate, LexBound, ScoreBound, SortedSetCommand, ZAddComparison, ZAddCondition,
+        ZAddOptions, ZRangeBy, ZRangeLimit,
+    },
+    Command, CommandResult,
+};
+use crate::core::data_types::{
+    data_type::{DataType, DataTypeError, GenericOperations, KeyspaceAccess},
+    encoding::{ListPack, MAX_PACKED_ENTRIES, MAX_PACKED_VALUE_LEN},
+    keyspace::Keyspace,
+};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+/// A score with a total order, so that it can be used as a key of an ordered collection.
+#[derive(Debug, Clone, Copy)]
+pub struct Score(f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Members ordered by score, members with the same score are ordered lexicographically.
+///
+/// A small sorted set is packed as members alternating with their scores, kept in order, and is
+/// reported by OBJECT ENCODING as `listpack`. Once it has more than `MAX_PACKED_ENTRIES` members
+/// or a member longer than `MAX_PACKED_VALUE_LEN`, it is converted to a `skiplist`: a map from
+/// members to scores and an ordered set of both. It is never converted back.
+#[derive(Debug, Clone)]
+pub enum SortedSet {
+    Packed(ListPack),
+    SkipList {
+        scores: HashMap<Bytes, f64>,
+        ordered: BTreeSet<(Score, Bytes)>,
+    },
+}
+
+/// Iterates over the members and scores of a packed sorted set.
+fn packed_pairs(pack: &ListPack) -> impl Iterator<Item = (&[u8], f64)> {
+    let mut elements = pack.iter();
+    std::iter::from_fn(move || {
+        let member = elements.next()?;
+        let score = elements
+            .next()?
+            .try_into()
+            .expect("packed score is 8 bytes");
+        Some((member, f64::from_le_bytes(score)))
+    })
+}
+
+impl SortedSet {
+    /// Sets the score of the member and returns the previous one.
+    pub fn insert(&mut self, member: Bytes, score: f64) -> Option<f64> {
+        // -0.0 and 0.0 are different for the total order, but must be the same score.
+        let score = if score == 0.0 { 0.0 } else { score };
+        if let SortedSet::Packed(pack) = self {
+            let previous = packed_pairs(pack)
+                .enumerate()
+                .find(|(_, (m, _))| *m == member.as_slice())
+                .map(|(index, (_, score))| (index, score));
+            let fits = pack.len() / 2 < MAX_PACKED_ENTRIES && member.len() <= MAX_PACKED_VALUE_LEN;
+            if previous.is_some() || fits {
+                if let Some((index, _)) = previous {
+                    pack.remove(index * 2);
+                    pack.remove(index * 2);
+                }
+                let index = packed_pairs(pack)
+                    .take_while(|(m, s)| (Score(*s), *m) < (Score(score), member.as_slice()))
+                    .count();
+                pack.insert(index * 2, &member);
+                pack.insert(index * 2 + 1, &score.to_le_bytes());
+                return previous.map(|(_, score)| score);
+            }
+            self.convert_to_skip_list();
+        }
+        let SortedSet::SkipList { scores, ordered } = self else {
+            unreachable!("converted to a skip list above");
+        };
+        let previous = scores.insert(member.clone(), score);
+        if let Some(previous) = previous {
+            ordered.remove(&(Score(previous), member.clone()));
+        }
+        ordered.insert((Score(score), member));
+        previous
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> Option<f64> {
+        match self {
+            SortedSet::Packed(pack) => {
+                let (index, score) = packed_pairs(pack)
+                    .enumerate()
+                    .find(|(_, (m, _))| *m == member)
+                    .map(|(index, (_, score))| (index, score))?;
+                pack.remove(index * 2);
+                pack.remove(index * 2);
+                Some(score)
+            }
+            SortedSet::SkipList { scores, ordered } => {
+                let score = scores.remove(member)?;
+                ordered.remove(&(Score(score), Bytes::from(member)));
+                Some(score)
+            }
+        }
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        match self {
+            SortedSet::Packed(pack) => packed_pairs(pack)
+                .find(|(m, _)| *m == member)
+                .map(|(_, score)| score),
+            SortedSet::SkipList { scores, .. } => scores.get(member).copied(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            SortedSet::Packed(pack) => pack.len() / 2,
+            SortedSet::SkipList { scores, .. } => scores.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over members from the lowest score to the highest.
+    pub fn iter(&self) -> Box<dyn DoubleEndedIterator<Item = (&[u8], f64)> + '_> {
+        match self {
+            // A packed set is small, collecting it is cheaper than walking it backwards.
+            SortedSet::Packed(pack) => Box::new(packed_pairs(pack).collect::<Vec<_>>().into_iter()),
+            SortedSet::SkipList { ordered, .. } => Box::new(
+                ordered
+                    .iter()
+                    .map(|(score, member)| (member.as_slice(), score.0)),
+            ),
+        }
+    }
+
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        match self {
+            SortedSet::Packed(pack) => packed_pairs(pack).position(|(m, _)| m == member),
+            SortedSet::SkipList { ordered, .. } => {
+                let score = self.score(member)?;
+                ordered
+                    .iter()
+                    .position(|(s, m)| *s == Score(score) && m == member)
+            }
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            SortedSet::Packed(_) => "listpack",
+            SortedSet::SkipList { .. } => "skiplist",
+        }
+    }
+
+    fn convert_to_skip_list(&mut self) {
+        if let SortedSet::Packed(pack) = self {
+            let mut scores = HashMap::with_capacity(pack.len() / 2);
+            let mut ordered = BTreeSet::new();
+            for (member, score) in packed_pairs(pack) {
+                scores.insert(Bytes::from(member), score);
+                ordered.insert((Score(score), Bytes::from(member)));
+            }
+            *self = SortedSet::SkipList { scores, ordered };
+        }
+    }
+}
+
+impl Default for SortedSet {
+    fn default() -> Self {
+        SortedSet::Packed(ListPack::new())
+    }
+}
+
+/// Stores sorted sets under keys.
+/// A key is removed as soon as its sorted set becomes empty.
+#[derive(Debug)]
+pub struct SortedSetStore {
+    data: Keyspace,
+}
+
+impl DataType for SortedSetStore {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(SortedSetStore {
+            data: Keyspace::new(),
+        })
+    }
+
+    fn handle_command(&mut self, command: Command) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            Command::SortedSet(cmd) => self.handle_sorted_set_command(cmd),
+            Command::Generic(cmd) => self.handle_generic_command(cmd),
+            #[allow(unreachable_patterns)]
+            _ => Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Command not supported by SortedSetStore",
+            ))),
+        }
+    }
+}
+
+impl KeyspaceAccess for SortedSetStore {
+    fn keyspace(&self) -> &Keyspace {
+        &self.data
+    }
+
+    fn keyspace_mut(&mut self) -> &mut Keyspace {
+        &mut self.data
+    }
+}
+
+impl SortedSetOperations for SortedSetStore {}
+
+fn to_array(members: Vec<(Bytes, f64)>, with_scores: bool) -> CommandResult {
+    let mut result = Vec::new();
+    for (member, score) in members {
+        result.push(CommandResult::String(member));
+        if with_scores {
+            result.push(CommandResult::String(Bytes::from(score.to_string())));
+        }
+    }
+    CommandResult::Array(result)
+}
+
+fn validate_options(options: &ZAddOptions) -> Result<(), DataTypeError> {
+    if options.condition == Some(ZAddCondition::Nx) && options.comparison.is_some() {
+        return Err(DataTypeError::SyntaxError);
+    }
+    Ok(())
+}
+
+/// Returns the score the member should get, or None if the options forbid the update.
+fn resolve_score(
+    current: Option<f64>,
+    score: f64,
+    options: &ZAddOptions,
+) -> Result<Option<f64>, DataTypeError> {
+    match (options.condition, current) {
+        (Some(ZAddCondition::Nx), Some(_)) | (Some(ZAddCondition::Xx), None) => return Ok(None),
+        _ => {}
+    }
+    let new_score = if options.incr {
+        current.unwrap_or(0.0) + score
+    } else {
+        score
+    };
+    if new_score.is_nan() {
+        return Err(DataTypeError::NotAFloat);
+    }
+    if let Some(current) = current {
+        match options.comparison {
+            Some(ZAddComparison::Gt) if new_score <= current => return Ok(None),
+            Some(ZAddComparison::Lt) if new_score >= current => return Ok(None),
+            _ => {}
+        }
+    }
+    Ok(Some(new_score))
+}
+
+fn score_above(score: f64, min: ScoreBound) -> bool {
+    match min {
+        ScoreBound::Inclusive(min) => score >= min,
+        ScoreBound::Exclusive(min) => score > min,
+    }
+}
+
+fn score_below(score: f64, max: ScoreBound) -> bool {
+    match max {
+        ScoreBound::Inclusive(max) => score <= max,
+        ScoreBound::Exclusive(max) => score < max,
+    }
+}
+
+fn lex_above(member: &[u8], min: &LexBound) -> bool {
+    match min {
+        LexBound::Min => true,
+        LexBound::Max => false,
+        LexBound::Inclusive(min) => member >= min.as_slice(),
+        LexBound::Exclusive(min) => member > min.as_slice(),
+    }
+}
+
+fn lex_below(member: &[u8], max: &LexBound) -> bool {
+    match max {
+        LexBound::Min => false,
+        LexBound::Max => true,
+        LexBound::Inclusive(max) => member <= max.as_slice(),
+        LexBound::Exclusive(max) => member < max.as_slice(),
+    }
+}
+
+/// Operations of the Sorted Set data type.
+pub trait SortedSetOperations: KeyspaceAccess {
+    /// Handles a sorted set command and returns the result.
+    fn handle_sorted_set_command(
+        &mut self,
+        command: SortedSetCommand,
+    ) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            SortedSetCommand::Add {
+                key,
+                members,
+                options,
+            } => {
+                if options.incr {
+                    let Some((increment, member)) = members.into_iter().next() else {
+                        return Err(Box::new(DataTypeError::SyntaxError));
+                    };
+                    let result = self.zadd_incr(&key, increment, member, &options)?;
+                    return Ok(result.map_or(CommandResult::Nil, |score| {
+                        CommandResult::String(Bytes::from(score.to_string()))
+                    }));
+                }
+                let count = self.zadd(&key, members, &options)?;
+                Ok(CommandResult::Int(count as i64))
+            }
+            SortedSetCommand::Rem { key, members } => {
+                let members_ref: Vec<&[u8]> = members.iter().map(|s| s.as_slice()).collect();
+                let removed = self.zrem(&key, members_ref)?;
+                Ok(CommandResult::Int(removed as i64))
+            }
+            SortedSetCommand::Score { key, member } => {
+                let score = self.zscore(&key, &member)?;
+                Ok(score.map_or(CommandResult::Nil, |score| {
+                    CommandResult::String(Bytes::from(score.to_string()))
+                }))
+            }
+            SortedSetCommand::Rank { key, member } => {
+                let rank = self.zrank(&key, &member, false)?;
+                Ok(rank.map_or(CommandResult::Nil, |rank| CommandResult::Int(rank as i64)))
+            }
+            SortedSetCommand::RevRank { key, member } => {
+                let rank = self.zrank(&key, &member, true)?;
+                Ok(rank.map_or(CommandResult::Nil, |rank| CommandResult::Int(rank as i64)))
+            }
+            SortedSetCommand::Range {
+                key,
+                by,
+                rev,
+                limit,
+                with_scores,
+            } => {
+                let members = self.zrange(&key, &by, rev, limit)?;
+                Ok(to_array(members, with_scores))
+            }
+            SortedSetCommand::Count { key, min, max } => {
+                let count = self.zcount(&key, min, max)?;
+                Ok(CommandResult::Int(count as i64))
+            }
+            SortedSetCommand::IncrBy {
+                key,
+                increment,
+                member,
+            } => {
+                let score = self.zincrby(&key, increment, member)?;
+                Ok(CommandResult::String(Bytes::from(score.to_string())))
+            }
+            SortedSetCommand::PopMin { key, count } => {
+                let popped = self.zpop(&key, count.unwrap_or(1), false)?;
+                Ok(to_array(popped, true))
+            }
+            SortedSetCommand::PopMax { key, count } => {
+                let popped = self.zpop(&key, count.unwrap_or(1), true)?;
+                Ok(to_array(popped, true))
+            }
+            SortedSetCommand::UnionStore {
+                destination,
+                keys,
+                weights,
+                aggregate,
+            } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                let card = self.zstore(&destination, keys_ref, weights, aggregate, false)?;
+                Ok(CommandResult::Int(card as i64))
+            }
+            SortedSetCommand::InterStore {
+                destination,
+                keys,
+                weights,
+                aggregate,
+            } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                let card = self.zstore(&destination, keys_ref, weights, aggregate, true)?;
+                Ok(CommandResult::Int(card as i64))
+            }
+        }
+    }
+
+    /// Returns the number of added members, or of added and changed ones with CH.
+    fn zadd(
+        &mut self,
+        key: &[u8],
+        members: Vec<(f64, Bytes)>,
+        options: &ZAddOptions,
+    ) -> Result<u64, Box<dyn Error>> {
+        validate_options(options)?;
+        if members.iter().any(|(score, _)| score.is_nan()) {
+            return Err(Box::new(DataTypeError::NotAFloat));
+        }
+        let set = self
+            .keyspace_mut()
+            .get_or_insert_default::<SortedSet>(key)?;
+        let mut added = 0;
+        let mut changed = 0;
+        for (score, member) in members {
+            let current = set.score(&member);
+            let Some(new_score) = resolve_score(current, score, options)? else {
+                continue;
+            };
+            match current {
+                None => added += 1,
+                Some(current) if current != new_score => changed += 1,
+                Some(_) => {}
+            }
+            set.insert(member, new_score);
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(if options.ch { added + changed } else { added })
+    }
+
+    /// ZADD with INCR, returns the new score or None if the options forbid the update.
+    fn zadd_incr(
+        &mut self,
+        key: &[u8],
+        increment: f64,
+        member: Bytes,
+        options: &ZAddOptions,
+    ) -> Result<Option<f64>, Box<dyn Error>> {
+        validate_options(options)?;
+        let current = self.zscore(key, &member)?;
+        let Some(new_score) = resolve_score(current, increment, options)? else {
+            return Ok(None);
+        };
+        self.keyspace_mut()
+            .get_or_insert_default::<SortedSet>(key)?
+            .insert(member, new_score);
+        Ok(Some(new_score))
+    }
+
+    fn zrem(&mut self, key: &[u8], members: Vec<&[u8]>) -> Result<u64, Box<dyn Error>> {
+        let Some(set) = self.keyspace_mut().get_mut::<SortedSet>(key)? else {
+            return Ok(0);
+        };
+        let removed = members
+            .into_iter()
+            .filter(|member| set.remove(member).is_some())
+            .count() as u64;
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(removed)
+    }
+
+    fn zscore(&self, key: &[u8], member: &[u8]) -> Result<Option<f64>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<SortedSet>(key)?
+            .and_then(|set| set.score(member)))
+    }
+
+    fn zrank(
+        &self,
+        key: &[u8],
+        member: &[u8],
+        reverse: bool,
+    ) -> Result<Option<u64>, Box<dyn Error>> {
+        let Some(set) = self.keyspace().get::<SortedSet>(key)? else {
+            return Ok(None);
+        };
+        Ok(set.rank(member).map(|rank| {
+            if reverse {
+                (set.len() - 1 - rank) as u64
+            } else {
+                rank as u64
+            }
+        }))
+    }
+
+    fn zrange(
+        &self,
+        key: &[u8],
+        by: &ZRangeBy,
+        rev: bool,
+        limit: Option<ZRangeLimit>,
+    ) -> Result<Vec<(Bytes, f64)>, Box<dyn Error>> {
+        let Some(set) = self.keyspace().get::<SortedSet>(key)? else {
+            return Ok(Vec::new());
+        };
+        let ordered: Box<dyn Iterator<Item = (&[u8], f64)>> = if rev {
+            Box::new(set.iter().rev())
+        } else {
+            Box::new(set.iter())
+        };
+        let selected: Vec<(&[u8], f64)> = match by {
+            ZRangeBy::Index { start, stop } => {
+                if limit.is_some() {
+                    return Err(Box::new(DataTypeError::SyntaxError));
+                }
+                let len = set.len() as i64;
+                let start = if *start < 0 {
+                    (len + start).max(0)
+                } else {
+                    *start
+                };
+                let stop = if *stop < 0 {
+                    len + stop
+                } else {
+                    (*stop).min(len - 1)
+                };
+                if start > stop || start >= len {
+                    return Ok(Vec::new());
+                }
+                ordered
+                    .skip(start as usize)
+                    .take((stop - start + 1) as usize)
+                    .collect()
+            }
+            ZRangeBy::Score { min, max } => ordered
+                .filter(|(_, score)| score_above(*score, *min) && score_below(*score, *max))
+                .collect(),
+            ZRangeBy::Lex { min, max } => ordered
+                .filter(|(member, _)| lex_above(member, min) && lex_below(member, max))
+                .collect(),
+        };
+        let (offset, count) = match limit {
+            Some(limit) => (limit.offset as usize, limit.count as usize),
+            None => (0, usize::MAX),
+        };
+        Ok(selected
+            .into_iter()
+            .skip(offset)
+            .take(count)
+            .map(|(member, score)| (Bytes::from(member), score))
+            .collect())
+    }
+
+    fn zcount(&self, key: &[u8], min: ScoreBound, max: ScoreBound) -> Result<u64, Box<dyn Error>> {
+        let Some(set) = self.keyspace().get::<SortedSet>(key)? else {
+            return Ok(0);
+        };
+        Ok(set
+            .iter()
+            .skip_while(|(_, score)| !score_above(*score, min))
+            .take_while(|(_, score)| score_below(*score, max))
+            .count() as u64)
+    }
+
+    fn zincrby(
+        &mut self,
+        key: &[u8],
+        increment: f64,
+        member: Bytes,
+    ) -> Result<f64, Box<dyn Error>> {
+        let current = self.zscore(key, &member)?.unwrap_or(0.0);
+        let new_score = current + increment;
+        if new_score.is_nan() {
+            return Err(Box::new(DataTypeError::NotAFloat));
+        }
+        self.keyspace_mut()
+            .get_or_insert_default::<SortedSet>(key)?
+            .insert(member, new_score);
+        Ok(new_score)
+    }
+
+    /// Removes and returns up to `count` members with the lowest (or highest) scores.
+    fn zpop(
+        &mut self,
+        key: &[u8],
+        count: u64,
+        highest: bool,
+    ) -> Result<Vec<(Bytes, f64)>, Box<dyn Error>> {
+        let Some(set) = self.keyspace_mut().get_mut::<SortedSet>(key)? else {
+            return Ok(Vec::new());
+        };
+        let popped: Vec<(Bytes, f64)> = if highest {
+            set.iter()
+                .rev()
+                .take(count as usize)
+                .map(|(member, score)| (Bytes::from(member), score))
+                .collect()
+        } else {
+            set.iter()
+                .take(count as usize)
+                .map(|(member, score)| (Bytes::from(member), score))
+                .collect()
+        };
+        for (member, _) in &popped {
+            set.remove(member);
+        }
+        self.keyspace_mut().remove_if_empty(key);
+        Ok(popped)
+    }
+
+    /// Computes the weighted union or intersection of the sorted sets, stores it in the
+    /// destination and returns its cardinality.
+    fn zstore(
+        &mut self,
+        destination: &[u8],
+        keys: Vec<&[u8]>,
+        weights: Option<Vec<f64>>,
+        aggregate: Aggregate,
+        intersect: bool,
+    ) -> Result<u64, Box<dyn Error>> {
+        let weights = weights.unwrap_or_else(|| vec![1.0; keys.len()]);
+        if keys.is_empty() || weights.len() != keys.len() {
+            return Err(Box::new(DataTypeError::SyntaxError));
+        }
+        let mut combined: HashMap<Bytes, f64> = HashMap::new();
+        let mut occurrences: HashMap<Bytes, usize> = HashMap::new();
+        for (key, weight) in keys.iter().zip(weights) {
+            let Some(set) = self.keyspace().get::<SortedSet>(key)? else {
+                continue;
+            };
+            for (member, score) in set.iter() {
+                let weighted = score * weight;
+                // inf * 0 is the only way to get NaN here, treat it as 0 like Redis does.
+                let weighted = if weighted.is_nan() { 0.0 } else { weighted };
+                *occurrences.entry(Bytes::from(member)).or_default() += 1;
+                combined
+                    .entry(Bytes::from(member))
+                    .and_modify(|current| {
+                        *current = match aggregate {
+                            Aggregate::Sum => {
+                                let sum = *current + weighted;
+                                if sum.is_nan() {
+                                    0.0
+                                } else {
+                                    sum
+                                }
+                            }
+                            Aggregate::Min => current.min(weighted),
+                            Aggregate::Max => current.max(weighted),
+                        }
+                    })
+                    .or_insert(weighted);
+            }
+        }
+        if intersect {
+            combined.retain(|member, _| occurrences[member] == keys.len());
+        }
+        let mut result = SortedSet::default();
+        for (member, score) in combined {
+            result.insert(member, score);
+        }
+        let card = result.len() as u64;
+        if result.is_empty() {
+            self.keyspace_mut().remove(destination);
+        } else {
+            self.keyspace_mut().insert(destination, result);
+        }
+        Ok(card)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::commands::generic::GenericCommand;
+
+    fn members(pairs: &[(f64, &str)]) -> Vec<(f64, Bytes)> {
+        pairs.iter().map(|(s, m)| (*s, Bytes::from(*m))).collect()
+    }
+
+    fn names(members: Vec<(Bytes, f64)>) -> Vec<Bytes> {
+        members.into_iter().map(|(m, _)| m).collect()
+    }
+
+    fn store_with(key: &str, pairs: &[(f64, &str)]) -> SortedSetStore {
+        let mut store = SortedSetStore::new().unwrap();
+        store
+            .zadd(key.as_bytes(), members(pairs), &ZAddOptions::default())
+            .unwrap();
+        store
+    }
+
+    fn by_score(min: &str, max: &str) -> ZRangeBy {
+        ZRangeBy::Score {
+            min: min.parse().unwrap(),
+            max: max.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        // Arrange
+        let store = SortedSetStore::new();
+        // Act
+        // Assert
+        assert!(store.is_ok());
+    }
+
+    #[test]
+    fn test_zadd_adds_and_updates_members() {
+        // Arrange
+        let mut store = SortedSetStore::new().unwrap();
+
+        // Act
+        let added = store
+            .zadd(
+                b"key",
+                members(&[(1.0, "a"), (2.0, "b")]),
+                &ZAddOptions::default(),
+            )
+            .unwrap();
+        let updated = store
+            .zadd(
+                b"key",
+                members(&[(3.0, "a"), (1.0, "c")]),
+                &ZAddOptions::default(),
+            )
+            .unwrap();
+
+        // Assert
+        assert_eq!(added, 2);
+        assert_eq!(updated, 1);
+        assert_eq!(store.zscore(b"key", b"a").unwrap(), Some(3.0));
+        assert_eq!(
+            names(
+                store
+                    .zrange(b"key", &ZRangeBy::Index { start: 0, stop: -1 }, false, None)
+                    .unwrap()
+            ),
+            vec!["c", "b", "a"]
+        );
+    }
+
+    #[test]
+    fn test_zadd_with_nx_xx_and_ch() {
+        // Arrange
+        let mut store = store_with("key", &[(1.0, "a")]);
+        let nx = ZAddOptions {
+            condition: Some(ZAddCondition::Nx),
+            ..Default::default()
+        };
+        let xx_ch = ZAddOptions {
+            condition: Some(ZAddCondition::Xx),
+            ch: true,
+            ..Default::default()
+        };
+
+        // Act
+        let result_nx = store
+            .zadd(b"key", members(&[(5.0, "a"), (2.0, "b")]), &nx)
+            .unwrap();
+        let result_xx = store
+            .zadd(b"key", members(&[(7.0, "a"), (3.0, "c")]), &xx_ch)
+            .unwrap();
+
+        // Assert
+        assert_eq!(result_nx, 1);
+        assert_eq!(result_xx, 1);
+        assert_eq!(store.zscore(b"key", b"a").unwrap(), Some(7.0));
+        assert_eq!(store.zscore(b"key", b"b").unwrap(), Some(2.0));
+        assert_eq!(store.zscore(b"key", b"c").unwrap(), None);
+    }
+
+    #[test]
+    fn test_zadd_with_gt_and_lt() {
+        // Arrange
+        let mut store = store_with("key", &[(5.0, "a"), (5.0, "b")]);
+        let gt = ZAddOptions {
+            comparison: Some(ZAddComparison::Gt),
+            ch: true,
+            ..Default::default()
+        };
+        let lt = ZAddOptions {
+            comparison: Some(ZAddComparison::Lt),
+            ..Default::default()
+        };
+
+        // Act
+        let result_gt = store
+            .zadd(b"key", members(&[(3.0, "a"), (8.0, "b"), (1.0, "c")]), &gt)
+            .unwrap();
+        store
+            .zadd(b"key", members(&[(4.0, "a"), (9.0, "b")]), &lt)
+            .unwrap();
+
+        // Assert
+        assert_eq!(result_gt, 2);
+        assert_eq!(store.zscore(b"key", b"a").unwrap(), Some(4.0));
+        assert_eq!(store.zscore(b"key", b"b").unwrap(), Some(8.0));
+        assert_eq!(store.zscore(b"key", b"c").unwrap(), Some(1.0));
+    }
+
+    #[test]
+    fn test_zadd_rejects_nx_with_gt() {
+        // Arrange
+        let mut store = SortedSetStore::new().unwrap();
+        let options = ZAddOptions {
+            condition: Some(ZAddCondition::Nx),
+            comparison: Some(ZAddComparison::Gt),
+            ..Default::default()
+        };
+
+        // Act
+        let result = store.zadd(b"key", members(&[(1.0, "a")]), &options);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::SyntaxError)
+        );
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_handle_command_zadd_with_incr() {
+        // Arrange
+        let mut store = store_with("key", &[(1.5, "a")]);
+        let incr = Command::SortedSet(SortedSetCommand::Add {
+            key: "key".into(),
+            members: members(&[(2.0, "a")]),
+            options: ZAddOptions {
+                incr: true,
+                ..Default::default()
+            },
+        });
+        let incr_nx = Command::SortedSet(SortedSetCommand::Add {
+            key: "key".into(),
+            members: members(&[(2.0, "a")]),
+            options: ZAddOptions {
+                incr: true,
+                condition: Some(ZAddCondition::Nx),
+                ..Default::default()
+            },
+        });
+
+        // Act
+        let result_1 = store.handle_command(incr).unwrap();
+        let result_2 = store.handle_command(incr_nx).unwrap();
+
+        // Assert
+        assert_eq!(result_1, CommandResult::String("3.5".into()));
+        assert_eq!(result_2, CommandResult::Nil);
+    }
+
+    #[test]
+    fn test_zrem_removes_key_when_sorted_set_becomes_empty() {
+        // Arrange
+        let mut store = store_with("key", &[(1.0, "a"), (2.0, "b")]);
+
+        // Act
+        let removed = store.zrem(b"key", vec![b"a", b"b", b"c"]).unwrap();
+
+        // Assert
+        assert_eq!(removed, 2);
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_zrank_and_zrevrank_with_equal_scores() {
+        // Arrange
+        let store = store_with("key", &[(1.0, "b"), (1.0, "a"), (0.0, "c")]);
+
+        // Act & Assert
+        assert_eq!(store.zrank(b"key", b"c", false).unwrap(), Some(0));
+        assert_eq!(store.zrank(b"key", b"a", false).unwrap(), Some(1));
+        assert_eq!(store.zrank(b"key", b"b", false).unwrap(), Some(2));
+        assert_eq!(store.zrank(b"key", b"b", true).unwrap(), Some(0));
+        assert_eq!(store.zrank(b"key", b"missing", false).unwrap(), None);
+    }
+
+    #[test]
+    fn test_zrange_by_index_with_rev() {
+        // Arrange
+        let store = store_with("key", &[(1.0, "a"), (2.0, "b"), (3.0, "c")]);
+
+        // Act
+        let result = store
+            .zrange(b"key", &ZRangeBy::Index { start: 0, stop: 1 }, true, None)
+            .unwrap();
+
+        // Assert
+        assert_eq!(result, vec![("c".into(), 3.0), ("b".into(), 2.0)]);
+    }
+
+    #[test]
+    fn test_zrange_by_score_with_exclusive_bounds_and_limit() {
+        // Arrange
+        let store = store_with(
+            "key",
+            &[(1.0, "a"), (2.0, "b"), (3.0, "c"), (4.0, "d"), (5.0, "e")],
+        );
+        let limit = Some(ZRangeLimit {
+            offset: 1,
+            count: 2,
+        });
+
+        // Act
+        let all = store
+            .zrange(b"key", &by_score("-inf", "+inf"), false, None)
+            .unwrap();
+        let exclusive = store
+            .zrange(b"key", &by_score("(1", "(4"), false, None)
+            .unwrap();
+        let limited = store
+            .zrange(b"key", &by_score("2", "5"), true, limit)
+            .unwrap();
+
+        // Assert
+        assert_eq!(names(all).len(), 5);
+        assert_eq!(names(exclusive), vec!["b", "c"]);
+        assert_eq!(names(limited), vec!["d", "c"]);
+    }
+
+    #[test]
+    fn test_zrange_by_lex() {
+        // Arrange
+        let store = store_with("key", &[(0.0, "a"), (0.0, "b"), (0.0, "c"), (0.0, "d")]);
+        let by = ZRangeBy::Lex {
+            min: "(a".parse().unwrap(),
+            max: "[c".parse().unwrap(),
+        };
+        let all = ZRangeBy::Lex {
+            min: LexBound::Min,
+            max: LexBound::Max,
+        };
+
+        // Act
+        let result = store.zrange(b"key", &by, false, None).unwrap();
+        let result_all = store.zrange(b"key", &all, true, None).unwrap();
+
+        // Assert
+        assert_eq!(names(result), vec!["b", "c"]);
+        assert_eq!(names(result_all), vec!["d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_zrange_by_index_rejects_limit() {
+        // Arrange
+        let store = store_with("key", &[(1.0, "a")]);
+        let limit = Some(ZRangeLimit {
+            offset: 0,
+            count: 1,
+        });
+
+        // Act
+        let result = store.zrange(
+            b"key",
+            &ZRangeBy::Index { start: 0, stop: -1 },
+            false,
+            limit,
+        );
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_zcount() {
+        // Arrange
+        let store = store_with("key", &[(1.0, "a"), (2.0, "b"), (3.0, "c")]);
+
+        // Act & Assert
+        assert_eq!(
+            store
+                .zcount(b"key", "2".parse().unwrap(), "+inf".parse().unwrap())
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            store
+                .zcount(b"key", "(1".parse().unwrap(), "(3".parse().unwrap())
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            store
+                .zcount(b"missing", "-inf".parse().unwrap(), "+inf".parse().unwrap())
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_zincrby() {
+        // Arrange
+        let mut store = SortedSetStore::new().unwrap();
+
+        // Act
+        let result_1 = store.zincrby(b"key", 2.5, "a".into()).unwrap();
+        let result_2 = store.zincrby(b"key", -1.0, "a".into()).unwrap();
+
+        // Assert
+        assert_eq!(result_1, 2.5);
+        assert_eq!(result_2, 1.5);
+    }
+
+    #[test]
+    fn test_zincrby_resulting_in_nan_fails() {
+        // Arrange
+        let mut store = store_with("key", &[(f64::INFINITY, "a")]);
+
+        // Act
+        let result = store.zincrby(b"key", f64::NEG_INFINITY, "a".into());
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::NotAFloat)
+        );
+    }
+
+    #[test]
+    fn test_zpopmin_and_zpopmax() {
+        // Arrange
+        let mut store = store_with("key", &[(1.0, "a"), (2.0, "b"), (3.0, "c")]);
+
+        // Act
+        let min = store.zpop(b"key", 1, false).unwrap();
+        let max = store.zpop(b"key", 5, true).unwrap();
+
+        // Assert
+        assert_eq!(min, vec![("a".into(), 1.0)]);
+        assert_eq!(max, vec![("c".into(), 3.0), ("b".into(), 2.0)]);
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_zunionstore_with_weights_and_aggregate() {
+        // Arrange
+        let mut store = store_with("key1", &[(1.0, "a"), (2.0, "b")]);
+        store
+            .zadd(
+                b"key2",
+                members(&[(10.0, "b"), (20.0, "c")]),
+                &ZAddOptions::default(),
+            )
+            .unwrap();
+
+        // Act
+        let card_sum = store
+            .zstore(
+                b"sum",
+                vec![b"key1", b"key2"],
+                Some(vec![2.0, 1.0]),
+                Aggregate::Sum,
+                false,
+            )
+            .unwrap();
+        let card_max = store
+            .zstore(b"max", vec![b"key1", b"key2"], None, Aggregate::Max, false)
+            .unwrap();
+
+        // Assert
+        assert_eq!(card_sum, 3);
+        assert_eq!(store.zscore(b"sum", b"a").unwrap(), Some(2.0));
+        assert_eq!(store.zscore(b"sum", b"b").unwrap(), Some(14.0));
+        assert_eq!(store.zscore(b"sum", b"c").unwrap(), Some(20.0));
+        assert_eq!(card_max, 3);
+        assert_eq!(store.zscore(b"max", b"b").unwrap(), Some(10.0));
+    }
+
+    #[test]
+    fn test_zinterstore_keeps_only_common_members() {
+        // Arrange
+        let mut store = store_with("key1", &[(1.0, "a"), (2.0, "b")]);
+        store
+            .zadd(
+                b"key2",
+                members(&[(10.0, "b"), (20.0, "c")]),
+                &ZAddOptions::default(),
+            )
+            .unwrap();
+
+        // Act
+        let card = store
+            .zstore(b"dest", vec![b"key1", b"key2"], None, Aggregate::Min, true)
+            .unwrap();
+        let card_missing = store
+            .zstore(
+                b"dest2",
+                vec![b"key1", b"missing"],
+                None,
+                Aggregate::Sum,
+                true,
+            )
+            .unwrap();
+
+        // Assert
+        assert_eq!(card, 1);
+        assert_eq!(store.zscore(b"dest", b"b").unwrap(), Some(2.0));
+        assert_eq!(card_missing, 0);
+        assert_eq!(store.exists(vec![b"dest2"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_zstore_with_wrong_number_of_weights_fails() {
+        // Arrange
+        let mut store = store_with("key1", &[(1.0, "a")]);
+
+        // Act
+        let result = store.zstore(
+            b"dest",
+            vec![b"key1"],
+            Some(vec![1.0, 2.0]),
+            Aggregate::Sum,
+            false,
+        );
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_handle_command_range_with_scores() {
+        // Arrange
+        let mut store = store_with("key", &[(1.0, "a"), (2.5, "b")]);
+        let command = Command::SortedSet(SortedSetCommand::Range {
+            key: "key".into(),
+            by: ZRangeBy::Index { start: 0, stop: -1 },
+            rev: false,
+            limit: None,
+            with_scores: true,
+        });
+
+        // Act
+        let result = store.handle_command(command).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            CommandResult::Array(vec![
+                CommandResult::String("a".into()),
+                CommandResult::String("1".into()),
+                CommandResult::String("b".into()),
+                CommandResult::String("2.5".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_handle_command_exists_and_delete() {
+        // Arrange
+        let mut store = store_with("key1", &[(1.0, "a")]);
+        let exists_command = Command::Generic(GenericCommand::Exists {
+            keys: vec!["key1".into(), "key2".into()],
+        });
+        let delete_command = Command::Generic(GenericCommand::Delete {
+            keys: vec!["key1".into(), "key2".into()],
+        });
+
+        // Act
+        let exists_result = store.handle_command(exists_command).unwrap();
+        let delete_result = store.handle_command(delete_command).unwrap();
+
+        // Assert
+        assert_eq!(exists_result, CommandResult::Int(1));
+        assert_eq!(delete_result, CommandResult::Int(1));
+        assert_eq!(store.zscore(b"key1", b"a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_packed_and_skip_list_keep_the_same_order() {
+        // Arrange
+        let mut packed = SortedSet::default();
+        let mut skip_list = SortedSet::default();
+        skip_list.insert(Bytes::from(vec![b'x'; 65]), 0.0);
+        skip_list.remove(&[b'x'; 65]);
+        let pairs = [(2.0, "c"), (1.0, "b"), (1.0, "a"), (-0.0, "z"), (3.0, "b")];
+
+        // Act
+        for (score, member) in pairs {
+            packed.insert(member.into(), score);
+            skip_list.insert(member.into(), score);
+        }
+
+        // Assert
+        assert_eq!(packed.encoding(), "listpack");
+        assert_eq!(skip_list.encoding(), "skiplist");
+        for set in [&packed, &skip_list] {
+            assert_eq!(
+                set.iter().collect::<Vec<_>>(),
+                vec![(&b"z"[..], 0.0), (b"a", 1.0), (b"c", 2.0), (b"b", 3.0)]
+            );
+            assert_eq!(set.rank(b"c"), Some(2));
+            assert_eq!(set.score(b"b"), Some(3.0));
+        }
+    }
+}
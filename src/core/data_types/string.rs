@@ -2,53 +2,134 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
+use crate::core::bytes::{parse_number, Bytes};
 use crate::core::commands::{
-    generic::GenericCommand, string::StringCommand, Command, CommandResult,
+    string::{GetExExpiration, SetExpiration, StringCommand},
+    Command, CommandResult,
 };
-use crate::core::data_types::data_type::{DataType, GenericOperations};
-use std::collections::HashMap;
+use crate::core::data_types::{
+    data_type::{DataType, DataTypeError, GenericOperations, KeyspaceAccess},
+    encoding::canonical_int,
+    keyspace::{unix_time_millis, Keyspace, Value},
+};
+use std::borrow::Cow;
 use std::error::Error;
 
+type OptionalValues<'a> = Vec<Option<Cow<'a, [u8]>>>;
+
+/// Strings up to this length are stored inline, without an allocation of their own.
+pub const MAX_INLINE_STRING_LEN: usize = 22;
+
+/// A string in the most compact of its encodings, as reported by OBJECT ENCODING: `int` for an
+/// integer in canonical form, `embstr` for a string short enough to be stored inline and `raw`
+/// for the rest. Every write picks the encoding again, so a string shrinking back is compact again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StringValue {
+    Int(i64),
+    Inline {
+        len: u8,
+        bytes: [u8; MAX_INLINE_STRING_LEN],
+    },
+    Raw(Bytes),
+}
+
+impl StringValue {
+    pub fn new(value: &[u8]) -> Self {
+        if let Some(value) = canonical_int(value) {
+            return StringValue::Int(value);
+        }
+        if value.len() <= MAX_INLINE_STRING_LEN {
+            let mut bytes = [0; MAX_INLINE_STRING_LEN];
+            bytes[..value.len()].copy_from_slice(value);
+            return StringValue::Inline {
+                len: value.len() as u8,
+                bytes,
+            };
+        }
+        StringValue::Raw(Bytes::from(value))
+    }
+
+    /// Integers are formatted on every read, the other encodings are borrowed.
+    pub fn as_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            StringValue::Int(value) => Cow::Owned(value.to_string().into_bytes()),
+            StringValue::Inline { len, bytes } => Cow::Borrowed(&bytes[..*len as usize]),
+            StringValue::Raw(bytes) => Cow::Borrowed(bytes),
+        }
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        match self {
+            StringValue::Raw(bytes) => bytes,
+            other => Bytes::from(other.as_bytes().as_ref()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            StringValue::Int(value) => value.to_string().len(),
+            StringValue::Inline { len, .. } => *len as usize,
+            StringValue::Raw(bytes) => bytes.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            StringValue::Int(_) => "int",
+            StringValue::Inline { .. } => "embstr",
+            StringValue::Raw(_) => "raw",
+        }
+    }
+}
+
+/// The empty string.
+impl Default for StringValue {
+    fn default() -> Self {
+        StringValue::Inline {
+            len: 0,
+            bytes: [0; MAX_INLINE_STRING_LEN],
+        }
+    }
+}
+
+/// Keeps the allocation of a long string rather than copying it.
+impl From<Bytes> for StringValue {
+    fn from(value: Bytes) -> Self {
+        // Too long to be inlined, or to be an integer.
+        if value.len() > MAX_INLINE_STRING_LEN {
+            StringValue::Raw(value)
+        } else {
+            StringValue::new(&value)
+        }
+    }
+}
+
+impl From<&str> for StringValue {
+    fn from(value: &str) -> Self {
+        StringValue::new(value.as_bytes())
+    }
+}
+
 #[derive(Debug)]
 pub struct StringStore {
-    data: HashMap<String, String>,
+    data: Keyspace,
 }
 
 impl DataType for StringStore {
     fn new() -> Result<Self, Box<dyn Error>> {
         Ok(StringStore {
-            data: HashMap::new(),
+            data: Keyspace::new(),
         })
     }
 
     fn handle_command(&mut self, command: Command) -> Result<CommandResult, Box<dyn Error>> {
         match command {
-            Command::String(cmd) => match cmd {
-                StringCommand::Set { key, value } => {
-                    self.set(&key, &value)?;
-                    Ok(CommandResult::String("OK".to_string()))
-                }
-                StringCommand::Get { key } => {
-                    let result = self.get(&key)?;
-                    Ok(result.map_or(CommandResult::Nil, |s| CommandResult::String(s.to_string())))
-                }
-                StringCommand::Append { key, value } => {
-                    let len = self.append(&key, &value)?;
-                    Ok(CommandResult::Int(len))
-                }
-            },
-            Command::Generic(cmd) => match cmd {
-                GenericCommand::Exists { keys } => {
-                    let keys_ref: Vec<&str> = keys.iter().map(|s| s.as_str()).collect();
-                    let result = self.exists(keys_ref)?;
-                    Ok(CommandResult::Int(result))
-                }
-                GenericCommand::Delete { keys } => {
-                    let keys_ref: Vec<&str> = keys.iter().map(|s| s.as_str()).collect();
-                    let result = self.delete(keys_ref)?;
-                    Ok(CommandResult::Int(result))
-                }
-            },
+            Command::String(cmd) => self.handle_string_command(cmd),
+            Command::Generic(cmd) => self.handle_generic_command(cmd),
             #[allow(unreachable_patterns)]
             _ => Err(Box::new(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
@@ -58,46 +139,350 @@ impl DataType for StringStore {
     }
 }
 
-impl StringStore {
-    fn set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
-        self.data.insert(key.to_string(), value.to_string());
-        Ok(())
+impl KeyspaceAccess for StringStore {
+    fn keyspace(&self) -> &Keyspace {
+        &self.data
     }
 
-    fn get(&self, key: &str) -> Result<Option<&str>, Box<dyn Error>> {
-        Ok(self.data.get(key).map(|s| s.as_str()))
+    fn keyspace_mut(&mut self) -> &mut Keyspace {
+        &mut self.data
     }
+}
 
-    fn append(&mut self, key: &str, value: &str) -> Result<u64, Box<dyn Error>> {
-        let entry = self.data.entry(key.to_string()).or_default();
-        entry.push_str(value);
-        Ok(entry.len() as u64)
+impl StringOperations for StringStore {}
+
+/// The largest length a string can grow to with SETRANGE, the same 512 MB limit Redis has.
+const MAX_STRING_LENGTH: u64 = 512 * 1024 * 1024;
+
+fn to_array(values: OptionalValues<'_>) -> CommandResult {
+    CommandResult::Array(
+        values
+            .into_iter()
+            .map(|v| {
+                v.map_or(CommandResult::Nil, |s| {
+                    CommandResult::String(s.as_ref().into())
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Converts an expiration option into a deadline in milliseconds since the Unix epoch.
+/// Returns None for zero and overflowing timeouts.
+fn deadline_of(expiration: SetExpiration, now: u64) -> Option<u64> {
+    match expiration {
+        SetExpiration::Ex(0)
+        | SetExpiration::Px(0)
+        | SetExpiration::ExAt(0)
+        | SetExpiration::PxAt(0)
+        | SetExpiration::KeepTtl => None,
+        SetExpiration::Ex(seconds) => seconds
+            .checked_mul(1000)
+            .and_then(|milliseconds| now.checked_add(milliseconds)),
+        SetExpiration::Px(milliseconds) => now.checked_add(milliseconds),
+        SetExpiration::ExAt(timestamp) => timestamp.checked_mul(1000),
+        SetExpiration::PxAt(timestamp) => Some(timestamp),
     }
 }
 
-impl GenericOperations for StringStore {
-    fn exists(&self, keys: Vec<&str>) -> Result<u64, Box<dyn Error>> {
-        let count = keys
+/// Operations of the String data type.
+pub trait StringOperations: KeyspaceAccess {
+    /// Handles a string command and returns the result.
+    fn handle_string_command(
+        &mut self,
+        command: StringCommand,
+    ) -> Result<CommandResult, Box<dyn Error>> {
+        match command {
+            StringCommand::Set {
+                key,
+                value,
+                expiration,
+            } => {
+                self.set_with_expiration(&key, &value, expiration)?;
+                Ok(CommandResult::String("OK".into()))
+            }
+            StringCommand::Get { key } => {
+                let result = self.get(&key)?;
+                Ok(result.map_or(CommandResult::Nil, |s| {
+                    CommandResult::String(s.as_ref().into())
+                }))
+            }
+            StringCommand::Append { key, value } => {
+                let len = self.append(&key, &value)?;
+                Ok(CommandResult::Int(len as i64))
+            }
+            StringCommand::Incr { key } => Ok(CommandResult::Int(self.incr_by(&key, 1)?)),
+            StringCommand::Decr { key } => Ok(CommandResult::Int(self.incr_by(&key, -1)?)),
+            StringCommand::IncrBy { key, increment } => {
+                Ok(CommandResult::Int(self.incr_by(&key, increment)?))
+            }
+            StringCommand::DecrBy { key, decrement } => {
+                let increment = decrement.checked_neg().ok_or(DataTypeError::Overflow)?;
+                Ok(CommandResult::Int(self.incr_by(&key, increment)?))
+            }
+            StringCommand::IncrByFloat { key, increment } => {
+                let value = self.incr_by_float(&key, increment)?;
+                Ok(CommandResult::String(value.to_string().into()))
+            }
+            StringCommand::MSet { pairs } => {
+                self.mset(pairs)?;
+                Ok(CommandResult::String("OK".into()))
+            }
+            StringCommand::MGet { keys } => {
+                let keys_ref: Vec<&[u8]> = keys.iter().map(|s| s.as_slice()).collect();
+                Ok(to_array(self.mget(keys_ref)?))
+            }
+            StringCommand::MSetNx { pairs } => Ok(CommandResult::Bool(self.msetnx(pairs)?)),
+            StringCommand::SetNx { key, value } => {
+                Ok(CommandResult::Bool(self.setnx(&key, &value)?))
+            }
+            StringCommand::GetSet { key, value } => {
+                let previous = self.getset(&key, &value)?;
+                Ok(previous.map_or(CommandResult::Nil, CommandResult::String))
+            }
+            StringCommand::GetDel { key } => {
+                let value = self.getdel(&key)?;
+                Ok(value.map_or(CommandResult::Nil, CommandResult::String))
+            }
+            StringCommand::GetEx { key, expiration } => {
+                let value = self.getex(&key, expiration)?;
+                Ok(value.map_or(CommandResult::Nil, CommandResult::String))
+            }
+            StringCommand::StrLen { key } => Ok(CommandResult::Int(self.strlen(&key)? as i64)),
+            StringCommand::GetRange { key, start, end } => {
+                Ok(CommandResult::String(self.getrange(&key, start, end)?))
+            }
+            StringCommand::SetRange { key, offset, value } => {
+                let len = self.setrange(&key, offset, &value)?;
+                Ok(CommandResult::Int(len as i64))
+            }
+        }
+    }
+
+    /// SET overwrites the key regardless of the type of the value it holds.
+    fn set(&mut self, key: &[u8], value: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.keyspace_mut().insert(key, StringValue::new(value));
+        Ok(())
+    }
+
+    /// SET with an expiration option. KEEPTTL retains the deadline the key already has.
+    fn set_with_expiration(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        expiration: Option<SetExpiration>,
+    ) -> Result<(), Box<dyn Error>> {
+        let deadline = match expiration {
+            None => None,
+            Some(SetExpiration::KeepTtl) => self.keyspace().expiry(key),
+            Some(expiration) => Some(
+                deadline_of(expiration, unix_time_millis())
+                    .ok_or(DataTypeError::InvalidExpireTime)?,
+            ),
+        };
+        self.set(key, value)?;
+        if let Some(deadline) = deadline {
+            self.keyspace_mut().set_expiry(key, deadline);
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Box<dyn Error>> {
+        Ok(self
+            .keyspace()
+            .get::<StringValue>(key)?
+            .map(StringValue::as_bytes))
+    }
+
+    fn append(&mut self, key: &[u8], value: &[u8]) -> Result<u64, Box<dyn Error>> {
+        let entry = self
+            .keyspace_mut()
+            .get_or_insert_default::<StringValue>(key)?;
+        let mut bytes = std::mem::take(entry).into_bytes();
+        bytes.as_mut_vec().extend_from_slice(value);
+        let len = bytes.len() as u64;
+        *entry = StringValue::from(bytes);
+        Ok(len)
+    }
+
+    /// Increments the integer stored at the key, a missing key is treated as 0.
+    /// The value stays a string and keeps its deadline.
+    fn incr_by(&mut self, key: &[u8], increment: i64) -> Result<i64, Box<dyn Error>> {
+        let current = match self.keyspace().get::<StringValue>(key)? {
+            Some(StringValue::Int(value)) => *value,
+            Some(value) => {
+                parse_number::<i64>(&value.as_bytes()).ok_or(DataTypeError::NotAnInteger)?
+            }
+            None => 0,
+        };
+        let new_value = current
+            .checked_add(increment)
+            .ok_or(DataTypeError::Overflow)?;
+        *self
+            .keyspace_mut()
+            .get_or_insert_default::<StringValue>(key)? = StringValue::Int(new_value);
+        Ok(new_value)
+    }
+
+    /// Increments the float stored at the key, a missing key is treated as 0.
+    /// A result that is not a finite number is rejected.
+    fn incr_by_float(&mut self, key: &[u8], increment: f64) -> Result<f64, Box<dyn Error>> {
+        let current = match self.get(key)? {
+            Some(value) => parse_number::<f64>(&value)
+                .filter(|value| value.is_finite())
+                .ok_or(DataTypeError::NotAFloat)?,
+            None => 0.0,
+        };
+        let new_value = current + increment;
+        if !new_value.is_finite() {
+            return Err(Box::new(DataTypeError::NotAFloat));
+        }
+        *self
+            .keyspace_mut()
+            .get_or_insert_default::<StringValue>(key)? =
+            StringValue::new(new_value.to_string().as_bytes());
+        Ok(new_value)
+    }
+
+    /// Sets all the pairs, the command is applied as a whole and replicated as one unit.
+    fn mset(&mut self, pairs: Vec<(Bytes, Bytes)>) -> Result<(), Box<dyn Error>> {
+        for (key, value) in pairs {
+            self.set(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the values of the keys, keys that do not hold a string are reported as missing.
+    fn mget(&self, keys: Vec<&[u8]>) -> Result<OptionalValues<'_>, Box<dyn Error>> {
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                self.keyspace()
+                    .get::<StringValue>(key)
+                    .ok()
+                    .flatten()
+                    .map(StringValue::as_bytes)
+            })
+            .collect())
+    }
+
+    /// Sets all the pairs if none of the keys exists, returns false otherwise.
+    fn msetnx(&mut self, pairs: Vec<(Bytes, Bytes)>) -> Result<bool, Box<dyn Error>> {
+        if pairs
             .iter()
-            .filter(|&&key| self.data.contains_key(key))
-            .count() as u64;
-        Ok(count)
+            .any(|(key, _)| self.keyspace().contains_key(key))
+        {
+            return Ok(false);
+        }
+        self.mset(pairs)?;
+        Ok(true)
+    }
+
+    fn setnx(&mut self, key: &[u8], value: &[u8]) -> Result<bool, Box<dyn Error>> {
+        if self.keyspace().contains_key(key) {
+            return Ok(false);
+        }
+        self.set(key, value)?;
+        Ok(true)
+    }
+
+    /// Sets the value and returns the previous one, the deadline of the key is discarded.
+    fn getset(&mut self, key: &[u8], value: &[u8]) -> Result<Option<Bytes>, Box<dyn Error>> {
+        let previous = self.get(key)?.map(|value| Bytes::from(value.as_ref()));
+        self.set(key, value)?;
+        Ok(previous)
+    }
+
+    fn getdel(&mut self, key: &[u8]) -> Result<Option<Bytes>, Box<dyn Error>> {
+        if self.get(key)?.is_none() {
+            return Ok(None);
+        }
+        Ok(match self.keyspace_mut().remove(key) {
+            Some(Value::String(value)) => Some(value.into_bytes()),
+            _ => None,
+        })
+    }
+
+    /// Returns the value and optionally changes the deadline of the key.
+    fn getex(
+        &mut self,
+        key: &[u8],
+        expiration: Option<GetExExpiration>,
+    ) -> Result<Option<Bytes>, Box<dyn Error>> {
+        let Some(value) = self.get(key)?.map(|value| Bytes::from(value.as_ref())) else {
+            return Ok(None);
+        };
+        let deadline = match expiration {
+            None => return Ok(Some(value)),
+            Some(GetExExpiration::Persist) => {
+                self.keyspace_mut().clear_expiry(key);
+                return Ok(Some(value));
+            }
+            Some(GetExExpiration::Ex(seconds)) => SetExpiration::Ex(seconds),
+            Some(GetExExpiration::Px(milliseconds)) => SetExpiration::Px(milliseconds),
+            Some(GetExExpiration::ExAt(timestamp)) => SetExpiration::ExAt(timestamp),
+            Some(GetExExpiration::PxAt(timestamp)) => SetExpiration::PxAt(timestamp),
+        };
+        let deadline =
+            deadline_of(deadline, unix_time_millis()).ok_or(DataTypeError::InvalidExpireTime)?;
+        self.keyspace_mut().set_expiry(key, deadline);
+        Ok(Some(value))
     }
 
-    fn delete(&mut self, keys: Vec<&str>) -> Result<u64, Box<dyn Error>> {
-        let mut count = 0;
-        for key in keys {
-            if self.data.remove(key).is_some() {
-                count += 1;
+    fn strlen(&self, key: &[u8]) -> Result<u64, Box<dyn Error>> {
+        Ok(self.get(key)?.map_or(0, |value| value.len() as u64))
+    }
+
+    /// Returns the bytes between the inclusive offsets, negative offsets count from the end.
+    fn getrange(&self, key: &[u8], start: i64, end: i64) -> Result<Bytes, Box<dyn Error>> {
+        let Some(value) = self.get(key)? else {
+            return Ok(Bytes::new());
+        };
+        let len = value.len() as i64;
+        let normalize = |index: i64| {
+            if index < 0 {
+                (len + index).max(0)
+            } else {
+                index
             }
+        };
+        let start = normalize(start);
+        let end = normalize(end).min(len - 1);
+        if start > end {
+            return Ok(Bytes::new());
+        }
+        Ok(Bytes::from(&value[start as usize..=end as usize]))
+    }
+
+    /// Overwrites the string starting at the offset and returns its new length.
+    /// A string shorter than the offset is padded with zero bytes.
+    fn setrange(&mut self, key: &[u8], offset: u64, value: &[u8]) -> Result<u64, Box<dyn Error>> {
+        if value.is_empty() {
+            return self.strlen(key);
+        }
+        if offset.saturating_add(value.len() as u64) > MAX_STRING_LENGTH {
+            return Err(Box::new(DataTypeError::IndexOutOfRange));
+        }
+        let entry = self
+            .keyspace_mut()
+            .get_or_insert_default::<StringValue>(key)?;
+        let mut current = std::mem::take(entry).into_bytes();
+        let offset = offset as usize;
+        if current.len() < offset + value.len() {
+            current.as_mut_vec().resize(offset + value.len(), 0);
         }
-        Ok(count)
+        current[offset..offset + value.len()].copy_from_slice(value);
+        let len = current.len() as u64;
+        *entry = StringValue::from(current);
+        Ok(len)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::commands::{generic::GenericCommand, sorted_set::LexBound};
+    use crate::core::data_types::{data_type::KeyValues, list::ListValue};
 
     #[test]
     fn test_new() {
@@ -114,7 +499,7 @@ mod tests {
         let mut store = StringStore::new().unwrap();
 
         // Act
-        let result = store.set("key", "value");
+        let result = store.set(b"key", b"value");
 
         // Assert
         assert!(result.is_ok());
@@ -126,7 +511,7 @@ mod tests {
         let store = StringStore::new().unwrap();
 
         // Act
-        let result = store.get("key");
+        let result = store.get(b"key");
 
         // Assert
         assert_eq!(result.unwrap(), None);
@@ -136,28 +521,28 @@ mod tests {
     fn test_get_operation_with_existent_key() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.set("key", "value").unwrap();
+        store.set(b"key", b"value").unwrap();
 
         // Act
-        let result = store.get("key");
+        let result = store.get(b"key");
 
         // Assert
-        assert_eq!(result.unwrap(), Some("value"));
+        assert_eq!(result.unwrap().as_deref(), Some(&b"value"[..]));
     }
 
     #[test]
     fn test_get_operations_with_same_existent_keys() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.set("key", "value").unwrap();
+        store.set(b"key", b"value").unwrap();
 
         // Act
-        let result_1 = store.get("key");
-        let result_2 = store.get("key");
+        let result_1 = store.get(b"key");
+        let result_2 = store.get(b"key");
 
         // Assert
-        assert_eq!(result_1.unwrap(), Some("value"));
-        assert_eq!(result_2.unwrap(), Some("value"));
+        assert_eq!(result_1.unwrap().as_deref(), Some(&b"value"[..]));
+        assert_eq!(result_2.unwrap().as_deref(), Some(&b"value"[..]));
     }
 
     #[test]
@@ -169,13 +554,13 @@ mod tests {
         let value_2 = "value2";
 
         // Act & Assert
-        store.set(key, value_1).unwrap();
-        let result_1 = store.get(key);
-        assert_eq!(result_1.unwrap(), Some(value_1));
+        store.set(key.as_bytes(), value_1.as_bytes()).unwrap();
+        let result_1 = store.get(key.as_bytes());
+        assert_eq!(result_1.unwrap().as_deref(), Some(value_1.as_bytes()));
 
-        store.set(key, value_2).unwrap();
-        let result_2 = store.get(key);
-        assert_eq!(result_2.unwrap(), Some(value_2));
+        store.set(key.as_bytes(), value_2.as_bytes()).unwrap();
+        let result_2 = store.get(key.as_bytes());
+        assert_eq!(result_2.unwrap().as_deref(), Some(value_2.as_bytes()));
     }
 
     #[test]
@@ -188,27 +573,27 @@ mod tests {
         let value_2 = "value2";
 
         // Act
-        store.set(key_1, value_1).unwrap();
-        store.set(key_2, value_2).unwrap();
-        let result_1 = store.get(key_1);
-        let result_2 = store.get(key_2);
+        store.set(key_1.as_bytes(), value_1.as_bytes()).unwrap();
+        store.set(key_2.as_bytes(), value_2.as_bytes()).unwrap();
+        let result_1 = store.get(key_1.as_bytes());
+        let result_2 = store.get(key_2.as_bytes());
 
         // Assert
-        assert_eq!(result_1.unwrap(), Some(value_1));
-        assert_eq!(result_2.unwrap(), Some(value_2));
+        assert_eq!(result_1.unwrap().as_deref(), Some(value_1.as_bytes()));
+        assert_eq!(result_2.unwrap().as_deref(), Some(value_2.as_bytes()));
     }
 
     #[test]
     fn test_append_command_creates_new_key_on_non_existent_key() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.append("key", "value").unwrap();
+        store.append(b"key", b"value").unwrap();
 
         // Act
-        let result = store.get("key");
+        let result = store.get(b"key");
 
         // Assert
-        assert_eq!(result.unwrap(), Some("value"));
+        assert_eq!(result.unwrap().as_deref(), Some(&b"value"[..]));
     }
 
     #[test]
@@ -220,15 +605,15 @@ mod tests {
         let value_2 = "value2";
 
         // Act & Assert
-        store.append(key, value_1).unwrap();
-        let result_1 = store.get(key);
-        assert_eq!(result_1.unwrap(), Some(value_1));
+        store.append(key.as_bytes(), value_1.as_bytes()).unwrap();
+        let result_1 = store.get(key.as_bytes());
+        assert_eq!(result_1.unwrap().as_deref(), Some(value_1.as_bytes()));
 
-        store.append(key, value_2).unwrap();
-        let result_2 = store.get(key);
+        store.append(key.as_bytes(), value_2.as_bytes()).unwrap();
+        let result_2 = store.get(key.as_bytes());
         assert_eq!(
-            result_2.unwrap(),
-            Some(format!("{}{}", value_1, value_2).as_str())
+            result_2.unwrap().as_deref(),
+            Some(format!("{}{}", value_1, value_2).as_bytes())
         );
     }
 
@@ -238,7 +623,7 @@ mod tests {
         let store = StringStore::new().unwrap();
 
         // Act
-        let result = store.exists(vec!["key"]).unwrap();
+        let result = store.exists(vec![b"key"]).unwrap();
 
         // Assert
         assert_eq!(result, 0);
@@ -248,10 +633,10 @@ mod tests {
     fn test_exists_command_for_existent_key() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.set("key", "value").unwrap();
+        store.set(b"key", b"value").unwrap();
 
         // Act
-        let result = store.exists(vec!["key"]).unwrap();
+        let result = store.exists(vec![b"key"]).unwrap();
 
         // Assert
         assert_eq!(result, 1);
@@ -261,20 +646,22 @@ mod tests {
     fn test_exists_command_for_ranges_of_existent_keys() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.set("key1", "value1").unwrap();
-        store.set("key2", "value2").unwrap();
-        store.set("key3", "value3").unwrap();
-        store.set("key4", "value4").unwrap();
-        store.set("key5", "value5").unwrap();
+        store.set(b"key1", b"value1").unwrap();
+        store.set(b"key2", b"value2").unwrap();
+        store.set(b"key3", b"value3").unwrap();
+        store.set(b"key4", b"value4").unwrap();
+        store.set(b"key5", b"value5").unwrap();
 
         // Act
         let result_1 = store
-            .exists(vec!["key1", "key2", "key3", "key4", "key5"])
+            .exists(vec![b"key1", b"key2", b"key3", b"key4", b"key5"])
             .unwrap();
-        let result_2 = store.exists(vec!["key1", "key2", "key3", "key4"]).unwrap();
-        let result_3 = store.exists(vec!["key1", "key2", "key3"]).unwrap();
-        let result_4 = store.exists(vec!["key1", "key2"]).unwrap();
-        let result_5 = store.exists(vec!["key1"]).unwrap();
+        let result_2 = store
+            .exists(vec![b"key1", b"key2", b"key3", b"key4"])
+            .unwrap();
+        let result_3 = store.exists(vec![b"key1", b"key2", b"key3"]).unwrap();
+        let result_4 = store.exists(vec![b"key1", b"key2"]).unwrap();
+        let result_5 = store.exists(vec![b"key1"]).unwrap();
         let result_6 = store.exists(vec![]).unwrap();
 
         // Assert
@@ -290,16 +677,18 @@ mod tests {
     fn test_exists_command_for_ranges_of_existent_and_non_existent_keys() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.set("key1", "value1").unwrap();
-        store.set("key2", "value2").unwrap();
-        store.set("key3", "value3").unwrap();
+        store.set(b"key1", b"value1").unwrap();
+        store.set(b"key2", b"value2").unwrap();
+        store.set(b"key3", b"value3").unwrap();
 
         // Act
-        let result_1 = store.exists(vec!["key1", "key2"]).unwrap();
-        let result_2 = store.exists(vec!["key1", "key2", "key3", "key4"]).unwrap();
-        let result_3 = store.exists(vec!["key3", "key4", "key5"]).unwrap();
-        let result_4 = store.exists(vec!["key4", "key5"]).unwrap();
-        let result_5 = store.exists(vec!["key1", "key5"]).unwrap();
+        let result_1 = store.exists(vec![b"key1", b"key2"]).unwrap();
+        let result_2 = store
+            .exists(vec![b"key1", b"key2", b"key3", b"key4"])
+            .unwrap();
+        let result_3 = store.exists(vec![b"key3", b"key4", b"key5"]).unwrap();
+        let result_4 = store.exists(vec![b"key4", b"key5"]).unwrap();
+        let result_5 = store.exists(vec![b"key1", b"key5"]).unwrap();
 
         // Assert
         assert_eq!(result_1, 2);
@@ -315,7 +704,7 @@ mod tests {
         let mut store = StringStore::new().unwrap();
 
         // Act
-        let result = store.delete(vec!["key"]).unwrap();
+        let result = store.delete(vec![b"key"]).unwrap();
 
         // Assert
         assert_eq!(result, 0);
@@ -325,10 +714,10 @@ mod tests {
     fn test_delete_command_for_existent_key() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.set("key", "value").unwrap();
+        store.set(b"key", b"value").unwrap();
 
         // Act
-        let result = store.delete(vec!["key"]).unwrap();
+        let result = store.delete(vec![b"key"]).unwrap();
 
         // Assert
         assert_eq!(result, 1);
@@ -338,17 +727,17 @@ mod tests {
     fn test_delete_command_for_ranges_of_existent_keys() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.set("key1", "value1").unwrap();
-        store.set("key2", "value2").unwrap();
-        store.set("key3", "value3").unwrap();
-        store.set("key4", "value4").unwrap();
-        store.set("key5", "value5").unwrap();
-        store.set("key6", "value6").unwrap();
+        store.set(b"key1", b"value1").unwrap();
+        store.set(b"key2", b"value2").unwrap();
+        store.set(b"key3", b"value3").unwrap();
+        store.set(b"key4", b"value4").unwrap();
+        store.set(b"key5", b"value5").unwrap();
+        store.set(b"key6", b"value6").unwrap();
 
         // Act
-        let result_1 = store.delete(vec!["key1", "key2", "key3"]).unwrap();
-        let result_2 = store.delete(vec!["key4", "key5"]).unwrap();
-        let result_3 = store.delete(vec!["key6"]).unwrap();
+        let result_1 = store.delete(vec![b"key1", b"key2", b"key3"]).unwrap();
+        let result_2 = store.delete(vec![b"key4", b"key5"]).unwrap();
+        let result_3 = store.delete(vec![b"key6"]).unwrap();
 
         // Assert
         assert_eq!(result_1, 3);
@@ -360,15 +749,17 @@ mod tests {
     fn test_delete_command_for_ranges_of_existent_and_non_existent_keys() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        store.set("key1", "value1").unwrap();
-        store.set("key2", "value2").unwrap();
-        store.set("key3", "value3").unwrap();
+        store.set(b"key1", b"value1").unwrap();
+        store.set(b"key2", b"value2").unwrap();
+        store.set(b"key3", b"value3").unwrap();
 
         // Act
-        let result_1 = store.delete(vec!["key1", "key2"]).unwrap();
-        let result_2 = store.delete(vec!["key1", "key2", "key3", "key4"]).unwrap();
-        let result_3 = store.delete(vec!["key3", "key4", "key5"]).unwrap();
-        let result_4 = store.delete(vec!["key1", "key5"]).unwrap();
+        let result_1 = store.delete(vec![b"key1", b"key2"]).unwrap();
+        let result_2 = store
+            .delete(vec![b"key1", b"key2", b"key3", b"key4"])
+            .unwrap();
+        let result_3 = store.delete(vec![b"key3", b"key4", b"key5"]).unwrap();
+        let result_4 = store.delete(vec![b"key1", b"key5"]).unwrap();
 
         // Assert
         assert_eq!(result_1, 2);
@@ -383,42 +774,39 @@ mod tests {
         // Arrange
         let mut store = StringStore::new().unwrap();
         let command = Command::String(StringCommand::Set {
-            key: "key".to_string(),
-            value: "value".to_string(),
+            key: "key".into(),
+            value: "value".into(),
+            expiration: None,
         });
 
         // Act
         let result = store.handle_command(command).unwrap();
-        let get_result = store.get("key").unwrap();
+        let get_result = store.get(b"key").unwrap();
 
         // Assert
-        assert_eq!(result, CommandResult::String("OK".to_string()));
-        assert_eq!(get_result, Some("value"));
+        assert_eq!(result, CommandResult::String("OK".into()));
+        assert_eq!(get_result.as_deref(), Some(&b"value"[..]));
     }
 
     #[test]
     fn test_handle_command_get_with_existent_key() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        let command = Command::String(StringCommand::Get {
-            key: "key".to_string(),
-        });
+        let command = Command::String(StringCommand::Get { key: "key".into() });
 
         // Act
-        store.set("key", "value").unwrap();
+        store.set(b"key", b"value").unwrap();
         let result = store.handle_command(command).unwrap();
 
         // Assert
-        assert_eq!(result, CommandResult::String("value".to_string()));
+        assert_eq!(result, CommandResult::String("value".into()));
     }
 
     #[test]
     fn test_handle_command_get_with_non_existent_key() {
         // Arrange
         let mut store = StringStore::new().unwrap();
-        let command = Command::String(StringCommand::Get {
-            key: "key".to_string(),
-        });
+        let command = Command::String(StringCommand::Get { key: "key".into() });
 
         // Act
         let result = store.handle_command(command).unwrap();
@@ -432,17 +820,17 @@ mod tests {
         // Arrange
         let mut store = StringStore::new().unwrap();
         let command = Command::String(StringCommand::Append {
-            key: "key".to_string(),
-            value: "value".to_string(),
+            key: "key".into(),
+            value: "value".into(),
         });
 
         // Act
         let result = store.handle_command(command).unwrap();
-        let get_result = store.get("key").unwrap();
+        let get_result = store.get(b"key").unwrap();
 
         // Assert
-        assert_eq!(result, CommandResult::Int("value".len() as u64));
-        assert_eq!(get_result, Some("value"));
+        assert_eq!(result, CommandResult::Int("value".len() as i64));
+        assert_eq!(get_result.as_deref(), Some(&b"value"[..]));
     }
 
     #[test]
@@ -450,18 +838,18 @@ mod tests {
         // Arrange
         let mut store = StringStore::new().unwrap();
         let command = Command::String(StringCommand::Append {
-            key: "key".to_string(),
-            value: "value2".to_string(),
+            key: "key".into(),
+            value: "value2".into(),
         });
 
         // Act
-        store.set("key", "value1").unwrap();
+        store.set(b"key", b"value1").unwrap();
         let result = store.handle_command(command).unwrap();
-        let get_result = store.get("key").unwrap();
+        let get_result = store.get(b"key").unwrap();
 
         // Assert
-        assert_eq!(result, CommandResult::Int("value1value2".len() as u64));
-        assert_eq!(get_result, Some("value1value2"));
+        assert_eq!(result, CommandResult::Int("value1value2".len() as i64));
+        assert_eq!(get_result.as_deref(), Some(&b"value1value2"[..]));
     }
 
     #[test]
@@ -469,17 +857,12 @@ mod tests {
         // Arrange
         let mut store = StringStore::new().unwrap();
         let command = Command::Generic(GenericCommand::Exists {
-            keys: vec![
-                "key1".to_string(),
-                "key2".to_string(),
-                "key3".to_string(),
-                "key4".to_string(),
-            ],
+            keys: vec!["key1".into(), "key2".into(), "key3".into(), "key4".into()],
         });
 
         // Act
-        store.set("key1", "value1").unwrap();
-        store.set("key2", "value2").unwrap();
+        store.set(b"key1", b"value1").unwrap();
+        store.set(b"key2", b"value2").unwrap();
         let result = store.handle_command(command).unwrap();
 
         // Assert
@@ -491,18 +874,384 @@ mod tests {
         // Arrange
         let mut store = StringStore::new().unwrap();
         let command = Command::Generic(GenericCommand::Delete {
-            keys: vec!["key1".to_string(), "key2".to_string(), "key3".to_string()],
+            keys: vec!["key1".into(), "key2".into(), "key3".into()],
         });
 
         // Act
-        store.set("key1", "value1").unwrap();
-        store.set("key2", "value2").unwrap();
+        store.set(b"key1", b"value1").unwrap();
+        store.set(b"key2", b"value2").unwrap();
         let result = store.handle_command(command).unwrap();
 
         // Assert
         assert_eq!(result, CommandResult::Int(2));
-        assert_eq!(store.get("key1").unwrap(), None);
-        assert_eq!(store.get("key2").unwrap(), None);
-        assert_eq!(store.get("key3").unwrap(), None);
+        assert_eq!(store.get(b"key1").unwrap(), None);
+        assert_eq!(store.get(b"key2").unwrap(), None);
+        assert_eq!(store.get(b"key3").unwrap(), None);
+    }
+
+    #[test]
+    fn test_incr_by_creates_missing_key_and_goes_negative() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+
+        // Act
+        let result_1 = store.incr_by(b"counter", 5).unwrap();
+        let result_2 = store.incr_by(b"counter", -8).unwrap();
+
+        // Assert
+        assert_eq!(result_1, 5);
+        assert_eq!(result_2, -3);
+        assert_eq!(store.get(b"counter").unwrap().as_deref(), Some(&b"-3"[..]));
+    }
+
+    #[test]
+    fn test_incr_by_on_non_integer_value_fails() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"key", b"1.5").unwrap();
+
+        // Act
+        let result = store.incr_by(b"key", 1);
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::NotAnInteger)
+        );
+        assert_eq!(store.get(b"key").unwrap().as_deref(), Some(&b"1.5"[..]));
+    }
+
+    #[test]
+    fn test_incr_by_detects_overflow_and_keeps_the_value() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"key", i64::MAX.to_string().as_bytes()).unwrap();
+
+        // Act
+        let result = store.incr_by(b"key", 1);
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::Overflow)
+        );
+        assert_eq!(
+            store.get(b"key").unwrap().as_deref(),
+            Some(i64::MAX.to_string().as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_handle_command_decrby_with_minimal_decrement_overflows() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        let command = Command::String(StringCommand::DecrBy {
+            key: "key".into(),
+            decrement: i64::MIN,
+        });
+
+        // Act
+        let result = store.handle_command(command);
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<DataTypeError>(),
+            Some(&DataTypeError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_handle_command_incr_and_decr() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        let incr = Command::String(StringCommand::Incr { key: "key".into() });
+        let decr = Command::String(StringCommand::Decr { key: "key".into() });
+
+        // Act
+        store.handle_command(incr.clone()).unwrap();
+        store.handle_command(incr).unwrap();
+        let result = store.handle_command(decr).unwrap();
+
+        // Assert
+        assert_eq!(result, CommandResult::Int(1));
+    }
+
+    #[test]
+    fn test_incr_by_float_formats_result_without_trailing_zeros() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"key", b"10.50").unwrap();
+        let command = Command::String(StringCommand::IncrByFloat {
+            key: "key".into(),
+            increment: 0.5,
+        });
+
+        // Act
+        let result = store.handle_command(command).unwrap();
+
+        // Assert
+        assert_eq!(result, CommandResult::String("11".into()));
+        assert_eq!(store.get(b"key").unwrap().as_deref(), Some(&b"11"[..]));
+    }
+
+    #[test]
+    fn test_incr_by_float_rejects_non_numeric_value_and_infinite_result() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"text", b"abc").unwrap();
+        store.set(b"big", f64::MAX.to_string().as_bytes()).unwrap();
+
+        // Act
+        let text = store.incr_by_float(b"text", 1.0);
+        let big = store.incr_by_float(b"big", f64::MAX);
+
+        // Assert
+        assert!(text.is_err());
+        assert!(big.is_err());
+    }
+
+    #[test]
+    fn test_mset_and_mget_with_missing_and_non_string_keys() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store
+            .data
+            .insert(b"list", ListValue::from_iter(["a".into()]));
+        let mset = Command::String(StringCommand::MSet {
+            pairs: vec![
+                ("key1".into(), "value1".into()),
+                ("key2".into(), "value2".into()),
+            ],
+        });
+        let mget = Command::String(StringCommand::MGet {
+            keys: vec![
+                "key1".into(),
+                "missing".into(),
+                "list".into(),
+                "key2".into(),
+            ],
+        });
+
+        // Act
+        store.handle_command(mset).unwrap();
+        let result = store.handle_command(mget).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            CommandResult::Array(vec![
+                CommandResult::String("value1".into()),
+                CommandResult::Nil,
+                CommandResult::Nil,
+                CommandResult::String("value2".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_msetnx_sets_nothing_if_any_key_exists() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"key2", b"old").unwrap();
+        let pairs = vec![
+            ("key1".into(), "value1".into()),
+            ("key2".into(), "value2".into()),
+        ];
+
+        // Act
+        let result = store.msetnx(pairs).unwrap();
+
+        // Assert
+        assert!(!result);
+        assert_eq!(store.get(b"key1").unwrap(), None);
+        assert_eq!(store.get(b"key2").unwrap().as_deref(), Some(&b"old"[..]));
+    }
+
+    #[test]
+    fn test_setnx_sets_only_missing_key() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+
+        // Act
+        let result_1 = store.setnx(b"key", b"value1").unwrap();
+        let result_2 = store.setnx(b"key", b"value2").unwrap();
+
+        // Assert
+        assert!(result_1);
+        assert!(!result_2);
+        assert_eq!(store.get(b"key").unwrap().as_deref(), Some(&b"value1"[..]));
+    }
+
+    #[test]
+    fn test_getset_and_getdel_return_previous_value() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"key", b"value1").unwrap();
+
+        // Act
+        let getset = store.getset(b"key", b"value2").unwrap();
+        let getdel = store.getdel(b"key").unwrap();
+        let missing = store.getdel(b"key").unwrap();
+
+        // Assert
+        assert_eq!(getset, Some("value1".into()));
+        assert_eq!(getdel, Some("value2".into()));
+        assert_eq!(missing, None);
+        assert_eq!(store.exists(vec![b"key"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_getex_sets_and_removes_deadline() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"key", b"value").unwrap();
+
+        // Act
+        let with_ex = store.getex(b"key", Some(GetExExpiration::Ex(100))).unwrap();
+        let deadline = store.data.expiry(b"key");
+        let with_persist = store.getex(b"key", Some(GetExExpiration::Persist)).unwrap();
+
+        // Assert
+        assert_eq!(with_ex, Some("value".into()));
+        assert!(deadline.is_some());
+        assert_eq!(with_persist, Some("value".into()));
+        assert_eq!(store.data.expiry(b"key"), None);
+    }
+
+    #[test]
+    fn test_strlen_and_getrange_with_negative_offsets() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"key", b"This is a string").unwrap();
+
+        // Act & Assert
+        assert_eq!(store.strlen(b"key").unwrap(), 16);
+        assert_eq!(store.strlen(b"missing").unwrap(), 0);
+        assert_eq!(store.getrange(b"key", 0, 3).unwrap(), "This");
+        assert_eq!(store.getrange(b"key", -3, -1).unwrap(), "ing");
+        assert_eq!(store.getrange(b"key", 0, -1).unwrap(), "This is a string");
+        assert_eq!(store.getrange(b"key", 10, 100).unwrap(), "string");
+        assert_eq!(store.getrange(b"key", 5, 3).unwrap(), "");
+        assert_eq!(store.getrange(b"missing", 0, -1).unwrap(), "");
+    }
+
+    #[test]
+    fn test_setrange_overwrites_and_pads_with_zero_bytes() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"key", b"Hello World").unwrap();
+
+        // Act
+        let overwritten = store.setrange(b"key", 6, b"Redis").unwrap();
+        let padded = store.setrange(b"padded", 3, b"abc").unwrap();
+        let empty = store.setrange(b"missing", 5, b"").unwrap();
+
+        // Assert
+        assert_eq!(overwritten, 11);
+        assert_eq!(
+            store.get(b"key").unwrap().as_deref(),
+            Some(&b"Hello Redis"[..])
+        );
+        assert_eq!(padded, 6);
+        assert_eq!(
+            store.get(b"padded").unwrap().as_deref(),
+            Some(&b"\0\0\0abc"[..])
+        );
+        assert_eq!(empty, 0);
+        assert_eq!(store.exists(vec![b"missing"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_range_respects_bounds_limit_and_reverse_order() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        for key in ["a", "b", "c", "d", "e"] {
+            store.set(key.as_bytes(), key.as_bytes()).unwrap();
+        }
+        let keys =
+            |range: KeyValues| -> Vec<Bytes> { range.into_iter().map(|(key, _)| key).collect() };
+        let (b, d) = (
+            LexBound::Inclusive("b".into()),
+            LexBound::Exclusive("d".into()),
+        );
+
+        // Act
+        let bounded = store.range(&b, &d, false, None, false).unwrap();
+        let open = store
+            .range(&LexBound::Min, &LexBound::Max, false, Some(2), false)
+            .unwrap();
+        let reversed = store
+            .range(&b, &LexBound::Max, true, Some(2), false)
+            .unwrap();
+        let inverted = store.range(&d, &b, false, None, false).unwrap();
+        let past_the_end = store
+            .range(&LexBound::Max, &LexBound::Max, false, None, false)
+            .unwrap();
+
+        // Assert
+        assert_eq!(keys(bounded), vec![Bytes::from("b"), "c".into()]);
+        assert_eq!(keys(open), vec![Bytes::from("a"), "b".into()]);
+        assert_eq!(keys(reversed), vec![Bytes::from("e"), "d".into()]);
+        assert!(inverted.is_empty());
+        assert!(past_the_end.is_empty());
+    }
+
+    #[test]
+    fn test_handle_command_prefix_returns_keys_under_the_prefix_with_values() {
+        // Arrange
+        let mut store = StringStore::new().unwrap();
+        store.set(b"user:4", b"other user").unwrap();
+        store.set(b"user:42:email", b"ann@example.com").unwrap();
+        store.set(b"user:42:name", b"Ann").unwrap();
+        store.set(b"user:43:name", b"Bob").unwrap();
+        store.keyspace_mut().insert(
+            b"user:42:tags",
+            ListValue::from_iter([Bytes::from("admin")]),
+        );
+        store.set(b"\xff\xff", b"last").unwrap();
+        let prefix = |prefix: &str, limit, with_values| {
+            Command::Generic(GenericCommand::Prefix {
+                prefix: prefix.into(),
+                limit,
+                with_values,
+            })
+        };
+
+        // Act
+        let with_values = store
+            .handle_command(prefix("user:42:", None, true))
+            .unwrap();
+        let limited = store
+            .handle_command(prefix("user:42:", Some(1), false))
+            .unwrap();
+        let last = store
+            .handle_command(Command::Generic(GenericCommand::Prefix {
+                prefix: Bytes::from(vec![0xff]),
+                limit: None,
+                with_values: false,
+            }))
+            .unwrap();
+
+        // Assert
+        let string = |value: &str| CommandResult::String(value.into());
+        assert_eq!(
+            with_values,
+            CommandResult::Array(vec![
+                string("user:42:email"),
+                string("ann@example.com"),
+                string("user:42:name"),
+                string("Ann"),
+                string("user:42:tags"),
+                CommandResult::Nil,
+            ])
+        );
+        assert_eq!(limited, CommandResult::Array(vec![string("user:42:email")]));
+        assert_eq!(
+            last,
+            CommandResult::Array(vec![CommandResult::String(Bytes::from(vec![0xff, 0xff]))])
+        );
     }
 }
@@ -0,0 +1,145 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use libp2p::identity::{KeyType, Keypair};
+
+#[derive(Debug)]
+pub enum IdentityError {
+    Read(PathBuf, io::Error),
+    Write(PathBuf, io::Error),
+    /// The file is not a keypair in the libp2p protobuf encoding.
+    Decode(PathBuf, String),
+    /// The file holds a keypair of another type, nodes only use Ed25519 keys.
+    NotEd25519(PathBuf, KeyType),
+}
+
+impl fmt::Display for IdentityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentityError::Read(path, e) => {
+                write!(f, "Failed to read identity {}: {}", path.display(), e)
+            }
+            IdentityError::Write(path, e) => {
+                write!(f, "Failed to write identity {}: {}", path.display(), e)
+            }
+            IdentityError::Decode(path, e) => {
+                write!(f, "Failed to decode identity {}: {}", path.display(), e)
+            }
+            IdentityError::NotEd25519(path, key_type) => write!(
+                f,
+                "Identity {} holds a {:?} key, expected Ed25519",
+                path.display(),
+                key_type
+            ),
+        }
+    }
+}
+
+impl Error for IdentityError {}
+
+/// Generates a new Ed25519 identity.
+pub fn generate() -> Keypair {
+    Keypair::generate_ed25519()
+}
+
+/// Loads an identity saved by `save`.
+pub fn load(path: impl AsRef<Path>) -> Result<Keypair, IdentityError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|e| IdentityError::Read(path.to_path_buf(), e))?;
+    let keypair = Keypair::from_protobuf_encoding(&bytes)
+        .map_err(|e| IdentityError::Decode(path.to_path_buf(), e.to_string()))?;
+    match keypair.key_type() {
+        KeyType::Ed25519 => Ok(keypair),
+        key_type => Err(IdentityError::NotEd25519(path.to_path_buf(), key_type)),
+    }
+}
+
+/// Saves an identity in the libp2p protobuf encoding, an existing file is never overwritten.
+/// On Unix, only the owner may read the file, as it holds the private key.
+pub fn save(keypair: &Keypair, path: impl AsRef<Path>) -> Result<(), IdentityError> {
+    let path = path.as_ref();
+    let bytes = keypair
+        .to_protobuf_encoding()
+        .map_err(|e| IdentityError::Decode(path.to_path_buf(), e.to_string()))?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(&bytes).and_then(|_| file.sync_all()))
+        .map_err(|e| IdentityError::Write(path.to_path_buf(), e))
+}
+
+/// Loads the identity from `path`, or generates and saves one there if the file doesn't exist.
+/// This way a node keeps its `PeerId` across restarts.
+pub fn load_or_generate(path: impl AsRef<Path>) -> Result<Keypair, IdentityError> {
+    let path = path.as_ref();
+    if path.exists() {
+        return load(path);
+    }
+    let keypair = generate();
+    save(&keypair, path)?;
+    Ok(keypair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "sphagnumdb-identity-{}-{}-{}",
+            std::process::id(),
+            rand::random::<u64>(),
+            name
+        ))
+    }
+
+    #[test]
+    fn test_load_or_generate_keeps_the_peer_id() {
+        // Arrange
+        let path = temp_path("node.key");
+
+        // Act
+        let generated = load_or_generate(&path).unwrap();
+        let loaded = load_or_generate(&path).unwrap();
+
+        // Assert
+        assert_eq!(
+            generated.public().to_peer_id(),
+            loaded.public().to_peer_id()
+        );
+        assert_eq!(loaded.key_type(), KeyType::Ed25519);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_save_refuses_to_overwrite_and_load_rejects_garbage() {
+        // Arrange
+        let path = temp_path("garbage.key");
+        fs::write(&path, b"not a key").unwrap();
+
+        // Act
+        let saved = save(&generate(), &path);
+        let loaded = load(&path);
+
+        // Assert
+        assert!(matches!(saved, Err(IdentityError::Write(_, _))));
+        assert!(matches!(loaded, Err(IdentityError::Decode(_, _))));
+        assert_eq!(fs::read(&path).unwrap(), b"not a key");
+        fs::remove_file(path).unwrap();
+    }
+}
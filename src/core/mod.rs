@@ -5,8 +5,17 @@
 pub mod commands;
 pub mod data_types;
 
+pub mod aof;
+pub mod bytes;
+pub mod client;
+pub mod config;
 pub mod data_storage;
+pub mod glob;
+pub mod identity;
+pub mod node_handle;
 pub mod passport;
 pub mod req_resp_codec;
+pub mod snapshot;
 pub mod sphagnum;
 pub mod sphagnum_behaviour;
+pub mod storage;
@@ -0,0 +1,159 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{error::Error, fmt, time::Duration};
+
+use libp2p::{Multiaddr, PeerId};
+use tokio::sync::{mpsc, oneshot};
+
+use super::{
+    client::{ClientError, ClientRequest, SphagnumClient},
+    commands::{Command, CommandResult},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeError {
+    /// The node has shut down or its task has ended.
+    Stopped,
+    /// The node rejected the request, e.g. the address can't be dialed.
+    Rejected(String),
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeError::Stopped => write!(f, "Node is not running"),
+            NodeError::Rejected(reason) => write!(f, "Request rejected: {}", reason),
+        }
+    }
+}
+
+impl Error for NodeError {}
+
+/// Administrative requests, the node answers each of them through `reply`.
+pub(crate) enum AdminRequest {
+    Dial {
+        addr: Multiaddr,
+        reply: oneshot::Sender<Result<(), NodeError>>,
+    },
+    Connect {
+        addr: Multiaddr,
+        reply: oneshot::Sender<Result<PeerId, NodeError>>,
+    },
+    AddToReplicaSet {
+        peer_id: PeerId,
+        reply: oneshot::Sender<()>,
+    },
+    ConnectedPeers {
+        reply: oneshot::Sender<Vec<PeerId>>,
+    },
+    Listeners {
+        reply: oneshot::Sender<Vec<Multiaddr>>,
+    },
+    SetPingingOutput {
+        enabled: bool,
+        reply: oneshot::Sender<()>,
+    },
+    Shutdown {
+        reply: oneshot::Sender<()>,
+    },
+}
+
+/// Controls a node running in its own task, see `SphagnumNode::spawn`.
+///
+/// All requests are passed to the node over channels, so using a handle never waits for the node
+/// to finish handling an event. Handles are cheap to clone and can be shared between tasks.
+#[derive(Clone)]
+pub struct NodeHandle {
+    peer_id: PeerId,
+    client_requests: mpsc::UnboundedSender<ClientRequest>,
+    admin_requests: mpsc::UnboundedSender<AdminRequest>,
+    client_timeout: Duration,
+}
+
+impl NodeHandle {
+    pub(crate) fn new(
+        peer_id: PeerId,
+        client_requests: mpsc::UnboundedSender<ClientRequest>,
+        admin_requests: mpsc::UnboundedSender<AdminRequest>,
+        client_timeout: Duration,
+    ) -> Self {
+        Self {
+            peer_id,
+            client_requests,
+            admin_requests,
+            client_timeout,
+        }
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    /// Creates a client that executes commands on `target` through this node.
+    pub fn client(&self, target: PeerId) -> SphagnumClient {
+        SphagnumClient::new(target, self.client_requests.clone()).with_timeout(self.client_timeout)
+    }
+
+    /// Executes a command on this node, writes are replicated as usual.
+    pub async fn execute(&self, command: Command) -> Result<CommandResult, ClientError> {
+        self.client(self.peer_id).execute(command).await
+    }
+
+    pub async fn dial(&self, addr: Multiaddr) -> Result<(), NodeError> {
+        self.request(|reply| AdminRequest::Dial { addr, reply })
+            .await?
+    }
+
+    /// Dials `addr` and waits until the connection is established, returning the peer on the other
+    /// end. Unlike `dial`, the address doesn't need to contain the peer id.
+    pub async fn connect(&self, addr: Multiaddr) -> Result<PeerId, NodeError> {
+        self.request(|reply| AdminRequest::Connect { addr, reply })
+            .await?
+    }
+
+    pub async fn add_to_replica_set(&self, peer_id: PeerId) -> Result<(), NodeError> {
+        self.request(|reply| AdminRequest::AddToReplicaSet { peer_id, reply })
+            .await
+    }
+
+    pub async fn connected_peers(&self) -> Result<Vec<PeerId>, NodeError> {
+        self.request(|reply| AdminRequest::ConnectedPeers { reply })
+            .await
+    }
+
+    pub async fn listeners(&self) -> Result<Vec<Multiaddr>, NodeError> {
+        self.request(|reply| AdminRequest::Listeners { reply })
+            .await
+    }
+
+    pub async fn set_pinging_output(&self, enabled: bool) -> Result<(), NodeError> {
+        self.request(|reply| AdminRequest::SetPingingOutput { enabled, reply })
+            .await
+    }
+
+    /// Stops the node and waits until it has stopped. Stopping a stopped node is not an error.
+    pub async fn shutdown(&self) -> Result<(), NodeError> {
+        match self.request(|reply| AdminRequest::Shutdown { reply }).await {
+            Err(NodeError::Stopped) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Resolves once the node has stopped, whoever stopped it.
+    pub async fn stopped(&self) {
+        self.admin_requests.closed().await
+    }
+
+    async fn request<T>(
+        &self,
+        request: impl FnOnce(oneshot::Sender<T>) -> AdminRequest,
+    ) -> Result<T, NodeError> {
+        let (reply, response) = oneshot::channel();
+        self.admin_requests
+            .send(request(reply))
+            .map_err(|_| NodeError::Stopped)?;
+        response.await.map_err(|_| NodeError::Stopped)
+    }
+}
@@ -2,16 +2,255 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-use super::commands::Command;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+use super::bytes::Bytes;
+use super::commands::{Command, CommandResult};
+use super::data_storage::DataStorageError;
+use super::data_types::data_type::DataTypeError;
+
+/// Identifies a request among all requests of its sender, the response carries the same id.
+pub type RequestId = u64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SphagnumRequest {
+    /// Chosen by the sender, echoed back in the response so that replies can be matched.
+    pub request_id: RequestId,
     pub command: Command,
-    pub payload: String, // leave it for compatibility, but maybe we don't use it yet
+    pub payload: Bytes, // leave it for compatibility, but maybe we don't use it yet
     pub is_replication: bool,
+    /// Position of a replicated command in the stream from its origin node.
+    /// Ignored unless `is_replication` is set.
+    #[serde(default)]
+    pub replication_seq: u64,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SphagnumResponse {
-    pub payload: String,
+    /// The id of the request this is the response to.
+    pub request_id: RequestId,
+    pub reply: Reply,
+}
+
+/// The outcome of a command as it is sent over the wire.
+/// A missing value is `Nil`, so it can't be confused with a stored value, whatever it contains.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Reply {
+    Value(Bytes),
+    Nil,
+    Int(i64),
+    Array(Vec<Reply>),
+    Error(ReplyError),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplyError {
+    pub code: ErrorCode,
+    /// Human-readable description, clients should rely on `code` instead.
+    pub message: String,
+}
+
+/// Stable identifiers of the errors a command can fail with.
+/// They are part of the wire format: variants may be added, but never renamed or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    WrongType,
+    NotAnInteger,
+    NotAFloat,
+    Overflow,
+    IndexOutOfRange,
+    NoSuchKey,
+    SyntaxError,
+    InvalidExpireTime,
+    InvalidCursor,
+    SameObject,
+    /// An argument is well-formed but can't be used, e.g. an address the node can't dial.
+    InvalidArgument,
+    /// The command can't be executed where it was sent, e.g. a server command sent to storage.
+    UnsupportedCommand,
+    /// The node is shutting down and no longer accepts requests.
+    ShuttingDown,
+    /// The node failed to execute the command for reasons unrelated to the command itself.
+    Internal,
+}
+
+impl From<&DataTypeError> for ErrorCode {
+    fn from(error: &DataTypeError) -> Self {
+        match error {
+            DataTypeError::NotAnInteger => ErrorCode::NotAnInteger,
+            DataTypeError::Overflow => ErrorCode::Overflow,
+            DataTypeError::IndexOutOfRange => ErrorCode::IndexOutOfRange,
+            DataTypeError::NoSuchKey => ErrorCode::NoSuchKey,
+            DataTypeError::NotAFloat => ErrorCode::NotAFloat,
+            DataTypeError::SyntaxError => ErrorCode::SyntaxError,
+            DataTypeError::WrongType => ErrorCode::WrongType,
+            DataTypeError::InvalidExpireTime => ErrorCode::InvalidExpireTime,
+            DataTypeError::InvalidCursor => ErrorCode::InvalidCursor,
+            DataTypeError::SameObject => ErrorCode::SameObject,
+        }
+    }
+}
+
+impl From<&DataStorageError> for ErrorCode {
+    fn from(error: &DataStorageError) -> Self {
+        match error {
+            DataStorageError::WrongType => ErrorCode::WrongType,
+            DataStorageError::CommandError(e) => e.into(),
+            DataStorageError::UnsupportedCommand => ErrorCode::UnsupportedCommand,
+            DataStorageError::InvalidArgument(_) => ErrorCode::InvalidArgument,
+            DataStorageError::InitializationError
+            | DataStorageError::PersistenceError(_)
+            | DataStorageError::StorageEngineError(_)
+            | DataStorageError::DataRetrievalError
+            | DataStorageError::DataModificationError => ErrorCode::Internal,
+        }
+    }
+}
+
+impl From<DataStorageError> for Reply {
+    fn from(error: DataStorageError) -> Self {
+        Reply::Error(ReplyError {
+            code: (&error).into(),
+            message: error.to_string(),
+        })
+    }
+}
+
+impl Reply {
+    /// Converts the reply back into the result of the command, or the error it failed with.
+    /// Errors nested in arrays are kept as `CommandResult::Error`.
+    pub fn into_result(self) -> Result<CommandResult, ReplyError> {
+        match self {
+            Reply::Error(error) => Err(error),
+            reply => Ok(reply.into_command_result()),
+        }
+    }
+
+    fn into_command_result(self) -> CommandResult {
+        match self {
+            Reply::Value(value) => CommandResult::String(value),
+            Reply::Nil => CommandResult::Nil,
+            Reply::Int(value) => CommandResult::Int(value),
+            Reply::Array(values) => {
+                CommandResult::Array(values.into_iter().map(Reply::into_command_result).collect())
+            }
+            Reply::Error(error) => CommandResult::Error(error.message),
+        }
+    }
+}
+
+/// Booleans are sent as 1 and 0, like the integer replies of the commands they come from.
+impl From<CommandResult> for Reply {
+    fn from(result: CommandResult) -> Self {
+        match result {
+            CommandResult::String(value) => Reply::Value(value),
+            CommandResult::Int(value) => Reply::Int(value),
+            CommandResult::Bool(value) => Reply::Int(value as i64),
+            CommandResult::Array(values) => {
+                Reply::Array(values.into_iter().map(Reply::from).collect())
+            }
+            CommandResult::Nil => Reply::Nil,
+            CommandResult::Error(message) => Reply::Error(ReplyError {
+                code: ErrorCode::Internal,
+                message,
+            }),
+        }
+    }
+}
+
+impl From<Result<CommandResult, DataStorageError>> for Reply {
+    fn from(result: Result<CommandResult, DataStorageError>) -> Self {
+        match result {
+            Ok(result) => result.into(),
+            Err(error) => error.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nil_and_stored_nil_string_are_distinct() {
+        // Arrange
+        let missing = CommandResult::Nil;
+        let stored = CommandResult::String("nil".into());
+
+        // Act
+        let missing = Reply::from(missing);
+        let stored = Reply::from(stored);
+
+        // Assert
+        assert_eq!(missing, Reply::Nil);
+        assert_eq!(stored, Reply::Value("nil".into()));
+    }
+
+    #[test]
+    fn test_nested_results_and_booleans_are_converted() {
+        // Arrange
+        let result = CommandResult::Array(vec![
+            CommandResult::Bool(true),
+            CommandResult::Nil,
+            CommandResult::Array(vec![CommandResult::String("a".into())]),
+        ]);
+
+        // Act
+        let reply = Reply::from(result);
+
+        // Assert
+        assert_eq!(
+            reply,
+            Reply::Array(vec![
+                Reply::Int(1),
+                Reply::Nil,
+                Reply::Array(vec![Reply::Value("a".into())]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_error_reply_carries_a_stable_code_on_the_wire() {
+        // Arrange
+        let response = SphagnumResponse {
+            request_id: 7,
+            reply: Reply::from(Err(DataStorageError::CommandError(
+                DataTypeError::NotAnInteger,
+            ))),
+        };
+
+        // Act
+        let json = serde_json::to_value(&response).unwrap();
+        let decoded: SphagnumResponse = serde_json::from_value(json.clone()).unwrap();
+
+        // Assert
+        assert_eq!(json["request_id"], 7);
+        assert_eq!(json["reply"]["Error"]["code"], "NOT_AN_INTEGER");
+        assert_eq!(
+            decoded.reply,
+            Reply::Error(ReplyError {
+                code: ErrorCode::NotAnInteger,
+                message: "Value is not an integer or out of range".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_request_without_replication_seq_is_accepted() {
+        // Arrange
+        let json = serde_json::json!({
+            "request_id": 3,
+            "command": { "Generic": "DbSize" },
+            "payload": [],
+            "is_replication": false,
+        });
+
+        // Act
+        let request: SphagnumRequest = serde_json::from_value(json).unwrap();
+
+        // Assert
+        assert_eq!(request.request_id, 3);
+        assert_eq!(request.replication_seq, 0);
+    }
 }
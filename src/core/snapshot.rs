@@ -0,0 +1,622 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use super::{
+    bytes::Bytes,
+    data_types::{
+        hash::HashValue,
+        keyspace::{unix_time_millis, KeyspaceSnapshot, Value},
+        list::ListValue,
+        set::SetValue,
+        sorted_set::SortedSet,
+        string::StringValue,
+    },
+};
+
+/// Identifies the file format, followed by the version as a little-endian u16.
+const MAGIC: &[u8; 6] = b"SPHSNP";
+
+/// The version written by this build. Older versions are read as well, newer ones are refused.
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// Magic, version, creation time, log offset and number of keys.
+const HEADER_LEN: u64 = MAGIC.len() as u64 + 2 + 8 + 8 + 8;
+
+/// The file ends with the CRC-32 of everything before it.
+const CHECKSUM_LEN: u64 = 4;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_HASH: u8 = 1;
+const TYPE_LIST: u8 = 2;
+const TYPE_SET: u8 = 3;
+const TYPE_SORTED_SET: u8 = 4;
+const TYPE_BITMAP: u8 = 5;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(PathBuf, io::Error),
+    /// The file doesn't start with the header of a snapshot.
+    NotASnapshot(PathBuf),
+    /// The snapshot was written by a newer version of the node.
+    UnsupportedVersion {
+        path: PathBuf,
+        version: u16,
+    },
+    /// The snapshot is truncated, or its checksum or content doesn't match.
+    Corrupted {
+        path: PathBuf,
+        reason: String,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(path, e) => write!(f, "Snapshot {} failed: {}", path.display(), e),
+            SnapshotError::NotASnapshot(path) => {
+                write!(f, "{} is not a snapshot", path.display())
+            }
+            SnapshotError::UnsupportedVersion { path, version } => write!(
+                f,
+                "Snapshot {} has version {}, this node reads up to version {}",
+                path.display(),
+                version,
+                SNAPSHOT_VERSION
+            ),
+            SnapshotError::Corrupted { path, reason } => {
+                write!(f, "Snapshot {} is corrupted: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+/// What a snapshot holds, as written by `save` or read by `load`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotReport {
+    /// How many keys the snapshot holds. Keys that expired since it was written are not loaded.
+    pub keys: u64,
+    /// When the snapshot was taken, in milliseconds since the Unix epoch.
+    pub created_at: u64,
+    /// Where the append-only log stood when the snapshot was taken. The records before this
+    /// offset are already in the snapshot, so only the ones after it are replayed.
+    pub aof_offset: u64,
+    /// The size of the file.
+    pub len: u64,
+}
+
+impl fmt::Display for SnapshotReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let created_at = UNIX_EPOCH + Duration::from_millis(self.created_at);
+        write!(
+            f,
+            "{} keys in {} bytes, taken at {}",
+            self.keys,
+            self.len,
+            humantime::format_rfc3339_millis(created_at)
+        )
+    }
+}
+
+/// Writes the keyspace to `path`, replacing the previous snapshot only once the new one is
+/// complete and on disk, so that a crash while saving leaves the previous snapshot intact.
+///
+/// The format is versioned and little-endian:
+///
+/// ```text
+/// "SPHSNP" version:u16 created_at:u64 aof_offset:u64 keys:u64
+/// keys * (type:u8 key deadline:u64 value)
+/// crc32:u32
+/// ```
+///
+/// Byte strings are prefixed with their length as u32, collections with their number of
+/// elements as u64, scores are f64. A deadline of 0 means the key doesn't expire.
+pub fn save(
+    path: impl AsRef<Path>,
+    snapshot: &KeyspaceSnapshot,
+    aof_offset: u64,
+) -> Result<SnapshotReport, SnapshotError> {
+    let path = path.as_ref();
+    let io_error = |e| SnapshotError::Io(path.to_path_buf(), e);
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let file = File::create(&temp_path).map_err(io_error)?;
+    let mut writer = ChecksumWriter::new(BufWriter::new(file));
+    let created_at = unix_time_millis();
+    writer.write_all(MAGIC).map_err(io_error)?;
+    writer
+        .write_all(&SNAPSHOT_VERSION.to_le_bytes())
+        .map_err(io_error)?;
+    writer.write_u64(created_at).map_err(io_error)?;
+    writer.write_u64(aof_offset).map_err(io_error)?;
+    writer.write_u64(snapshot.len() as u64).map_err(io_error)?;
+    for (key, value, deadline) in snapshot {
+        write_entry(&mut writer, key, value, deadline.unwrap_or(0)).map_err(io_error)?;
+    }
+    let checksum = writer.hasher.clone().finalize();
+    writer
+        .write_all(&checksum.to_le_bytes())
+        .map_err(io_error)?;
+    let len = writer.written;
+    let file = writer
+        .inner
+        .into_inner()
+        .map_err(|e| io_error(e.into_error()))?;
+    file.sync_all().map_err(io_error)?;
+    drop(file);
+
+    fs::rename(&temp_path, path).map_err(io_error)?;
+    // The rename is only durable once the directory is synced. Not every platform can open a
+    // directory, the snapshot is complete either way.
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(SnapshotReport {
+        keys: snapshot.len() as u64,
+        created_at,
+        aof_offset,
+        len,
+    })
+}
+
+/// Reads the snapshot at `path` and passes every key that hasn't expired to `apply`.
+/// Returns None if there is no snapshot yet.
+///
+/// The checksum covers the whole file and is only known at its end, so a corrupted snapshot
+/// may have been partially applied when the error is returned.
+pub fn load(
+    path: impl AsRef<Path>,
+    mut apply: impl FnMut(Bytes, Value, Option<u64>),
+) -> Result<Option<SnapshotReport>, SnapshotError> {
+    let path = path.as_ref();
+    let io_error = |e| SnapshotError::Io(path.to_path_buf(), e);
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io_error(e)),
+    };
+    let len = file.metadata().map_err(io_error)?.len();
+    let corrupted = |reason: String| SnapshotError::Corrupted {
+        path: path.to_path_buf(),
+        reason,
+    };
+    if len < HEADER_LEN + CHECKSUM_LEN {
+        let mut magic = Vec::new();
+        file.take(MAGIC.len() as u64)
+            .read_to_end(&mut magic)
+            .map_err(io_error)?;
+        if !MAGIC.starts_with(&magic) {
+            return Err(SnapshotError::NotASnapshot(path.to_path_buf()));
+        }
+        return Err(corrupted(format!("only {} bytes long", len)));
+    }
+
+    let mut reader = ChecksumReader::new(BufReader::new(file), len - CHECKSUM_LEN);
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic).map_err(io_error)?;
+    if &magic != MAGIC {
+        return Err(SnapshotError::NotASnapshot(path.to_path_buf()));
+    }
+    let mut version = [0; 2];
+    reader.read_exact(&mut version).map_err(io_error)?;
+    let version = u16::from_le_bytes(version);
+    if version > SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion {
+            path: path.to_path_buf(),
+            version,
+        });
+    }
+
+    let decode_error = |e: io::Error| match e.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => corrupted(e.to_string()),
+        _ => io_error(e),
+    };
+    let created_at = reader.read_u64().map_err(decode_error)?;
+    let aof_offset = reader.read_u64().map_err(decode_error)?;
+    let keys = reader.read_u64().map_err(decode_error)?;
+    let now = unix_time_millis();
+    for _ in 0..keys {
+        let (key, value, deadline) = read_entry(&mut reader).map_err(decode_error)?;
+        let deadline = (deadline != 0).then_some(deadline);
+        if deadline.is_none_or(|deadline| deadline > now) {
+            apply(key, value, deadline);
+        }
+    }
+    if reader.remaining != 0 {
+        return Err(corrupted(format!(
+            "{} bytes follow the last key",
+            reader.remaining
+        )));
+    }
+
+    let expected = reader.hasher.clone().finalize();
+    let mut checksum = [0; CHECKSUM_LEN as usize];
+    reader.inner.read_exact(&mut checksum).map_err(io_error)?;
+    if u32::from_le_bytes(checksum) != expected {
+        return Err(corrupted("checksum mismatch".to_string()));
+    }
+    Ok(Some(SnapshotReport {
+        keys,
+        created_at,
+        aof_offset,
+        len,
+    }))
+}
+
+fn write_entry(
+    writer: &mut impl Write,
+    key: &[u8],
+    value: &Value,
+    deadline: u64,
+) -> io::Result<()> {
+    writer.write_all(&[type_tag(value)])?;
+    writer.write_bytes(key)?;
+    writer.write_u64(deadline)?;
+    write_value(writer, value)
+}
+
+fn type_tag(value: &Value) -> u8 {
+    match value {
+        Value::String(_) => TYPE_STRING,
+        Value::Hash(_) => TYPE_HASH,
+        Value::List(_) => TYPE_LIST,
+        Value::Set(_) => TYPE_SET,
+        Value::SortedSet(_) => TYPE_SORTED_SET,
+        Value::Bitmap(_) => TYPE_BITMAP,
+    }
+}
+
+fn write_value(writer: &mut impl Write, value: &Value) -> io::Result<()> {
+    match value {
+        Value::String(string) => writer.write_bytes(&string.as_bytes())?,
+        Value::Hash(hash) => {
+            writer.write_u64(hash.len() as u64)?;
+            for (field, value) in hash.iter() {
+                writer.write_bytes(field)?;
+                writer.write_bytes(value)?;
+            }
+        }
+        Value::List(list) => {
+            writer.write_u64(list.len() as u64)?;
+            for element in list.iter() {
+                writer.write_bytes(element)?;
+            }
+        }
+        Value::Set(set) => {
+            writer.write_u64(set.len() as u64)?;
+            for member in set.iter() {
+                writer.write_bytes(&member)?;
+            }
+        }
+        Value::SortedSet(sorted_set) => {
+            writer.write_u64(sorted_set.len() as u64)?;
+            for (member, score) in sorted_set.iter() {
+                writer.write_bytes(member)?;
+                writer.write_all(&score.to_le_bytes())?;
+            }
+        }
+        Value::Bitmap(bitmap) => writer.write_bytes(bitmap)?,
+    }
+    Ok(())
+}
+
+fn read_entry<R: Read>(reader: &mut ChecksumReader<R>) -> io::Result<(Bytes, Value, u64)> {
+    let mut type_tag = [0];
+    reader.read_exact(&mut type_tag)?;
+    let key = reader.read_bytes()?;
+    let deadline = reader.read_u64()?;
+    let Some(value) = read_value(reader, type_tag[0])? else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown type {} of key {:?}", type_tag[0], key),
+        ));
+    };
+    Ok((key, value, deadline))
+}
+
+/// Returns None if the type is unknown.
+fn read_value<R: Read>(reader: &mut ChecksumReader<R>, type_tag: u8) -> io::Result<Option<Value>> {
+    let value = match type_tag {
+        TYPE_STRING => Value::String(StringValue::from(reader.read_bytes()?)),
+        TYPE_HASH => {
+            let mut hash = HashValue::default();
+            for _ in 0..reader.read_count()? {
+                hash.insert(reader.read_bytes()?, reader.read_bytes()?);
+            }
+            Value::Hash(hash)
+        }
+        TYPE_LIST => {
+            let mut list = ListValue::default();
+            for _ in 0..reader.read_count()? {
+                list.push_back(reader.read_bytes()?);
+            }
+            Value::List(list)
+        }
+        TYPE_SET => {
+            let mut set = SetValue::default();
+            for _ in 0..reader.read_count()? {
+                set.insert(reader.read_bytes()?);
+            }
+            Value::Set(set)
+        }
+        TYPE_SORTED_SET => {
+            let mut sorted_set = SortedSet::default();
+            for _ in 0..reader.read_count()? {
+                let member = reader.read_bytes()?;
+                let score = f64::from_le_bytes(reader.read_u64()?.to_le_bytes());
+                sorted_set.insert(member, score);
+            }
+            Value::SortedSet(sorted_set)
+        }
+        TYPE_BITMAP => Value::Bitmap(reader.read_bytes()?.into_vec()),
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+/// Encodes a value the way a snapshot holds it, preceded by its type.
+/// The on-disk storage engine keeps values in this form, see `storage::lsm`.
+pub fn encode_value(value: &Value) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![type_tag(value)];
+    write_value(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+/// Decodes a value encoded by `encode_value`.
+pub fn decode_value(bytes: &[u8]) -> io::Result<Value> {
+    let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidData, reason);
+    let (&type_tag, payload) = bytes
+        .split_first()
+        .ok_or_else(|| invalid("empty value".to_string()))?;
+    let mut reader = ChecksumReader::new(payload, payload.len() as u64);
+    let value = read_value(&mut reader, type_tag)?
+        .ok_or_else(|| invalid(format!("unknown type {}", type_tag)))?;
+    if reader.remaining != 0 {
+        return Err(invalid(format!(
+            "{} bytes follow the value",
+            reader.remaining
+        )));
+    }
+    Ok(value)
+}
+
+trait WriteExt: Write {
+    fn write_u64(&mut self, value: u64) -> io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(bytes.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "values longer than 4 GiB can't be saved",
+            )
+        })?;
+        self.write_all(&len.to_le_bytes())?;
+        self.write_all(bytes)
+    }
+}
+
+impl<W: Write> WriteExt for W {}
+
+/// Computes the checksum of everything written through it.
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    written: u64,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            written: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Computes the checksum of everything read through it, and refuses to read past `remaining`
+/// bytes, so that a damaged length can't make the reader allocate more than the file holds.
+struct ChecksumReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    remaining: u64,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn new(inner: R, remaining: u64) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            remaining,
+        }
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        self.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Reads the number of elements of a collection. Every element takes at least 4 bytes.
+    fn read_count(&mut self) -> io::Result<u64> {
+        let count = self.read_u64()?;
+        if count > self.remaining / 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} elements don't fit in the rest of the file", count),
+            ));
+        }
+        Ok(count)
+    }
+
+    fn read_bytes(&mut self) -> io::Result<Bytes> {
+        let mut len = [0; 4];
+        self.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as u64;
+        if len > self.remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} bytes don't fit in the rest of the file", len),
+            ));
+        }
+        let mut bytes = vec![0; len as usize];
+        self.read_exact(&mut bytes)?;
+        Ok(Bytes::from(bytes))
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let limit = buf.len().min(self.remaining as usize);
+        let read = self.inner.read(&mut buf[..limit])?;
+        self.hasher.update(&buf[..read]);
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::data_types::keyspace::Keyspace;
+    use std::sync::Arc;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "sphagnumdb-snapshot-{}-{}-{}",
+            std::process::id(),
+            rand::random::<u64>(),
+            name
+        ))
+    }
+
+    fn load_all(path: &Path) -> Result<(Keyspace, Option<SnapshotReport>), SnapshotError> {
+        let mut keyspace = Keyspace::new();
+        let report = load(path, |key, value, deadline| {
+            keyspace.insert_value(&key, value);
+            if let Some(deadline) = deadline {
+                keyspace.set_expiry(&key, deadline);
+            }
+        })?;
+        Ok((keyspace, report))
+    }
+
+    #[test]
+    fn test_saved_snapshot_loads_every_type_and_deadline() {
+        // Arrange
+        let path = temp_path("types.snapshot");
+        let deadline = unix_time_millis() + 60_000;
+        let mut sorted_set = SortedSet::default();
+        sorted_set.insert("member".into(), -1.5);
+        let values = [
+            Value::String("value".into()),
+            Value::Hash(HashValue::from_iter([("field".into(), "value".into())])),
+            Value::List(ListValue::from_iter(["a".into(), "b".into()])),
+            Value::Set(SetValue::from_iter(["member".into()])),
+            Value::SortedSet(sorted_set),
+            Value::Bitmap(vec![0b1010_0000, 0xff]),
+        ];
+        let snapshot: KeyspaceSnapshot = values
+            .iter()
+            .map(|value| {
+                let key = Bytes::from(value.type_name());
+                let deadline = (value.type_name() == "list").then_some(deadline);
+                (key, Arc::new(value.clone()), deadline)
+            })
+            .collect();
+
+        // Act
+        let saved = save(&path, &snapshot, 42).unwrap();
+        let (keyspace, loaded) = load_all(&path).unwrap();
+
+        // Assert
+        assert_eq!(loaded, Some(saved.clone()));
+        assert_eq!(saved.keys, 6);
+        assert_eq!(saved.aof_offset, 42);
+        assert_eq!(saved.len, std::fs::metadata(&path).unwrap().len());
+        for value in &values {
+            let loaded = keyspace.peek_value(value.type_name().as_bytes()).unwrap();
+            assert_eq!(format!("{:?}", loaded), format!("{:?}", value));
+        }
+        assert_eq!(keyspace.expiry(b"list"), Some(deadline));
+        assert_eq!(keyspace.expiry(b"string"), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_snapshot_is_not_an_error() {
+        // Act
+        let (keyspace, report) = load_all(&temp_path("missing.snapshot")).unwrap();
+
+        // Assert
+        assert_eq!(report, None);
+        assert!(keyspace.is_empty());
+    }
+
+    #[test]
+    fn test_damaged_and_newer_snapshots_are_refused() {
+        // Arrange
+        let path = temp_path("damaged.snapshot");
+        let snapshot = vec![(
+            Bytes::from("key"),
+            Arc::new(Value::String("value".into())),
+            None,
+        )];
+        save(&path, &snapshot, 0).unwrap();
+        let content = std::fs::read(&path).unwrap();
+        let mut damaged = content.clone();
+        *damaged.last_mut().unwrap() ^= 0xff;
+        let mut newer = content.clone();
+        newer[MAGIC.len()] = SNAPSHOT_VERSION as u8 + 1;
+        let truncated = &content[..content.len() - 3];
+
+        // Act
+        std::fs::write(&path, damaged).unwrap();
+        let damaged = load_all(&path);
+        std::fs::write(&path, newer).unwrap();
+        let newer = load_all(&path);
+        std::fs::write(&path, truncated).unwrap();
+        let truncated = load_all(&path);
+
+        // Assert
+        assert!(
+            matches!(damaged, Err(SnapshotError::Corrupted { reason, .. }) if reason == "checksum mismatch")
+        );
+        assert!(matches!(
+            newer,
+            Err(SnapshotError::UnsupportedVersion { version, .. }) if version == SNAPSHOT_VERSION + 1
+        ));
+        assert!(matches!(truncated, Err(SnapshotError::Corrupted { .. })));
+        std::fs::remove_file(path).unwrap();
+    }
+}
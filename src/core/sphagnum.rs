@@ -2,26 +2,48 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-use std::{error::Error, time::Duration};
+use std::{error::Error, path::PathBuf, time::Duration};
 
 use futures::prelude::*;
 use libp2p::{
     noise, ping,
-    request_response::{self, OutboundRequestId, ProtocolSupport},
-    swarm::{Swarm, SwarmEvent},
+    request_response::{self, OutboundRequestId, ProtocolSupport, ResponseChannel},
+    swarm::{dial_opts::DialOpts, ConnectionId, Swarm, SwarmEvent},
     tcp, yamux, Multiaddr, PeerId, StreamProtocol,
 };
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::{JoinError, JoinHandle},
+    time::Instant,
+};
 
 use super::{
-    commands::{generic::GenericCommand, string::StringCommand, Command, CommandResult},
-    data_storage::DataStorage,
+    aof::{AppendOnlyLog, AOF_SYNC_INTERVAL},
+    bytes::Bytes,
+    client::{ClientError, ClientReply, ClientRequest, SphagnumClient},
+    commands::{server::ServerCommand, Command, CommandResult},
+    config::NodeConfig,
+    data_storage::{DataStorage, DataStorageError},
+    data_types::keyspace::{unix_time_millis, KeyspaceSnapshot},
+    identity,
+    node_handle::{AdminRequest, NodeError, NodeHandle},
     passport::Passport,
-    req_resp_codec::{SphagnumRequest, SphagnumResponse},
+    req_resp_codec::{ErrorCode, Reply, ReplyError, RequestId, SphagnumRequest, SphagnumResponse},
+    snapshot::{self, SnapshotError, SnapshotReport},
     sphagnum_behaviour::{SphagnumBehaviour, SphagnumBehaviourEvent},
 };
 
+/// Prints a message about what the node is doing, unless the event output is disabled.
+macro_rules! event_output {
+    ($node:expr, $($arg:tt)*) => {
+        if $node.is_event_output_enabled {
+            println!($($arg)*);
+        }
+    };
+}
+
 /// Reminder: in this project, the nodes are called sphagnums. Thus, this structure is a node
 /// structure. At this stage, this is a highly simplified representation of the node, and it will be
 /// further refined.
@@ -31,16 +53,122 @@ pub struct SphagnumNode {
     pub swarm: Swarm<SphagnumBehaviour>, // todo remove pub
     pub connected_peers: HashSet<PeerId>,
     is_pinging_output_enabled: bool,
+    is_event_output_enabled: bool,
 
     /// Multiple nodes to which data will be replicated
     replica_set: HashSet<PeerId>,
+
+    /// Sequence number of the next replicated command for each replica.
+    /// Replicas apply commands strictly in this order, so that, e.g., list elements end up in the
+    /// same order on every node even if the requests overtake each other on the wire.
+    outbound_replication_seqs: HashMap<PeerId, u64>,
+    /// Replication requests that have not been answered yet, they are resent on failure.
+    in_flight_replications: HashMap<OutboundRequestId, InFlightReplication>,
+    /// Sequence number of the next replicated command expected from each peer.
+    inbound_replication_seqs: HashMap<PeerId, u64>,
+    /// Replicated commands that arrived ahead of their turn.
+    pending_replications: HashMap<PeerId, BTreeMap<u64, PendingReplication>>,
+
+    /// Log of the applied writes, if the node persists them.
+    aof: Option<AppendOnlyLog>,
+    /// When the append-only log is next checked for a due sync and the storage engine is synced.
+    next_sync: Instant,
+    /// Where snapshots of the keyspace are saved, if the node saves them.
+    snapshot_file: Option<PathBuf>,
+    snapshot_interval: Option<Duration>,
+    /// When the next scheduled snapshot is due.
+    next_snapshot: Instant,
+    /// The snapshot being saved in the background, see `ServerCommand::BgSave`.
+    background_save: Option<BackgroundSave>,
+    /// Writes applied since the last snapshot was taken. Scheduled snapshots are skipped without
+    /// them, and the node saves one when it stops unless this is zero.
+    unsaved_writes: u64,
+    /// When the last snapshot was saved, or taken if it was loaded, in milliseconds since the
+    /// Unix epoch. Zero if there is none.
+    last_save: u64,
+
+    /// When the next active expiration sweep is due.
+    next_expiration_sweep: Instant,
+    expiration_sweep_interval: Duration,
+
+    /// The id of the next request sent by this node.
+    next_request_id: RequestId,
+
+    /// Commands submitted by the clients of this node.
+    client_requests: mpsc::UnboundedReceiver<ClientRequest>,
+    client_sender: mpsc::UnboundedSender<ClientRequest>,
+    /// Clients waiting for the reply of another node.
+    pending_client_requests: HashMap<OutboundRequestId, PendingClientRequest>,
+
+    /// Requests of the handles of this node.
+    admin_requests: mpsc::UnboundedReceiver<AdminRequest>,
+    admin_sender: mpsc::UnboundedSender<AdminRequest>,
+    /// Handles waiting for their dial to connect, see `NodeHandle::connect`.
+    pending_connections: HashMap<ConnectionId, oneshot::Sender<Result<PeerId, NodeError>>>,
+    /// Handles waiting for the node to stop.
+    shutdown_replies: Vec<oneshot::Sender<()>>,
+    /// Set once the node is shutting down: it no longer accepts requests, and stops when the
+    /// requests in flight are finished or this deadline passes, whichever comes first.
+    shutdown_deadline: Option<Instant>,
+    /// How long the shutdown waits, see `NodeConfig::drain_timeout`.
+    drain_timeout: Duration,
+    /// How long the clients of this node wait for a reply.
+    client_timeout: Duration,
+    /// Responses handed to the swarm that have not been sent yet.
+    unsent_responses: usize,
+}
+
+struct PendingClientRequest {
+    peer: PeerId,
+    reply: oneshot::Sender<ClientReply>,
 }
 
+type PendingReplication = (RequestId, Command, ResponseChannel<SphagnumResponse>);
+
+/// A replicated command waiting for the response of the replica.
+struct InFlightReplication {
+    peer: PeerId,
+    request: SphagnumRequest,
+    /// How many times the request was sent, see `MAX_REPLICATION_ATTEMPTS`.
+    attempts: u32,
+}
+
+/// The result of saving a snapshot, together with the number of writes the snapshot holds.
+type SaveOutcome = (Result<SnapshotReport, SnapshotError>, u64);
+
+/// Saves a snapshot on a blocking thread.
+type BackgroundSave = JoinHandle<SaveOutcome>;
+
+/// How often expired keys that nobody accesses are evicted.
+pub const DEFAULT_EXPIRATION_SWEEP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a drained node waits before it closes its connections. A response counts as sent once
+/// it is written, so the peer needs a moment to read it before the connection goes away.
+const CONNECTION_CLOSE_DELAY: Duration = Duration::from_millis(200);
+
+/// How many times a replicated command is sent before the node gives up and disconnects the
+/// replica. The replica can't apply the later commands without it, and both sides start the
+/// stream over once they reconnect.
+const MAX_REPLICATION_ATTEMPTS: u32 = 5;
+
 impl SphagnumNode {
     pub fn new() -> Result<SphagnumNode, Box<dyn Error>> {
-        let behaviours = Self::configure_behaviours()?;
+        Self::with_config(NodeConfig::default())
+    }
 
-        let swarm = libp2p::SwarmBuilder::with_new_identity()
+    /// Creates a node that listens on, dials and replicates to the nodes given by `config`.
+    /// Like any node, it only makes progress while its events are handled.
+    /// Fails if the configuration is not valid, see `NodeConfig::validate`.
+    pub fn with_config(config: NodeConfig) -> Result<SphagnumNode, Box<dyn Error>> {
+        config.validate()?;
+        let behaviours = Self::configure_behaviours(config.request_timeout)?;
+        let idle_timeout = config.idle_timeout.unwrap_or(Duration::from_secs(u64::MAX));
+
+        let keypair = match &config.identity_file {
+            Some(path) => identity::load_or_generate(path)?,
+            None => identity::generate(),
+        };
+        let swarm = libp2p::SwarmBuilder::with_existing_identity(keypair)
             .with_tokio()
             .with_tcp(
                 tcp::Config::default(),
@@ -48,29 +176,222 @@ impl SphagnumNode {
                 yamux::Config::default,
             )?
             .with_behaviour(|_| behaviours)?
-            .with_swarm_config(|cfg| {
-                cfg.with_idle_connection_timeout(Duration::from_secs(u64::MAX))
-            })
+            .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(idle_timeout))
             .build();
+        let (client_sender, client_requests) = mpsc::unbounded_channel();
+        let (admin_sender, admin_requests) = mpsc::unbounded_channel();
+
+        let mut data_storage = DataStorage::with_config(&config.storage)?;
+        let mut aof_offset = 0;
+        let mut last_save = 0;
+        if let Some(path) = &config.persistence.snapshot_file {
+            let mut restore_error = None;
+            let report = snapshot::load(path, |key, value, deadline| {
+                if let Err(e) = data_storage.restore(&key, value, deadline) {
+                    restore_error.get_or_insert(e);
+                }
+            })?;
+            if let Some(e) = restore_error {
+                return Err(Box::new(e));
+            }
+            if let Some(report) = report {
+                if config.event_output {
+                    println!("Loaded snapshot {}: {}", path.display(), report);
+                }
+                aof_offset = report.aof_offset;
+                last_save = report.created_at;
+            }
+        }
+        let mut unsaved_writes = 0;
+        let aof = match &config.persistence.aof_file {
+            Some(path) => {
+                // The writes before the offset are in the snapshot already.
+                let (aof, report) = AppendOnlyLog::open_from(
+                    path,
+                    config.persistence.aof_fsync,
+                    aof_offset,
+                    |command| data_storage.handle_command(command).map(|_| ()),
+                )?;
+                if config.event_output {
+                    println!("Append-only log {}: {}", path.display(), report);
+                }
+                unsaved_writes = report.commands;
+                Some(aof)
+            }
+            None => None,
+        };
+
+        let mut passport = Passport::new()?;
+        passport.set_field(config.passport.field)?;
 
-        Ok(SphagnumNode {
-            data_storage: DataStorage::new()?,
-            passport: Passport::new()?,
+        let mut sphagnum = SphagnumNode {
+            data_storage,
+            passport,
             swarm,
             connected_peers: HashSet::new(),
             is_pinging_output_enabled: false,
+            is_event_output_enabled: config.event_output,
             replica_set: HashSet::new(),
+            outbound_replication_seqs: HashMap::new(),
+            in_flight_replications: HashMap::new(),
+            inbound_replication_seqs: HashMap::new(),
+            pending_replications: HashMap::new(),
+            aof,
+            next_sync: Instant::now() + AOF_SYNC_INTERVAL,
+            snapshot_file: config.persistence.snapshot_file,
+            snapshot_interval: config.persistence.snapshot_interval,
+            next_snapshot: Instant::now()
+                + config.persistence.snapshot_interval.unwrap_or_default(),
+            background_save: None,
+            unsaved_writes,
+            last_save,
+            next_expiration_sweep: Instant::now() + config.storage.expiration_sweep_interval,
+            expiration_sweep_interval: config.storage.expiration_sweep_interval,
+            next_request_id: 0,
+            client_requests,
+            client_sender,
+            pending_client_requests: HashMap::new(),
+            admin_requests,
+            admin_sender,
+            pending_connections: HashMap::new(),
+            shutdown_replies: Vec::new(),
+            shutdown_deadline: None,
+            drain_timeout: config.drain_timeout,
+            client_timeout: config.client_timeout,
+            unsent_responses: 0,
+        };
+        for addr in config.listen_addrs {
+            sphagnum.listen_on(addr)?;
+        }
+        for addr in config.bootstrap_peers {
+            sphagnum.swarm.dial(addr)?;
+        }
+        for peer_id in config.replica_set {
+            sphagnum.add_to_replica_set(peer_id)?;
+        }
+        Ok(sphagnum)
+    }
+
+    /// Starts a node configured by `config` in its own task and returns a handle to it.
+    /// The node runs until it is shut down. Must be called from within a Tokio runtime.
+    pub fn spawn(config: NodeConfig) -> Result<NodeHandle, Box<dyn Error>> {
+        let sphagnum = SphagnumNode::with_config(config)?;
+        let handle = sphagnum.handle();
+        tokio::spawn(sphagnum.run());
+        Ok(handle)
+    }
+
+    pub fn handle(&self) -> NodeHandle {
+        NodeHandle::new(
+            *self.swarm.local_peer_id(),
+            self.client_sender.clone(),
+            self.admin_sender.clone(),
+            self.client_timeout,
+        )
+    }
+
+    /// Handles events until the node is shut down.
+    /// Shutting down, the node first finishes the requests in flight, then closes its connections
+    /// so that the peers see them closed rather than lost.
+    async fn run(mut self) {
+        while !self.is_drained() {
+            self.handle_event_logged().await;
+        }
+        let close_at = Instant::now() + CONNECTION_CLOSE_DELAY;
+        while Instant::now() < close_at && !self.is_shutdown_overdue() {
+            self.handle_event_logged().await;
+        }
+        let peers: Vec<PeerId> = self.connected_peers.iter().copied().collect();
+        for peer_id in peers {
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+        while !self.connected_peers.is_empty() && !self.is_shutdown_overdue() {
+            self.handle_event_logged().await;
+        }
+
+        if let Some(aof) = &mut self.aof {
+            if let Err(e) = aof.sync() {
+                event_output!(self, "Failed to sync {}: {}", aof.path().display(), e);
+            }
+        }
+        if let Err(e) = self.data_storage.sync() {
+            event_output!(self, "Failed to sync the storage engine: {}", e);
+        }
+        self.save_on_shutdown().await;
+        let shutdown_replies = std::mem::take(&mut self.shutdown_replies);
+        drop(self);
+        for reply in shutdown_replies {
+            let _ = reply.send(());
+        }
+    }
+
+    async fn handle_event_logged(&mut self) {
+        if let Err(e) = self.handle_event().await {
+            event_output!(
+                self,
+                "Node {} failed to handle event: {}",
+                self.swarm.local_peer_id(),
+                e
+            );
+        }
+    }
+
+    /// Stops accepting requests, the node stops once the requests in flight are finished.
+    fn begin_shutdown(&mut self) {
+        if self.shutdown_deadline.is_none() {
+            event_output!(self, "Node {} is shutting down", self.swarm.local_peer_id());
+            self.shutdown_deadline = Some(Instant::now() + self.drain_timeout);
+        }
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        self.shutdown_deadline.is_some()
+    }
+
+    fn is_shutdown_overdue(&self) -> bool {
+        self.shutdown_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Returns true once a shutting down node has nothing left to wait for.
+    fn is_drained(&self) -> bool {
+        self.is_shutting_down()
+            && ((self.pending_client_requests.is_empty()
+                && self.in_flight_replications.is_empty()
+                && self.unsent_responses == 0)
+                || self.is_shutdown_overdue())
+    }
+
+    fn shutting_down_reply() -> Reply {
+        Reply::Error(ReplyError {
+            code: ErrorCode::ShuttingDown,
+            message: "Node is shutting down".to_string(),
         })
     }
 
-    fn configure_behaviours() -> Result<SphagnumBehaviour, Box<dyn Error>> {
+    fn respond(
+        &mut self,
+        channel: ResponseChannel<SphagnumResponse>,
+        response: SphagnumResponse,
+    ) -> Result<(), SphagnumResponse> {
+        self.swarm
+            .behaviour_mut()
+            .request_response
+            .send_response(channel, response)?;
+        self.unsent_responses += 1;
+        Ok(())
+    }
+
+    fn configure_behaviours(
+        request_timeout: Duration,
+    ) -> Result<SphagnumBehaviour, Box<dyn Error>> {
         let ping = ping::Behaviour::default();
         let request_response = request_response::json::Behaviour::new(
             [(
                 StreamProtocol::new("/SphagnumDB/1.0.0"),
                 ProtocolSupport::Full,
             )],
-            request_response::Config::default(),
+            request_response::Config::default().with_request_timeout(request_timeout),
         );
 
         Ok(SphagnumBehaviour {
@@ -126,20 +447,125 @@ impl SphagnumNode {
             .collect();
 
         for peer_id in peers_to_replicate {
+            let request_id = self.next_request_id();
+            let seq = self.outbound_replication_seqs.entry(peer_id).or_insert(0);
             let request = SphagnumRequest {
+                request_id,
                 command: command.clone(),
-                payload: String::new(),
+                payload: Bytes::new(),
                 is_replication: true,
+                replication_seq: *seq,
             };
+            *seq += 1;
 
-            self.swarm
-                .behaviour_mut()
-                .request_response
-                .send_request(&peer_id, request);
+            self.send_replication_request(peer_id, request, 1);
         }
         Ok(())
     }
 
+    fn send_replication_request(&mut self, peer: PeerId, request: SphagnumRequest, attempts: u32) {
+        let request_id = self
+            .swarm
+            .behaviour_mut()
+            .request_response
+            .send_request(&peer, request.clone());
+        self.in_flight_replications.insert(
+            request_id,
+            InFlightReplication {
+                peer,
+                request,
+                attempts,
+            },
+        );
+    }
+
+    /// Resends a replication request that failed, unless it was sent `MAX_REPLICATION_ATTEMPTS`
+    /// times already, then the replica is disconnected.
+    fn retry_replication(&mut self, request_id: OutboundRequestId) {
+        let Some(replication) = self.in_flight_replications.remove(&request_id) else {
+            return;
+        };
+        if !self.connected_peers.contains(&replication.peer) {
+            return;
+        }
+        if replication.attempts < MAX_REPLICATION_ATTEMPTS {
+            self.send_replication_request(
+                replication.peer,
+                replication.request,
+                replication.attempts + 1,
+            );
+            return;
+        }
+        event_output!(
+            self,
+            "Replication to {} failed {} times, disconnecting it",
+            replication.peer,
+            replication.attempts
+        );
+        let _ = self.swarm.disconnect_peer_id(replication.peer);
+    }
+
+    /// Buffers a replicated command and applies all commands from this peer that are now in order.
+    /// Commands that were already applied (e.g. resent after a lost response) are skipped.
+    fn handle_replication_request(
+        &mut self,
+        peer: PeerId,
+        request: SphagnumRequest,
+        channel: ResponseChannel<SphagnumResponse>,
+    ) {
+        let expected = self
+            .inbound_replication_seqs
+            .get(&peer)
+            .copied()
+            .unwrap_or(0);
+        if request.replication_seq < expected {
+            let response = SphagnumResponse {
+                request_id: request.request_id,
+                reply: Reply::Value("Already replicated".into()),
+            };
+            let _ = self.respond(channel, response);
+            return;
+        }
+
+        let pending = self.pending_replications.entry(peer).or_default();
+        pending.insert(
+            request.replication_seq,
+            (request.request_id, request.command, channel),
+        );
+
+        let mut next = expected;
+        while let Some((request_id, command, channel)) = self
+            .pending_replications
+            .get_mut(&peer)
+            .and_then(|pending| pending.remove(&next))
+        {
+            let result = self.apply_write(command).map(|(result, _)| result);
+            let response = SphagnumResponse {
+                request_id,
+                reply: result.into(),
+            };
+            if let Err(response) = self.respond(channel, response) {
+                event_output!(
+                    self,
+                    "Failed to respond to replication from {}: {:?}",
+                    peer,
+                    response
+                );
+            }
+            next += 1;
+        }
+        self.inbound_replication_seqs.insert(peer, next);
+    }
+
+    /// Forgets the replication stream state of a peer, both sides start from zero on reconnect.
+    fn reset_replication_state(&mut self, peer_id: &PeerId) {
+        self.outbound_replication_seqs.remove(peer_id);
+        self.inbound_replication_seqs.remove(peer_id);
+        self.pending_replications.remove(peer_id);
+        self.in_flight_replications
+            .retain(|_, replication| replication.peer != *peer_id);
+    }
+
     // todo: redesign
     // warning: no replication, if you want replication - use handle_event
     pub fn handle_command(&mut self, command: Command) -> Result<CommandResult, Box<dyn Error>> {
@@ -148,8 +574,341 @@ impl SphagnumNode {
             .map_err(|e| Box::new(e) as Box<dyn Error>)
     }
 
+    /// Creates a client that executes commands on `target`, which may also be this node.
+    pub fn client(&self, target: PeerId) -> SphagnumClient {
+        SphagnumClient::new(target, self.client_sender.clone()).with_timeout(self.client_timeout)
+    }
+
+    /// Executes a command received from a client or another node and replicates it if it is a
+    /// write.
+    async fn execute(&mut self, command: Command) -> Result<CommandResult, DataStorageError> {
+        if let Command::Server(command) = command {
+            return self.handle_server_command(command);
+        }
+        // Replicas must receive the same deadlines as the ones applied here.
+        let command = command.with_absolute_expiry(unix_time_millis());
+        if !command.is_write() {
+            return self.data_storage.handle_command(command);
+        }
+        let (result, replicated) = self.apply_write(command)?;
+        if let Err(e) = self.send_to_replicas(replicated).await {
+            event_output!(self, "Replication failed: {:?}", e);
+        }
+        Ok(result)
+    }
+
+    /// Applies a write and logs it, if the node keeps a log, in the form it is replicated in, see
+    /// `Command::for_replication`. Returns the result and that form.
+    /// The write is logged before it is acknowledged. If it can't be, it isn't applied either, so
+    /// that the log, the replicas and this node never differ.
+    fn apply_write(
+        &mut self,
+        command: Command,
+    ) -> Result<(CommandResult, Command), DataStorageError> {
+        let original = command.clone();
+        let mut replicated = None;
+        let result = match &mut self.aof {
+            Some(aof) => self
+                .data_storage
+                .handle_command_confirmed(command, |result| {
+                    let command = original.clone().for_replication(result);
+                    aof.append(&command)
+                        .map_err(|e| DataStorageError::PersistenceError(e.to_string()))?;
+                    replicated = Some(command);
+                    Ok(())
+                })?,
+            None => self.data_storage.handle_command(command)?,
+        };
+        self.unsaved_writes += 1;
+        let replicated = replicated.unwrap_or_else(|| original.for_replication(&result));
+        Ok((result, replicated))
+    }
+
+    fn handle_server_command(
+        &mut self,
+        command: ServerCommand,
+    ) -> Result<CommandResult, DataStorageError> {
+        let ok = || Ok(CommandResult::String("OK".into()));
+        match command {
+            ServerCommand::Shutdown => {
+                self.begin_shutdown();
+                ok()
+            }
+            ServerCommand::ClusterPeers => Ok(Self::peer_list(self.connected_peers.iter())),
+            ServerCommand::ClusterReplicas => Ok(Self::peer_list(self.replica_set.iter())),
+            ServerCommand::ClusterMeet { addr } => match self.swarm.dial(addr.clone()) {
+                Ok(()) => ok(),
+                Err(e) => Err(DataStorageError::InvalidArgument(format!(
+                    "Can't dial {}: {}",
+                    addr, e
+                ))),
+            },
+            ServerCommand::ClusterAddReplica { peer_id } => {
+                if peer_id == *self.swarm.local_peer_id() {
+                    return Err(DataStorageError::InvalidArgument(
+                        "A node can't be a replica of itself".to_string(),
+                    ));
+                }
+                self.replica_set.insert(peer_id);
+                ok()
+            }
+            ServerCommand::Save => {
+                self.save().map_err(DataStorageError::PersistenceError)?;
+                ok()
+            }
+            ServerCommand::BgSave => {
+                self.start_background_save()
+                    .map_err(DataStorageError::PersistenceError)?;
+                Ok(CommandResult::String("Background saving started".into()))
+            }
+            ServerCommand::LastSave => Ok(CommandResult::Int((self.last_save / 1000) as i64)),
+        }
+    }
+
+    /// Returns where snapshots are saved, or why they can't be.
+    fn snapshot_path(&self) -> Result<PathBuf, String> {
+        if self.background_save.is_some() {
+            return Err("Background save already in progress".to_string());
+        }
+        self.snapshot_file
+            .clone()
+            .ok_or_else(|| "Snapshots are disabled, see persistence.snapshot_file".to_string())
+    }
+
+    /// Takes a snapshot of the keyspace, together with the length of the append-only log it
+    /// covers. The log is synced first: a snapshot must not cover records a crash can still lose.
+    fn take_snapshot(&mut self) -> Result<(KeyspaceSnapshot, u64), String> {
+        let aof_offset = match &mut self.aof {
+            Some(aof) => {
+                aof.sync().map_err(|e| e.to_string())?;
+                aof.offset()
+            }
+            None => 0,
+        };
+        let snapshot = self.data_storage.snapshot().map_err(|e| e.to_string())?;
+        Ok((snapshot, aof_offset))
+    }
+
+    /// Saves a snapshot before returning, see `ServerCommand::Save`.
+    fn save(&mut self) -> Result<(), String> {
+        let path = self.snapshot_path()?;
+        let writes = self.unsaved_writes;
+        let (snapshot, aof_offset) = self.take_snapshot()?;
+        let result = snapshot::save(&path, &snapshot, aof_offset);
+        self.finish_snapshot(result, writes)
+    }
+
+    /// Takes a snapshot and saves it on a blocking thread, see `ServerCommand::BgSave`.
+    /// Taking it only copies the keys, the values are shared until they are modified.
+    fn start_background_save(&mut self) -> Result<(), String> {
+        let path = self.snapshot_path()?;
+        let writes = self.unsaved_writes;
+        let (snapshot, aof_offset) = self.take_snapshot()?;
+        self.background_save = Some(tokio::task::spawn_blocking(move || {
+            (snapshot::save(&path, &snapshot, aof_offset), writes)
+        }));
+        Ok(())
+    }
+
+    /// Records a saved snapshot, `writes` is the number of writes it holds.
+    fn finish_snapshot(
+        &mut self,
+        result: Result<SnapshotReport, SnapshotError>,
+        writes: u64,
+    ) -> Result<(), String> {
+        match result {
+            Ok(report) => {
+                self.last_save = report.created_at;
+                self.unsaved_writes = self.unsaved_writes.saturating_sub(writes);
+                event_output!(self, "Saved snapshot: {}", report);
+                Ok(())
+            }
+            Err(e) => {
+                event_output!(self, "{}", e);
+                Err(e.to_string())
+            }
+        }
+    }
+
+    fn finish_background_save(&mut self, result: Result<SaveOutcome, JoinError>) {
+        self.background_save = None;
+        match result {
+            Ok((result, writes)) => {
+                let _ = self.finish_snapshot(result, writes);
+            }
+            Err(e) => event_output!(self, "Background save failed: {}", e),
+        }
+    }
+
+    /// Waits for the background save, if any, then saves the writes no snapshot holds yet, so
+    /// that the node starts from the snapshot alone next time.
+    async fn save_on_shutdown(&mut self) {
+        if let Some(background_save) = self.background_save.take() {
+            let result = background_save.await;
+            self.finish_background_save(result);
+        }
+        let Some(path) = &self.snapshot_file else {
+            return;
+        };
+        if self.unsaved_writes > 0 || !path.exists() {
+            // A failure is already reported, there is nothing else to do about it now.
+            let _ = self.save();
+        }
+    }
+
+    /// Sorted, so that the reply doesn't depend on the order of a `HashSet`.
+    fn peer_list<'a>(peers: impl Iterator<Item = &'a PeerId>) -> CommandResult {
+        let mut peers: Vec<String> = peers.map(PeerId::to_string).collect();
+        peers.sort();
+        CommandResult::Array(
+            peers
+                .into_iter()
+                .map(|peer| CommandResult::String(peer.into()))
+                .collect(),
+        )
+    }
+
+    /// Executes a client command on this node, or forwards it to the target node.
+    /// The reply to a forwarded command is delivered once its response arrives.
+    async fn handle_client_request(&mut self, request: ClientRequest) {
+        if self.is_shutting_down() {
+            let reply = Self::shutting_down_reply();
+            let _ = request
+                .reply
+                .send(reply.into_result().map_err(ClientError::Command));
+            return;
+        }
+        if request.target == *self.swarm.local_peer_id() {
+            let reply: Reply = self.execute(request.command).await.into();
+            let _ = request
+                .reply
+                .send(reply.into_result().map_err(ClientError::Command));
+            return;
+        }
+
+        let sphagnum_request = SphagnumRequest {
+            request_id: self.next_request_id(),
+            command: request.command,
+            payload: Bytes::new(),
+            is_replication: false,
+            replication_seq: 0,
+        };
+        let outbound_id = self
+            .swarm
+            .behaviour_mut()
+            .request_response
+            .send_request(&request.target, sphagnum_request);
+        self.pending_client_requests.insert(
+            outbound_id,
+            PendingClientRequest {
+                peer: request.target,
+                reply: request.reply,
+            },
+        );
+    }
+
+    fn handle_admin_request(&mut self, request: AdminRequest) {
+        match request {
+            AdminRequest::Dial { addr, reply } => {
+                let result = self
+                    .swarm
+                    .dial(addr)
+                    .map_err(|e| NodeError::Rejected(e.to_string()));
+                let _ = reply.send(result);
+            }
+            AdminRequest::Connect { addr, reply } => {
+                let opts = DialOpts::from(addr);
+                let connection_id = opts.connection_id();
+                match self.swarm.dial(opts) {
+                    Ok(()) => {
+                        self.pending_connections.insert(connection_id, reply);
+                    }
+                    Err(e) => {
+                        let _ = reply.send(Err(NodeError::Rejected(e.to_string())));
+                    }
+                }
+            }
+            AdminRequest::AddToReplicaSet { peer_id, reply } => {
+                self.replica_set.insert(peer_id);
+                let _ = reply.send(());
+            }
+            AdminRequest::ConnectedPeers { reply } => {
+                let _ = reply.send(self.connected_peers.iter().copied().collect());
+            }
+            AdminRequest::Listeners { reply } => {
+                let _ = reply.send(self.listeners().cloned().collect());
+            }
+            AdminRequest::SetPingingOutput { enabled, reply } => {
+                self.is_pinging_output_enabled = enabled;
+                let _ = reply.send(());
+            }
+            AdminRequest::Shutdown { reply } => {
+                self.shutdown_replies.push(reply);
+                self.begin_shutdown();
+            }
+        }
+    }
+
+    /// Fails all client requests waiting for a reply of a peer that disconnected.
+    fn fail_pending_client_requests(&mut self, peer_id: &PeerId) {
+        let disconnected: Vec<OutboundRequestId> = self
+            .pending_client_requests
+            .iter()
+            .filter(|(_, pending)| pending.peer == *peer_id)
+            .map(|(request_id, _)| *request_id)
+            .collect();
+        for request_id in disconnected {
+            if let Some(pending) = self.pending_client_requests.remove(&request_id) {
+                let _ = pending.reply.send(Err(ClientError::PeerDisconnected));
+            }
+        }
+    }
+
+    fn next_request_id(&mut self) -> RequestId {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        request_id
+    }
+
     pub async fn handle_event(&mut self) -> Result<(), Box<dyn Error>> {
-        match self.swarm.select_next_some().await {
+        let event = tokio::select! {
+            event = self.swarm.select_next_some() => event,
+            _ = tokio::time::sleep_until(self.next_sync) => {
+                self.next_sync = Instant::now() + AOF_SYNC_INTERVAL;
+                if let Some(aof) = &mut self.aof {
+                    aof.sync_if_due()?;
+                }
+                self.data_storage.sync()?;
+                return Ok(());
+            }
+            _ = tokio::time::sleep_until(self.next_snapshot), if self.snapshot_interval.is_some() => {
+                self.next_snapshot = Instant::now() + self.snapshot_interval.unwrap_or_default();
+                if self.unsaved_writes > 0 && self.background_save.is_none() {
+                    self.start_background_save()?;
+                }
+                return Ok(());
+            }
+            result = background_save_finished(&mut self.background_save) => {
+                self.finish_background_save(result);
+                return Ok(());
+            }
+            _ = tokio::time::sleep_until(self.next_expiration_sweep) => {
+                if let Err(e) = self.data_storage.evict_expired() {
+                    event_output!(self, "Failed to evict expired keys: {}", e);
+                }
+                self.next_expiration_sweep = Instant::now() + self.expiration_sweep_interval;
+                return Ok(());
+            }
+            Some(request) = self.client_requests.recv() => {
+                self.handle_client_request(request).await;
+                return Ok(());
+            }
+            Some(request) = self.admin_requests.recv() => {
+                self.handle_admin_request(request);
+                return Ok(());
+            }
+        };
+        match event {
             SwarmEvent::ConnectionEstablished {
                 peer_id,
                 connection_id,
@@ -159,27 +918,38 @@ impl SphagnumNode {
                 established_in,
             } => {
                 self.connected_peers.insert(peer_id);
+                if let Some(reply) = self.pending_connections.remove(&connection_id) {
+                    let _ = reply.send(Ok(peer_id));
+                }
                 if endpoint.is_dialer() {
-                    println!(
+                    event_output!(
+                        self,
                         "Node {} successfully dialed {} (connection_id: {:?})",
                         self.swarm.local_peer_id(),
                         peer_id,
                         connection_id
                     );
                 } else {
-                    println!("Node {} accepted connection from {} (connection_id: {:?}, num_established: {}, established_in: {:?})", 
+                    event_output!(self, "Node {} accepted connection from {} (connection_id: {:?}, num_established: {}, established_in: {:?})", 
                         self.swarm.local_peer_id(), peer_id, connection_id, num_established, established_in);
                 }
                 if let Some(errors) = concurrent_dial_errors {
                     for (addr, err) in errors {
-                        println!("Dial attempt to {:?} failed with error: {:?}", addr, err);
+                        event_output!(
+                            self,
+                            "Dial attempt to {:?} failed with error: {:?}",
+                            addr,
+                            err
+                        );
                     }
                 }
-                println!(
+                event_output!(
+                    self,
                     "Total number of established connections with peer {}: {}",
-                    peer_id, num_established
+                    peer_id,
+                    num_established
                 );
-                println!("Connection established in: {:?}", established_in);
+                event_output!(self, "Connection established in: {:?}", established_in);
                 Ok(())
             }
             SwarmEvent::ConnectionClosed {
@@ -190,10 +960,32 @@ impl SphagnumNode {
                 cause,
             } => {
                 self.connected_peers.remove(&peer_id);
-                println!("Node {} closed connection with {} (connection_id: {:?}, endpoint: {:?}, num_established: {})", 
+                if num_established == 0 {
+                    self.reset_replication_state(&peer_id);
+                    self.fail_pending_client_requests(&peer_id);
+                }
+                event_output!(self, "Node {} closed connection with {} (connection_id: {:?}, endpoint: {:?}, num_established: {})", 
                     self.swarm.local_peer_id(), peer_id, connection_id, endpoint, num_established);
                 if let Some(err) = cause {
-                    println!("Cause of disconnection: {:?}", err);
+                    event_output!(self, "Cause of disconnection: {:?}", err);
+                }
+                Ok(())
+            }
+            SwarmEvent::OutgoingConnectionError {
+                connection_id,
+                peer_id,
+                error,
+            } => {
+                event_output!(
+                    self,
+                    "Node {} failed to dial {:?} (connection_id: {:?}): {}",
+                    self.swarm.local_peer_id(),
+                    peer_id,
+                    connection_id,
+                    error
+                );
+                if let Some(reply) = self.pending_connections.remove(&connection_id) {
+                    let _ = reply.send(Err(NodeError::Rejected(error.to_string())));
                 }
                 Ok(())
             }
@@ -201,7 +993,8 @@ impl SphagnumNode {
                 listener_id,
                 address,
             } => {
-                println!(
+                event_output!(
+                    self,
                     "Node {} is now listening on {:?} with listener ID: {:?}",
                     self.swarm.local_peer_id(),
                     address,
@@ -214,25 +1007,33 @@ impl SphagnumNode {
                 addresses,
                 reason,
             } => {
-                println!(
+                event_output!(
+                    self,
                     "Listener {} closed. Addresses: {:?}",
-                    listener_id, addresses
+                    listener_id,
+                    addresses
                 );
                 match reason {
-                    Ok(_) => println!("Listener closed successfully."),
-                    Err(err) => println!("Listener closed with error: {:?}", err),
+                    Ok(_) => event_output!(self, "Listener closed successfully."),
+                    Err(err) => event_output!(self, "Listener closed with error: {:?}", err),
                 }
                 Ok(())
             }
             SwarmEvent::ListenerError { listener_id, error } => {
-                println!("Listener {} encountered an error: {:?}", listener_id, error);
+                event_output!(
+                    self,
+                    "Listener {} encountered an error: {:?}",
+                    listener_id,
+                    error
+                );
                 Ok(())
             }
             SwarmEvent::Dialing {
                 peer_id,
                 connection_id,
             } => {
-                println!(
+                event_output!(
+                    self,
                     "Node {} is dialing peer {:?} (connection_id: {:?})",
                     self.swarm.local_peer_id(),
                     peer_id,
@@ -241,7 +1042,8 @@ impl SphagnumNode {
                 Ok(())
             }
             SwarmEvent::NewExternalAddrCandidate { address } => {
-                println!(
+                event_output!(
+                    self,
                     "Node {} discovered a new external address: {:?}",
                     self.swarm.local_peer_id(),
                     address
@@ -249,7 +1051,8 @@ impl SphagnumNode {
                 Ok(())
             }
             SwarmEvent::ExternalAddrConfirmed { address } => {
-                println!(
+                event_output!(
+                    self,
                     "Node {} confirmed external address: {:?}",
                     self.swarm.local_peer_id(),
                     address
@@ -257,7 +1060,8 @@ impl SphagnumNode {
                 Ok(())
             }
             SwarmEvent::ExternalAddrExpired { address } => {
-                println!(
+                event_output!(
+                    self,
                     "Node {} detected the expiration of external address: {:?}",
                     self.swarm.local_peer_id(),
                     address
@@ -265,7 +1069,8 @@ impl SphagnumNode {
                 Ok(())
             }
             SwarmEvent::NewExternalAddrOfPeer { peer_id, address } => {
-                println!(
+                event_output!(
+                    self,
                     "Node {} discovered a new address for peer {:?}: {:?}",
                     self.swarm.local_peer_id(),
                     peer_id,
@@ -277,13 +1082,15 @@ impl SphagnumNode {
                 if self.is_pinging_output_enabled {
                     let ping::Event { peer, result, .. } = event;
                     match result {
-                        Ok(rtt) => println!(
+                        Ok(rtt) => event_output!(
+                            self,
                             "Node {} received ping from {}: {:?}",
                             self.swarm.local_peer_id(),
                             peer,
                             rtt
                         ),
-                        Err(e) => println!(
+                        Err(e) => event_output!(
+                            self,
                             "Node {} failed to ping {}: {:?}",
                             self.swarm.local_peer_id(),
                             peer,
@@ -305,141 +1112,48 @@ impl SphagnumNode {
                             request,
                             channel,
                         } => {
-                            println!("Node {} received request from {} (connection: {:?}, request_id: {:?}): {:?}", 
+                            event_output!(self, "Node {} received request from {} (connection: {:?}, request_id: {:?}): {:?}", 
                                     self.swarm.local_peer_id(), peer, connection_id, request_id, request);
 
-                            let command_to_replicate = request.command.clone();
-                            let response = match request.command {
-                                Command::String(StringCommand::Set { key, value }) => {
-                                    match self.data_storage.handle_command(Command::String(
-                                        StringCommand::Set { key, value },
-                                    )) {
-                                        Ok(CommandResult::String(ok)) => {
-                                            if ok == "OK" && !request.is_replication {
-                                                if let Err(e) = self
-                                                    .send_to_replicas(command_to_replicate)
-                                                    .await
-                                                {
-                                                    println!("Replication failed: {:?}", e);
-                                                }
-                                            }
-                                            SphagnumResponse { payload: ok }
-                                        }
-                                        Ok(_) => SphagnumResponse {
-                                            payload: "Unexpected response".to_string(),
-                                        },
-                                        Err(e) => SphagnumResponse {
-                                            payload: format!("Error setting value: {:?}", e),
-                                        },
-                                    }
-                                }
-                                Command::String(StringCommand::Get { key }) => {
-                                    match self
-                                        .data_storage
-                                        .handle_command(Command::String(StringCommand::Get { key }))
-                                    {
-                                        Ok(CommandResult::String(value)) => {
-                                            SphagnumResponse { payload: value }
-                                        }
-                                        Ok(CommandResult::Nil) => SphagnumResponse {
-                                            payload: "nil".to_string(),
-                                        },
-                                        Ok(_) => SphagnumResponse {
-                                            payload: "Unexpected response".to_string(),
-                                        },
-                                        Err(e) => SphagnumResponse {
-                                            payload: format!("Error getting value: {:?}", e),
-                                        },
-                                    }
-                                }
-                                Command::String(StringCommand::Append { key, value }) => {
-                                    match self.data_storage.handle_command(Command::String(
-                                        StringCommand::Append { key, value },
-                                    )) {
-                                        Ok(CommandResult::Int(len)) => {
-                                            if !request.is_replication {
-                                                if let Err(e) = self
-                                                    .send_to_replicas(command_to_replicate)
-                                                    .await
-                                                {
-                                                    println!("Replication failed: {:?}", e);
-                                                }
-                                            }
-                                            SphagnumResponse {
-                                                payload: len.to_string(),
-                                            }
-                                        }
-                                        Ok(_) => SphagnumResponse {
-                                            payload: "Unexpected response".to_string(),
-                                        },
-                                        Err(e) => SphagnumResponse {
-                                            payload: format!("Error appending value: {:?}", e),
-                                        },
-                                    }
-                                }
-                                Command::Generic(GenericCommand::Exists { keys }) => {
-                                    match self.data_storage.handle_command(Command::Generic(
-                                        GenericCommand::Exists { keys },
-                                    )) {
-                                        Ok(CommandResult::Int(count)) => {
-                                            if !request.is_replication {
-                                                if let Err(e) = self
-                                                    .send_to_replicas(command_to_replicate)
-                                                    .await
-                                                {
-                                                    println!("Replication failed: {:?}", e);
-                                                }
-                                            }
-                                            SphagnumResponse {
-                                                payload: count.to_string(),
-                                            }
-                                        }
-                                        Ok(_) => SphagnumResponse {
-                                            payload: "Unexpected response".to_string(),
-                                        },
-                                        Err(e) => SphagnumResponse {
-                                            payload: format!("Error checking existence: {:?}", e),
-                                        },
-                                    }
-                                }
-                                Command::Generic(GenericCommand::Delete { keys }) => {
-                                    match self.data_storage.handle_command(Command::Generic(
-                                        GenericCommand::Delete { keys },
-                                    )) {
-                                        Ok(CommandResult::Int(count)) => {
-                                            if !request.is_replication {
-                                                if let Err(e) = self
-                                                    .send_to_replicas(command_to_replicate)
-                                                    .await
-                                                {
-                                                    println!("Replication failed: {:?}", e);
-                                                }
-                                            }
-                                            SphagnumResponse {
-                                                payload: count.to_string(),
-                                            }
-                                        }
-                                        Ok(_) => SphagnumResponse {
-                                            payload: "Unexpected response".to_string(),
-                                        },
-                                        Err(e) => SphagnumResponse {
-                                            payload: format!("Error deleting keys: {:?}", e),
-                                        },
-                                    }
-                                }
-                            };
+                            if request.is_replication {
+                                self.handle_replication_request(peer, request, channel);
+                                return Ok(());
+                            }
 
-                            self.swarm
-                                .behaviour_mut()
-                                .request_response
-                                .send_response(channel, response)
-                                .unwrap();
+                            let reply = if self.is_shutting_down() {
+                                Self::shutting_down_reply()
+                            } else {
+                                self.execute(request.command).await.into()
+                            };
+                            let response = SphagnumResponse {
+                                request_id: request.request_id,
+                                reply,
+                            };
+                            if let Err(response) = self.respond(channel, response) {
+                                event_output!(
+                                    self,
+                                    "Failed to respond to {}: {:?}",
+                                    peer,
+                                    response
+                                );
+                            }
                         }
                         request_response::Message::Response {
                             request_id,
                             response,
                         } => {
-                            println!("Node {} received response from {} (connection: {:?}, request_id: {:?}): {:?}", 
+                            self.in_flight_replications.remove(&request_id);
+                            if let Some(pending) = self.pending_client_requests.remove(&request_id)
+                            {
+                                let _ = pending.reply.send(
+                                    response
+                                        .reply
+                                        .clone()
+                                        .into_result()
+                                        .map_err(ClientError::Command),
+                                );
+                            }
+                            event_output!(self, "Node {} received response from {} (connection: {:?}, request_id: {:?}): {:?}", 
                                     self.swarm.local_peer_id(), peer, connection_id, request_id, response);
                         }
                     },
@@ -449,8 +1163,19 @@ impl SphagnumNode {
                         request_id,
                         error,
                     } => {
-                        println!("Node {} outbound request to {} (connection: {:?}, request: {:?}) failed: {:?}", 
+                        event_output!(self, "Node {} outbound request to {} (connection: {:?}, request: {:?}) failed: {:?}", 
                                 self.swarm.local_peer_id(), peer, connection_id, request_id, error);
+                        self.retry_replication(request_id);
+                        if let Some(pending) = self.pending_client_requests.remove(&request_id) {
+                            let error = match error {
+                                request_response::OutboundFailure::Timeout => ClientError::Timeout,
+                                request_response::OutboundFailure::ConnectionClosed => {
+                                    ClientError::PeerDisconnected
+                                }
+                                error => ClientError::RequestFailed(error.to_string()),
+                            };
+                            let _ = pending.reply.send(Err(error));
+                        }
                     }
                     request_response::Event::InboundFailure {
                         peer,
@@ -458,15 +1183,18 @@ impl SphagnumNode {
                         request_id,
                         error,
                     } => {
-                        println!("Node {} inbound request from {} (connection: {:?}, request: {:?}) failed: {:?}", 
+                        event_output!(self, "Node {} inbound request from {} (connection: {:?}, request: {:?}) failed: {:?}", 
                                 self.swarm.local_peer_id(), peer, connection_id, request_id, error);
+                        self.unsent_responses = self.unsent_responses.saturating_sub(1);
                     }
                     request_response::Event::ResponseSent {
                         peer,
                         connection_id,
                         request_id,
                     } => {
-                        println!(
+                        self.unsent_responses = self.unsent_responses.saturating_sub(1);
+                        event_output!(
+                            self,
                             "Node {} sent response to {} (connection: {:?}, request: {:?})",
                             self.swarm.local_peer_id(),
                             peer,
@@ -477,11 +1205,8 @@ impl SphagnumNode {
                 }
                 Ok(())
             }
-            _ => {
-                println!(
-                    "Unhandled event for SwarmEvent: {:?}",
-                    self.swarm.select_next_some().await
-                );
+            event => {
+                event_output!(self, "Unhandled event for SwarmEvent: {:?}", event);
                 Ok(())
             }
         }
@@ -493,18 +1218,21 @@ impl SphagnumNode {
         Ok(())
     }
 
+    /// Sends a command to another node and returns the id its response will carry.
     pub async fn send_request_to_sphagnum(
         &mut self,
         peer_id: PeerId,
         command: Command,
-    ) -> Result<OutboundRequestId, Box<dyn Error>> {
+    ) -> Result<RequestId, Box<dyn Error>> {
+        let request_id = self.next_request_id();
         let request = SphagnumRequest {
+            request_id,
             command,
-            payload: String::new(),
+            payload: Bytes::new(),
             is_replication: false, // by default
+            replication_seq: 0,
         };
-        let request_id = self
-            .swarm
+        self.swarm
             .behaviour_mut()
             .request_response
             .send_request(&peer_id, request);
@@ -512,9 +1240,25 @@ impl SphagnumNode {
     }
 }
 
+/// Resolves once the background save finishes, never if there is none.
+async fn background_save_finished(
+    background_save: &mut Option<BackgroundSave>,
+) -> Result<SaveOutcome, JoinError> {
+    match background_save {
+        Some(background_save) => background_save.await,
+        None => std::future::pending().await,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::aof::FsyncPolicy;
+    use crate::core::commands::{
+        generic::GenericCommand, list::ListCommand, string::StringCommand,
+    };
+    use crate::core::config::ConfigError;
+    use crate::core::storage::EngineKind;
 
     #[test]
     fn test_new() {
@@ -533,6 +1277,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_with_config_rejects_persistence_files_with_the_lsm_engine() {
+        // Arrange
+        let mut lsm = NodeConfig::default();
+        lsm.storage.engine = EngineKind::Lsm;
+        lsm.storage.data_dir = Some(std::env::temp_dir().join("sphagnumdb-never-created"));
+        let mut with_aof = lsm.clone();
+        with_aof.persistence.aof_file = Some("node.aof".into());
+        let mut with_snapshot = lsm;
+        with_snapshot.persistence.snapshot_file = Some("node.snapshot".into());
+
+        // Act
+        let with_aof = SphagnumNode::with_config(with_aof);
+        let with_snapshot = SphagnumNode::with_config(with_snapshot);
+
+        // Assert
+        let setting = |result: Result<SphagnumNode, Box<dyn Error>>| match result {
+            Err(e) => match e.downcast_ref::<ConfigError>() {
+                Some(ConfigError::InvalidValue { setting, .. }) => *setting,
+                _ => panic!("Unexpected error: {}", e),
+            },
+            Ok(_) => panic!("The configuration was accepted"),
+        };
+        assert_eq!(setting(with_aof), "persistence.aof_file");
+        assert_eq!(setting(with_snapshot), "persistence.snapshot_file");
+    }
+
+    #[tokio::test]
+    async fn test_failed_replication_is_resent_a_limited_number_of_times() {
+        // Arrange
+        let mut node = SphagnumNode::new().unwrap();
+        let replica = PeerId::random();
+        node.connected_peers.insert(replica);
+        let request = SphagnumRequest {
+            request_id: 1,
+            command: Command::Generic(GenericCommand::DbSize),
+            payload: Bytes::new(),
+            is_replication: true,
+            replication_seq: 0,
+        };
+        node.send_replication_request(replica, request, 1);
+        let fail = |node: &mut SphagnumNode| {
+            let request_id = *node.in_flight_replications.keys().next().unwrap();
+            node.retry_replication(request_id);
+        };
+
+        // Act
+        for _ in 1..MAX_REPLICATION_ATTEMPTS {
+            fail(&mut node);
+        }
+        let attempts: Vec<u32> = node
+            .in_flight_replications
+            .values()
+            .map(|replication| replication.attempts)
+            .collect();
+        fail(&mut node);
+
+        // Assert
+        assert_eq!(attempts, vec![MAX_REPLICATION_ATTEMPTS]);
+        assert!(node.in_flight_replications.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cluster_commands_reject_unusable_arguments() {
+        // Arrange
+        let mut node = SphagnumNode::new().unwrap();
+        node.listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        while node.listeners().next().is_none() {
+            node.handle_event().await.unwrap();
+        }
+        let own_addr = node.listeners().next().unwrap().clone();
+        let own_id = node.peer_id().unwrap();
+
+        // Act
+        let meet = node
+            .execute(Command::Server(ServerCommand::ClusterMeet {
+                addr: own_addr,
+            }))
+            .await;
+        let add_replica = node
+            .execute(Command::Server(ServerCommand::ClusterAddReplica {
+                peer_id: own_id,
+            }))
+            .await;
+
+        // Assert
+        let code = |result: Result<CommandResult, DataStorageError>| match Reply::from(result) {
+            Reply::Error(error) => error.code,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+        assert_eq!(code(meet), ErrorCode::InvalidArgument);
+        assert_eq!(code(add_replica), ErrorCode::InvalidArgument);
+        assert!(node.replica_set.is_empty());
+    }
+
     #[tokio::test]
     async fn test_listen_on_valid_addr() {
         let mut sphagnum = SphagnumNode::new().unwrap();
@@ -588,15 +1428,513 @@ mod tests {
         let mut sphagnum = SphagnumNode::new().unwrap();
         let peer_id = PeerId::random();
         let command = Command::String(StringCommand::Set {
-            key: "key".to_string(),
-            value: "value".to_string(),
+            key: "key".into(),
+            value: "value".into(),
+            expiration: None,
         });
-        let result = sphagnum.send_request_to_sphagnum(peer_id, command).await;
+        let result = sphagnum
+            .send_request_to_sphagnum(peer_id, command.clone())
+            .await;
         assert!(result.is_ok(), "send_request_to_sphagnum should return Ok");
         let request_id = result.unwrap();
-        assert!(
-            request_id.to_string().len() > 0,
-            "Request ID should be non-empty"
+        let next_request_id = sphagnum
+            .send_request_to_sphagnum(peer_id, command)
+            .await
+            .unwrap();
+        assert_ne!(
+            request_id, next_request_id,
+            "Each request should get its own ID"
         );
     }
+
+    /// Handles the events of the node in the background until the test ends.
+    fn run(mut sphagnum: SphagnumNode) {
+        tokio::spawn(async move {
+            loop {
+                let _ = sphagnum.handle_event().await;
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_client_returns_results_of_commands_on_the_local_node() {
+        // Arrange
+        let sphagnum = SphagnumNode::new().unwrap();
+        let client = sphagnum.client(sphagnum.peer_id().unwrap());
+        run(sphagnum);
+
+        // Act
+        let set = client
+            .execute(Command::String(StringCommand::Set {
+                key: "key".into(),
+                value: "value".into(),
+                expiration: None,
+            }))
+            .await;
+        let get = client
+            .execute(Command::String(StringCommand::Get { key: "key".into() }))
+            .await;
+        let incr = client
+            .execute(Command::String(StringCommand::Incr { key: "key".into() }))
+            .await;
+
+        // Assert
+        assert_eq!(set, Ok(CommandResult::String("OK".into())));
+        assert_eq!(get, Ok(CommandResult::String("value".into())));
+        assert!(matches!(
+            incr,
+            Err(ClientError::Command(ReplyError {
+                code: ErrorCode::NotAnInteger,
+                ..
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_request_to_unreachable_peer_fails() {
+        // Arrange
+        let sphagnum = SphagnumNode::new().unwrap();
+        let client = sphagnum.client(PeerId::random());
+        run(sphagnum);
+
+        // Act
+        let result = client
+            .execute(Command::String(StringCommand::Get { key: "key".into() }))
+            .await;
+
+        // Assert
+        assert!(matches!(result, Err(ClientError::RequestFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_client_times_out_and_detects_stopped_node() {
+        // Arrange
+        let sphagnum = SphagnumNode::new().unwrap();
+        let client = sphagnum
+            .client(sphagnum.peer_id().unwrap())
+            .with_timeout(Duration::from_millis(50));
+        let command = Command::String(StringCommand::Get { key: "key".into() });
+
+        // Act
+        let idle = client.execute(command.clone()).await;
+        drop(sphagnum);
+        let stopped = client.execute(command).await;
+
+        // Assert
+        assert_eq!(idle, Err(ClientError::Timeout));
+        assert_eq!(stopped, Err(ClientError::NodeStopped));
+    }
+
+    #[tokio::test]
+    async fn test_spawned_nodes_connect_to_bootstrap_peers_and_execute_commands() {
+        // Arrange
+        let first = SphagnumNode::spawn(NodeConfig {
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            ..NodeConfig::default()
+        })
+        .unwrap();
+        let mut listeners = first.listeners().await.unwrap();
+        while listeners.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            listeners = first.listeners().await.unwrap();
+        }
+        let second = SphagnumNode::spawn(NodeConfig {
+            bootstrap_peers: listeners,
+            ..NodeConfig::default()
+        })
+        .unwrap();
+
+        // Act
+        let mut connected = second.connected_peers().await.unwrap();
+        while connected.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            connected = second.connected_peers().await.unwrap();
+        }
+        let set = second
+            .client(first.peer_id())
+            .execute(Command::String(StringCommand::Set {
+                key: "key".into(),
+                value: "value".into(),
+                expiration: None,
+            }))
+            .await;
+        let get = first
+            .execute(Command::String(StringCommand::Get { key: "key".into() }))
+            .await;
+
+        // Assert
+        assert_eq!(connected, vec![first.peer_id()]);
+        assert_eq!(set, Ok(CommandResult::String("OK".into())));
+        assert_eq!(get, Ok(CommandResult::String("value".into())));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_the_node_for_all_handles() {
+        // Arrange
+        let handle = SphagnumNode::spawn(NodeConfig::default()).unwrap();
+        let other_handle = handle.clone();
+
+        // Act
+        handle.shutdown().await.unwrap();
+
+        // Assert
+        assert_eq!(
+            other_handle.connected_peers().await,
+            Err(NodeError::Stopped)
+        );
+        assert_eq!(
+            other_handle
+                .execute(Command::String(StringCommand::Get { key: "key".into() }))
+                .await,
+            Err(ClientError::NodeStopped)
+        );
+        assert_eq!(other_handle.shutdown().await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_command_stops_the_node_and_closes_connections() {
+        // Arrange
+        let first = SphagnumNode::spawn(NodeConfig {
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            ..NodeConfig::default()
+        })
+        .unwrap();
+        let mut listeners = first.listeners().await.unwrap();
+        while listeners.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            listeners = first.listeners().await.unwrap();
+        }
+        let second = SphagnumNode::spawn(NodeConfig {
+            bootstrap_peers: listeners,
+            ..NodeConfig::default()
+        })
+        .unwrap();
+        while second.connected_peers().await.unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Act
+        let reply = second
+            .client(first.peer_id())
+            .execute(Command::Server(ServerCommand::Shutdown))
+            .await;
+        while first.connected_peers().await.is_ok() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Assert
+        assert_eq!(reply, Ok(CommandResult::String("OK".into())));
+        assert_eq!(second.connected_peers().await, Ok(Vec::new()));
+        second.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_returns_the_peer_and_cluster_commands_change_membership() {
+        // Arrange
+        let first = SphagnumNode::spawn(NodeConfig {
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            ..NodeConfig::default()
+        })
+        .unwrap();
+        let mut listeners = first.listeners().await.unwrap();
+        while listeners.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            listeners = first.listeners().await.unwrap();
+        }
+        let second = SphagnumNode::spawn(NodeConfig::default()).unwrap();
+
+        // Act
+        let connected = second.connect(listeners[0].clone()).await;
+        let client = second.client(first.peer_id());
+        let added = client
+            .execute(Command::Server(ServerCommand::ClusterAddReplica {
+                peer_id: second.peer_id(),
+            }))
+            .await;
+        let replicas = client
+            .execute(Command::Server(ServerCommand::ClusterReplicas))
+            .await;
+        let peers = client
+            .execute(Command::Server(ServerCommand::ClusterPeers))
+            .await;
+        let unreachable = second
+            .connect("/ip4/127.0.0.1/tcp/1".parse().unwrap())
+            .await;
+
+        // Assert
+        let second_id = CommandResult::String(second.peer_id().to_string().into());
+        assert_eq!(connected, Ok(first.peer_id()));
+        assert_eq!(added, Ok(CommandResult::String("OK".into())));
+        assert_eq!(replicas, Ok(CommandResult::Array(vec![second_id.clone()])));
+        assert_eq!(peers, Ok(CommandResult::Array(vec![second_id])));
+        assert!(matches!(unreachable, Err(NodeError::Rejected(_))));
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutting_down_node_rejects_new_requests() {
+        // Arrange
+        let mut sphagnum = SphagnumNode::new().unwrap();
+        let target = sphagnum.peer_id().unwrap();
+        let (reply, response) = oneshot::channel();
+        sphagnum.begin_shutdown();
+
+        // Act
+        sphagnum
+            .handle_client_request(ClientRequest {
+                target,
+                command: Command::String(StringCommand::Get { key: "key".into() }),
+                reply,
+            })
+            .await;
+
+        // Assert
+        assert!(matches!(
+            response.await.unwrap(),
+            Err(ClientError::Command(ReplyError {
+                code: ErrorCode::ShuttingDown,
+                ..
+            }))
+        ));
+        assert!(sphagnum.is_drained());
+    }
+
+    #[tokio::test]
+    async fn test_with_config_applies_passport_and_client_timeout() {
+        // Arrange
+        let config = NodeConfig::builder()
+            .passport_field("forest")
+            .client_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        // Act
+        let sphagnum = SphagnumNode::with_config(config).unwrap();
+        let result = sphagnum
+            .client(sphagnum.peer_id().unwrap())
+            .execute(Command::String(StringCommand::Get { key: "key".into() }))
+            .await;
+
+        // Assert
+        assert_eq!(
+            sphagnum.get_passport().unwrap().get_field().unwrap(),
+            "forest"
+        );
+        assert_eq!(result, Err(ClientError::Timeout));
+    }
+
+    #[tokio::test]
+    async fn test_identity_file_keeps_peer_id_across_restarts() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!(
+            "sphagnumdb-node-{}-{}.key",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let config = NodeConfig::builder().identity_file(&path).build().unwrap();
+
+        // Act
+        let first = SphagnumNode::with_config(config.clone()).unwrap();
+        let restarted = SphagnumNode::with_config(config).unwrap();
+
+        // Assert
+        assert!(path.exists());
+        assert_eq!(first.peer_id().unwrap(), restarted.peer_id().unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writes_survive_restart_with_append_only_log() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!(
+            "sphagnumdb-node-{}-{}.aof",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let config = NodeConfig::builder()
+            .aof_file(&path)
+            .aof_fsync(FsyncPolicy::Always)
+            .event_output(false)
+            .build()
+            .unwrap();
+        let node = SphagnumNode::spawn(config.clone()).unwrap();
+        node.execute(Command::String(StringCommand::Set {
+            key: "key".into(),
+            value: "value".into(),
+            expiration: None,
+        }))
+        .await
+        .unwrap();
+        node.execute(Command::String(StringCommand::Append {
+            key: "key".into(),
+            value: "!".into(),
+        }))
+        .await
+        .unwrap();
+        node.shutdown().await.unwrap();
+
+        // Act
+        let restarted = SphagnumNode::spawn(config).unwrap();
+        let value = restarted
+            .execute(Command::String(StringCommand::Get { key: "key".into() }))
+            .await;
+
+        // Assert
+        assert_eq!(value, Ok(CommandResult::String("value!".into())));
+        restarted.shutdown().await.unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_that_cannot_be_logged_is_not_applied() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!(
+            "sphagnumdb-node-{}-{}.aof",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let config = NodeConfig::builder()
+            .aof_file(&path)
+            .aof_fsync(FsyncPolicy::Always)
+            .event_output(false)
+            .build()
+            .unwrap();
+        let mut node = SphagnumNode::with_config(config).unwrap();
+        let rpush = |value: &str| {
+            Command::List(ListCommand::RPush {
+                key: "list".into(),
+                values: vec![value.into()],
+            })
+        };
+        node.execute(rpush("a")).await.unwrap();
+        let logged = node.aof.as_ref().unwrap().offset();
+        node.aof.as_mut().unwrap().fail_writes();
+
+        // Act
+        let pushed = node.execute(rpush("b")).await;
+        let len = node
+            .execute(Command::List(ListCommand::Len { key: "list".into() }))
+            .await;
+
+        // Assert
+        assert!(matches!(pushed, Err(DataStorageError::PersistenceError(_))));
+        assert_eq!(len.unwrap(), CommandResult::Int(1));
+        assert_eq!(node.aof.as_ref().unwrap().offset(), logged);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), logged);
+        assert_eq!(node.unsaved_writes, 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_background_save_is_loaded_on_restart() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!(
+            "sphagnumdb-node-{}-{}.snapshot",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let config = NodeConfig::builder()
+            .snapshot_file(&path)
+            .event_output(false)
+            .build()
+            .unwrap();
+        let node = SphagnumNode::spawn(config.clone()).unwrap();
+        node.execute(Command::List(ListCommand::RPush {
+            key: "list".into(),
+            values: vec!["a".into(), "b".into()],
+        }))
+        .await
+        .unwrap();
+        node.execute(Command::Generic(GenericCommand::Expire {
+            key: "list".into(),
+            seconds: 60,
+        }))
+        .await
+        .unwrap();
+        let started = node
+            .execute(Command::Server(ServerCommand::BgSave))
+            .await
+            .unwrap();
+        let mut last_save = CommandResult::Int(0);
+        while last_save == CommandResult::Int(0) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            last_save = node
+                .execute(Command::Server(ServerCommand::LastSave))
+                .await
+                .unwrap();
+        }
+        let saved = std::fs::read(&path).unwrap();
+        node.shutdown().await.unwrap();
+
+        // Act
+        let restarted = SphagnumNode::spawn(config).unwrap();
+        let len = restarted
+            .execute(Command::List(ListCommand::Len { key: "list".into() }))
+            .await;
+        let ttl = restarted
+            .execute(Command::Generic(GenericCommand::Ttl { key: "list".into() }))
+            .await;
+        let restarted_last_save = restarted
+            .execute(Command::Server(ServerCommand::LastSave))
+            .await;
+
+        // Assert
+        assert_eq!(
+            started,
+            CommandResult::String("Background saving started".into())
+        );
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            saved,
+            "nothing was written since"
+        );
+        assert_eq!(len, Ok(CommandResult::Int(2)));
+        assert!(matches!(ttl, Ok(CommandResult::Int(seconds)) if seconds > 0 && seconds <= 60));
+        assert_eq!(restarted_last_save, Ok(last_save));
+        restarted.shutdown().await.unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_log_is_replayed_after_the_snapshot_only() {
+        // Arrange
+        let dir = std::env::temp_dir();
+        let name = format!(
+            "sphagnumdb-node-{}-{}",
+            std::process::id(),
+            rand::random::<u64>()
+        );
+        let aof_path = dir.join(format!("{}.aof", name));
+        let snapshot_path = dir.join(format!("{}.snapshot", name));
+        let config = NodeConfig::builder()
+            .aof_file(&aof_path)
+            .aof_fsync(FsyncPolicy::Always)
+            .snapshot_file(&snapshot_path)
+            .event_output(false)
+            .build()
+            .unwrap();
+        let append = || {
+            Command::String(StringCommand::Append {
+                key: "key".into(),
+                value: "!".into(),
+            })
+        };
+        let mut node = SphagnumNode::with_config(config.clone()).unwrap();
+        node.execute(append()).await.unwrap();
+        let saved = node.execute(Command::Server(ServerCommand::Save)).await;
+        node.execute(append()).await.unwrap();
+        // A crash: the node doesn't stop, so it doesn't save the last write.
+        drop(node);
+
+        // Act
+        let mut restarted = SphagnumNode::with_config(config).unwrap();
+        let value = restarted.execute(Command::String(StringCommand::Get { key: "key".into() }));
+
+        // Assert
+        assert_eq!(saved.unwrap(), CommandResult::String("OK".into()));
+        assert_eq!(value.await.unwrap(), CommandResult::String("!!".into()));
+        std::fs::remove_file(aof_path).unwrap();
+        std::fs::remove_file(snapshot_path).unwrap();
+    }
 }
@@ -0,0 +1,1181 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    iter::{self, Peekable},
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::{
+    after_end, before_start, is_empty_range, EngineError, KeyIter, Record, RecordIter,
+    StorageEngine,
+};
+use crate::core::bytes::Bytes;
+use crate::core::snapshot::{decode_value, encode_value};
+
+/// How many bytes of writes the memtable takes before it is flushed to a table by default.
+pub const DEFAULT_MEMTABLE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Identifies the write-ahead log, the last two bytes are its version.
+const WAL_MAGIC: &[u8; 8] = b"SPHWAL01";
+
+/// Identifies a table, at its start and at its end.
+const TABLE_MAGIC: &[u8; 8] = b"SPHSST01";
+
+/// The first line of the manifest, the other lines name the live tables, newest first.
+const MANIFEST_HEADER: &str = "SPHLSM01";
+
+const WAL_FILE: &str = "wal.log";
+const MANIFEST_FILE: &str = "MANIFEST";
+const TABLE_EXTENSION: &str = "sst";
+
+/// Every record of the log and of a table starts with the length and the CRC-32 of its payload,
+/// both little-endian, the same way the append-only log frames its records.
+const RECORD_HEADER_LEN: u64 = 8;
+
+/// A table ends with the offsets of its index and Bloom filter, their CRC-32 and the magic.
+const FOOTER_LEN: u64 = 8 + 8 + 4 + TABLE_MAGIC.len() as u64;
+
+/// A table keeps every this many keys in its index, a lookup reads at most this many records.
+const INDEX_INTERVAL: usize = 16;
+
+/// Once there are more tables than this, they are all merged into one.
+const MAX_TABLES: usize = 4;
+
+/// About 1% false positives.
+const BLOOM_BITS_PER_KEY: usize = 10;
+const BLOOM_HASHES: u32 = 7;
+
+const KIND_DELETE: u8 = 0;
+const KIND_PUT: u8 = 1;
+
+/// A log-structured merge tree in a directory of its own.
+///
+/// Writes are appended to the write-ahead log and applied to the memtable, an ordered map held
+/// in memory. Once the log grows past the memtable size, the memtable is written out as an
+/// immutable sorted table and the log starts over. Reads look into the memtable, then into the
+/// tables from the newest to the oldest, a deletion is recorded as a tombstone until a
+/// compaction merges the tables into one and drops it.
+///
+/// Every table keeps a sparse index of its keys and a Bloom filter in memory, so a lookup reads
+/// a few records of the tables that may hold the key. The manifest names the live tables and is
+/// replaced atomically, so a crash during a flush or a compaction leaves the previous tables in
+/// place, and the files nobody lists are removed when the engine opens.
+///
+/// A write reaches the operating system before the command completes, so it survives a crash of
+/// the node. The node syncs the log once a second, see `StorageEngine::sync`.
+///
+/// ```text
+/// record: len:u32 crc32:u32 payload
+/// payload: kind:u8 key_len:u32 key [deadline:u64 accessed:u64 value]
+/// table: "SPHSST01" records index bloom index_offset:u64 bloom_offset:u64 crc32:u32 "SPHSST01"
+/// index: count:u32 count * (key_len:u32 key offset:u64)
+/// ```
+///
+/// Values are encoded the way snapshots hold them, a deadline of 0 means the key doesn't expire.
+#[derive(Debug)]
+pub struct LsmEngine {
+    dir: PathBuf,
+    wal_path: PathBuf,
+    wal: File,
+    /// The size of the log, the memtable is flushed once it exceeds `memtable_size`.
+    wal_len: u64,
+    memtable_size: usize,
+    /// Whether the log was written since the last sync.
+    unsynced: bool,
+    /// None is a deletion, which hides the key in the tables.
+    memtable: BTreeMap<Bytes, Option<Record>>,
+    /// Newest first.
+    tables: Vec<Table>,
+    next_table: u64,
+}
+
+impl LsmEngine {
+    /// Opens the engine in `dir`, creating it if it doesn't exist, and replays the log into the
+    /// memtable. A truncated tail of the log, e.g. a record the node was writing when it crashed,
+    /// is cut off.
+    pub fn open(dir: impl AsRef<Path>, memtable_size: usize) -> Result<Self, EngineError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(EngineError::io(&dir))?;
+
+        let names = read_manifest(&dir)?;
+        remove_unlisted_files(&dir, &names)?;
+        let mut tables = Vec::with_capacity(names.len());
+        let mut next_table = 0;
+        for name in &names {
+            let table = Table::open(dir.join(name))?;
+            next_table = next_table.max(table.id + 1);
+            tables.push(table);
+        }
+
+        let wal_path = dir.join(WAL_FILE);
+        let mut memtable = BTreeMap::new();
+        let (wal, wal_len) = open_wal(&wal_path, &mut memtable)?;
+        Ok(Self {
+            dir,
+            wal_path,
+            wal,
+            wal_len,
+            memtable_size,
+            unsynced: false,
+            memtable,
+            tables,
+            next_table,
+        })
+    }
+
+    /// Appends the write to the log and applies it to the memtable.
+    fn write(&mut self, key: &[u8], record: Option<Record>) -> Result<(), EngineError> {
+        let payload =
+            encode_entry(key, record.as_ref()).map_err(EngineError::io(&self.wal_path))?;
+        let framed = frame(&payload);
+        self.wal
+            .write_all(&framed)
+            .map_err(EngineError::io(&self.wal_path))?;
+        self.wal_len += framed.len() as u64;
+        self.unsynced = true;
+        self.memtable.insert(Bytes::from(key), record);
+        if self.wal_len > self.memtable_size as u64 {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the memtable out as the newest table and empties the log, then compacts the
+    /// tables if there are too many.
+    fn flush(&mut self) -> Result<(), EngineError> {
+        if self.memtable.is_empty() {
+            return Ok(());
+        }
+        let entries = self.memtable.iter().map(|(key, record)| {
+            let payload =
+                encode_entry(key, record.as_ref()).map_err(EngineError::io(&self.wal_path))?;
+            Ok((key.clone(), payload))
+        });
+        let table = Table::write(&self.dir, self.next_table, entries)?;
+        self.next_table += 1;
+        self.tables.insert(0, table);
+        write_manifest(&self.dir, &self.tables)?;
+
+        // The table holds everything the log held, so the log starts over.
+        let io_error = EngineError::io(&self.wal_path);
+        self.wal.set_len(WAL_MAGIC.len() as u64).map_err(io_error)?;
+        self.wal
+            .sync_data()
+            .map_err(EngineError::io(&self.wal_path))?;
+        self.wal_len = WAL_MAGIC.len() as u64;
+        self.unsynced = false;
+        self.memtable.clear();
+
+        if self.tables.len() > MAX_TABLES {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Merges all tables into one. Nothing older remains, so the tombstones are dropped.
+    fn compact(&mut self) -> Result<(), EngineError> {
+        let sources = self
+            .tables
+            .iter()
+            .map(|table| table.entries(Bound::Unbounded))
+            .collect();
+        let entries = Merge::new(sources, false)
+            .filter(|entry| !matches!(entry, Ok((_, payload)) if payload[0] == KIND_DELETE));
+        let table = Table::write(&self.dir, self.next_table, entries)?;
+        self.next_table += 1;
+        let merged = std::mem::replace(&mut self.tables, vec![table]);
+        write_manifest(&self.dir, &self.tables)?;
+        for table in merged {
+            fs::remove_file(&table.path).map_err(EngineError::io(&table.path))?;
+        }
+        Ok(())
+    }
+
+    /// Merges the memtable and the tables within the bounds, without the deleted keys, in order
+    /// or from the highest key with `rev`.
+    /// `from_memtable` and `from_table` convert a record to the item, the latter from its payload.
+    fn merged<'a, T: 'a>(
+        &'a self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        rev: bool,
+        from_memtable: impl Fn(&Record) -> T + 'a,
+        from_table: impl Fn(RecordRef) -> Result<T, String> + Copy + 'a,
+    ) -> Box<dyn Iterator<Item = Result<(Bytes, T), EngineError>> + 'a> {
+        if is_empty_range(start, end) {
+            return Box::new(iter::empty());
+        }
+        let memtable = self
+            .memtable
+            .range::<[u8], _>((start, end))
+            .map(move |(key, record)| Ok((key.clone(), record.as_ref().map(&from_memtable))));
+        let mut sources: Vec<Source<'a, Option<T>>> = if rev {
+            vec![Box::new(memtable.rev())]
+        } else {
+            vec![Box::new(memtable)]
+        };
+        for table in &self.tables {
+            let path = &table.path;
+            let entries = if rev {
+                table.entries_rev(end)
+            } else {
+                table.entries(start)
+            };
+            let entries = entries.map(move |entry| {
+                let (key, payload) = entry?;
+                let item = EntryRef::parse(&payload)
+                    .and_then(|entry| entry.record.map(from_table).transpose())
+                    .map_err(|reason| EngineError::corrupted(path, reason))?;
+                Ok((key, item))
+            });
+            sources.push(Box::new(entries));
+        }
+        let (start, end) = (start.map(Bytes::from), end.map(Bytes::from));
+        Box::new(
+            Merge::new(sources, rev)
+                .take_while(move |entry| {
+                    entry.as_ref().map_or(true, |(key, _)| {
+                        if rev {
+                            !before_start(borrowed(&start), key)
+                        } else {
+                            !after_end(borrowed(&end), key)
+                        }
+                    })
+                })
+                .filter_map(|entry| match entry {
+                    Ok((key, Some(item))) => Some(Ok((key, item))),
+                    Ok((_, None)) => None,
+                    Err(e) => Some(Err(e)),
+                }),
+        )
+    }
+}
+
+impl StorageEngine for LsmEngine {
+    fn get(&self, key: &[u8]) -> Result<Option<Record>, EngineError> {
+        if let Some(record) = self.memtable.get(key) {
+            return Ok(record.clone());
+        }
+        for table in &self.tables {
+            if let Some(record) = table.get(key)? {
+                return Ok(record);
+            }
+        }
+        Ok(None)
+    }
+
+    fn put(&mut self, key: &[u8], record: Record) -> Result<(), EngineError> {
+        self.write(key, Some(record))
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<(), EngineError> {
+        self.write(key, None)
+    }
+
+    /// Only the access times of the keys in the memtable are updated, the others keep the time
+    /// they were last written at.
+    fn touch(&mut self, key: &[u8], accessed: u64) -> Result<(), EngineError> {
+        if let Some(Some(record)) = self.memtable.get_mut(key) {
+            record.accessed = accessed;
+        }
+        Ok(())
+    }
+
+    fn range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, rev: bool) -> RecordIter<'_> {
+        self.merged(start, end, rev, Record::clone, |record| record.decode())
+    }
+
+    fn keys(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, rev: bool) -> KeyIter<'_> {
+        self.merged(
+            start,
+            end,
+            rev,
+            |record| record.deadline,
+            |record| Ok(record.deadline),
+        )
+    }
+
+    fn sync(&mut self) -> Result<(), EngineError> {
+        if self.unsynced {
+            self.wal
+                .sync_data()
+                .map_err(EngineError::io(&self.wal_path))?;
+            self.unsynced = false;
+        }
+        Ok(())
+    }
+}
+
+type Source<'a, T> = Box<dyn Iterator<Item = Result<(Bytes, T), EngineError>> + 'a>;
+
+/// Merges iterators ordered by key into one, the first iterator holding a key wins.
+/// The memtable comes first and the tables follow from the newest, so the latest write wins.
+/// With `rev` the iterators go from the highest key, and so does the merge.
+struct Merge<'a, T> {
+    sources: Vec<Peekable<Source<'a, T>>>,
+    rev: bool,
+}
+
+impl<'a, T> Merge<'a, T> {
+    fn new(sources: Vec<Source<'a, T>>, rev: bool) -> Self {
+        Self {
+            sources: sources.into_iter().map(Iterator::peekable).collect(),
+            rev,
+        }
+    }
+}
+
+impl<T> Iterator for Merge<'_, T> {
+    type Item = Result<(Bytes, T), EngineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The key that comes first is the smallest one, or the largest one going backwards.
+        let precedes = if self.rev {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+        let mut first: Option<Bytes> = None;
+        for source in &mut self.sources {
+            match source.peek() {
+                Some(Ok((key, _)))
+                    if first
+                        .as_ref()
+                        .is_none_or(|first| key.cmp(first) == precedes) =>
+                {
+                    first = Some(key.clone());
+                }
+                Some(Ok(_)) | None => {}
+                Some(Err(_)) => return source.next(),
+            }
+        }
+        let first = first?;
+        let mut winner = None;
+        for source in &mut self.sources {
+            if matches!(source.peek(), Some(Ok((key, _))) if *key == first) {
+                let entry = source.next();
+                winner = winner.or(entry);
+            }
+        }
+        winner
+    }
+}
+
+/// An entry of the log or of a table, with the value still encoded.
+struct EntryRef<'a> {
+    key: &'a [u8],
+    /// None for a deletion.
+    record: Option<RecordRef<'a>>,
+}
+
+struct RecordRef<'a> {
+    deadline: Option<u64>,
+    accessed: u64,
+    value: &'a [u8],
+}
+
+impl<'a> EntryRef<'a> {
+    fn parse(payload: &'a [u8]) -> Result<Self, String> {
+        let truncated = || "truncated entry".to_string();
+        let (&kind, rest) = payload.split_first().ok_or_else(truncated)?;
+        let (key_len, rest) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+        let key_len = u32::from_le_bytes(*key_len) as usize;
+        if rest.len() < key_len {
+            return Err(truncated());
+        }
+        let (key, rest) = rest.split_at(key_len);
+        let record = match kind {
+            KIND_DELETE if rest.is_empty() => None,
+            KIND_PUT => {
+                let (deadline, rest) = rest.split_first_chunk::<8>().ok_or_else(truncated)?;
+                let (accessed, value) = rest.split_first_chunk::<8>().ok_or_else(truncated)?;
+                let deadline = u64::from_le_bytes(*deadline);
+                Some(RecordRef {
+                    deadline: (deadline != 0).then_some(deadline),
+                    accessed: u64::from_le_bytes(*accessed),
+                    value,
+                })
+            }
+            kind => return Err(format!("unknown entry kind {}", kind)),
+        };
+        Ok(EntryRef { key, record })
+    }
+}
+
+impl RecordRef<'_> {
+    fn decode(&self) -> Result<Record, String> {
+        let value = decode_value(self.value).map_err(|e| e.to_string())?;
+        Ok(Record {
+            value: Arc::new(value),
+            deadline: self.deadline,
+            accessed: self.accessed,
+        })
+    }
+}
+
+fn encode_entry(key: &[u8], record: Option<&Record>) -> io::Result<Vec<u8>> {
+    let key_len = u32::try_from(key.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "keys longer than 4 GiB can't be stored",
+        )
+    })?;
+    let mut payload = Vec::with_capacity(1 + 4 + key.len() + 16);
+    payload.push(if record.is_some() {
+        KIND_PUT
+    } else {
+        KIND_DELETE
+    });
+    payload.extend_from_slice(&key_len.to_le_bytes());
+    payload.extend_from_slice(key);
+    if let Some(record) = record {
+        payload.extend_from_slice(&record.deadline.unwrap_or(0).to_le_bytes());
+        payload.extend_from_slice(&record.accessed.to_le_bytes());
+        payload.extend_from_slice(&encode_value(&record.value)?);
+    }
+    Ok(payload)
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(RECORD_HEADER_LEN as usize + payload.len());
+    framed.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    framed.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    framed.extend_from_slice(payload);
+    framed
+}
+
+enum Frame {
+    Payload(Vec<u8>),
+    /// The file ends before the record does.
+    Truncated,
+    /// The record is complete, but its checksum doesn't match.
+    Mismatch {
+        len: u64,
+    },
+}
+
+/// Reads the record at the position of the reader, `available` bytes are left in the file.
+fn read_frame(reader: &mut impl Read, available: u64) -> io::Result<Frame> {
+    if available < RECORD_HEADER_LEN {
+        return Ok(Frame::Truncated);
+    }
+    let mut header = [0; RECORD_HEADER_LEN as usize];
+    reader.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as u64;
+    let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if available - RECORD_HEADER_LEN < len {
+        return Ok(Frame::Truncated);
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    if crc32fast::hash(&payload) != checksum {
+        return Ok(Frame::Mismatch { len });
+    }
+    Ok(Frame::Payload(payload))
+}
+
+/// Opens the log and applies its records to the memtable, returns the log and its length.
+fn open_wal(
+    path: &Path,
+    memtable: &mut BTreeMap<Bytes, Option<Record>>,
+) -> Result<(File, u64), EngineError> {
+    let io_error = EngineError::io(path);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)
+        .map_err(&io_error)?;
+    let file_len = file.metadata().map_err(&io_error)?.len();
+    if file_len < WAL_MAGIC.len() as u64 {
+        // A new log, or one that crashed before its header was written.
+        let mut header = vec![0; file_len as usize];
+        file.read_exact(&mut header).map_err(&io_error)?;
+        if !WAL_MAGIC.starts_with(&header) {
+            return Err(EngineError::corrupted(path, "not a write-ahead log"));
+        }
+        file.set_len(0).map_err(&io_error)?;
+        file.write_all(WAL_MAGIC).map_err(&io_error)?;
+        file.sync_all().map_err(&io_error)?;
+        return Ok((file, WAL_MAGIC.len() as u64));
+    }
+
+    let mut reader = BufReader::new(&mut file);
+    let mut magic = [0; WAL_MAGIC.len()];
+    reader.read_exact(&mut magic).map_err(&io_error)?;
+    if &magic != WAL_MAGIC {
+        return Err(EngineError::corrupted(path, "not a write-ahead log"));
+    }
+    let mut valid_len = WAL_MAGIC.len() as u64;
+    while valid_len < file_len {
+        let payload = match read_frame(&mut reader, file_len - valid_len).map_err(&io_error)? {
+            Frame::Payload(payload) => payload,
+            Frame::Truncated => break,
+            Frame::Mismatch { len } if valid_len + RECORD_HEADER_LEN + len == file_len => break,
+            Frame::Mismatch { .. } => {
+                return Err(EngineError::corrupted(
+                    path,
+                    format!("checksum mismatch at byte {}", valid_len),
+                ))
+            }
+        };
+        let entry = EntryRef::parse(&payload)
+            .and_then(|entry| Ok((entry.key, entry.record.map(|r| r.decode()).transpose()?)))
+            .map_err(|reason| EngineError::corrupted(path, reason))?;
+        memtable.insert(Bytes::from(entry.0), entry.1);
+        valid_len += RECORD_HEADER_LEN + payload.len() as u64;
+    }
+    drop(reader);
+    if valid_len < file_len {
+        file.set_len(valid_len).map_err(&io_error)?;
+        file.sync_all().map_err(&io_error)?;
+    }
+    Ok((file, valid_len))
+}
+
+/// Returns the file names of the live tables, newest first.
+fn read_manifest(dir: &Path) -> Result<Vec<String>, EngineError> {
+    let path = dir.join(MANIFEST_FILE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(EngineError::Io(path, e)),
+    };
+    let mut lines = contents.lines();
+    if lines.next() != Some(MANIFEST_HEADER) {
+        return Err(EngineError::corrupted(&path, "not a manifest"));
+    }
+    lines
+        .map(|name| match table_id(name) {
+            Some(_) => Ok(name.to_string()),
+            None => Err(EngineError::corrupted(
+                &path,
+                format!("{:?} is not a table", name),
+            )),
+        })
+        .collect()
+}
+
+/// Replaces the manifest with one listing the tables, only once it is on disk.
+fn write_manifest(dir: &Path, tables: &[Table]) -> Result<(), EngineError> {
+    let path = dir.join(MANIFEST_FILE);
+    let temp_path = dir.join(format!("{}.tmp", MANIFEST_FILE));
+    let mut contents = format!("{}\n", MANIFEST_HEADER);
+    for table in tables {
+        contents.push_str(&table_name(table.id));
+        contents.push('\n');
+    }
+    let io_error = EngineError::io(&temp_path);
+    let mut file = File::create(&temp_path).map_err(&io_error)?;
+    file.write_all(contents.as_bytes()).map_err(&io_error)?;
+    file.sync_all().map_err(&io_error)?;
+    fs::rename(&temp_path, &path).map_err(EngineError::io(&path))?;
+    sync_dir(dir);
+    Ok(())
+}
+
+/// Removes the tables a flush or a compaction left behind when the node crashed.
+fn remove_unlisted_files(dir: &Path, tables: &[String]) -> Result<(), EngineError> {
+    let io_error = EngineError::io(dir);
+    for entry in fs::read_dir(dir).map_err(&io_error)? {
+        let path = entry.map_err(&io_error)?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let unlisted = table_id(name).is_some() && !tables.iter().any(|table| table == name);
+        if unlisted || name.ends_with(".tmp") {
+            fs::remove_file(&path).map_err(EngineError::io(&path))?;
+        }
+    }
+    Ok(())
+}
+
+/// The rename of a file is only durable once the directory is synced. Not every platform can
+/// open a directory, the file is complete either way.
+fn sync_dir(dir: &Path) {
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+fn table_name(id: u64) -> String {
+    format!("{:06}.{}", id, TABLE_EXTENSION)
+}
+
+fn table_id(name: &str) -> Option<u64> {
+    let (id, extension) = name.split_once('.')?;
+    if extension != TABLE_EXTENSION || !id.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    id.parse().ok()
+}
+
+fn borrowed(bound: &Bound<Bytes>) -> Bound<&[u8]> {
+    bound.as_ref().map(|key| key.as_slice())
+}
+
+/// An immutable sorted run of entries on disk, with its index and Bloom filter in memory.
+#[derive(Debug)]
+struct Table {
+    id: u64,
+    path: PathBuf,
+    file: File,
+    /// Every `INDEX_INTERVAL`th key with the offset of its record.
+    index: Vec<(Bytes, u64)>,
+    bloom: Bloom,
+    /// Where the records end and the index starts.
+    data_end: u64,
+}
+
+impl Table {
+    /// Writes the entries, ordered by key, to a new table and opens it. The table only gets its
+    /// name once it is complete and on disk.
+    fn write(
+        dir: &Path,
+        id: u64,
+        entries: impl Iterator<Item = Result<(Bytes, Vec<u8>), EngineError>>,
+    ) -> Result<Table, EngineError> {
+        let path = dir.join(table_name(id));
+        let temp_path = dir.join(format!("{}.tmp", table_name(id)));
+        let io_error = EngineError::io(&temp_path);
+        let mut writer = BufWriter::new(File::create(&temp_path).map_err(&io_error)?);
+        writer.write_all(TABLE_MAGIC).map_err(&io_error)?;
+
+        let mut offset = TABLE_MAGIC.len() as u64;
+        let mut index = Vec::new();
+        let mut hashes = Vec::new();
+        for (i, entry) in entries.enumerate() {
+            let (key, payload) = entry?;
+            hashes.push(Bloom::hashes(&key));
+            if i % INDEX_INTERVAL == 0 {
+                index.push((key, offset));
+            }
+            let framed = frame(&payload);
+            writer.write_all(&framed).map_err(&io_error)?;
+            offset += framed.len() as u64;
+        }
+
+        let bloom = Bloom::build(&hashes);
+        let mut meta = Vec::new();
+        meta.extend_from_slice(&(index.len() as u32).to_le_bytes());
+        for (key, offset) in &index {
+            meta.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            meta.extend_from_slice(key);
+            meta.extend_from_slice(&offset.to_le_bytes());
+        }
+        let bloom_offset = offset + meta.len() as u64;
+        meta.extend_from_slice(&bloom.bits);
+        writer.write_all(&meta).map_err(&io_error)?;
+        writer.write_all(&offset.to_le_bytes()).map_err(&io_error)?;
+        writer
+            .write_all(&bloom_offset.to_le_bytes())
+            .map_err(&io_error)?;
+        writer
+            .write_all(&crc32fast::hash(&meta).to_le_bytes())
+            .map_err(&io_error)?;
+        writer.write_all(TABLE_MAGIC).map_err(&io_error)?;
+        let file = writer.into_inner().map_err(|e| io_error(e.into_error()))?;
+        file.sync_all().map_err(&io_error)?;
+        drop(file);
+
+        fs::rename(&temp_path, &path).map_err(EngineError::io(&path))?;
+        sync_dir(dir);
+        let file = File::open(&path).map_err(EngineError::io(&path))?;
+        Ok(Table {
+            id,
+            path,
+            file,
+            index,
+            bloom,
+            data_end: offset,
+        })
+    }
+
+    /// Opens a table and reads its index and Bloom filter.
+    fn open(path: PathBuf) -> Result<Table, EngineError> {
+        let io_error = EngineError::io(&path);
+        let corrupted = |reason: &str| EngineError::corrupted(&path, reason);
+        let id = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(table_id)
+            .ok_or_else(|| corrupted("not a table name"))?;
+        let mut file = File::open(&path).map_err(&io_error)?;
+        let len = file.metadata().map_err(&io_error)?.len();
+        if len < TABLE_MAGIC.len() as u64 + FOOTER_LEN {
+            return Err(corrupted("truncated table"));
+        }
+        let mut magic = [0; TABLE_MAGIC.len()];
+        file.read_exact(&mut magic).map_err(&io_error)?;
+        let mut footer = [0; FOOTER_LEN as usize];
+        file.seek(SeekFrom::Start(len - FOOTER_LEN))
+            .map_err(&io_error)?;
+        file.read_exact(&mut footer).map_err(&io_error)?;
+        if &magic != TABLE_MAGIC || footer[20..] != TABLE_MAGIC[..] {
+            return Err(corrupted("not a table"));
+        }
+        let data_end = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        let bloom_offset = u64::from_le_bytes(footer[8..16].try_into().unwrap());
+        let checksum = u32::from_le_bytes(footer[16..20].try_into().unwrap());
+        if data_end < TABLE_MAGIC.len() as u64
+            || bloom_offset < data_end
+            || bloom_offset > len - FOOTER_LEN
+        {
+            return Err(corrupted("footer points outside of the table"));
+        }
+
+        let mut meta = vec![0; (len - FOOTER_LEN - data_end) as usize];
+        file.seek(SeekFrom::Start(data_end)).map_err(&io_error)?;
+        file.read_exact(&mut meta).map_err(&io_error)?;
+        if crc32fast::hash(&meta) != checksum {
+            return Err(corrupted("checksum mismatch of the index"));
+        }
+        let (index_meta, bloom_bits) = meta.split_at((bloom_offset - data_end) as usize);
+        let index = parse_index(index_meta).ok_or_else(|| corrupted("damaged index"))?;
+        Ok(Table {
+            id,
+            path: path.clone(),
+            file,
+            index,
+            bloom: Bloom {
+                bits: bloom_bits.to_vec(),
+            },
+            data_end,
+        })
+    }
+
+    /// Returns the record of the key, None if the table doesn't hold the key and Some(None) if
+    /// the table holds its deletion.
+    fn get(&self, key: &[u8]) -> Result<Option<Option<Record>>, EngineError> {
+        if !self.bloom.may_contain(key) {
+            return Ok(None);
+        }
+        let io_error = EngineError::io(&self.path);
+        let mut position = self.seek_offset(Bound::Included(key));
+        let mut reader = BufReader::new(&self.file);
+        reader.seek(SeekFrom::Start(position)).map_err(&io_error)?;
+        while position < self.data_end {
+            let payload = self.read_payload(&mut reader, position)?;
+            position += RECORD_HEADER_LEN + payload.len() as u64;
+            let entry =
+                EntryRef::parse(&payload).map_err(|e| EngineError::corrupted(&self.path, e))?;
+            match entry.key.cmp(key) {
+                Ordering::Less => continue,
+                Ordering::Greater => return Ok(None),
+                Ordering::Equal => {
+                    return entry
+                        .record
+                        .map(|record| record.decode())
+                        .transpose()
+                        .map(Some)
+                        .map_err(|e| EngineError::corrupted(&self.path, e))
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterates over the keys from `start` on with the payloads of their records.
+    fn entries(&self, start: Bound<&[u8]>) -> Source<'static, Vec<u8>> {
+        let offset = self.seek_offset(start);
+        let reader = File::open(&self.path).and_then(|file| {
+            let mut reader = BufReader::new(file);
+            reader.seek(SeekFrom::Start(offset))?;
+            Ok(reader)
+        });
+        let reader = match reader {
+            Ok(reader) => reader,
+            Err(e) => return Box::new(iter::once(Err(EngineError::Io(self.path.clone(), e)))),
+        };
+        let start = start.map(Bytes::from);
+        let cursor = TableCursor {
+            path: self.path.clone(),
+            reader,
+            position: offset,
+            data_end: self.data_end,
+        };
+        Box::new(cursor.skip_while(move |entry| {
+            entry
+                .as_ref()
+                .is_ok_and(|(key, _)| before_start(borrowed(&start), key))
+        }))
+    }
+
+    /// Iterates over the keys up to `end` with the payloads of their records, from the highest.
+    /// Records can only be read forwards, so the blocks between two keys of the index are read
+    /// one at a time from the last one, which keeps at most `INDEX_INTERVAL` records in memory.
+    fn entries_rev(&self, end: Bound<&[u8]>) -> Source<'static, Vec<u8>> {
+        let blocks = match end {
+            Bound::Included(end) | Bound::Excluded(end) => {
+                self.index.partition_point(|(key, _)| key.as_slice() <= end)
+            }
+            Bound::Unbounded => self.index.len(),
+        };
+        let blocks = (0..blocks)
+            .map(|i| {
+                let block_end = self.index.get(i + 1).map_or(self.data_end, |(_, at)| *at);
+                (self.index[i].1, block_end)
+            })
+            .collect();
+        let reader = match File::open(&self.path) {
+            Ok(file) => BufReader::new(file),
+            Err(e) => return Box::new(iter::once(Err(EngineError::Io(self.path.clone(), e)))),
+        };
+        let end = end.map(Bytes::from);
+        let cursor = ReverseTableCursor {
+            cursor: TableCursor {
+                path: self.path.clone(),
+                reader,
+                position: 0,
+                data_end: 0,
+            },
+            blocks,
+            block: Vec::new(),
+        };
+        Box::new(cursor.skip_while(move |entry| {
+            entry
+                .as_ref()
+                .is_ok_and(|(key, _)| after_end(borrowed(&end), key))
+        }))
+    }
+
+    /// Where the records of keys from `start` on begin, or a bit earlier.
+    fn seek_offset(&self, start: Bound<&[u8]>) -> u64 {
+        let first = TABLE_MAGIC.len() as u64;
+        let (Bound::Included(start) | Bound::Excluded(start)) = start else {
+            return first;
+        };
+        match self
+            .index
+            .partition_point(|(key, _)| key.as_slice() <= start)
+        {
+            0 => first,
+            i => self.index[i - 1].1,
+        }
+    }
+
+    fn read_payload(&self, reader: &mut impl Read, position: u64) -> Result<Vec<u8>, EngineError> {
+        match read_frame(reader, self.data_end - position).map_err(EngineError::io(&self.path))? {
+            Frame::Payload(payload) => Ok(payload),
+            Frame::Truncated => Err(EngineError::corrupted(
+                &self.path,
+                format!("truncated record at byte {}", position),
+            )),
+            Frame::Mismatch { .. } => Err(EngineError::corrupted(
+                &self.path,
+                format!("checksum mismatch at byte {}", position),
+            )),
+        }
+    }
+}
+
+fn parse_index(mut meta: &[u8]) -> Option<Vec<(Bytes, u64)>> {
+    let (count, rest) = meta.split_first_chunk::<4>()?;
+    meta = rest;
+    let count = u32::from_le_bytes(*count) as usize;
+    let mut index = Vec::with_capacity(count.min(meta.len() / 12));
+    for _ in 0..count {
+        let (key_len, rest) = meta.split_first_chunk::<4>()?;
+        let key_len = u32::from_le_bytes(*key_len) as usize;
+        if rest.len() < key_len {
+            return None;
+        }
+        let (key, rest) = rest.split_at(key_len);
+        let (offset, rest) = rest.split_first_chunk::<8>()?;
+        index.push((Bytes::from(key), u64::from_le_bytes(*offset)));
+        meta = rest;
+    }
+    meta.is_empty().then_some(index)
+}
+
+/// Reads the records of a table one after another.
+struct TableCursor {
+    path: PathBuf,
+    reader: BufReader<File>,
+    position: u64,
+    data_end: u64,
+}
+
+impl Iterator for TableCursor {
+    type Item = Result<(Bytes, Vec<u8>), EngineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.data_end {
+            return None;
+        }
+        let payload = match read_frame(&mut self.reader, self.data_end - self.position) {
+            Ok(Frame::Payload(payload)) => payload,
+            Ok(_) => {
+                let reason = format!("damaged record at byte {}", self.position);
+                self.position = self.data_end;
+                return Some(Err(EngineError::corrupted(&self.path, reason)));
+            }
+            Err(e) => {
+                self.position = self.data_end;
+                return Some(Err(EngineError::Io(self.path.clone(), e)));
+            }
+        };
+        self.position += RECORD_HEADER_LEN + payload.len() as u64;
+        match EntryRef::parse(&payload) {
+            Ok(entry) => Some(Ok((Bytes::from(entry.key), payload))),
+            Err(reason) => {
+                self.position = self.data_end;
+                Some(Err(EngineError::corrupted(&self.path, reason)))
+            }
+        }
+    }
+}
+
+/// Reads the records of a table from the last one, a block of the index at a time.
+struct ReverseTableCursor {
+    /// Reads the current block, its `data_end` is where the block ends.
+    cursor: TableCursor,
+    /// Where the blocks still to be read start and end, the last one is read next.
+    blocks: Vec<(u64, u64)>,
+    /// The records of the current block that were not returned yet, the last one comes next.
+    block: Vec<Result<(Bytes, Vec<u8>), EngineError>>,
+}
+
+impl Iterator for ReverseTableCursor {
+    type Item = Result<(Bytes, Vec<u8>), EngineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.block.pop() {
+                return Some(entry);
+            }
+            let (start, end) = self.blocks.pop()?;
+            if let Err(e) = self.cursor.reader.seek(SeekFrom::Start(start)) {
+                self.blocks.clear();
+                return Some(Err(EngineError::Io(self.cursor.path.clone(), e)));
+            }
+            self.cursor.position = start;
+            self.cursor.data_end = end;
+            self.block = (&mut self.cursor).collect();
+            // The cursor stops at a damaged record, which ends the iteration.
+            if let Some(Err(_)) = self.block.last() {
+                self.blocks.clear();
+                self.block.drain(..self.block.len() - 1);
+            }
+        }
+    }
+}
+
+/// Tells whether a table may hold a key, so that a lookup skips most of the tables that don't.
+#[derive(Debug)]
+struct Bloom {
+    bits: Vec<u8>,
+}
+
+impl Bloom {
+    /// Two independent hashes, the probes are their linear combinations.
+    fn hashes(key: &[u8]) -> (u32, u32) {
+        let mut second = crc32fast::Hasher::new_with_initial(0x9e37_79b9);
+        second.update(key);
+        (crc32fast::hash(key), second.finalize() | 1)
+    }
+
+    fn build(hashes: &[(u32, u32)]) -> Self {
+        let len = (hashes.len() * BLOOM_BITS_PER_KEY).div_ceil(8).max(8);
+        let mut bloom = Bloom { bits: vec![0; len] };
+        for &(first, second) in hashes {
+            for bit in bloom.probes(first, second) {
+                bloom.bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        bloom
+    }
+
+    fn may_contain(&self, key: &[u8]) -> bool {
+        if self.bits.is_empty() {
+            return true;
+        }
+        let (first, second) = Self::hashes(key);
+        self.probes(first, second)
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    fn probes(&self, first: u32, second: u32) -> impl Iterator<Item = usize> {
+        let len = self.bits.len() * 8;
+        (0..BLOOM_HASHES).map(move |i| first.wrapping_add(i.wrapping_mul(second)) as usize % len)
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::core::data_types::{keyspace::Value, string::StringValue};
+
+    /// An engine in a temporary directory, removed when the engine is dropped.
+    pub struct TempEngine {
+        pub dir: PathBuf,
+        pub engine: LsmEngine,
+    }
+
+    impl TempEngine {
+        pub fn open(memtable_size: usize) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "sphagnumdb-lsm-{}-{}",
+                std::process::id(),
+                rand::random::<u64>()
+            ));
+            let engine = LsmEngine::open(&dir, memtable_size).unwrap();
+            Self { dir, engine }
+        }
+
+        /// Opens the directory again, as a restarted node does.
+        pub fn reopen(&mut self) -> Result<(), EngineError> {
+            self.engine = LsmEngine::open(&self.dir, self.engine.memtable_size)?;
+            Ok(())
+        }
+
+        fn tables(&self) -> usize {
+            fs::read_dir(&self.dir)
+                .unwrap()
+                .filter(|entry| {
+                    let path = entry.as_ref().unwrap().path();
+                    path.extension().is_some_and(|ext| ext == TABLE_EXTENSION)
+                })
+                .count()
+        }
+    }
+
+    impl Drop for TempEngine {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn record(value: &str) -> Record {
+        Record {
+            value: Arc::new(Value::String(StringValue::from(value))),
+            deadline: None,
+            accessed: 1,
+        }
+    }
+
+    fn value(engine: &LsmEngine, key: &str) -> Option<Vec<u8>> {
+        engine
+            .get(key.as_bytes())
+            .unwrap()
+            .map(|record| match record.value.as_ref() {
+                Value::String(string) => string.as_bytes().to_vec(),
+                other => panic!("not a string: {:?}", other),
+            })
+    }
+
+    #[test]
+    fn test_reopen_recovers_the_tables_and_the_log() {
+        // Arrange
+        let mut temp = TempEngine::open(512);
+        for i in 0..100 {
+            temp.engine
+                .put(format!("key:{}", i).as_bytes(), record(&i.to_string()))
+                .unwrap();
+        }
+        temp.engine.delete(b"key:7").unwrap();
+        temp.engine.put(b"key:8", record("last")).unwrap();
+        temp.engine.sync().unwrap();
+
+        // Act
+        temp.reopen().unwrap();
+
+        // Assert
+        assert!(!temp.engine.tables.is_empty());
+        assert!(!temp.engine.memtable.is_empty());
+        assert_eq!(value(&temp.engine, "key:0"), Some(b"0".to_vec()));
+        assert_eq!(value(&temp.engine, "key:7"), None);
+        assert_eq!(value(&temp.engine, "key:8"), Some(b"last".to_vec()));
+        assert_eq!(value(&temp.engine, "key:99"), Some(b"99".to_vec()));
+    }
+
+    #[test]
+    fn test_truncated_log_tail_is_cut_off() {
+        // Arrange
+        let mut temp = TempEngine::open(DEFAULT_MEMTABLE_SIZE);
+        temp.engine.put(b"kept", record("value")).unwrap();
+        temp.engine.put(b"torn", record("value")).unwrap();
+        let wal_path = temp.dir.join(WAL_FILE);
+        let len = fs::metadata(&wal_path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&wal_path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        // Act
+        temp.reopen().unwrap();
+        temp.engine.put(b"after", record("value")).unwrap();
+        temp.reopen().unwrap();
+
+        // Assert
+        assert!(value(&temp.engine, "kept").is_some());
+        assert!(value(&temp.engine, "torn").is_none());
+        assert!(value(&temp.engine, "after").is_some());
+    }
+
+    #[test]
+    fn test_compaction_merges_the_tables_and_drops_deletions() {
+        // Arrange
+        let mut temp = TempEngine::open(256);
+
+        // Act
+        for round in 0..20 {
+            for i in 0..10 {
+                let key = format!("key:{}", i);
+                temp.engine
+                    .put(key.as_bytes(), record(&round.to_string()))
+                    .unwrap();
+            }
+            temp.engine.delete(b"key:0").unwrap();
+        }
+
+        // Assert
+        assert!(temp.tables() <= MAX_TABLES);
+        assert_eq!(temp.tables(), temp.engine.tables.len());
+        let oldest = temp.engine.tables.last().unwrap();
+        let deletions = oldest
+            .entries(Bound::Unbounded)
+            .filter(|entry| entry.as_ref().unwrap().1[0] == KIND_DELETE)
+            .count();
+        assert_eq!(deletions, 0);
+        assert_eq!(value(&temp.engine, "key:0"), None);
+        assert_eq!(value(&temp.engine, "key:9"), Some(b"19".to_vec()));
+    }
+
+    #[test]
+    fn test_files_left_by_a_crash_are_removed_and_damage_is_reported() {
+        // Arrange
+        let mut temp = TempEngine::open(256);
+        for i in 0..50 {
+            temp.engine
+                .put(format!("key:{}", i).as_bytes(), record("value"))
+                .unwrap();
+        }
+        let leftover = temp.dir.join(table_name(999));
+        fs::write(&leftover, b"half a table").unwrap();
+        let table = temp.engine.tables[0].path.clone();
+
+        // Act
+        temp.reopen().unwrap();
+        let leftover_removed = !leftover.exists();
+        let mut bytes = fs::read(&table).unwrap();
+        let footer = bytes.len() - FOOTER_LEN as usize;
+        bytes[footer - 1] ^= 0xff;
+        fs::write(&table, bytes).unwrap();
+        let damaged = temp.reopen();
+
+        // Assert
+        assert!(leftover_removed);
+        assert!(matches!(damaged, Err(EngineError::Corrupted { .. })));
+    }
+}
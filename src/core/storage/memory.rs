@@ -0,0 +1,85 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use super::{is_empty_range, EngineError, KeyIter, Record, RecordIter, StorageEngine};
+use crate::core::bytes::Bytes;
+
+/// Keeps every record in memory, the engine of a node that doesn't configure one.
+///
+/// Values are shared with the keyspace and with the snapshots taken from it, so loading a key
+/// copies no value.
+#[derive(Debug, Default)]
+pub struct MemoryEngine {
+    records: BTreeMap<Bytes, Record>,
+}
+
+impl MemoryEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageEngine for MemoryEngine {
+    fn get(&self, key: &[u8]) -> Result<Option<Record>, EngineError> {
+        Ok(self.records.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &[u8], record: Record) -> Result<(), EngineError> {
+        self.records.insert(Bytes::from(key), record);
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &[u8]) -> Result<(), EngineError> {
+        self.records.remove(key);
+        Ok(())
+    }
+
+    fn touch(&mut self, key: &[u8], accessed: u64) -> Result<(), EngineError> {
+        if let Some(record) = self.records.get_mut(key) {
+            record.accessed = accessed;
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, key: &[u8]) {
+        self.records.remove(key);
+    }
+
+    fn range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, rev: bool) -> RecordIter<'_> {
+        if is_empty_range(start, end) {
+            return Box::new(std::iter::empty());
+        }
+        let records = self
+            .records
+            .range::<[u8], _>((start, end))
+            .map(|(key, record)| Ok((key.clone(), record.clone())));
+        if rev {
+            Box::new(records.rev())
+        } else {
+            Box::new(records)
+        }
+    }
+
+    fn keys(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, rev: bool) -> KeyIter<'_> {
+        if is_empty_range(start, end) {
+            return Box::new(std::iter::empty());
+        }
+        let keys = self
+            .records
+            .range::<[u8], _>((start, end))
+            .map(|(key, record)| Ok((key.clone(), record.deadline)));
+        if rev {
+            Box::new(keys.rev())
+        } else {
+            Box::new(keys)
+        }
+    }
+
+    fn sync(&mut self) -> Result<(), EngineError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,441 @@
+// SphagnumDB
+// © 2025 Anton Anisimov & Contributors
+// Licensed under the MIT License
+
+use std::{
+    error::Error,
+    fmt, io,
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::{bytes::Bytes, config::StorageConfig, data_types::keyspace::Value};
+
+pub mod lsm;
+pub mod memory;
+
+use lsm::LsmEngine;
+use memory::MemoryEngine;
+
+/// Where a node keeps its keyspace, see `StorageEngine`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineKind {
+    /// Everything in memory. The data is lost when the node stops, unless it keeps an
+    /// append-only log or snapshots.
+    #[default]
+    Memory,
+    /// A log-structured merge tree in `storage.data_dir`, only the recent writes are kept in
+    /// memory, see `LsmEngine`.
+    Lsm,
+}
+
+/// A value as the engine stores it, together with what the keyspace knows about it.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub value: Arc<Value>,
+    /// Expiration deadline in milliseconds since the Unix epoch.
+    pub deadline: Option<u64>,
+    /// When the value was last accessed, in milliseconds since the Unix epoch.
+    pub accessed: u64,
+}
+
+#[derive(Debug)]
+pub enum EngineError {
+    Io(PathBuf, io::Error),
+    /// A file of the engine is damaged, or it is not one of its files.
+    Corrupted {
+        path: PathBuf,
+        reason: String,
+    },
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Io(path, e) => {
+                write!(f, "Storage engine file {} failed: {}", path.display(), e)
+            }
+            EngineError::Corrupted { path, reason } => write!(
+                f,
+                "Storage engine file {} is corrupted: {}",
+                path.display(),
+                reason
+            ),
+        }
+    }
+}
+
+impl Error for EngineError {}
+
+impl EngineError {
+    pub(crate) fn io(path: &Path) -> impl Fn(io::Error) -> EngineError + '_ {
+        move |e| EngineError::Io(path.to_path_buf(), e)
+    }
+
+    pub(crate) fn corrupted(path: &Path, reason: impl Into<String>) -> EngineError {
+        EngineError::Corrupted {
+            path: path.to_path_buf(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Records in key order or in reverse, see `StorageEngine::range`.
+pub type RecordIter<'a> = Box<dyn Iterator<Item = Result<(Bytes, Record), EngineError>> + 'a>;
+
+/// Keys in order or in reverse with their deadlines, see `StorageEngine::keys`.
+pub type KeyIter<'a> = Box<dyn Iterator<Item = Result<(Bytes, Option<u64>), EngineError>> + 'a>;
+
+/// Keeps the records of the keyspace in key order. The keyspace loads the keys a command works
+/// on, and writes back the ones it changed once the command completes, see `Keyspace`.
+///
+/// Every engine must pass the same conformance suite, see the tests of this module.
+pub trait StorageEngine: fmt::Debug + Send {
+    fn get(&self, key: &[u8]) -> Result<Option<Record>, EngineError>;
+
+    /// Stores the record, replacing the one the key had.
+    fn put(&mut self, key: &[u8], record: Record) -> Result<(), EngineError>;
+
+    /// Removes the key, if it exists.
+    fn delete(&mut self, key: &[u8]) -> Result<(), EngineError>;
+
+    /// Records a read of the key. Engines may keep only recent access times, so that a read
+    /// never costs a write to disk.
+    fn touch(&mut self, key: &[u8], accessed: u64) -> Result<(), EngineError>;
+
+    /// The keyspace is about to modify the value it got from `get` in place, and puts it back
+    /// once the command completes. An engine that shares the value drops its reference, so that
+    /// the value is not copied.
+    fn release(&mut self, _key: &[u8]) {}
+
+    /// Iterates over the records with keys within the bounds, in order, or from the highest key
+    /// with `rev`. Either way only the records that are iterated over are read.
+    fn range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, rev: bool) -> RecordIter<'_>;
+
+    /// Iterates over the keys within the bounds like `range`, without their values.
+    fn keys(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, rev: bool) -> KeyIter<'_>;
+
+    /// Makes the writes so far survive a crash of the machine, e.g. when the node stops.
+    fn sync(&mut self) -> Result<(), EngineError>;
+}
+
+/// Opens the engine the configuration selects.
+pub fn open(config: &StorageConfig) -> Result<Box<dyn StorageEngine>, EngineError> {
+    match config.engine {
+        EngineKind::Memory => Ok(Box::new(MemoryEngine::new())),
+        EngineKind::Lsm => {
+            let Some(dir) = &config.data_dir else {
+                return Err(EngineError::Io(
+                    PathBuf::new(),
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the lsm engine requires storage.data_dir",
+                    ),
+                ));
+            };
+            Ok(Box::new(LsmEngine::open(dir, config.memtable_size)?))
+        }
+    }
+}
+
+/// Whether no key can be within the bounds, e.g. the start is past the end.
+/// `BTreeMap::range` panics on such bounds.
+pub(crate) fn is_empty_range(start: Bound<&[u8]>, end: Bound<&[u8]>) -> bool {
+    match (start, end) {
+        (Bound::Included(start), Bound::Included(end)) => start > end,
+        (Bound::Included(start) | Bound::Excluded(start), Bound::Excluded(end))
+        | (Bound::Excluded(start), Bound::Included(end)) => start >= end,
+        _ => false,
+    }
+}
+
+/// Whether the key is past the end bound.
+pub(crate) fn after_end(end: Bound<&[u8]>, key: &[u8]) -> bool {
+    match end {
+        Bound::Included(end) => key > end,
+        Bound::Excluded(end) => key >= end,
+        Bound::Unbounded => false,
+    }
+}
+
+/// Whether the key is before the start bound.
+pub(crate) fn before_start(start: Bound<&[u8]>, key: &[u8]) -> bool {
+    match start {
+        Bound::Included(start) => key < start,
+        Bound::Excluded(start) => key <= start,
+        Bound::Unbounded => false,
+    }
+}
+
+/// The same checks run against every engine, so that the keyspace behaves the same on all of
+/// them. Each check gets an empty engine.
+#[cfg(test)]
+mod conformance {
+    use super::*;
+    use crate::core::data_types::{
+        hash::HashValue, list::ListValue, set::SetValue, sorted_set::SortedSet, string::StringValue,
+    };
+    use crate::core::snapshot::encode_value;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::BTreeMap;
+
+    fn record(value: &str) -> Record {
+        Record {
+            value: Arc::new(Value::String(StringValue::from(value))),
+            deadline: None,
+            accessed: 1,
+        }
+    }
+
+    fn string(record: &Record) -> String {
+        match record.value.as_ref() {
+            Value::String(string) => String::from_utf8(string.as_bytes().to_vec()).unwrap(),
+            other => panic!("not a string: {:?}", other),
+        }
+    }
+
+    fn range_keys(
+        engine: &dyn StorageEngine,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        rev: bool,
+    ) -> Vec<Bytes> {
+        engine
+            .range(start, end, rev)
+            .map(|entry| entry.unwrap().0)
+            .collect()
+    }
+
+    fn put_replaces_and_delete_removes(engine: &mut dyn StorageEngine) {
+        // Arrange
+        engine.put(b"key", record("first")).unwrap();
+        engine.put(b"other", record("other")).unwrap();
+
+        // Act
+        engine.put(b"key", record("second")).unwrap();
+        engine.delete(b"other").unwrap();
+        engine.delete(b"missing").unwrap();
+
+        // Assert
+        assert_eq!(string(&engine.get(b"key").unwrap().unwrap()), "second");
+        assert!(engine.get(b"other").unwrap().is_none());
+        assert!(engine.get(b"missing").unwrap().is_none());
+    }
+
+    fn range_respects_bounds_in_order(engine: &mut dyn StorageEngine) {
+        // Arrange
+        for key in ["c", "a", "ba", "b", "\u{7f}", ""] {
+            engine.put(key.as_bytes(), record(key)).unwrap();
+        }
+
+        // Act
+        let all = range_keys(engine, Bound::Unbounded, Bound::Unbounded, false);
+        let included = range_keys(engine, Bound::Included(b"b"), Bound::Included(b"c"), false);
+        let excluded = range_keys(engine, Bound::Excluded(b"b"), Bound::Excluded(b"c"), false);
+        let inverted = range_keys(engine, Bound::Included(b"c"), Bound::Excluded(b"b"), false);
+        let reversed = range_keys(engine, Bound::Excluded(b"a"), Bound::Included(b"c"), true);
+        let keys: Vec<Bytes> = engine
+            .keys(Bound::Included(b"a"), Bound::Excluded(b"b"), false)
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        let reversed_keys: Vec<Bytes> = engine
+            .keys(Bound::Unbounded, Bound::Excluded(b"b"), true)
+            .map(|entry| entry.unwrap().0)
+            .collect();
+
+        // Assert
+        let expected: Vec<Bytes> = ["", "a", "b", "ba", "c", "\u{7f}"]
+            .into_iter()
+            .map(Bytes::from)
+            .collect();
+        assert_eq!(all, expected);
+        assert_eq!(included, expected[2..5]);
+        assert_eq!(excluded, expected[3..4]);
+        assert_eq!(keys, expected[1..2]);
+        assert!(inverted.is_empty());
+        assert_eq!(reversed, ["c", "ba", "b"].map(Bytes::from));
+        assert_eq!(reversed_keys, ["a", ""].map(Bytes::from));
+    }
+
+    fn records_keep_values_deadlines_and_access_times(engine: &mut dyn StorageEngine) {
+        // Arrange
+        let values = [
+            Value::String(StringValue::from("12")),
+            Value::Hash(HashValue::from_iter([(Bytes::from("f"), Bytes::from("v"))])),
+            Value::List(ListValue::from_iter([Bytes::from("a"), Bytes::from("b")])),
+            Value::Set(SetValue::from_iter([Bytes::from("1"), Bytes::from("2")])),
+            Value::SortedSet(SortedSet::default()),
+            Value::Bitmap(vec![0xff, 0]),
+        ];
+        for (i, value) in values.iter().enumerate() {
+            let record = Record {
+                value: Arc::new(value.clone()),
+                deadline: (i % 2 == 0).then_some(1000 + i as u64),
+                accessed: 500 + i as u64,
+            };
+            engine.put(&[i as u8], record).unwrap();
+        }
+
+        // Act
+        engine.touch(&[1], 2000).unwrap();
+        let records: Vec<(Bytes, Record)> = engine
+            .range(Bound::Unbounded, Bound::Unbounded, false)
+            .map(Result::unwrap)
+            .collect();
+        let deadlines: Vec<Option<u64>> = engine
+            .keys(Bound::Unbounded, Bound::Unbounded, false)
+            .map(|entry| entry.unwrap().1)
+            .collect();
+
+        // Assert
+        assert_eq!(records.len(), values.len());
+        for (i, (key, record)) in records.iter().enumerate() {
+            assert_eq!(key.as_slice(), [i as u8]);
+            assert_eq!(
+                encode_value(&record.value).unwrap(),
+                encode_value(&values[i]).unwrap()
+            );
+            assert_eq!(record.deadline, deadlines[i]);
+            assert_eq!(record.deadline, (i % 2 == 0).then_some(1000 + i as u64));
+        }
+        assert_eq!(engine.get(&[1]).unwrap().unwrap().accessed, 2000);
+        assert_eq!(engine.get(&[2]).unwrap().unwrap().accessed, 502);
+    }
+
+    fn released_key_takes_the_record_put_back(engine: &mut dyn StorageEngine) {
+        // Arrange
+        engine.put(b"key", record("before")).unwrap();
+
+        // Act
+        engine.get(b"key").unwrap();
+        engine.release(b"key");
+        engine.put(b"key", record("after")).unwrap();
+
+        // Assert
+        assert_eq!(string(&engine.get(b"key").unwrap().unwrap()), "after");
+    }
+
+    /// Enough random writes to make an on-disk engine flush and compact, compared against a map.
+    fn random_writes_match_a_map(engine: &mut dyn StorageEngine) {
+        // Arrange
+        let mut rng = StdRng::seed_from_u64(24);
+        let mut expected = BTreeMap::new();
+
+        // Act
+        for i in 0..5000 {
+            let key = Bytes::from(format!("key:{:03}", rng.gen_range(0..300)));
+            if rng.gen_bool(0.2) {
+                engine.delete(&key).unwrap();
+                expected.remove(&key);
+            } else {
+                let value = format!("{}:{}", i, "x".repeat(rng.gen_range(0..100)));
+                engine.put(&key, record(&value)).unwrap();
+                expected.insert(key, value);
+            }
+        }
+
+        // Assert
+        let actual: BTreeMap<Bytes, String> = engine
+            .range(Bound::Unbounded, Bound::Unbounded, false)
+            .map(|entry| {
+                let (key, record) = entry.unwrap();
+                (key, string(&record))
+            })
+            .collect();
+        assert_eq!(actual, expected);
+        for i in 0..300 {
+            let key = format!("key:{:03}", i);
+            let record = engine.get(key.as_bytes()).unwrap();
+            assert_eq!(
+                record.as_ref().map(string).as_ref(),
+                expected.get(key.as_bytes())
+            );
+        }
+        let middle: Vec<&Bytes> = expected
+            .keys()
+            .filter(|key| key.as_slice() >= &b"key:100"[..] && key.as_slice() < &b"key:200"[..])
+            .collect();
+        let range = range_keys(
+            engine,
+            Bound::Included(b"key:100"),
+            Bound::Excluded(b"key:200"),
+            false,
+        );
+        assert_eq!(range.iter().collect::<Vec<_>>(), middle);
+        let reversed = range_keys(
+            engine,
+            Bound::Included(b"key:100"),
+            Bound::Excluded(b"key:200"),
+            true,
+        );
+        assert_eq!(reversed.iter().rev().collect::<Vec<_>>(), middle);
+        let last: Vec<Bytes> = engine
+            .keys(Bound::Unbounded, Bound::Unbounded, true)
+            .take(3)
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        assert_eq!(
+            last.iter().collect::<Vec<_>>(),
+            expected.keys().rev().take(3).collect::<Vec<_>>()
+        );
+    }
+
+    /// An empty engine for a single check, with whatever it leaves behind cleaned up on drop.
+    trait Fixture {
+        fn engine(&mut self) -> &mut dyn StorageEngine;
+    }
+
+    impl Fixture for MemoryEngine {
+        fn engine(&mut self) -> &mut dyn StorageEngine {
+            self
+        }
+    }
+
+    impl Fixture for lsm::tests::TempEngine {
+        fn engine(&mut self) -> &mut dyn StorageEngine {
+            &mut self.engine
+        }
+    }
+
+    macro_rules! conformance_suite {
+        ($name:ident, $engine:expr) => {
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn test_put_replaces_and_delete_removes() {
+                    put_replaces_and_delete_removes($engine.engine());
+                }
+
+                #[test]
+                fn test_range_respects_bounds_in_order() {
+                    range_respects_bounds_in_order($engine.engine());
+                }
+
+                #[test]
+                fn test_records_keep_values_deadlines_and_access_times() {
+                    records_keep_values_deadlines_and_access_times($engine.engine());
+                }
+
+                #[test]
+                fn test_released_key_takes_the_record_put_back() {
+                    released_key_takes_the_record_put_back($engine.engine());
+                }
+
+                #[test]
+                fn test_random_writes_match_a_map() {
+                    random_writes_match_a_map($engine.engine());
+                }
+            }
+        };
+    }
+
+    conformance_suite!(memory_engine, MemoryEngine::new());
+    // A small memtable, so that the checks run against tables and compactions as well.
+    conformance_suite!(lsm_engine, lsm::tests::TempEngine::open(1024));
+}
@@ -2,4 +2,5 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
+pub mod cli;
 pub mod core;
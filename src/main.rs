@@ -2,277 +2,303 @@
 // © 2025 Anton Anisimov & Contributors
 // Licensed under the MIT License
 
-use libp2p::Multiaddr;
+use clap::{Args, Parser, Subcommand};
+use libp2p::{Multiaddr, PeerId};
+use rustyline::{error::ReadlineError, history::DefaultHistory, Editor};
+use sphagnumdb::cli::{
+    completion::CommandCompleter, output::format_result, parser::parse_command, tokenizer::tokenize,
+};
 use sphagnumdb::core::{
-    commands::{generic::GenericCommand, string::StringCommand, Command as SphagnumCommand},
+    client::{ClientError, SphagnumClient},
+    commands::{server::ServerCommand, Command as SphagnumCommand},
+    config::{ConfigOverrides, NodeConfig},
+    identity,
+    node_handle::NodeHandle,
     sphagnum::SphagnumNode,
 };
 use std::error::Error;
-use std::sync::Arc;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use tokio::io::{self, AsyncBufReadExt, BufReader};
-use tokio::sync::Mutex;
+use tokio::signal;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    // creating 3 nodes for a future cluster
-    let mut sp1 = SphagnumNode::new()?;
-    let mut sp2 = SphagnumNode::new()?;
-    let mut sp3 = SphagnumNode::new()?;
+#[derive(Parser)]
+#[command(name = "sphagnumdb", version, about = "SphagnumDB node")]
+struct Cli {
+    #[command(subcommand)]
+    command: CliCommand,
+}
 
-    // setting listening ports
-    sp1.listen_on("/ip4/127.0.0.1/tcp/3301".parse::<Multiaddr>()?)?;
-    sp2.listen_on("/ip4/127.0.0.1/tcp/3302".parse::<Multiaddr>()?)?;
-    sp3.listen_on("/ip4/127.0.0.1/tcp/3303".parse::<Multiaddr>()?)?;
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Run a node until it is shut down
+    Server(Box<ConfigOverrides>),
+    /// Execute commands on a node interactively, or the ones given with --eval or on stdin
+    Cli(CliArgs),
+    /// Create and inspect node identity key files
+    Keygen {
+        #[command(subcommand)]
+        command: KeygenCommand,
+    },
+    /// Inspect and change the cluster membership of a node
+    Cluster {
+        #[command(flatten)]
+        peer: PeerArgs,
+        #[command(subcommand)]
+        command: ClusterCommand,
+    },
+}
 
-    // dialing nodes
-    sp1.dial("/ip4/127.0.0.1/tcp/3302")?;
-    sp1.dial("/ip4/127.0.0.1/tcp/3303")?;
-    sp2.dial("/ip4/127.0.0.1/tcp/3301")?;
-    sp2.dial("/ip4/127.0.0.1/tcp/3303")?;
-    sp3.dial("/ip4/127.0.0.1/tcp/3301")?;
-    sp3.dial("/ip4/127.0.0.1/tcp/3302")?;
+#[derive(Args)]
+struct PeerArgs {
+    /// Address of the node, e.g. /ip4/127.0.0.1/tcp/3301
+    #[arg(long, env = "SPHAGNUM_PEER")]
+    peer: Multiaddr,
+}
 
-    // config replication
-    sp1.add_to_replica_set(sp2.peer_id()?)?;
-    sp1.add_to_replica_set(sp3.peer_id()?)?;
-    sp2.add_to_replica_set(sp1.peer_id()?)?;
-    sp2.add_to_replica_set(sp3.peer_id()?)?;
-    sp3.add_to_replica_set(sp1.peer_id()?)?;
-    sp3.add_to_replica_set(sp2.peer_id()?)?;
+#[derive(Args)]
+struct CliArgs {
+    #[command(flatten)]
+    peer: PeerArgs,
+    /// Command to execute instead of starting a session, may be repeated
+    #[arg(short, long)]
+    eval: Vec<String>,
+    /// Where the session history is kept, ~/.sphagnumdb_history by default
+    #[arg(long, env = "SPHAGNUM_HISTORY_FILE")]
+    history_file: Option<PathBuf>,
+}
 
-    let sp_arc_1 = Arc::new(Mutex::new(sp1));
-    let sp_arc_2 = Arc::new(Mutex::new(sp2));
-    let sp_arc_3 = Arc::new(Mutex::new(sp3));
+#[derive(Subcommand)]
+enum KeygenCommand {
+    /// Generate a new Ed25519 identity and print its Peer ID
+    Generate {
+        file: PathBuf,
+        /// Replace the file if it exists, the node using it gets a new Peer ID
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the Peer ID and the public key of an identity
+    Inspect { file: PathBuf },
+}
 
-    let nodes = std::collections::HashMap::from([
-        ("sp1".to_string(), Arc::clone(&sp_arc_1)),
-        ("sp2".to_string(), Arc::clone(&sp_arc_2)),
-        ("sp3".to_string(), Arc::clone(&sp_arc_3)),
-    ]);
+#[derive(Subcommand)]
+enum ClusterCommand {
+    /// List the peers the node is connected to
+    Peers,
+    /// List the peers the node replicates its writes to
+    Replicas,
+    /// Make the node dial another node
+    Meet { addr: Multiaddr },
+    /// Make the node replicate its writes to another node
+    AddReplica { peer_id: PeerId },
+    /// Shut the node down gracefully
+    Shutdown,
+}
 
-    let handle_events_1 = {
-        let sp_arc_1 = Arc::clone(&sp_arc_1);
-        tokio::spawn(async move {
-            loop {
-                let mut sphagnum = sp_arc_1.lock().await;
-                if let Err(e) = sphagnum.handle_event().await {
-                    eprintln!("Error handling event: {}", e);
-                }
-            }
-        })
-    };
+impl From<ClusterCommand> for ServerCommand {
+    fn from(command: ClusterCommand) -> Self {
+        match command {
+            ClusterCommand::Peers => ServerCommand::ClusterPeers,
+            ClusterCommand::Replicas => ServerCommand::ClusterReplicas,
+            ClusterCommand::Meet { addr } => ServerCommand::ClusterMeet { addr },
+            ClusterCommand::AddReplica { peer_id } => ServerCommand::ClusterAddReplica { peer_id },
+            ClusterCommand::Shutdown => ServerCommand::Shutdown,
+        }
+    }
+}
 
-    let handle_events_2 = {
-        let sp_arc_2 = Arc::clone(&sp_arc_2);
-        tokio::spawn(async move {
-            loop {
-                let mut sphagnum = sp_arc_2.lock().await;
-                if let Err(e) = sphagnum.handle_event().await {
-                    eprintln!("Error handling event: {}", e);
-                }
+fn keygen(command: KeygenCommand) -> Result<(), Box<dyn Error>> {
+    match command {
+        KeygenCommand::Generate { file, force } => {
+            if force && file.exists() {
+                std::fs::remove_file(&file)?;
             }
-        })
-    };
+            let keypair = identity::generate();
+            identity::save(&keypair, &file)?;
+            println!("{}", keypair.public().to_peer_id());
+        }
+        KeygenCommand::Inspect { file } => {
+            let keypair = identity::load(&file)?;
+            let public_key: String = keypair
+                .public()
+                .try_into_ed25519()?
+                .to_bytes()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            println!("Peer ID:    {}", keypair.public().to_peer_id());
+            println!("Key type:   {:?}", keypair.key_type());
+            println!("Public key: {}", public_key);
+        }
+    }
+    Ok(())
+}
 
-    let handle_events_3 = {
-        let sp_arc_3 = Arc::clone(&sp_arc_3);
-        tokio::spawn(async move {
-            loop {
-                let mut sphagnum = sp_arc_3.lock().await;
-                if let Err(e) = sphagnum.handle_event().await {
-                    eprintln!("Error handling event: {}", e);
-                }
-            }
-        })
-    };
+/// Resolves on Ctrl-C, or on SIGTERM on Unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler");
+        tokio::select! {
+            _ = signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = signal::ctrl_c().await;
+}
 
-    let handle_input = {
-        let nodes = nodes.clone();
-        tokio::spawn(async move {
-            let stdin = io::stdin();
-            let mut reader = BufReader::new(stdin).lines();
+/// Runs a node until a signal arrives or the node is shut down by a SHUTDOWN command.
+async fn server(overrides: ConfigOverrides) -> Result<(), Box<dyn Error>> {
+    let node = SphagnumNode::spawn(overrides.load()?)?;
+    println!("Node {} started", node.peer_id());
+    tokio::select! {
+        _ = shutdown_signal() => {
+            println!("Shutting down...");
+            node.shutdown().await?;
+        }
+        _ = node.stopped() => {}
+    }
+    Ok(())
+}
 
-            while let Some(line) = reader.next_line().await.unwrap_or(None) {
-                let mut parts = line.split_whitespace();
-                if let Some(node_name) = parts.next() {
-                    if let Some(node_arc) = nodes.get(node_name) {
-                        if let Some(command) = parts.next() {
-                            match command.to_lowercase().as_str() {
-                                "get" => {
-                                    if let Some(key) = parts.next() {
-                                        let mut sphagnum = node_arc.lock().await;
-                                        if let Some(peer_id) =
-                                            sphagnum.connected_peers.iter().next().copied()
-                                        {
-                                            let cmd = SphagnumCommand::String(StringCommand::Get {
-                                                key: key.to_string(),
-                                            });
-                                            match sphagnum
-                                                .send_request_to_sphagnum(peer_id, cmd)
-                                                .await
-                                            {
-                                                Ok(_) => {
-                                                    println!("Get request sent for key: {}", key)
-                                                }
-                                                Err(e) => {
-                                                    eprintln!("Failed to send Get request: {}", e)
-                                                }
-                                            }
-                                        } else {
-                                            eprintln!("Not connected to any node.");
-                                        }
-                                    } else {
-                                        eprintln!("Usage: <node> get <key>");
-                                    }
-                                }
-                                "set" => {
-                                    if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-                                        let mut sphagnum = node_arc.lock().await;
-                                        if let Some(peer_id) =
-                                            sphagnum.connected_peers.iter().next().copied()
-                                        {
-                                            let cmd = SphagnumCommand::String(StringCommand::Set {
-                                                key: key.to_string(),
-                                                value: value.to_string(),
-                                            });
-                                            match sphagnum
-                                                .send_request_to_sphagnum(peer_id, cmd)
-                                                .await
-                                            {
-                                                Ok(_) => println!(
-                                                    "Set request sent with key: {}, value: {}",
-                                                    key, value
-                                                ),
-                                                Err(e) => {
-                                                    eprintln!("Failed to send Set request: {}", e)
-                                                }
-                                            }
-                                        } else {
-                                            eprintln!("Not connected to any node.");
-                                        }
-                                    } else {
-                                        eprintln!("Usage: <node> set <key> <value>");
-                                    }
-                                }
-                                "append" => {
-                                    if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-                                        let mut sphagnum = node_arc.lock().await;
-                                        if let Some(peer_id) =
-                                            sphagnum.connected_peers.iter().next().copied()
-                                        {
-                                            let cmd =
-                                                SphagnumCommand::String(StringCommand::Append {
-                                                    key: key.to_string(),
-                                                    value: value.to_string(),
-                                                });
-                                            match sphagnum
-                                                .send_request_to_sphagnum(peer_id, cmd)
-                                                .await
-                                            {
-                                                Ok(_) => println!(
-                                                    "Append request sent with key: {}, value: {}",
-                                                    key, value
-                                                ),
-                                                Err(e) => eprintln!(
-                                                    "Failed to send Append request: {}",
-                                                    e
-                                                ),
-                                            }
-                                        } else {
-                                            eprintln!("Not connected to any node.");
-                                        }
-                                    } else {
-                                        eprintln!("Usage: <node> append <key> <value>");
-                                    }
-                                }
-                                "exists" => {
-                                    let keys: Vec<String> = parts.map(|s| s.to_string()).collect();
-                                    if keys.is_empty() {
-                                        eprintln!("Usage: <node> exists <key> [key ...]");
-                                    } else {
-                                        let mut sphagnum = node_arc.lock().await;
-                                        if let Some(peer_id) =
-                                            sphagnum.connected_peers.iter().next().copied()
-                                        {
-                                            let cmd =
-                                                SphagnumCommand::Generic(GenericCommand::Exists {
-                                                    keys,
-                                                });
-                                            match sphagnum
-                                                .send_request_to_sphagnum(peer_id, cmd)
-                                                .await
-                                            {
-                                                Ok(_) => println!("Exists request sent for keys"),
-                                                Err(e) => eprintln!(
-                                                    "Failed to send Exists request: {}",
-                                                    e
-                                                ),
-                                            }
-                                        } else {
-                                            eprintln!("Not connected to any node.");
-                                        }
-                                    }
-                                }
-                                "del" => {
-                                    let keys: Vec<String> = parts.map(|s| s.to_string()).collect();
-                                    if keys.is_empty() {
-                                        eprintln!("Usage: <node> del <key> [key ...]");
-                                    } else {
-                                        let mut sphagnum = node_arc.lock().await;
-                                        if let Some(peer_id) =
-                                            sphagnum.connected_peers.iter().next().copied()
-                                        {
-                                            let cmd =
-                                                SphagnumCommand::Generic(GenericCommand::Delete {
-                                                    keys,
-                                                });
-                                            match sphagnum
-                                                .send_request_to_sphagnum(peer_id, cmd)
-                                                .await
-                                            {
-                                                Ok(_) => println!("Delete request sent for keys"),
-                                                Err(e) => eprintln!(
-                                                    "Failed to send Delete request: {}",
-                                                    e
-                                                ),
-                                            }
-                                        } else {
-                                            eprintln!("Not connected to any node.");
-                                        }
-                                    }
-                                }
-                                "enable_pinging_output" => {
-                                    let mut sphagnum = node_arc.lock().await;
-                                    sphagnum.enable_pinging_output();
-                                }
-                                "disable_pinging_output" => {
-                                    let mut sphagnum = node_arc.lock().await;
-                                    sphagnum.disable_pinging_output();
-                                }
-                                _ => {
-                                    eprintln!("Unknown command: {}", command);
-                                }
-                            }
-                        } else {
-                            eprintln!("Please specify a command after node name");
-                        }
-                    } else {
-                        eprintln!(
-                            "Unknown node: {}. Available nodes: sp1, sp2, sp3",
-                            node_name
-                        );
-                    }
-                }
-            }
-        })
+/// Starts a quiet local node without listeners and connects it to `peer`.
+/// Commands reach the peer through this node, as the nodes only talk libp2p.
+async fn connect(peer: Multiaddr) -> Result<(NodeHandle, SphagnumClient), Box<dyn Error>> {
+    let node = SphagnumNode::spawn(NodeConfig::builder().event_output(false).build()?)?;
+    let target = node.connect(peer).await?;
+    let client = node.client(target);
+    Ok((node, client))
+}
+
+async fn cluster(peer: Multiaddr, command: ClusterCommand) -> Result<(), Box<dyn Error>> {
+    let (node, client) = connect(peer).await?;
+    let result = client
+        .execute(SphagnumCommand::Server(command.into()))
+        .await;
+    node.shutdown().await?;
+    println!("{}", format_result(&result?));
+    Ok(())
+}
+
+/// Executes a line on the peer and prints the result, returns false if the command failed.
+async fn execute_line(client: &SphagnumClient, line: &str) -> bool {
+    let command = tokenize(line)
+        .map_err(|e| e.to_string())
+        .and_then(|tokens| parse_command(&tokens).map_err(|e| e.to_string()));
+    let result = match command {
+        Ok(command) => client.execute(command).await.map_err(|e| match e {
+            ClientError::Command(error) => error.message,
+            e => e.to_string(),
+        }),
+        Err(e) => Err(e),
     };
+    match result {
+        Ok(result) => {
+            println!("{}", format_result(&result));
+            true
+        }
+        Err(e) => {
+            eprintln!("(error) {}", e);
+            false
+        }
+    }
+}
+
+/// Executes the lines of stdin one by one, e.g. `sphagnumdb cli --peer ... < commands.txt`.
+async fn execute_stdin(client: &SphagnumClient) -> Result<bool, Box<dyn Error>> {
+    let mut lines = BufReader::new(io::stdin()).lines();
+    let mut succeeded = true;
+    while let Some(line) = lines.next_line().await? {
+        if !line.trim().is_empty() {
+            succeeded &= execute_line(client, &line).await;
+        }
+    }
+    Ok(succeeded)
+}
+
+/// Reads commands with line editing, history and completion until `exit`, Ctrl-C or Ctrl-D.
+/// The editor blocks, so each line is read on a blocking thread.
+async fn repl(
+    client: &SphagnumClient,
+    prompt: String,
+    history_file: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let mut editor: Editor<CommandCompleter, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(CommandCompleter));
+    if let Some(path) = &history_file {
+        // There is no history on the first start.
+        let _ = editor.load_history(path);
+    }
 
-    let _ = tokio::join!(
-        handle_events_1,
-        handle_events_2,
-        handle_events_3,
-        handle_input
-    );
+    loop {
+        let prompt = prompt.clone();
+        let (returned, line) = tokio::task::spawn_blocking(move || {
+            let line = editor.readline(&prompt);
+            (editor, line)
+        })
+        .await?;
+        editor = returned;
+        let line = match line {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(trimmed)?;
+        if trimmed.eq_ignore_ascii_case("exit") || trimmed.eq_ignore_ascii_case("quit") {
+            break;
+        }
+        execute_line(client, trimmed).await;
+    }
 
+    if let Some(path) = &history_file {
+        editor.save_history(path)?;
+    }
     Ok(())
 }
+
+/// Runs the commands given with `--eval`, or the ones piped to stdin, or an interactive session.
+/// Returns false if any of the commands failed.
+async fn interactive_client(args: CliArgs) -> Result<bool, Box<dyn Error>> {
+    let (node, client) = connect(args.peer.peer.clone()).await?;
+    let result = if !args.eval.is_empty() {
+        let mut succeeded = true;
+        for line in &args.eval {
+            succeeded &= execute_line(&client, line).await;
+        }
+        Ok(succeeded)
+    } else if !std::io::stdin().is_terminal() {
+        execute_stdin(&client).await
+    } else {
+        println!("Connected to {}", client.target());
+        let history_file = args.history_file.or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".sphagnumdb_history"))
+        });
+        repl(&client, format!("{}> ", args.peer.peer), history_file)
+            .await
+            .map(|_| true)
+    };
+    node.shutdown().await?;
+    result
+}
+
+#[tokio::main]
+async fn main() -> Result<ExitCode, Box<dyn Error>> {
+    match Cli::parse().command {
+        CliCommand::Server(overrides) => server(*overrides).await?,
+        CliCommand::Cli(args) => {
+            if !interactive_client(args).await? {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        CliCommand::Keygen { command } => keygen(command)?,
+        CliCommand::Cluster { peer, command } => cluster(peer.peer, command).await?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -5,105 +5,66 @@
 use libp2p::Multiaddr;
 use sphagnumdb::core::{
     commands::{generic::GenericCommand, string::StringCommand, Command, CommandResult},
+    config::NodeConfig,
     sphagnum::SphagnumNode,
 };
-use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Mutex;
 use tokio::time::sleep;
 
+fn listening_on(addr: &str) -> NodeConfig {
+    NodeConfig::builder()
+        .listen_addr(addr.parse::<Multiaddr>().unwrap())
+        .build()
+        .unwrap()
+}
+
 #[tokio::test]
 async fn test_config_cluster_and_check_replication() {
     // Arrange
-    let mut sp1 = SphagnumNode::new().unwrap();
-    let mut sp2 = SphagnumNode::new().unwrap();
-    let mut sp3 = SphagnumNode::new().unwrap();
-
-    sp1.listen_on("/ip4/127.0.0.1/tcp/3301".parse::<Multiaddr>().unwrap())
-        .unwrap();
-    sp2.listen_on("/ip4/127.0.0.1/tcp/3302".parse::<Multiaddr>().unwrap())
-        .unwrap();
-    sp3.listen_on("/ip4/127.0.0.1/tcp/3303".parse::<Multiaddr>().unwrap())
-        .unwrap();
-
-    sp1.dial("/ip4/127.0.0.1/tcp/3302").unwrap();
-    sp1.dial("/ip4/127.0.0.1/tcp/3303").unwrap();
-    sp2.dial("/ip4/127.0.0.1/tcp/3301").unwrap();
-    sp2.dial("/ip4/127.0.0.1/tcp/3303").unwrap();
-    sp3.dial("/ip4/127.0.0.1/tcp/3301").unwrap();
-    sp3.dial("/ip4/127.0.0.1/tcp/3302").unwrap();
-
-    let peer_id1 = sp1.peer_id().unwrap();
-    let peer_id2 = sp2.peer_id().unwrap();
-    let peer_id3 = sp3.peer_id().unwrap();
-    sp1.add_to_replica_set(peer_id2).unwrap();
-    sp1.add_to_replica_set(peer_id3).unwrap();
-    sp2.add_to_replica_set(peer_id1).unwrap();
-    sp2.add_to_replica_set(peer_id3).unwrap();
-    sp3.add_to_replica_set(peer_id1).unwrap();
-    sp3.add_to_replica_set(peer_id2).unwrap();
-
-    let sp_arc_1 = Arc::new(Mutex::new(sp1));
-    let sp_arc_2 = Arc::new(Mutex::new(sp2));
-    let sp_arc_3 = Arc::new(Mutex::new(sp3));
-
-    let handle_events_1 = {
-        let sp_arc_1 = Arc::clone(&sp_arc_1);
-        tokio::spawn(async move {
-            loop {
-                let mut sphagnum = sp_arc_1.lock().await;
-                if let Err(e) = sphagnum.handle_event().await {
-                    eprintln!("Error handling event on sp1: {}", e);
-                }
-            }
-        })
-    };
-
-    let handle_events_2 = {
-        let sp_arc_2 = Arc::clone(&sp_arc_2);
-        tokio::spawn(async move {
-            loop {
-                let mut sphagnum = sp_arc_2.lock().await;
-                if let Err(e) = sphagnum.handle_event().await {
-                    eprintln!("Error handling event on sp2: {}", e);
-                }
-            }
-        })
-    };
-
-    let handle_events_3 = {
-        let sp_arc_3 = Arc::clone(&sp_arc_3);
-        tokio::spawn(async move {
-            loop {
-                let mut sphagnum = sp_arc_3.lock().await;
-                if let Err(e) = sphagnum.handle_event().await {
-                    eprintln!("Error handling event on sp3: {}", e);
-                }
-            }
-        })
+    let sp1 = SphagnumNode::spawn(listening_on("/ip4/127.0.0.1/tcp/3301")).unwrap();
+    let sp2 = SphagnumNode::spawn(listening_on("/ip4/127.0.0.1/tcp/3302")).unwrap();
+    let sp3 = SphagnumNode::spawn(listening_on("/ip4/127.0.0.1/tcp/3303")).unwrap();
+
+    let addr = |port: u16| {
+        format!("/ip4/127.0.0.1/tcp/{}", port)
+            .parse::<Multiaddr>()
+            .unwrap()
     };
+    sp1.dial(addr(3302)).await.unwrap();
+    sp1.dial(addr(3303)).await.unwrap();
+    sp2.dial(addr(3301)).await.unwrap();
+    sp2.dial(addr(3303)).await.unwrap();
+    sp3.dial(addr(3301)).await.unwrap();
+    sp3.dial(addr(3302)).await.unwrap();
+
+    let peer_id1 = sp1.peer_id();
+    let peer_id2 = sp2.peer_id();
+    let peer_id3 = sp3.peer_id();
+    sp1.add_to_replica_set(peer_id2).await.unwrap();
+    sp1.add_to_replica_set(peer_id3).await.unwrap();
+    sp2.add_to_replica_set(peer_id1).await.unwrap();
+    sp2.add_to_replica_set(peer_id3).await.unwrap();
+    sp3.add_to_replica_set(peer_id1).await.unwrap();
+    sp3.add_to_replica_set(peer_id2).await.unwrap();
+
+    let client_3_to_1 = sp3.client(peer_id1);
+    let client_1_to_2 = sp1.client(peer_id2);
 
     sleep(Duration::from_millis(1000)).await;
 
-    //let mut node1 = sp_arc_1.lock().await;
-    //let mut node2 = sp_arc_2.lock().await;
-    //let mut node3 = sp_arc_3.lock().await;
-
     // Act & Assert
 
     // Step 1: assert Set command
     let set_command = Command::String(StringCommand::Set {
-        key: "key".to_string(),
-        value: "value".to_string(),
-    });
-    let get_command = Command::String(StringCommand::Get {
-        key: "key".to_string(),
+        key: "key".into(),
+        value: "value".into(),
+        expiration: None,
     });
+    let get_command = Command::String(StringCommand::Get { key: "key".into() });
 
     // sp1 -> request -> sp2
     {
-        let mut node1 = sp_arc_1.lock().await;
-        let result = node1.send_request_to_sphagnum(peer_id2, set_command).await;
+        let result = sp1.client(peer_id2).execute(set_command).await;
         assert!(
             result.is_ok(),
             "Failed to send Set request from sp1 to sp2: {:?}",
@@ -114,37 +75,23 @@ async fn test_config_cluster_and_check_replication() {
     // Time for replication
     sleep(Duration::from_millis(5000)).await;
 
-    let get_sp1 = {
-        let mut node1 = sp_arc_1.lock().await;
-        node1.handle_command(get_command.clone()).unwrap()
-    };
-    let get_sp2 = {
-        let mut node2 = sp_arc_2.lock().await;
-        node2.handle_command(get_command.clone()).unwrap()
-    };
-    let get_sp3 = {
-        let mut node3 = sp_arc_3.lock().await;
-        node3.handle_command(get_command.clone()).unwrap()
-    };
-    assert_eq!(get_sp1, CommandResult::String("value".to_string()));
-    assert_eq!(get_sp2, CommandResult::String("value".to_string()));
-    assert_eq!(get_sp3, CommandResult::String("value".to_string()));
+    let get_sp1 = sp1.execute(get_command.clone()).await.unwrap();
+    let get_sp2 = sp2.execute(get_command.clone()).await.unwrap();
+    let get_sp3 = sp3.execute(get_command.clone()).await.unwrap();
+    assert_eq!(get_sp1, CommandResult::String("value".into()));
+    assert_eq!(get_sp2, CommandResult::String("value".into()));
+    assert_eq!(get_sp3, CommandResult::String("value".into()));
 
     // Step 2: assert Append command
     let append_command = Command::String(StringCommand::Append {
-        key: "key".to_string(),
-        value: "appended_part".to_string(),
-    });
-    let get_command = Command::String(StringCommand::Get {
-        key: "key".to_string(),
+        key: "key".into(),
+        value: "appended_part".into(),
     });
+    let get_command = Command::String(StringCommand::Get { key: "key".into() });
 
     // sp2 -> request -> sp1
     {
-        let mut node2 = sp_arc_2.lock().await;
-        let result = node2
-            .send_request_to_sphagnum(peer_id1, append_command)
-            .await;
+        let result = sp2.client(peer_id1).execute(append_command).await;
         assert!(
             result.is_ok(),
             "Failed to send Append request from sp2 to sp1: {:?}",
@@ -155,45 +102,22 @@ async fn test_config_cluster_and_check_replication() {
     // Time for replication
     sleep(Duration::from_millis(5000)).await;
 
-    let get_sp1 = {
-        let mut node1 = sp_arc_1.lock().await;
-        node1.handle_command(get_command.clone()).unwrap()
-    };
-    let get_sp2 = {
-        let mut node2 = sp_arc_2.lock().await;
-        node2.handle_command(get_command.clone()).unwrap()
-    };
-    let get_sp3 = {
-        let mut node3 = sp_arc_3.lock().await;
-        node3.handle_command(get_command.clone()).unwrap()
-    };
-    assert_eq!(
-        get_sp1,
-        CommandResult::String("valueappended_part".to_string())
-    );
-    assert_eq!(
-        get_sp2,
-        CommandResult::String("valueappended_part".to_string())
-    );
-    assert_eq!(
-        get_sp3,
-        CommandResult::String("valueappended_part".to_string())
-    );
+    let get_sp1 = sp1.execute(get_command.clone()).await.unwrap();
+    let get_sp2 = sp2.execute(get_command.clone()).await.unwrap();
+    let get_sp3 = sp3.execute(get_command.clone()).await.unwrap();
+    assert_eq!(get_sp1, CommandResult::String("valueappended_part".into()));
+    assert_eq!(get_sp2, CommandResult::String("valueappended_part".into()));
+    assert_eq!(get_sp3, CommandResult::String("valueappended_part".into()));
 
     // Step 3: assert Delete command
     let delete_command = Command::Generic(GenericCommand::Delete {
-        keys: vec!["key".to_string()],
-    });
-    let get_command = Command::String(StringCommand::Get {
-        key: "key".to_string(),
+        keys: vec!["key".into()],
     });
+    let get_command = Command::String(StringCommand::Get { key: "key".into() });
 
     // sp2 -> request -> sp3
     {
-        let mut node2 = sp_arc_2.lock().await;
-        let result = node2
-            .send_request_to_sphagnum(peer_id3, delete_command)
-            .await;
+        let result = sp2.client(peer_id3).execute(delete_command).await;
         assert!(
             result.is_ok(),
             "Failed to send Delete request from sp2 to sp3: {:?}",
@@ -204,44 +128,52 @@ async fn test_config_cluster_and_check_replication() {
     // Time for replication
     sleep(Duration::from_millis(5000)).await;
 
-    let get_sp1 = {
-        let mut node1 = sp_arc_1.lock().await;
-        node1.handle_command(get_command.clone()).unwrap()
-    };
-    let get_sp2 = {
-        let mut node2 = sp_arc_2.lock().await;
-        node2.handle_command(get_command.clone()).unwrap()
-    };
-    let get_sp3 = {
-        let mut node3 = sp_arc_3.lock().await;
-        node3.handle_command(get_command.clone()).unwrap()
-    };
+    let get_sp1 = sp1.execute(get_command.clone()).await.unwrap();
+    let get_sp2 = sp2.execute(get_command.clone()).await.unwrap();
+    let get_sp3 = sp3.execute(get_command.clone()).await.unwrap();
     assert_eq!(get_sp1, CommandResult::Nil);
     assert_eq!(get_sp2, CommandResult::Nil);
     assert_eq!(get_sp3, CommandResult::Nil);
 
     // Step 4: assert Exists command
     let exists_command = Command::Generic(GenericCommand::Exists {
-        keys: vec!["key".to_string()],
+        keys: vec!["key".into()],
     });
 
-    let exists_sp1 = {
-        let mut node1 = sp_arc_1.lock().await;
-        node1.handle_command(exists_command.clone()).unwrap()
-    };
-    let exists_sp2 = {
-        let mut node2 = sp_arc_2.lock().await;
-        node2.handle_command(exists_command.clone()).unwrap()
-    };
-    let exists_sp3 = {
-        let mut node3 = sp_arc_3.lock().await;
-        node3.handle_command(exists_command.clone()).unwrap()
-    };
+    let exists_sp1 = sp1.execute(exists_command.clone()).await.unwrap();
+    let exists_sp2 = sp2.execute(exists_command.clone()).await.unwrap();
+    let exists_sp3 = sp3.execute(exists_command.clone()).await.unwrap();
     assert_eq!(exists_sp1, CommandResult::Int(0));
     assert_eq!(exists_sp2, CommandResult::Int(0));
     assert_eq!(exists_sp3, CommandResult::Int(0));
 
-    handle_events_1.abort();
-    handle_events_2.abort();
-    handle_events_3.abort();
+    // Step 5: assert results are returned to clients
+    let set_reply = client_3_to_1
+        .execute(Command::String(StringCommand::Set {
+            key: "client_key".into(),
+            value: "client_value".into(),
+            expiration: None,
+        }))
+        .await;
+    assert_eq!(set_reply, Ok(CommandResult::String("OK".into())));
+
+    // Time for replication
+    sleep(Duration::from_millis(2000)).await;
+
+    let get_reply = client_1_to_2
+        .execute(Command::String(StringCommand::Get {
+            key: "client_key".into(),
+        }))
+        .await;
+    let missing_reply = client_1_to_2
+        .execute(Command::String(StringCommand::Get {
+            key: "missing".into(),
+        }))
+        .await;
+    assert_eq!(get_reply, Ok(CommandResult::String("client_value".into())));
+    assert_eq!(missing_reply, Ok(CommandResult::Nil));
+
+    sp1.shutdown().await.unwrap();
+    sp2.shutdown().await.unwrap();
+    sp3.shutdown().await.unwrap();
 }